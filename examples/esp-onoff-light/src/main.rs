@@ -45,7 +45,7 @@ println!("here2");
     {
         let mut node = dm.node.write().unwrap();
 println!("here3");
-        let endpoint = device_type_add_on_off_light(&mut node).unwrap();
+        let endpoint = device_type_add_on_off_light(&mut node, dm.group_keys.clone()).unwrap();
         println!("Added OnOff Light Device type at endpoint id: {}", endpoint);
         println!("Data Model now is: {}", node);
     }
@@ -1,7 +1,32 @@
 mod dev_att;
-use matter::core;
+use matter::core::{self, CommissioningData};
 use matter::data_model::cluster_basic_information::BasicInfoConfig;
+use matter::data_model::cluster_on_off;
 use matter::data_model::device_types::device_type_add_on_off_light;
+use matter::data_model::objects::AttrValue;
+use matter::pairing::{DiscoveryCapabilities, OnboardingPayload};
+use matter::secure_channel::spake2p::VerifierData;
+use matter::sys::FileKvStore;
+use std::sync::Arc;
+use std::{env, process, thread};
+
+const DEFAULT_PASSCODE: u32 = 123456;
+const DEFAULT_DISCRIMINATOR: u16 = 250;
+
+// The passcode and the discriminator are the optional command line arguments:
+// onoff_light [passcode] [discriminator]
+fn comm_args() -> (u32, u16) {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let passcode = args.first().map_or(Ok(DEFAULT_PASSCODE), |p| p.parse());
+    let discriminator = args.get(1).map_or(Ok(DEFAULT_DISCRIMINATOR), |d| d.parse());
+    match (passcode, discriminator) {
+        (Ok(passcode), Ok(discriminator)) if args.len() <= 2 => (passcode, discriminator),
+        _ => {
+            eprintln!("Usage: onoff_light [passcode] [discriminator]");
+            process::exit(1);
+        }
+    }
+}
 
 fn main() {
     env_logger::init();
@@ -14,15 +39,49 @@ fn main() {
         sw_ver: 1,
     };
     let dev_att = Box::new(dev_att::HardCodedDevAtt::new());
+    let (passcode, discriminator) = comm_args();
+    let verifier = VerifierData::new_with_pw(passcode).unwrap_or_else(|_| {
+        eprintln!("Invalid passcode: {}", passcode);
+        process::exit(1);
+    });
+    let comm_data = CommissioningData {
+        verifier,
+        discriminator,
+    };
+
+    let payload = OnboardingPayload::new(
+        &dev_info,
+        discriminator,
+        passcode,
+        DiscoveryCapabilities::IP,
+    );
+    println!("QR Code: {}", payload.to_qr_code().unwrap());
+    println!("Manual Pairing Code: {}", payload.to_manual_code());
 
-    let mut matter = core::Matter::new(dev_info, dev_att).unwrap();
+    // Each device on a host needs its own storage
+    let store = Arc::new(FileKvStore::new("/var/tmp/matter-onoff-light").unwrap());
+    let mut matter = core::Matter::new(dev_info, dev_att, comm_data, store).unwrap();
     let dm = matter.get_data_model();
     {
         let mut node = dm.node.write().unwrap();
-        let endpoint = device_type_add_on_off_light(&mut node).unwrap();
+        let endpoint = device_type_add_on_off_light(&mut node, dm.group_keys.clone()).unwrap();
         println!("Added OnOff Light Device type at endpoint id: {}", endpoint);
         println!("Data Model now is: {}", node);
     }
 
+    // Drive the light, whenever the controller turns it on or off. A physical button
+    // would instead update the attribute with dm.write_attribute_raw()
+    let attr_changes = dm.attr_changes();
+    thread::spawn(move || {
+        for change in attr_changes {
+            if change.cluster == cluster_on_off::ID
+                && change.attr == cluster_on_off::Attributes::OnOff as u16
+            {
+                let on = change.value == AttrValue::Bool(true);
+                println!("Light on endpoint {} is now {}", change.endpoint, on);
+            }
+        }
+    });
+
     matter.start_daemon().unwrap();
 }
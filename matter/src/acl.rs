@@ -0,0 +1,433 @@
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    data_model::objects::Access,
+    error::Error,
+    fabric::MAX_SUPPORTED_FABRICS,
+    interaction_model::messages::GenericPath,
+    persist::KvStore,
+    tlv::{self, nullable, TLVElement, TLVWriter, TagType},
+    transport::session::{Session, SessionMode},
+    utils::writebuf::WriteBuf,
+};
+use log::error;
+use num_derive::FromPrimitive;
+
+/* Access Control
+ *
+ * - Every interaction is checked against the Access Control List (ACL) of the accessing
+ *   fabric. An entry grants a privilege to a set of subjects (Node IDs or CATs) over a set
+ *   of targets (endpoints/clusters). Empty subjects or targets match everything
+ * - A PASE session (commissioning) is implicitly granted the Administer privilege
+ * - Plain text sessions are not allowed to access anything
+ */
+
+// The minimum values as required by the spec
+pub const SUBJECTS_PER_ENTRY: usize = 4;
+pub const TARGETS_PER_ENTRY: usize = 3;
+pub const ENTRIES_PER_FABRIC: usize = 4;
+const MAX_ACL_ENTRIES: usize = ENTRIES_PER_FABRIC * MAX_SUPPORTED_FABRICS;
+const MAX_ACL_TLV_LEN: usize = 2048;
+const ST_ACL: &str = "acl";
+
+pub const MAX_CAT_IDS: usize = 3;
+/// The CASE Authenticated Tags of a peer, an ID of 0 is unused
+pub type NocCatIds = [u32; MAX_CAT_IDS];
+
+// A CAT subject in an ACL entry is encoded in the Node ID as 0xFFFF_FFFD_IIII_VVVV
+const CAT_SUBJECT_PREFIX: u64 = 0xFFFF_FFFD_0000_0000;
+const CAT_SUBJECT_MASK: u64 = 0xFFFF_FFFF_0000_0000;
+
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Privilege {
+    View = 1,
+    ProxyView = 2,
+    Operate = 3,
+    Manage = 4,
+    Administer = 5,
+}
+
+impl Privilege {
+    /// The privilege that is needed for an operation on an attribute or a command with
+    /// 'access'. Writes and invokes need at least Operate.
+    pub fn required(access: Access, is_read: bool) -> Self {
+        if is_read && access.contains(Access::NEED_VIEW) {
+            Privilege::View
+        } else if access.contains(Access::NEED_ADMIN) {
+            Privilege::Administer
+        } else if access.contains(Access::NEED_MANAGE) {
+            Privilege::Manage
+        } else if access.contains(Access::NEED_OPERATE) || !is_read {
+            Privilege::Operate
+        } else {
+            Privilege::View
+        }
+    }
+}
+
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMode {
+    Pase = 1,
+    Case = 2,
+    Group = 3,
+}
+
+/// The subject of an interaction, as derived from the session that it arrived on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Accessor {
+    pub fab_idx: u8,
+    // None for sessions that aren't authenticated
+    pub auth_mode: Option<AuthMode>,
+    node_id: u64,
+    cat_ids: NocCatIds,
+}
+
+impl Accessor {
+    pub fn new(sess: &Session) -> Self {
+        let (fab_idx, auth_mode) = match sess.get_session_mode() {
+            SessionMode::Case(fab_idx) => (fab_idx, Some(AuthMode::Case)),
+            SessionMode::Pase => (0, Some(AuthMode::Pase)),
+            SessionMode::PlainText => (0, None),
+            SessionMode::Group(fab_idx, _) => (fab_idx, Some(AuthMode::Group)),
+        };
+        // The subject of a group interaction is the group, rather than the sender
+        let node_id = match sess.get_session_mode() {
+            SessionMode::Group(_, group_id) => group_id as u64,
+            _ => sess.get_peer_node_id().unwrap_or_default(),
+        };
+        Self {
+            fab_idx,
+            auth_mode,
+            node_id,
+            cat_ids: *sess.get_peer_cat_ids(),
+        }
+    }
+
+    /// The group, that a group interaction was addressed to
+    pub fn group_id(&self) -> Option<u16> {
+        match self.auth_mode {
+            Some(AuthMode::Group) => Some(self.node_id as u16),
+            _ => None,
+        }
+    }
+
+    fn match_subject(&self, subject: u64) -> bool {
+        if subject & CAT_SUBJECT_MASK == CAT_SUBJECT_PREFIX {
+            // The CAT identifiers must match, and our version must be at least the entry's
+            let cat_id = (subject >> 16) as u16;
+            let cat_ver = subject as u16;
+            self.cat_ids
+                .iter()
+                .any(|c| *c != 0 && (c >> 16) as u16 == cat_id && *c as u16 >= cat_ver)
+        } else {
+            subject == self.node_id
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Target {
+    pub cluster: Option<u32>,
+    pub endpoint: Option<u16>,
+    pub device_type: Option<u32>,
+}
+
+enum TargetTag {
+    Cluster = 0,
+    Endpoint = 1,
+    DeviceType = 2,
+}
+
+impl Target {
+    pub fn new(endpoint: Option<u16>, cluster: Option<u32>, device_type: Option<u32>) -> Self {
+        Self {
+            cluster,
+            endpoint,
+            device_type,
+        }
+    }
+
+    fn match_path(&self, path: &GenericPath) -> bool {
+        // Device Type targets aren't supported yet, such a target never matches
+        self.device_type.is_none()
+            && (self.endpoint.is_none() || self.endpoint == path.endpoint)
+            && (self.cluster.is_none() || self.cluster == path.cluster)
+    }
+
+    fn from_tlv(t: &TLVElement) -> Result<Self, Error> {
+        let target = Self {
+            cluster: nullable(t, TargetTag::Cluster as u32, |e| e.u32())?,
+            endpoint: nullable(t, TargetTag::Endpoint as u32, |e| e.u16())?,
+            device_type: nullable(t, TargetTag::DeviceType as u32, |e| e.u32())?,
+        };
+        if target == Target::default() {
+            // At least one of the fields must be present
+            return Err(Error::Invalid);
+        }
+        Ok(target)
+    }
+
+    fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        tw.start_struct(tag)?;
+        match self.cluster {
+            Some(c) => tw.u32(TagType::Context(TargetTag::Cluster as u8), c)?,
+            None => tw.null(TagType::Context(TargetTag::Cluster as u8))?,
+        }
+        match self.endpoint {
+            Some(e) => tw.u16(TagType::Context(TargetTag::Endpoint as u8), e)?,
+            None => tw.null(TagType::Context(TargetTag::Endpoint as u8))?,
+        }
+        match self.device_type {
+            Some(d) => tw.u32(TagType::Context(TargetTag::DeviceType as u8), d)?,
+            None => tw.null(TagType::Context(TargetTag::DeviceType as u8))?,
+        }
+        tw.end_container()
+    }
+}
+
+enum AclEntryTag {
+    Privilege = 1,
+    AuthMode = 2,
+    Subjects = 3,
+    Targets = 4,
+    FabricIndex = 0xFE,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AclEntry {
+    pub privilege: Privilege,
+    pub auth_mode: AuthMode,
+    subjects: Vec<u64>,
+    targets: Vec<Target>,
+    pub fab_idx: u8,
+}
+
+impl AclEntry {
+    pub fn new(fab_idx: u8, privilege: Privilege, auth_mode: AuthMode) -> Self {
+        Self {
+            privilege,
+            auth_mode,
+            subjects: Vec::with_capacity(SUBJECTS_PER_ENTRY),
+            targets: Vec::with_capacity(TARGETS_PER_ENTRY),
+            fab_idx,
+        }
+    }
+
+    pub fn add_subject(&mut self, subject: u64) -> Result<(), Error> {
+        if self.subjects.len() >= SUBJECTS_PER_ENTRY {
+            return Err(Error::NoSpace);
+        }
+        self.subjects.push(subject);
+        Ok(())
+    }
+
+    /// Add a CASE Authenticated Tag as a subject
+    pub fn add_subject_catid(&mut self, cat_id: u32) -> Result<(), Error> {
+        self.add_subject(CAT_SUBJECT_PREFIX | cat_id as u64)
+    }
+
+    pub fn add_target(&mut self, target: Target) -> Result<(), Error> {
+        if self.targets.len() >= TARGETS_PER_ENTRY {
+            return Err(Error::NoSpace);
+        }
+        self.targets.push(target);
+        Ok(())
+    }
+
+    fn allow(&self, accessor: &Accessor, path: &GenericPath, privilege: Privilege) -> bool {
+        Some(self.auth_mode) == accessor.auth_mode
+            && self.fab_idx == accessor.fab_idx
+            && self.privilege >= privilege
+            && (self.subjects.is_empty()
+                || self.subjects.iter().any(|s| accessor.match_subject(*s)))
+            && (self.targets.is_empty() || self.targets.iter().any(|t| t.match_path(path)))
+    }
+
+    /// Decode an entry, the fabric index is not part of the encoding when the entry is
+    /// written, it should be set by the caller
+    pub fn from_tlv(t: &TLVElement) -> Result<Self, Error> {
+        let privilege = t.find_tag(AclEntryTag::Privilege as u32)?.u8()?;
+        let privilege = num::FromPrimitive::from_u8(privilege).ok_or(Error::Invalid)?;
+        let auth_mode = t.find_tag(AclEntryTag::AuthMode as u32)?.u8()?;
+        let auth_mode = num::FromPrimitive::from_u8(auth_mode).ok_or(Error::Invalid)?;
+        let fab_idx = nullable(t, AclEntryTag::FabricIndex as u32, |e| e.u8())?;
+        let mut entry = AclEntry::new(fab_idx.unwrap_or_default(), privilege, auth_mode);
+
+        if let Some(subjects) = nullable(t, AclEntryTag::Subjects as u32, |e| e.confirm_array())? {
+            if let Some(iter) = subjects.iter() {
+                for s in iter {
+                    entry.add_subject(s.u64()?)?;
+                }
+            }
+        }
+        if let Some(targets) = nullable(t, AclEntryTag::Targets as u32, |e| e.confirm_array())? {
+            if let Some(iter) = targets.iter() {
+                for t in iter {
+                    entry.add_target(Target::from_tlv(&t)?)?;
+                }
+            }
+        }
+        Ok(entry)
+    }
+
+    pub fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        tw.start_struct(tag)?;
+        tw.u8(
+            TagType::Context(AclEntryTag::Privilege as u8),
+            self.privilege as u8,
+        )?;
+        tw.u8(
+            TagType::Context(AclEntryTag::AuthMode as u8),
+            self.auth_mode as u8,
+        )?;
+        if self.subjects.is_empty() {
+            tw.null(TagType::Context(AclEntryTag::Subjects as u8))?;
+        } else {
+            tw.start_array(TagType::Context(AclEntryTag::Subjects as u8))?;
+            for s in &self.subjects {
+                tw.u64(TagType::Anonymous, *s)?;
+            }
+            tw.end_container()?;
+        }
+        if self.targets.is_empty() {
+            tw.null(TagType::Context(AclEntryTag::Targets as u8))?;
+        } else {
+            tw.start_array(TagType::Context(AclEntryTag::Targets as u8))?;
+            for t in &self.targets {
+                t.to_tlv(tw, TagType::Anonymous)?;
+            }
+            tw.end_container()?;
+        }
+        tw.u8(
+            TagType::Context(AclEntryTag::FabricIndex as u8),
+            self.fab_idx,
+        )?;
+        tw.end_container()
+    }
+}
+
+/// An accessor, along with the ACL that its accesses are checked against
+pub struct AccessReq<'a> {
+    accessor: &'a Accessor,
+    acl_mgr: &'a AclMgr,
+}
+
+impl<'a> AccessReq<'a> {
+    pub fn new(accessor: &'a Accessor, acl_mgr: &'a AclMgr) -> Self {
+        Self { accessor, acl_mgr }
+    }
+
+    pub fn accessor(&self) -> &Accessor {
+        self.accessor
+    }
+
+    pub fn allow(&self, path: &GenericPath, privilege: Privilege) -> bool {
+        self.acl_mgr.allow(self.accessor, path, privilege)
+    }
+}
+
+pub struct AclMgr {
+    entries: RwLock<Vec<AclEntry>>,
+    store: Arc<dyn KvStore>,
+}
+
+impl AclMgr {
+    /// Create an ACL Manager, that persists the entries in 'store'
+    pub fn new(store: Arc<dyn KvStore>) -> Result<Self, Error> {
+        let am = Self {
+            entries: RwLock::new(Vec::with_capacity(MAX_ACL_ENTRIES)),
+            store,
+        };
+        am.load()?;
+        Ok(am)
+    }
+
+    pub fn erase_all(&self) -> Result<(), Error> {
+        let mut entries = self.entries.write()?;
+        entries.clear();
+        self.store(&entries)
+    }
+
+    pub fn add(&self, entry: AclEntry) -> Result<(), Error> {
+        let mut entries = self.entries.write()?;
+        let count = entries
+            .iter()
+            .filter(|e| e.fab_idx == entry.fab_idx)
+            .count();
+        if count >= ENTRIES_PER_FABRIC {
+            return Err(Error::NoSpace);
+        }
+        entries.push(entry);
+        self.store(&entries)
+    }
+
+    /// Replace all the entries of a fabric
+    pub fn set_for_fabric(&self, fab_idx: u8, new: Vec<AclEntry>) -> Result<(), Error> {
+        if new.len() > ENTRIES_PER_FABRIC || new.iter().any(|e| e.fab_idx != fab_idx) {
+            return Err(Error::Invalid);
+        }
+        let mut entries = self.entries.write()?;
+        entries.retain(|e| e.fab_idx != fab_idx);
+        entries.extend(new);
+        self.store(&entries)
+    }
+
+    pub fn delete_for_fabric(&self, fab_idx: u8) -> Result<(), Error> {
+        self.set_for_fabric(fab_idx, Vec::new())
+    }
+
+    pub fn for_each_acl<T>(&self, mut f: T) -> Result<(), Error>
+    where
+        T: FnMut(&AclEntry),
+    {
+        let entries = self.entries.read()?;
+        for entry in entries.iter() {
+            f(entry)
+        }
+        Ok(())
+    }
+
+    /// Check if the accessor has at least 'privilege' on the path
+    pub fn allow(&self, accessor: &Accessor, path: &GenericPath, privilege: Privilege) -> bool {
+        match accessor.auth_mode {
+            Some(AuthMode::Pase) => true,
+            Some(_) => match self.entries.read() {
+                Ok(entries) => entries.iter().any(|e| e.allow(accessor, path, privilege)),
+                Err(_) => false,
+            },
+            None => false,
+        }
+    }
+
+    fn store(&self, entries: &[AclEntry]) -> Result<(), Error> {
+        let mut buf = [0u8; MAX_ACL_TLV_LEN];
+        let buf_len = buf.len();
+        let mut wb = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut wb);
+        tw.start_array(TagType::Anonymous)?;
+        for entry in entries {
+            entry.to_tlv(&mut tw, TagType::Anonymous)?;
+        }
+        tw.end_container()?;
+        self.store.set_kv_slice(ST_ACL, wb.as_borrow_slice())
+    }
+
+    fn load(&self) -> Result<(), Error> {
+        let mut buf = Vec::new();
+        if self.store.get_kv_slice(ST_ACL, &mut buf).is_err() {
+            // Nothing stored yet
+            return Ok(());
+        }
+        let root = tlv::get_root_node(&buf)?;
+        let mut entries = self.entries.write()?;
+        if let Some(iter) = root.confirm_array()?.iter() {
+            for t in iter {
+                match AclEntry::from_tlv(&t) {
+                    Ok(entry) => entries.push(entry),
+                    Err(e) => error!("Ignoring invalid stored ACL entry: {:?}", e),
+                }
+            }
+        }
+        Ok(())
+    }
+}
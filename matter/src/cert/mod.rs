@@ -273,6 +273,16 @@ impl DistNames {
             .find(|(id, _)| *id == match_id as u8)
             .map(|(_, value)| *value)
     }
+
+    fn u32_arr(&self, match_id: DnTags, output: &mut [u32]) {
+        let mut out_index = 0;
+        for (_, val) in self.dn.iter().filter(|(id, _)| *id == match_id as u8) {
+            if out_index < output.len() {
+                output[out_index] = *val as u32;
+                out_index += 1;
+            }
+        }
+    }
 }
 
 impl<'a> FromTLV<'a> for DistNames {
@@ -401,6 +411,11 @@ impl Cert {
         self.subject.u64(DnTags::FabricId).ok_or(Error::NoFabricId)
     }
 
+    /// Fill 'output' with the CASE Authenticated Tags (CATs) that are part of the subject
+    pub fn get_cat_ids(&self, output: &mut [u32]) {
+        self.subject.u32_arr(DnTags::NocCat, output)
+    }
+
     pub fn get_pubkey(&self) -> &[u8] {
         self.pubkey.as_slice()
     }
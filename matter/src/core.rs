@@ -1,21 +1,47 @@
 use crate::{
+    acl::AclMgr,
     data_model::{
-        cluster_basic_information::BasicInfoConfig, core::DataModel,
+        cluster_basic_information::BasicInfoConfig, core::DataModel, events::EventLog,
         sdm::dev_att::DevAttDataFetcher,
     },
     error::*,
-    fabric::FabricMgr,
-    interaction_model::InteractionModel,
-    secure_channel::core::SecureChannel,
+    fabric::{FabricMgr, MAX_SUPPORTED_FABRICS},
+    group_keys::GroupKeys,
+    interaction_model::{client::ImClient, InteractionModel},
+    persist::KvStore,
+    secure_channel::{
+        case::CaseClient,
+        core::SecureChannel,
+        pake::{CommWindowStatus, PaseMgr},
+        spake2p::VerifierData,
+    },
+    sys::Mdns,
     transport,
 };
+use log::info;
 use std::sync::Arc;
 
+// The discriminator is 12-bit
+const MAX_DISCRIMINATOR: u16 = 0xFFF;
+
+/// Device Commissioning Data
+pub struct CommissioningData {
+    /// The data like password or verifier that is required to authenticate
+    pub verifier: VerifierData,
+    /// The 12-bit discriminator used to differentiate between multiple devices
+    pub discriminator: u16,
+}
+
 /// The primary Matter Object
 pub struct Matter {
     transport_mgr: transport::mgr::Mgr,
     data_model: DataModel,
+    im_client: ImClient,
+    case_client: CaseClient,
     fabric_mgr: Arc<FabricMgr>,
+    acl_mgr: Arc<AclMgr>,
+    group_keys: Arc<GroupKeys>,
+    pase_mgr: PaseMgr,
 }
 
 impl Matter {
@@ -25,25 +51,53 @@ impl Matter {
     /// * dev_att: An object that implements the trait [DevAttDataFetcher]. Any Matter device
     /// requires a set of device attestation certificates and keys. It is the responsibility of
     /// this object to return the device attestation details when queried upon.
+    /// * dev_comm: The passcode (or the verifier computed from it) and the discriminator
+    /// that a commissioner uses to discover and to authenticate with the device.
+    /// * store: The storage for the fabrics, ACLs, group keys and event numbers, one per device
     pub fn new(
         dev_det: BasicInfoConfig,
         dev_att: Box<dyn DevAttDataFetcher>,
+        dev_comm: CommissioningData,
+        store: Arc<dyn KvStore>,
     ) -> Result<Box<Matter>, Error> {
-        let fabric_mgr = Arc::new(FabricMgr::new()?);
-        let open_comm_window = fabric_mgr.is_empty();
-        let data_model = DataModel::new(dev_det, dev_att, fabric_mgr.clone())?;
+        if dev_comm.discriminator > MAX_DISCRIMINATOR {
+            return Err(Error::Invalid);
+        }
+        Mdns::get()?.lock()?.set_values(dev_det.vid, dev_det.pid);
+        let fabric_mgr = Arc::new(FabricMgr::new(store.clone())?);
+        let acl_mgr = Arc::new(AclMgr::new(store.clone())?);
+        let group_keys = Arc::new(GroupKeys::new(store.clone())?);
+        let events = Arc::new(EventLog::new(store)?);
+        let pase_mgr = PaseMgr::new(dev_comm.verifier, dev_comm.discriminator);
+        if fabric_mgr.is_empty() {
+            pase_mgr.open_basic_window(None, None)?;
+        }
+        let data_model = DataModel::new(
+            dev_det,
+            dev_att,
+            fabric_mgr.clone(),
+            acl_mgr.clone(),
+            group_keys.clone(),
+            events,
+            pase_mgr.clone(),
+        )?;
+        let interaction_model = Box::new(InteractionModel::new(Box::new(data_model.clone())));
+        let secure_channel = Box::new(SecureChannel::new(fabric_mgr.clone(), pase_mgr.clone()));
         let mut matter = Box::new(Matter {
-            transport_mgr: transport::mgr::Mgr::new()?,
+            transport_mgr: transport::mgr::Mgr::new(
+                fabric_mgr.clone(),
+                group_keys.clone(),
+                data_model.failsafe.clone(),
+            )?,
             data_model,
+            im_client: interaction_model.get_client(),
+            case_client: secure_channel.get_case_client(),
             fabric_mgr,
+            acl_mgr,
+            group_keys,
+            pase_mgr,
         });
-        let interaction_model =
-            Box::new(InteractionModel::new(Box::new(matter.data_model.clone())));
         matter.transport_mgr.register_protocol(interaction_model)?;
-        let mut secure_channel = Box::new(SecureChannel::new(matter.fabric_mgr.clone()));
-        if open_comm_window {
-            secure_channel.open_comm_window();
-        }
         matter.transport_mgr.register_protocol(secure_channel)?;
         Ok(matter)
     }
@@ -57,6 +111,45 @@ impl Matter {
         self.data_model.clone()
     }
 
+    /// Returns the [ImClient] for reading, writing, invoking and subscribing on other nodes
+    ///
+    /// The interactions run over the existing CASE/PASE sessions, once the daemon is
+    /// started. The calls block until the peer responds, so they must not be made from
+    /// the daemon's own thread.
+    pub fn get_im_client(&self) -> ImClient {
+        self.im_client.clone()
+    }
+
+    /// Returns the [CaseClient] for establishing CASE sessions with other nodes
+    ///
+    /// The session that is established can then be used with the [ImClient]. Like the
+    /// [ImClient], the calls block until the peer responds.
+    pub fn get_case_client(&self) -> CaseClient {
+        self.case_client.clone()
+    }
+
+    /// Resets the device to its factory state
+    ///
+    /// All the fabrics are removed, along with their ACLs, groups and keys, from the
+    /// storage too, and the commissioning window is opened, so that the device can be
+    /// commissioned afresh. As [start_daemon](Matter::start_daemon) doesn't return, this
+    /// is meant to be called before it, like when a reset button is held during boot.
+    pub fn factory_reset(&self) -> Result<(), Error> {
+        info!("Factory reset");
+        for fab_idx in 1..MAX_SUPPORTED_FABRICS as u8 {
+            match self.fabric_mgr.remove(fab_idx) {
+                Ok(()) | Err(Error::NotFound) => (),
+                Err(e) => return Err(e),
+            }
+            self.group_keys.remove_fabric(fab_idx)?;
+        }
+        self.acl_mgr.erase_all()?;
+        if self.pase_mgr.window_status()?.0 == CommWindowStatus::NotOpen {
+            self.pase_mgr.open_basic_window(None, None)?;
+        }
+        Ok(())
+    }
+
     /// Starts the Matter daemon
     ///
     /// This call does NOT return
@@ -169,10 +169,11 @@ impl CryptoKeyPair for KeyPair {
         safemem::write_bytes(signature, 0);
 
         let sig = EcdsaSig::sign(&msg, self.private_key()?)?;
+        // The leading zeroes of r and s are dropped, they are right-aligned in their 32 bytes
         let r = sig.r().to_vec();
-        signature[0..r.len()].copy_from_slice(r.as_slice());
+        signature[(32 - r.len())..32].copy_from_slice(r.as_slice());
         let s = sig.s().to_vec();
-        signature[32..(32 + s.len())].copy_from_slice(s.as_slice());
+        signature[(64 - s.len())..64].copy_from_slice(s.as_slice());
         Ok(64)
     }
 
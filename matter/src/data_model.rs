@@ -1,8 +1,10 @@
 pub mod core;
 pub mod device_types;
+pub mod events;
 pub mod objects;
 
 pub mod cluster_basic_information;
+pub mod cluster_groups;
 pub mod cluster_on_off;
 pub mod cluster_template;
 pub mod sdm;
@@ -0,0 +1,299 @@
+use std::sync::Arc;
+
+use super::objects::*;
+use crate::{
+    cmd_enter,
+    error::*,
+    group_keys::{GroupKeys, MAX_GROUPS_PER_FABRIC},
+    interaction_model::{command::CommandReq, core::IMStatusCode, messages::ib},
+    tlv::{TLVElement, TLVWriter, TagType, ToTLV},
+};
+use log::info;
+use num_derive::FromPrimitive;
+
+pub const ID: u32 = 0x0004;
+
+// The longest Group Name that is accepted
+const MAX_GROUP_NAME_LEN: usize = 16;
+// The Name Support bit of the NameSupport attribute
+const NAME_SUPPORT: u8 = 0x80;
+
+pub enum Attributes {
+    NameSupport = 0x0,
+}
+
+#[derive(FromPrimitive)]
+pub enum Commands {
+    AddGroup = 0x00,
+    ViewGroup = 0x01,
+    GetGroupMembership = 0x02,
+    RemoveGroup = 0x03,
+    RemoveAllGroups = 0x04,
+    AddGroupIfIdentifying = 0x05,
+}
+
+// The responses carry the same ids as their requests
+pub enum RespCommands {
+    AddGroupResp = 0x00,
+    ViewGroupResp = 0x01,
+    GetGroupMembershipResp = 0x02,
+    RemoveGroupResp = 0x03,
+}
+
+fn attr_name_support_new() -> Result<Attribute, Error> {
+    Attribute::new(
+        Attributes::NameSupport as u16,
+        AttrValue::Uint8(NAME_SUPPORT),
+        Access::RV,
+        Quality::FIXED,
+    )
+}
+
+pub struct GroupsCluster {
+    base: Cluster,
+    group_keys: Arc<GroupKeys>,
+}
+
+impl GroupsCluster {
+    pub fn new(group_keys: Arc<GroupKeys>) -> Result<Box<Self>, Error> {
+        let mut cluster = Box::new(GroupsCluster {
+            base: Cluster::new(ID)?,
+            group_keys,
+        });
+        cluster.base.add_attribute(attr_name_support_new()?)?;
+        for cmd in [
+            Commands::AddGroup,
+            Commands::RemoveGroup,
+            Commands::RemoveAllGroups,
+            Commands::AddGroupIfIdentifying,
+        ] {
+            cluster
+                .base
+                .add_command(Command::new(cmd as u16, Access::NEED_MANAGE))?;
+        }
+        Ok(cluster)
+    }
+
+    // The group memberships are fabric-scoped, so these commands need a CASE or a
+    // Group session
+    fn fab_idx(cmd_req: &CommandReq) -> Result<u8, IMStatusCode> {
+        cmd_req
+            .trans
+            .session
+            .get_local_fabric_idx()
+            .ok_or(IMStatusCode::UnsupportedAccess)
+    }
+
+    fn endpoint(cmd_req: &CommandReq) -> Result<u16, IMStatusCode> {
+        cmd_req.cmd.path.endpoint.ok_or(IMStatusCode::Failure)
+    }
+
+    fn group_id(data: &TLVElement) -> Result<u16, IMStatusCode> {
+        data.find_tag(0)
+            .and_then(|t| t.u16())
+            .map_err(|_| IMStatusCode::InvalidCommand)
+    }
+
+    fn group_name(data: &TLVElement) -> Result<String, IMStatusCode> {
+        let name = data
+            .find_tag(1)
+            .and_then(|t| t.slice())
+            .map_err(|_| IMStatusCode::InvalidCommand)?;
+        if name.len() > MAX_GROUP_NAME_LEN {
+            return Err(IMStatusCode::ConstraintError);
+        }
+        Ok(String::from_utf8_lossy(name).into_owned())
+    }
+
+    fn add_group(&self, cmd_req: &CommandReq, group_id: u16) -> Result<(), IMStatusCode> {
+        let fab_idx = GroupsCluster::fab_idx(cmd_req)?;
+        let endpoint = GroupsCluster::endpoint(cmd_req)?;
+        let name = GroupsCluster::group_name(&cmd_req.data)?;
+        if group_id == 0 {
+            return Err(IMStatusCode::ConstraintError);
+        }
+        // A group can only be added, once its messages can be decrypted
+        if !self.group_keys.is_group_mapped(fab_idx, group_id)? {
+            return Err(IMStatusCode::UnsupportedAccess);
+        }
+        info!("Adding endpoint {} to group {:x}", endpoint, group_id);
+        self.group_keys
+            .add_group_endpoint(fab_idx, group_id, endpoint, &name)
+            .map_err(|e| match e {
+                Error::NoSpace => IMStatusCode::ResourceExhausted,
+                _ => IMStatusCode::Failure,
+            })
+    }
+
+    fn handle_command_add_group(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+        cmd_enter!("AddGroup");
+        let group_id = GroupsCluster::group_id(&cmd_req.data)?;
+        let status = match self.add_group(cmd_req, group_id) {
+            Ok(()) => IMStatusCode::Sucess,
+            Err(IMStatusCode::InvalidCommand) => return Err(IMStatusCode::InvalidCommand),
+            Err(e) => e,
+        };
+        let cmd_data = |t: &mut TLVWriter| {
+            t.u8(TagType::Context(0), status as u8)?;
+            t.u16(TagType::Context(1), group_id)
+        };
+        self.send_resp(cmd_req, RespCommands::AddGroupResp, &cmd_data)
+    }
+
+    fn handle_command_view_group(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+        cmd_enter!("ViewGroup");
+        let group_id = GroupsCluster::group_id(&cmd_req.data)?;
+        let fab_idx = GroupsCluster::fab_idx(cmd_req)?;
+        let endpoint = GroupsCluster::endpoint(cmd_req)?;
+        let group = self
+            .group_keys
+            .get_groups(fab_idx)?
+            .into_iter()
+            .find(|g| g.group_id == group_id && g.endpoints.contains(&endpoint));
+        let status = if group_id == 0 {
+            IMStatusCode::ConstraintError
+        } else if group.is_none() {
+            IMStatusCode::NotFound
+        } else {
+            IMStatusCode::Sucess
+        };
+        let name = group.map(|g| g.name).unwrap_or_default();
+
+        let cmd_data = |t: &mut TLVWriter| {
+            t.u8(TagType::Context(0), status as u8)?;
+            t.u16(TagType::Context(1), group_id)?;
+            t.utf8(TagType::Context(2), name.as_bytes())
+        };
+        self.send_resp(cmd_req, RespCommands::ViewGroupResp, &cmd_data)
+    }
+
+    fn handle_command_get_membership(
+        &mut self,
+        cmd_req: &mut CommandReq,
+    ) -> Result<(), IMStatusCode> {
+        cmd_enter!("GetGroupMembership");
+        let mut requested = Vec::new();
+        let list = cmd_req
+            .data
+            .find_tag(0)
+            .and_then(|t| t.confirm_array())
+            .map_err(|_| IMStatusCode::InvalidCommand)?;
+        if let Some(iter) = list.iter() {
+            for e in iter {
+                requested.push(e.u16().map_err(|_| IMStatusCode::InvalidCommand)?);
+            }
+        }
+        let fab_idx = GroupsCluster::fab_idx(cmd_req)?;
+        let endpoint = GroupsCluster::endpoint(cmd_req)?;
+
+        let groups = self.group_keys.get_groups(fab_idx)?;
+        let capacity = MAX_GROUPS_PER_FABRIC.saturating_sub(groups.len()) as u8;
+        // An empty list asks for all the groups of this endpoint
+        let members: Vec<u16> = groups
+            .iter()
+            .filter(|g| g.endpoints.contains(&endpoint))
+            .map(|g| g.group_id)
+            .filter(|g| requested.is_empty() || requested.contains(g))
+            .collect();
+
+        let cmd_data = |t: &mut TLVWriter| {
+            t.u8(TagType::Context(0), capacity)?;
+            t.start_array(TagType::Context(1))?;
+            for g in &members {
+                t.u16(TagType::Anonymous, *g)?;
+            }
+            t.end_container()
+        };
+        self.send_resp(cmd_req, RespCommands::GetGroupMembershipResp, &cmd_data)
+    }
+
+    fn handle_command_remove_group(
+        &mut self,
+        cmd_req: &mut CommandReq,
+    ) -> Result<(), IMStatusCode> {
+        cmd_enter!("RemoveGroup");
+        let group_id = GroupsCluster::group_id(&cmd_req.data)?;
+        let fab_idx = GroupsCluster::fab_idx(cmd_req)?;
+        let endpoint = GroupsCluster::endpoint(cmd_req)?;
+        let status = if group_id == 0 {
+            IMStatusCode::ConstraintError
+        } else {
+            match self
+                .group_keys
+                .remove_group_endpoint(fab_idx, group_id, endpoint)
+            {
+                Ok(()) => IMStatusCode::Sucess,
+                Err(Error::NotFound) => IMStatusCode::NotFound,
+                Err(_) => IMStatusCode::Failure,
+            }
+        };
+
+        let cmd_data = |t: &mut TLVWriter| {
+            t.u8(TagType::Context(0), status as u8)?;
+            t.u16(TagType::Context(1), group_id)
+        };
+        self.send_resp(cmd_req, RespCommands::RemoveGroupResp, &cmd_data)
+    }
+
+    fn handle_command_remove_all(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+        cmd_enter!("RemoveAllGroups");
+        let fab_idx = GroupsCluster::fab_idx(cmd_req)?;
+        let endpoint = GroupsCluster::endpoint(cmd_req)?;
+        self.group_keys
+            .remove_all_group_endpoint(fab_idx, endpoint)?;
+        cmd_req.trans.complete();
+        Err(IMStatusCode::Sucess)
+    }
+
+    fn handle_command_add_if_identifying(
+        &mut self,
+        cmd_req: &mut CommandReq,
+    ) -> Result<(), IMStatusCode> {
+        cmd_enter!("AddGroupIfIdentifying");
+        // There is no Identify cluster yet, so none of the endpoints is ever
+        // identifying, and no action is taken
+        let _ = GroupsCluster::group_id(&cmd_req.data)?;
+        cmd_req.trans.complete();
+        Err(IMStatusCode::Sucess)
+    }
+
+    fn send_resp(
+        &self,
+        cmd_req: &mut CommandReq,
+        resp_cmd: RespCommands,
+        cmd_data: &dyn Fn(&mut TLVWriter) -> Result<(), Error>,
+    ) -> Result<(), IMStatusCode> {
+        let endpoint = GroupsCluster::endpoint(cmd_req)?;
+        let resp = ib::InvResp::cmd_new(endpoint, ID, resp_cmd as u16, cmd_data);
+        let _ = resp.to_tlv(cmd_req.resp, TagType::Anonymous);
+        cmd_req.trans.complete();
+        Ok(())
+    }
+}
+
+impl ClusterType for GroupsCluster {
+    fn base(&self) -> &Cluster {
+        &self.base
+    }
+    fn base_mut(&mut self) -> &mut Cluster {
+        &mut self.base
+    }
+
+    fn handle_command(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+        let cmd = cmd_req
+            .cmd
+            .path
+            .leaf
+            .map(num::FromPrimitive::from_u32)
+            .ok_or(IMStatusCode::UnsupportedCommand)?
+            .ok_or(IMStatusCode::UnsupportedCommand)?;
+        match cmd {
+            Commands::AddGroup => self.handle_command_add_group(cmd_req),
+            Commands::ViewGroup => self.handle_command_view_group(cmd_req),
+            Commands::GetGroupMembership => self.handle_command_get_membership(cmd_req),
+            Commands::RemoveGroup => self.handle_command_remove_group(cmd_req),
+            Commands::RemoveAllGroups => self.handle_command_remove_all(cmd_req),
+            Commands::AddGroupIfIdentifying => self.handle_command_add_if_identifying(cmd_req),
+        }
+    }
+}
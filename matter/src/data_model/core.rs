@@ -1,30 +1,59 @@
 use super::{
     cluster_basic_information::BasicInfoConfig,
     device_types::device_type_add_root_node,
+    events::{self, EventDataGen, EventLog, EventPriority},
     objects::{self, *},
-    sdm::dev_att::DevAttDataFetcher,
+    sdm::{dev_att::DevAttDataFetcher, failsafe::FailSafe},
     system_model::descriptor::DescriptorCluster,
 };
 use crate::{
+    acl::{AccessReq, Accessor, AclMgr, Privilege},
     error::*,
     fabric::FabricMgr,
+    group_keys::GroupKeys,
     interaction_model::{
         command::CommandReq,
         core::IMStatusCode,
         messages::{
-            ib::{self, AttrData, AttrDataType, AttrPath},
+            ib::{self, AttrData, AttrDataType, AttrPath, DataVersionFilter, EventPath},
             msg::{self, ReadReq, WriteReq},
             GenericPath,
         },
-        InteractionConsumer, Transaction,
+        InteractionConsumer, ReadCursor, Transaction,
     },
-    tlv::{TLVElement, TLVWriter, TagType, ToTLV},
+    secure_channel::pake::PaseMgr,
+    tlv::{get_root_node, Nullable, TLVElement, TLVWriter, TagType, ToTLV},
+    utils::writebuf::WriteBuf,
 };
 use log::{error, info};
-use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::{
+    mpsc::{self, Receiver, Sender},
+    Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
+
+// The largest list attribute, that can be split across several ReportData messages
+const MAX_LIST_ATTR_LEN: usize = 8192;
 
 pub struct DataModel {
     pub node: Arc<RwLock<Box<Node>>>,
+    pub events: Arc<EventLog>,
+    pub acl_mgr: Arc<AclMgr>,
+    pub group_keys: Arc<GroupKeys>,
+    pub failsafe: Arc<FailSafe>,
+    // The application's receivers of the attribute changes
+    attr_changes: Arc<Mutex<Vec<Sender<AttrChange>>>>,
+}
+
+// The state of a read, as its reports are encoded in a ReportData
+struct ReportCtx<'a> {
+    access_req: &'a AccessReq<'a>,
+    fab_filter: bool,
+    dataver_filters: &'a [DataVersionFilter],
+    // Where the reports start in the ReportData, a report that doesn't fit from here, must be split
+    start: usize,
+    cursor: ReadCursor,
+    // Set once a report doesn't fit, the next ReportData resumes from the cursor
+    full: bool,
 }
 
 impl DataModel {
@@ -32,14 +61,28 @@ impl DataModel {
         dev_details: BasicInfoConfig,
         dev_att: Box<dyn DevAttDataFetcher>,
         fabric_mgr: Arc<FabricMgr>,
+        acl_mgr: Arc<AclMgr>,
+        group_keys: Arc<GroupKeys>,
+        events: Arc<EventLog>,
+        pase_mgr: PaseMgr,
     ) -> Result<Self, Error> {
         let dm = DataModel {
             node: Arc::new(RwLock::new(Node::new()?)),
+            events,
+            acl_mgr: acl_mgr.clone(),
+            group_keys: group_keys.clone(),
+            failsafe: Arc::new(FailSafe::new(
+                fabric_mgr.clone(),
+                acl_mgr,
+                group_keys,
+                pase_mgr.clone(),
+            )),
+            attr_changes: Arc::new(Mutex::new(Vec::new())),
         };
         {
             let mut node = dm.node.write()?;
             node.set_changes_cb(Box::new(dm.clone()));
-            device_type_add_root_node(&mut node, dev_details, dev_att, fabric_mgr)?;
+            device_type_add_root_node(&mut node, &dm, dev_details, dev_att, fabric_mgr, pase_mgr)?;
         }
         Ok(dm)
     }
@@ -52,30 +95,89 @@ impl DataModel {
     ) -> Result<AttrValue, IMStatusCode> {
         let node = self.node.read().unwrap();
         let cluster = node.get_cluster(endpoint, cluster)?;
-        cluster.base().read_attribute_raw(attr).map(|a| *a)
+        cluster.base().read_attribute_raw(attr).cloned()
     }
 
-    // A valid attribute on a valid cluster should be encoded. Both wildcard and non-wildcard paths end up calling this API
+    /// Update an attribute from the application, e.g. on a button press. The data version
+    /// of the cluster is bumped, so that the subscribers get reported
+    pub fn write_attribute_raw(
+        &self,
+        endpoint: u16,
+        cluster: u32,
+        attr: u16,
+        value: AttrValue,
+    ) -> Result<(), IMStatusCode> {
+        let changes = {
+            let mut node = self.node.write().unwrap();
+            let cluster = node.get_cluster_mut(endpoint, cluster)?;
+            cluster.base_mut().write_attribute_raw(attr, value)?;
+            node.take_attr_changes()
+        };
+        self.notify_attr_changes(changes);
+        Ok(())
+    }
+
+    /// Returns a receiver of all the changes to the attribute values, whether they are made
+    /// by a Write, as the side-effect of a command, or by the application itself. This is
+    /// how the application drives the hardware, e.g. when the On command turns a light on
+    pub fn attr_changes(&self) -> Receiver<AttrChange> {
+        let (tx, rx) = mpsc::channel();
+        self.attr_changes.lock().unwrap().push(tx);
+        rx
+    }
+
+    // This is done once the node is unlocked, so that the application may access it right
+    // away. The receivers that are gone are dropped
+    fn notify_attr_changes(&self, changes: Vec<AttrChange>) {
+        if changes.is_empty() {
+            return;
+        }
+        let mut senders = self.attr_changes.lock().unwrap();
+        senders.retain(|tx| changes.iter().all(|c| tx.send(c.clone()).is_ok()));
+    }
+
+    /// Emit an event from the given cluster. Returns the event number of the event
+    pub fn emit_event(
+        &self,
+        endpoint: u16,
+        cluster: u32,
+        event_id: u32,
+        priority: EventPriority,
+        data: EventDataGen,
+    ) -> Result<u64, Error> {
+        self.node.read()?.get_cluster(endpoint, cluster)?;
+        self.events
+            .emit(endpoint, cluster, event_id, priority, data)
+    }
+
+    // A valid attribute on a valid cluster should be written. Both wildcard and non-wildcard paths end up calling this API
     fn handle_write_attr_data(
+        access_req: &AccessReq,
         c: &mut dyn ClusterType,
-        tw: &mut TLVWriter,
         path: &GenericPath,
         data: &AttrDataType,
         attr_id: u16,
-        skip_error: bool,
-    ) {
-        let result = match data {
+        timed: bool,
+    ) -> Result<(), IMStatusCode> {
+        match data {
             AttrDataType::Closure(_) => {
                 error!("Not supported");
                 Err(IMStatusCode::Failure)
             }
-            AttrDataType::Tlv(t) => c.write_attribute(t, attr_id),
-        };
-        if skip_error && result.is_err() {
-            // For wildcard scenarios
-            return;
+            AttrDataType::Tlv(t) => {
+                let access = c.base().get_attribute_access(attr_id)?;
+                DataModel::check_access(access_req, path, access, false)?;
+                DataModel::check_timed_attr(c, attr_id, timed)?;
+                c.write_attribute(access_req.accessor(), t, attr_id)
+            }
         }
+    }
 
+    fn encode_write_status(
+        tw: &mut TLVWriter,
+        path: &GenericPath,
+        result: Result<(), IMStatusCode>,
+    ) {
         let status_code = if let Err(e) = result {
             e
         } else {
@@ -86,30 +188,82 @@ impl DataModel {
         let _ = attr_status.to_tlv(tw, TagType::Anonymous);
     }
 
+    // The accessor must have the privilege, that the access of the attribute/command requires
+    fn check_access(
+        access_req: &AccessReq,
+        path: &GenericPath,
+        access: Access,
+        is_read: bool,
+    ) -> Result<(), IMStatusCode> {
+        if access_req.allow(path, Privilege::required(access, is_read)) {
+            Ok(())
+        } else {
+            Err(IMStatusCode::UnsupportedAccess)
+        }
+    }
+
+    // Attributes with TIMED_ONLY access can only be written in a Timed Interaction
+    fn check_timed_attr(
+        c: &dyn ClusterType,
+        attr_id: u16,
+        timed: bool,
+    ) -> Result<(), IMStatusCode> {
+        match c.base().get_attribute_access(attr_id) {
+            Ok(access) if access.contains(Access::TIMED_ONLY) && !timed => {
+                Err(IMStatusCode::NeedsTimedInteraction)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Commands with TIMED_ONLY access can only be invoked in a Timed Interaction
+    fn check_timed_cmd(c: &dyn ClusterType, cmd_req: &CommandReq) -> Result<(), IMStatusCode> {
+        let cmd = cmd_req.cmd.path.leaf.unwrap_or_default() as u16;
+        if c.base()
+            .get_command_access(cmd)
+            .contains(Access::TIMED_ONLY)
+            && !cmd_req.trans.is_timed()
+        {
+            Err(IMStatusCode::NeedsTimedInteraction)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_cmd(
+        access_req: &AccessReq,
+        c: &dyn ClusterType,
+        cmd_req: &CommandReq,
+    ) -> Result<(), IMStatusCode> {
+        let cmd = cmd_req.cmd.path.leaf.unwrap_or_default() as u16;
+        let access = c.base().get_command_access(cmd);
+        DataModel::check_access(access_req, &cmd_req.cmd.path, access, false)?;
+        DataModel::check_timed_cmd(c, cmd_req)
+    }
+
     // Encode a write attribute from a path that may or may not be wildcard
     fn handle_write_attr_path(
         node: &mut RwLockWriteGuard<Box<Node>>,
+        access_req: &AccessReq,
         attr_data: &AttrData,
         tw: &mut TLVWriter,
+        timed: bool,
     ) {
         let gen_path = attr_data.path.to_gp();
         if let Ok((e, c, a)) = gen_path.not_wildcard() {
             // The non-wildcard path
-            let cluster = node.get_cluster_mut(e, c);
-            match cluster {
+            let result = match node.get_cluster_mut(e, c) {
                 Ok(cluster) => DataModel::handle_write_attr_data(
+                    access_req,
                     cluster,
-                    tw,
                     &gen_path,
                     &attr_data.data,
                     a as u16,
-                    false,
+                    timed,
                 ),
-                Err(e) => {
-                    let attr_status = ib::AttrStatus::new(&gen_path, e.into(), 0);
-                    let _ = attr_status.to_tlv(tw, TagType::Anonymous);
-                }
-            }
+                Err(e) => Err(e.into()),
+            };
+            DataModel::encode_write_status(tw, &gen_path, result);
         } else {
             // The wildcard path
             if attr_data.path.cluster.is_none() || attr_data.path.attr.is_none() {
@@ -126,35 +280,168 @@ impl DataModel {
             // The wildcard path
             node.for_each_cluster_mut(&gen_path, |path, c| {
                 let attr_id = if let Some(a) = path.leaf { a } else { 0 } as u16;
-                DataModel::handle_write_attr_data(c, tw, path, &attr_data.data, attr_id, true);
+                let result = DataModel::handle_write_attr_data(
+                    access_req,
+                    c,
+                    path,
+                    &attr_data.data,
+                    attr_id,
+                    timed,
+                );
+                // For wildcard scenarios, errors are not encoded
+                if result.is_ok() {
+                    DataModel::encode_write_status(tw, path, result);
+                }
             });
         }
     }
 
+    // A report that didn't fit in the ReportData is rewound. Returns false if it didn't
+    fn rewind_if_overflowed(tw: &mut TLVWriter, anchor: usize) -> bool {
+        if tw.is_overflowed() {
+            tw.rewind_to(anchor);
+            tw.clear_overflow();
+            false
+        } else {
+            true
+        }
+    }
+
     // A valid attribute on a valid cluster should be encoded. Both wildcard and non-wildcard paths end up calling this API
     // Note that it is possibe that some internal checks don't match even at this stage (read on a write-only attribute, invalid attr-id).
     // If there was an error, we rewind, so the TLVWriter doesn't include any half-baked data about the 'AttrData' IB
     fn handle_read_attr_data(
+        ctx: &mut ReportCtx,
         c: &dyn ClusterType,
         tw: &mut TLVWriter,
         path: AttrPath,
         attr_id: u16,
     ) -> Result<(), IMStatusCode> {
+        let access = c.base().get_attribute_access(attr_id)?;
+        DataModel::check_access(ctx.access_req, &path.to_gp(), access, true)?;
+
+        let attr = AttrDetails {
+            attr_id,
+            fab_idx: ctx.access_req.accessor().fab_idx,
+            fab_filter: ctx.fab_filter && access.contains(Access::FAB_SCOPED),
+            fab_sensitive: access.contains(Access::FAB_SENSITIVE),
+        };
+        if ctx.cursor.list_idx.is_some() {
+            return DataModel::handle_read_list_entries(ctx, c, tw, path, &attr);
+        }
         let anchor = tw.get_tail();
-        let data = |tag: TagType, tw: &mut TLVWriter| Cluster::read_attribute(c, tag, tw, attr_id);
+        let data = |tag: TagType, tw: &mut TLVWriter| Cluster::read_attribute(c, tag, tw, &attr);
 
         let attr_resp =
             ib::AttrResp::new(c.base().get_dataver(), &path, AttrDataType::Closure(&data));
         let result = attr_resp.write_tlv(tw, TagType::Anonymous);
+        if !DataModel::rewind_if_overflowed(tw, anchor) {
+            if anchor == ctx.start {
+                // It doesn't fit even in a ReportData of its own, a list is split into its entries
+                return DataModel::handle_read_list_entries(ctx, c, tw, path, &attr);
+            }
+            ctx.full = true;
+            return Ok(());
+        }
         if result.is_err() {
             tw.rewind_to(anchor);
         }
         result
     }
 
-    // Encode a read attribute from a path that may or may not be wildcard
+    // A list attribute that is too large for a ReportData is reported as an empty list,
+    // followed by its entries, that are appended to it one at a time (with a null list index)
+    fn handle_read_list_entries(
+        ctx: &mut ReportCtx,
+        c: &dyn ClusterType,
+        tw: &mut TLVWriter,
+        mut path: AttrPath,
+        attr: &AttrDetails,
+    ) -> Result<(), IMStatusCode> {
+        // This is only put back, if the entries don't all fit in this ReportData
+        let list_idx = ctx.cursor.list_idx.take();
+        let mut buf = vec![0; MAX_LIST_ATTR_LEN];
+        let buf_len = buf.len();
+        let mut wb = WriteBuf::new(&mut buf, buf_len);
+        let mut list_tw = TLVWriter::new(&mut wb);
+        Cluster::read_attribute(c, TagType::Anonymous, &mut list_tw, attr)?;
+        if list_tw.is_overflowed() {
+            error!("Attribute {:?} is too large to be reported", path);
+            return Err(IMStatusCode::ResourceExhausted);
+        }
+        let list = get_root_node(wb.as_borrow_slice())
+            .and_then(|l| l.confirm_array())
+            .map_err(|_| {
+                error!("Attribute {:?} doesn't fit in a ReportData", path);
+                IMStatusCode::ResourceExhausted
+            })?;
+        let dataver = c.base().get_dataver();
+
+        let list_idx = match list_idx {
+            Some(list_idx) => list_idx,
+            None => {
+                let anchor = tw.get_tail();
+                let empty = |tag: TagType, tw: &mut TLVWriter| {
+                    let _ = tw.start_array(tag);
+                    let _ = tw.end_container();
+                    Ok(())
+                };
+                let attr_resp = ib::AttrResp::new(dataver, &path, AttrDataType::Closure(&empty));
+                let _ = attr_resp.write_tlv(tw, TagType::Anonymous);
+                if !DataModel::rewind_if_overflowed(tw, anchor) {
+                    return Err(IMStatusCode::ResourceExhausted);
+                }
+                0
+            }
+        };
+
+        path.list_index = Some(Nullable::Null);
+        for (idx, entry) in list.iter().into_iter().flatten().enumerate().skip(list_idx) {
+            let anchor = tw.get_tail();
+            let data = |tag: TagType, tw: &mut TLVWriter| {
+                entry.to_tlv(tw, tag).map_err(|_| IMStatusCode::Failure)
+            };
+            let attr_resp = ib::AttrResp::new(dataver, &path, AttrDataType::Closure(&data));
+            let _ = attr_resp.write_tlv(tw, TagType::Anonymous);
+            if !DataModel::rewind_if_overflowed(tw, anchor) {
+                if anchor == ctx.start {
+                    // The requester is told, rather than silently missing the entry
+                    error!(
+                        "Entry {} of attribute {:?} doesn't fit in a ReportData",
+                        idx, path
+                    );
+                    let attr_status =
+                        ib::AttrStatus::new(&path.to_gp(), IMStatusCode::ResourceExhausted, 0);
+                    let _ = ib::AttrResp::Status(attr_status).to_tlv(tw, TagType::Anonymous);
+                    continue;
+                }
+                ctx.cursor.list_idx = Some(idx);
+                ctx.full = true;
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    // The requester already has the current data of a cluster, if one of its Data Version
+    // Filters matches the cluster's data version
+    fn dataver_matches(
+        dataver_filters: &[DataVersionFilter],
+        endpoint: u16,
+        c: &dyn ClusterType,
+    ) -> bool {
+        dataver_filters.iter().any(|f| {
+            f.path.endpoint == endpoint
+                && f.path.cluster == c.base().id()
+                && f.data_ver == c.base().get_dataver()
+        })
+    }
+
+    // Encode a read attribute from a path that may or may not be wildcard. This stops once
+    // the ReportData is full, the cursor then has the attribute to resume from
     fn handle_read_attr_path(
         node: &RwLockReadGuard<Box<Node>>,
+        ctx: &mut ReportCtx,
         attr_path: AttrPath,
         tw: &mut TLVWriter,
     ) {
@@ -163,39 +450,145 @@ impl DataModel {
             // The non-wildcard path
             let cluster = node.get_cluster(e, c);
             let result = match cluster {
-                Ok(cluster) => DataModel::handle_read_attr_data(cluster, tw, attr_path, a as u16),
+                Ok(cluster) if DataModel::dataver_matches(ctx.dataver_filters, e, cluster) => {
+                    Ok(())
+                }
+                Ok(cluster) => {
+                    DataModel::handle_read_attr_data(ctx, cluster, tw, attr_path, a as u16)
+                }
                 Err(e) => Err(e.into()),
             };
 
             if let Err(e) = result {
+                let anchor = tw.get_tail();
                 let attr_status = ib::AttrStatus::new(&gen_path, e, 0);
                 let attr_resp = ib::AttrResp::Status(attr_status);
                 let _ = attr_resp.to_tlv(tw, TagType::Anonymous);
+                if !DataModel::rewind_if_overflowed(tw, anchor) {
+                    ctx.full = true;
+                }
             }
         } else {
             // The wildcard path
+            let mut attr_idx = 0;
             node.for_each_attribute(&gen_path, |path, c| {
-                let attr_id = if let Some(a) = path.leaf { a } else { 0 } as u16;
-                let path = ib::AttrPath::new(path);
-                // Note: In the case of wildcard scenario, we do NOT encode AttrStatus in case of errors
-                // This is as per the spec, because we don't want ot encode UnsupportedRead/UnsupportedWrite type of errors
-
-                // TODO: It is likely that there may be genuine cases where the error code needs to be encoded
-                // in this response. If such a thing is desirable, we'll have to make the wildcard traversal
-                // routines 'Access' aware, so that they only provide attributes that are compatible with the
-                // operation under consideration (Access:RV for read, Access:W*for write)
-                let _ = DataModel::handle_read_attr_data(c, tw, path, attr_id);
+                let idx = attr_idx;
+                attr_idx += 1;
+                if ctx.full || idx < ctx.cursor.leaf_idx {
+                    return;
+                }
+                let endpoint = path.endpoint.unwrap_or_default();
+                if !DataModel::dataver_matches(ctx.dataver_filters, endpoint, c) {
+                    let attr_id = if let Some(a) = path.leaf { a } else { 0 } as u16;
+                    let path = ib::AttrPath::new(path);
+                    // Note: In the case of wildcard scenario, we do NOT encode AttrStatus in case of errors
+                    // This is as per the spec, because we don't want ot encode UnsupportedRead/UnsupportedWrite type of errors
+
+                    // TODO: It is likely that there may be genuine cases where the error code needs to be encoded
+                    // in this response. If such a thing is desirable, we'll have to make the wildcard traversal
+                    // routines 'Access' aware, so that they only provide attributes that are compatible with the
+                    // operation under consideration (Access:RV for read, Access:W*for write)
+                    let _ = DataModel::handle_read_attr_data(ctx, c, tw, path, attr_id);
+                }
+                if !ctx.full {
+                    ctx.cursor.leaf_idx = idx + 1;
+                }
             });
         }
     }
 
+    fn handle_read_event_path(
+        &self,
+        node: &RwLockReadGuard<Box<Node>>,
+        ctx: &mut ReportCtx,
+        event_path: &EventPath,
+        event_min: u64,
+        tw: &mut TLVWriter,
+    ) -> Result<(), Error> {
+        let gen_path = event_path.to_gp();
+        if let (Some(e), Some(c)) = (event_path.endpoint, event_path.cluster) {
+            // The endpoint and cluster must exist for a non-wildcard path
+            let status = match node.get_cluster(e, c) {
+                Err(err) => Some(err.into()),
+                Ok(_) if !ctx.access_req.allow(&gen_path, Privilege::View) => {
+                    Some(IMStatusCode::UnsupportedAccess)
+                }
+                Ok(_) => None,
+            };
+            if let Some(status) = status {
+                let anchor = tw.get_tail();
+                let _ = events::write_event_status(tw, &gen_path, status);
+                if !DataModel::rewind_if_overflowed(tw, anchor) {
+                    ctx.full = true;
+                }
+                return Ok(());
+            }
+        }
+        // Events of clusters that the accessor can't view are skipped silently
+        let access_req = ctx.access_req;
+        let done = self.events.read(
+            event_path,
+            event_min,
+            ctx.cursor.leaf_idx,
+            ctx.start,
+            tw,
+            |e, c| access_req.allow(&GenericPath::new(Some(e), Some(c), None), Privilege::View),
+        )?;
+        if tw.is_overflowed() {
+            tw.clear_overflow();
+            ctx.cursor.leaf_idx = done;
+            ctx.full = true;
+        }
+        Ok(())
+    }
+
+    // A group command is invoked on all the endpoints that are members of the group. The
+    // path mustn't have an endpoint, and nothing is ever responded
+    fn handle_group_command(
+        node: &mut RwLockWriteGuard<Box<Node>>,
+        access_req: &AccessReq,
+        cmd_req: &mut CommandReq,
+        group_keys: &GroupKeys,
+        group_id: u16,
+    ) {
+        if cmd_req.cmd.path.endpoint.is_some() {
+            error!("Dropping group command with an endpoint");
+            return;
+        }
+        let (cluster, cmd) = match (cmd_req.cmd.path.cluster, cmd_req.cmd.path.leaf) {
+            (Some(c), Some(cmd)) => (c, cmd as u16),
+            _ => return,
+        };
+        let fab_idx = access_req.accessor().fab_idx;
+        let endpoints = group_keys
+            .get_group_endpoints(fab_idx, group_id)
+            .unwrap_or_default();
+        for e in endpoints {
+            cmd_req.cmd.path.endpoint = Some(e);
+            if let Ok(c) = node.get_cluster_mut(e, cluster) {
+                let result = DataModel::check_cmd(access_req, c, cmd_req)
+                    .and_then(|_| c.handle_command(cmd_req));
+                if let Err(e) = result {
+                    if e != IMStatusCode::Sucess {
+                        error!("Group command {} failed: {:?}", cmd, e);
+                    }
+                }
+            }
+        }
+    }
+
     // Handle command from a path that may or may not be wildcard
-    fn handle_command_path(node: &mut RwLockWriteGuard<Box<Node>>, cmd_req: &mut CommandReq) {
+    fn handle_command_path(
+        node: &mut RwLockWriteGuard<Box<Node>>,
+        access_req: &AccessReq,
+        cmd_req: &mut CommandReq,
+    ) {
         if let Ok((e, c, _cmd)) = cmd_req.cmd.path.not_wildcard() {
             // The non-wildcard path
             let cluster = node.get_cluster_mut(e, c);
             let result: Result<(), IMStatusCode> = match cluster {
-                Ok(cluster) => cluster.handle_command(cmd_req),
+                Ok(cluster) => DataModel::check_cmd(access_req, cluster, cmd_req)
+                    .and_then(|_| cluster.handle_command(cmd_req)),
                 Err(e) => Err(e.into()),
             };
 
@@ -209,7 +602,8 @@ impl DataModel {
             let path = cmd_req.cmd.path;
             node.for_each_cluster_mut(&path, |path, c| {
                 cmd_req.cmd.path = *path;
-                let result = c.handle_command(cmd_req);
+                let result = DataModel::check_cmd(access_req, c, cmd_req)
+                    .and_then(|_| c.handle_command(cmd_req));
                 if let Err(e) = result {
                     // It is likely that we might have to do an 'Access' aware traversal
                     // if there are other conditions in the wildcard scenario that shouldn't be
@@ -229,6 +623,11 @@ impl Clone for DataModel {
     fn clone(&self) -> Self {
         DataModel {
             node: self.node.clone(),
+            events: self.events.clone(),
+            acl_mgr: self.acl_mgr.clone(),
+            group_keys: self.group_keys.clone(),
+            failsafe: self.failsafe.clone(),
+            attr_changes: self.attr_changes.clone(),
         }
     }
 }
@@ -241,37 +640,136 @@ impl objects::ChangeConsumer for DataModel {
 }
 
 impl InteractionConsumer for DataModel {
-    fn consume_write_attr(&self, write_req: &WriteReq, tw: &mut TLVWriter) -> Result<(), Error> {
+    fn consume_write_attr(
+        &self,
+        accessor: &Accessor,
+        write_req: &WriteReq,
+        tw: &mut TLVWriter,
+    ) -> Result<(), Error> {
         let mut node = self.node.write().unwrap();
+        let access_req = AccessReq::new(accessor, &self.acl_mgr);
+        // The Interaction Model has already validated the timed interaction
+        let timed = write_req.timed_request == Some(true);
 
         tw.start_array(TagType::Context(msg::WriteRespTag::WriteResponses as u8))?;
         for attr_data in write_req.write_requests.iter() {
-            DataModel::handle_write_attr_path(&mut node, &attr_data, tw);
+            DataModel::handle_write_attr_path(&mut node, &access_req, &attr_data, tw, timed);
         }
+        let changes = node.take_attr_changes();
+        drop(node);
+        self.notify_attr_changes(changes);
         tw.end_container()?;
 
         Ok(())
     }
 
-    fn consume_read_attr(&self, read_req: &ReadReq, tw: &mut TLVWriter) -> Result<(), Error> {
-        if read_req.fabric_filtered {
-            error!("Fabric scoped attribute read not yet supported");
-        }
-        if read_req.dataver_filters.is_some() {
-            error!("Data Version Filter not yet supported");
-        }
+    fn consume_read_attr(
+        &self,
+        accessor: &Accessor,
+        read_req: &ReadReq,
+        cursor: ReadCursor,
+        tw: &mut TLVWriter,
+    ) -> Result<Option<ReadCursor>, Error> {
+        // The clusters that the requester already has the current data of, are skipped
+        let dataver_filters: Vec<DataVersionFilter> = read_req
+            .dataver_filters
+            .as_ref()
+            .map_or_else(Vec::new, |f| f.iter().collect());
 
         let node = self.node.read().unwrap();
+        let access_req = AccessReq::new(accessor, &self.acl_mgr);
+        let mut ctx = ReportCtx {
+            access_req: &access_req,
+            fab_filter: read_req.fabric_filtered,
+            dataver_filters: &dataver_filters,
+            start: 0,
+            cursor,
+            full: false,
+        };
+        let mut path_idx = 0;
+        let mut attrs_reported = false;
         if let Some(attr_requests) = &read_req.attr_requests {
             tw.start_array(TagType::Context(msg::ReportDataTag::AttributeReports as u8))?;
+            // The array is closed, even once the reports fill up the ReportData
+            tw.shrink(1)?;
+            ctx.start = tw.get_tail();
 
             for attr_path in attr_requests.iter() {
-                DataModel::handle_read_attr_path(&node, attr_path, tw);
+                if path_idx >= ctx.cursor.path_idx {
+                    DataModel::handle_read_attr_path(&node, &mut ctx, attr_path, tw);
+                    if ctx.full {
+                        break;
+                    }
+                    ctx.cursor = ReadCursor {
+                        path_idx: path_idx + 1,
+                        ..Default::default()
+                    };
+                }
+                path_idx += 1;
             }
 
+            attrs_reported = tw.get_tail() != ctx.start;
+            tw.expand(1)?;
             tw.end_container()?;
         }
-        Ok(())
+        if ctx.full {
+            return Ok(Some(ctx.cursor));
+        }
+        if let Some(event_requests) = &read_req.event_requests {
+            // Only events with an event number of at least EventMin are reported
+            let event_min = read_req
+                .event_filters
+                .as_ref()
+                .and_then(|f| f.iter().filter_map(|f| f.event_min).max())
+                .unwrap_or(0);
+            let anchor = tw.get_tail();
+            let started = tw
+                .start_array(TagType::Context(msg::ReportDataTag::EventReports as u8))
+                .and_then(|_| tw.shrink(1));
+            if started.is_err() {
+                // The attribute reports filled up the ReportData
+                tw.rewind_to(anchor);
+                tw.clear_overflow();
+                return Ok(Some(ctx.cursor));
+            }
+            // After some attribute reports, an event that doesn't fit goes in the next ReportData
+            if !attrs_reported {
+                ctx.start = tw.get_tail();
+            }
+
+            for event_path in event_requests.iter() {
+                if path_idx >= ctx.cursor.path_idx {
+                    self.handle_read_event_path(&node, &mut ctx, &event_path, event_min, tw)?;
+                    if ctx.full {
+                        break;
+                    }
+                    ctx.cursor = ReadCursor {
+                        path_idx: path_idx + 1,
+                        ..Default::default()
+                    };
+                }
+                path_idx += 1;
+            }
+
+            tw.expand(1)?;
+            tw.end_container()?;
+        }
+        Ok(if ctx.full { Some(ctx.cursor) } else { None })
+    }
+
+    fn get_next_event_no(&self) -> u64 {
+        self.events.get_next_event_no()
+    }
+
+    fn get_dataver_digest(&self, attr_paths: &[AttrPath]) -> u32 {
+        let node = self.node.read().unwrap();
+        let mut digest = 0_u32;
+        for attr_path in attr_paths {
+            node.for_each_cluster(&attr_path.to_gp(), |_, c| {
+                digest = digest.wrapping_add(c.base().get_dataver());
+            });
+        }
+        digest
     }
 
     fn consume_invoke_cmd(
@@ -283,6 +781,8 @@ impl InteractionConsumer for DataModel {
     ) -> Result<(), Error> {
         info!("Invoke Commmand Handler executing: {:?}", cmd_path_ib);
 
+        let accessor = Accessor::new(trans.session);
+        let access_req = AccessReq::new(&accessor, &self.acl_mgr);
         let mut cmd_req = CommandReq {
             cmd: *cmd_path_ib,
             data,
@@ -291,7 +791,20 @@ impl InteractionConsumer for DataModel {
         };
 
         let mut node = self.node.write().unwrap();
-        DataModel::handle_command_path(&mut node, &mut cmd_req);
+        if let Some(group_id) = accessor.group_id() {
+            DataModel::handle_group_command(
+                &mut node,
+                &access_req,
+                &mut cmd_req,
+                &self.group_keys,
+                group_id,
+            );
+        } else {
+            DataModel::handle_command_path(&mut node, &access_req, &mut cmd_req);
+        }
+        let changes = node.take_attr_changes();
+        drop(node);
+        self.notify_attr_changes(changes);
 
         Ok(())
     }
@@ -1,23 +1,33 @@
 use super::cluster_basic_information::BasicInfoCluster;
 use super::cluster_basic_information::BasicInfoConfig;
+use super::cluster_groups::GroupsCluster;
 use super::cluster_on_off::OnOffCluster;
+use super::core::DataModel;
 use super::objects::*;
+use super::sdm::admin_commissioning::AdminCommCluster;
 use super::sdm::dev_att::DevAttDataFetcher;
 use super::sdm::general_commissioning::GenCommCluster;
+use super::sdm::group_key_management::GrpKeyMgmtCluster;
 use super::sdm::noc::NocCluster;
 use super::sdm::nw_commissioning::NwCommCluster;
+use super::system_model::access_control::AccessControlCluster;
 use crate::error::*;
 use crate::fabric::FabricMgr;
+use crate::group_keys::GroupKeys;
+use crate::secure_channel::pake::PaseMgr;
 use std::sync::Arc;
 use std::sync::RwLockWriteGuard;
 
 type WriteNode<'a> = RwLockWriteGuard<'a, Box<Node>>;
 
+/// Add the root endpoint to the node of 'dm', its clusters share the managers of 'dm'
 pub fn device_type_add_root_node(
     node: &mut WriteNode,
+    dm: &DataModel,
     dev_info: BasicInfoConfig,
     dev_att: Box<dyn DevAttDataFetcher>,
     fabric_mgr: Arc<FabricMgr>,
+    pase_mgr: PaseMgr,
 ) -> Result<u32, Error> {
     // Add the root endpoint
     let endpoint = node.add_endpoint()?;
@@ -27,16 +37,33 @@ pub fn device_type_add_root_node(
     };
     // Add the mandatory clusters
     node.add_cluster(0, BasicInfoCluster::new(dev_info)?)?;
-    let general_commissioning = GenCommCluster::new()?;
-    let failsafe = general_commissioning.failsafe();
-    node.add_cluster(0, general_commissioning)?;
+    node.add_cluster(0, GenCommCluster::new(dm.failsafe.clone())?)?;
     node.add_cluster(0, NwCommCluster::new()?)?;
-    node.add_cluster(0, NocCluster::new(dev_att, fabric_mgr, failsafe)?)?;
+    node.add_cluster(
+        0,
+        NocCluster::new(
+            dev_att,
+            fabric_mgr.clone(),
+            dm.acl_mgr.clone(),
+            dm.group_keys.clone(),
+            dm.failsafe.clone(),
+        )?,
+    )?;
+    node.add_cluster(
+        0,
+        AdminCommCluster::new(pase_mgr, fabric_mgr, dm.failsafe.clone())?,
+    )?;
+    node.add_cluster(0, AccessControlCluster::new(dm.acl_mgr.clone())?)?;
+    node.add_cluster(0, GrpKeyMgmtCluster::new(dm.group_keys.clone())?)?;
     Ok(endpoint)
 }
 
-pub fn device_type_add_on_off_light(node: &mut WriteNode) -> Result<u32, Error> {
+pub fn device_type_add_on_off_light(
+    node: &mut WriteNode,
+    group_keys: Arc<GroupKeys>,
+) -> Result<u32, Error> {
     let endpoint = node.add_endpoint()?;
+    node.add_cluster(endpoint, GroupsCluster::new(group_keys)?)?;
     node.add_cluster(endpoint, OnOffCluster::new()?)?;
     Ok(endpoint)
 }
@@ -0,0 +1,243 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, RwLock},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use log::error;
+
+use crate::{
+    error::Error,
+    interaction_model::{
+        core::IMStatusCode,
+        messages::{
+            ib::{EventDataTag, EventPath, EventRespTag, EventStatus},
+            GenericPath,
+        },
+    },
+    persist::KvStore,
+    tlv::{TLVWriter, TagType, ToTLV},
+    utils::writebuf::WriteBuf,
+};
+
+/* Event Log
+ *
+ * - Events are logged in a separate bounded queue for each priority, so that a burst of
+ *   Debug events doesn't push out the Critical ones. Once a queue is full, the oldest
+ *   event of that priority is dropped
+ * - Event numbers are allocated from a single counter, across all the priorities. They
+ *   keep increasing across reboots: the counter reserves its numbers in the storage, an
+ *   epoch at a time, and resumes after the reserved numbers on boot
+ * - An event carries an Epoch Timestamp, once the clock is set, or else a System Timestamp
+ * - The EventDataIB is encoded at the time the event is emitted, and is copied as is
+ *   into the EventReportIBs
+ */
+
+pub const MAX_EVENTS_PER_PRIORITY: usize = 8;
+const MAX_EVENT_SIZE: usize = 256;
+const NUM_PRIORITIES: usize = 3;
+// The number of event numbers that are reserved in the storage at a time
+const EVENT_NO_EPOCH: u64 = 0x1000;
+// The clock is taken as not set, while it is before 2000-01-01
+const MIN_EPOCH_SECS: u64 = 946_684_800;
+
+const ST_EVENT_NO: &str = "event_no";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventPriority {
+    Debug = 0,
+    Info = 1,
+    Critical = 2,
+}
+
+pub type EventDataGen<'a> = &'a dyn Fn(TagType, &mut TLVWriter) -> Result<(), Error>;
+
+struct Event {
+    endpoint: u16,
+    cluster: u32,
+    event_id: u32,
+    event_no: u64,
+    // The encoded EventDataIB, with the EventReportIB's context tag
+    data: Vec<u8>,
+}
+
+impl Event {
+    fn matches(&self, path: &EventPath) -> bool {
+        // A field that isn't set in the path is a wildcard
+        (path.endpoint.is_none() || path.endpoint == Some(self.endpoint))
+            && (path.cluster.is_none() || path.cluster == Some(self.cluster))
+            && (path.event.is_none() || path.event == Some(self.event_id))
+    }
+}
+
+struct EventLogInner {
+    next_event_no: u64,
+    // The event numbers below this are reserved in the storage
+    reserved_event_no: u64,
+    events: [VecDeque<Event>; NUM_PRIORITIES],
+}
+
+pub struct EventLog {
+    // The System Timestamp is the time since boot
+    boot_time: Instant,
+    store: Arc<dyn KvStore>,
+    inner: RwLock<EventLogInner>,
+}
+
+impl EventLog {
+    /// Create the Event Log, the event numbers resume after the ones that were reserved in
+    /// 'store' before the reboot
+    pub fn new(store: Arc<dyn KvStore>) -> Result<Self, Error> {
+        let mut next_event_no = 0;
+        match store.get_kv_u64(ST_EVENT_NO, &mut next_event_no) {
+            Ok(()) | Err(Error::NotFound) => (),
+            Err(e) => return Err(e),
+        }
+        let reserved_event_no = next_event_no + EVENT_NO_EPOCH;
+        store.set_kv_u64(ST_EVENT_NO, reserved_event_no)?;
+        Ok(Self {
+            boot_time: Instant::now(),
+            store,
+            inner: RwLock::new(EventLogInner {
+                next_event_no,
+                reserved_event_no,
+                events: Default::default(),
+            }),
+        })
+    }
+
+    /// Log an event, the 'data' closure encodes the event's fields. Returns the event number
+    /// that was allocated to the event
+    pub fn emit(
+        &self,
+        endpoint: u16,
+        cluster: u32,
+        event_id: u32,
+        priority: EventPriority,
+        data: EventDataGen,
+    ) -> Result<u64, Error> {
+        let mut inner = self.inner.write()?;
+        let event_no = inner.next_event_no;
+        if event_no >= inner.reserved_event_no {
+            self.store
+                .set_kv_u64(ST_EVENT_NO, inner.reserved_event_no + EVENT_NO_EPOCH)?;
+            inner.reserved_event_no += EVENT_NO_EPOCH;
+        }
+
+        let mut buf = [0u8; MAX_EVENT_SIZE];
+        let buf_len = buf.len();
+        let mut wb = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut wb);
+        tw.start_struct(TagType::Context(EventRespTag::Data as u8))?;
+        let path = GenericPath::new(Some(endpoint), Some(cluster), Some(event_id));
+        EventPath::new(&path).to_tlv(&mut tw, TagType::Context(EventDataTag::Path as u8))?;
+        tw.u64(TagType::Context(EventDataTag::EventNumber as u8), event_no)?;
+        tw.u8(
+            TagType::Context(EventDataTag::Priority as u8),
+            priority as u8,
+        )?;
+        match epoch_timestamp() {
+            Some(timestamp) => tw.u64(
+                TagType::Context(EventDataTag::EpochTimestamp as u8),
+                timestamp,
+            )?,
+            None => tw.u64(
+                TagType::Context(EventDataTag::SystemTimestamp as u8),
+                self.boot_time.elapsed().as_millis() as u64,
+            )?,
+        }
+        data(TagType::Context(EventDataTag::Data as u8), &mut tw)?;
+        tw.end_container()?;
+
+        inner.next_event_no += 1;
+        let queue = &mut inner.events[priority as usize];
+        if queue.len() >= MAX_EVENTS_PER_PRIORITY {
+            queue.pop_front();
+        }
+        queue.push_back(Event {
+            endpoint,
+            cluster,
+            event_id,
+            event_no,
+            data: wb.as_borrow_slice().to_vec(),
+        });
+        Ok(event_no)
+    }
+
+    /// The event number that will be allocated to the next event
+    pub fn get_next_event_no(&self) -> u64 {
+        self.inner.read().map_or(0, |i| i.next_event_no)
+    }
+
+    /// Encode EventReportIBs for all the logged events that match the path, and that have
+    /// an event number of at least 'event_min'. Only the events for which 'allow' returns
+    /// true for the (endpoint, cluster) are encoded. The events are encoded in the order in
+    /// which they were emitted, after skipping the first 'skip' of them. Returns the number
+    /// of events done, including the skipped ones, as this stops once the buffer fills up.
+    /// An event that doesn't fit even at 'start', where the reports start, is dropped
+    pub fn read<F>(
+        &self,
+        path: &EventPath,
+        event_min: u64,
+        skip: usize,
+        start: usize,
+        tw: &mut TLVWriter,
+        allow: F,
+    ) -> Result<usize, Error>
+    where
+        F: Fn(u16, u32) -> bool,
+    {
+        let inner = self.inner.read()?;
+        let mut events: Vec<&Event> = inner
+            .events
+            .iter()
+            .flatten()
+            .filter(|e| e.event_no >= event_min && e.matches(path) && allow(e.endpoint, e.cluster))
+            .collect();
+        events.sort_by_key(|e| e.event_no);
+
+        let mut done = skip;
+        for e in events.iter().skip(skip) {
+            let anchor = tw.get_tail();
+            let result = tw
+                .start_struct(TagType::Anonymous)
+                .and_then(|_| tw.raw(&e.data))
+                .and_then(|_| tw.end_container());
+            if result.is_err() {
+                tw.rewind_to(anchor);
+                if anchor != start {
+                    break;
+                }
+                // It would never fit, retrying it in the next report would loop forever
+                error!(
+                    "Event {} doesn't fit in a ReportData, dropping it",
+                    e.event_no
+                );
+                tw.clear_overflow();
+            }
+            done += 1;
+        }
+        Ok(done)
+    }
+}
+
+// The milliseconds since the Unix epoch, if the clock is set
+fn epoch_timestamp() -> Option<u64> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    if now.as_secs() < MIN_EPOCH_SECS {
+        None
+    } else {
+        Some(now.as_millis() as u64)
+    }
+}
+
+/// Encode an EventReportIB that carries an EventStatusIB
+pub fn write_event_status(
+    tw: &mut TLVWriter,
+    path: &GenericPath,
+    status: IMStatusCode,
+) -> Result<(), Error> {
+    tw.start_struct(TagType::Anonymous)?;
+    EventStatus::new(path, status, 0).to_tlv(tw, TagType::Context(EventRespTag::Status as u8))?;
+    tw.end_container()
+}
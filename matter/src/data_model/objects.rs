@@ -1,8 +1,9 @@
 use crate::{
+    acl::Accessor,
     error::*,
     interaction_model::{command::CommandReq, core::IMStatusCode, messages::GenericPath},
     // TODO: This layer shouldn't really depend on the TLV layer, should create an abstraction layer
-    tlv::{TLVElement, TLVWriter, TagType, ToTLV},
+    tlv::{ElementType, TLVElement, TLVWriter, TagType, ToTLV},
 };
 use bitflags::bitflags;
 use log::error;
@@ -44,30 +45,70 @@ bitflags! {
  * - instead of arrays, can use linked-lists to conserve space and avoid the internal fragmentation
  */
 pub const ENDPTS_PER_ACC: usize = 3;
-pub const CLUSTERS_PER_ENDPT: usize = 7;
+pub const CLUSTERS_PER_ENDPT: usize = 9;
 pub const ATTRS_PER_CLUSTER: usize = 8;
 pub const CMDS_PER_CLUSTER: usize = 8;
 
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Clone)]
 pub enum AttrValue {
+    Int8(i8),
+    Int16(i16),
+    Int32(i32),
     Int64(i64),
     Uint8(u8),
     Uint16(u16),
     Uint32(u32),
     Uint64(u64),
     Bool(bool),
+    Float(f32),
+    Double(f64),
+    Enum8(u8),
+    Enum16(u16),
+    Bitmap8(u8),
+    Bitmap16(u16),
+    Bitmap32(u32),
+    Utf8(String),
+    OctetStr(Vec<u8>),
+    /// A nullable value. While it is null, 'value' still gives the type that writes are
+    /// decoded into
+    Nullable {
+        value: Box<AttrValue>,
+        null: bool,
+    },
+    /// A list. 'entry' gives the type, that the entries of writes are decoded into
+    List {
+        entry: Box<AttrValue>,
+        values: Vec<AttrValue>,
+    },
+    /// A struct, as its fields along with their context tags
+    Struct(Vec<(u8, AttrValue)>),
     Custom,
 }
 
 impl Debug for AttrValue {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
         match &self {
+            AttrValue::Int8(v) => write!(f, "{:?}", *v),
+            AttrValue::Int16(v) => write!(f, "{:?}", *v),
+            AttrValue::Int32(v) => write!(f, "{:?}", *v),
             AttrValue::Int64(v) => write!(f, "{:?}", *v),
-            AttrValue::Uint8(v) => write!(f, "{:?}", *v),
-            AttrValue::Uint16(v) => write!(f, "{:?}", *v),
-            AttrValue::Uint32(v) => write!(f, "{:?}", *v),
+            AttrValue::Uint8(v) | AttrValue::Enum8(v) | AttrValue::Bitmap8(v) => {
+                write!(f, "{:?}", *v)
+            }
+            AttrValue::Uint16(v) | AttrValue::Enum16(v) | AttrValue::Bitmap16(v) => {
+                write!(f, "{:?}", *v)
+            }
+            AttrValue::Uint32(v) | AttrValue::Bitmap32(v) => write!(f, "{:?}", *v),
             AttrValue::Uint64(v) => write!(f, "{:?}", *v),
             AttrValue::Bool(v) => write!(f, "{:?}", *v),
+            AttrValue::Float(v) => write!(f, "{:?}", *v),
+            AttrValue::Double(v) => write!(f, "{:?}", *v),
+            AttrValue::Utf8(v) => write!(f, "{:?}", v),
+            AttrValue::OctetStr(v) => write!(f, "{:x?}", v),
+            AttrValue::Nullable { null: true, .. } => write!(f, "null"),
+            AttrValue::Nullable { value, .. } => write!(f, "{:?}", value),
+            AttrValue::List { values, .. } => write!(f, "{:?}", values),
+            AttrValue::Struct(v) => write!(f, "{:?}", v),
             AttrValue::Custom => write!(f, "custom-attribute"),
         }?;
         Ok(())
@@ -79,12 +120,52 @@ impl ToTLV for AttrValue {
         // What is the time complexity of such long match statements?
         match self {
             AttrValue::Bool(v) => tw.bool(tag_type, *v),
-            AttrValue::Uint8(v) => tw.u8(tag_type, *v),
-            AttrValue::Uint16(v) => tw.u16(tag_type, *v),
-            AttrValue::Uint32(v) => tw.u32(tag_type, *v),
+            AttrValue::Int8(v) => tw.i8(tag_type, *v),
+            AttrValue::Int16(v) => tw.i16(tag_type, *v),
+            AttrValue::Int32(v) => tw.i32(tag_type, *v),
+            AttrValue::Int64(v) => tw.i64(tag_type, *v),
+            AttrValue::Uint8(v) | AttrValue::Enum8(v) | AttrValue::Bitmap8(v) => {
+                tw.u8(tag_type, *v)
+            }
+            AttrValue::Uint16(v) | AttrValue::Enum16(v) | AttrValue::Bitmap16(v) => {
+                tw.u16(tag_type, *v)
+            }
+            AttrValue::Uint32(v) | AttrValue::Bitmap32(v) => tw.u32(tag_type, *v),
             AttrValue::Uint64(v) => tw.u64(tag_type, *v),
-            _ => {
-                error!("Attribute type not yet supported");
+            AttrValue::Float(v) => tw.f32(tag_type, *v),
+            AttrValue::Double(v) => tw.f64(tag_type, *v),
+            AttrValue::Utf8(v) => {
+                if v.len() > 255 {
+                    tw.utf16(tag_type, v.as_bytes())
+                } else {
+                    tw.utf8(tag_type, v.as_bytes())
+                }
+            }
+            AttrValue::OctetStr(v) => {
+                if v.len() > 255 {
+                    tw.str16(tag_type, v)
+                } else {
+                    tw.str8(tag_type, v)
+                }
+            }
+            AttrValue::Nullable { null: true, .. } => tw.null(tag_type),
+            AttrValue::Nullable { value, .. } => value.to_tlv(tw, tag_type),
+            AttrValue::List { values, .. } => {
+                tw.start_array(tag_type)?;
+                for e in values {
+                    e.to_tlv(tw, TagType::Anonymous)?;
+                }
+                tw.end_container()
+            }
+            AttrValue::Struct(v) => {
+                tw.start_struct(tag_type)?;
+                for (tag, e) in v {
+                    e.to_tlv(tw, TagType::Context(*tag))?;
+                }
+                tw.end_container()
+            }
+            AttrValue::Custom => {
+                error!("Custom attributes are encoded by their cluster");
                 Err(Error::AttributeNotFound)
             }
         }
@@ -92,19 +173,140 @@ impl ToTLV for AttrValue {
 }
 
 impl AttrValue {
-    fn update_from_tlv(&mut self, tr: &TLVElement) -> Result<(), Error> {
+    /// A nullable value of the type of 'value', that is null
+    pub fn null(value: AttrValue) -> Self {
+        AttrValue::Nullable {
+            value: Box::new(value),
+            null: true,
+        }
+    }
+
+    /// A nullable value, that is currently set to 'value'
+    pub fn nullable(value: AttrValue) -> Self {
+        AttrValue::Nullable {
+            value: Box::new(value),
+            null: false,
+        }
+    }
+
+    /// A list of entries of the type of 'entry'
+    pub fn list(entry: AttrValue, values: Vec<AttrValue>) -> Self {
+        AttrValue::List {
+            entry: Box::new(entry),
+            values,
+        }
+    }
+
+    // The value of an integer, enum or bitmap
+    fn as_integer(&self) -> Option<i128> {
         match self {
-            AttrValue::Bool(v) => *v = tr.bool()?,
-            AttrValue::Uint8(v) => *v = tr.u8()?,
-            AttrValue::Uint16(v) => *v = tr.u16()?,
-            AttrValue::Uint32(v) => *v = tr.u32()?,
-            AttrValue::Uint64(v) => *v = tr.u64()?,
-            _ => {
-                error!("Attribute type not yet supported");
+            AttrValue::Int8(v) => Some((*v).into()),
+            AttrValue::Int16(v) => Some((*v).into()),
+            AttrValue::Int32(v) => Some((*v).into()),
+            AttrValue::Int64(v) => Some((*v).into()),
+            AttrValue::Uint8(v) | AttrValue::Enum8(v) | AttrValue::Bitmap8(v) => Some((*v).into()),
+            AttrValue::Uint16(v) | AttrValue::Enum16(v) | AttrValue::Bitmap16(v) => {
+                Some((*v).into())
+            }
+            AttrValue::Uint32(v) | AttrValue::Bitmap32(v) => Some((*v).into()),
+            AttrValue::Uint64(v) => Some((*v).into()),
+            _ => None,
+        }
+    }
+
+    // Decode the TLV into a value of the same type as this one
+    fn decode_as(&self, tr: &TLVElement) -> Result<AttrValue, Error> {
+        let value = match self {
+            AttrValue::Bool(_) => AttrValue::Bool(tr.bool()?),
+            AttrValue::Int8(_) => AttrValue::Int8(tr.i8()?),
+            AttrValue::Int16(_) => AttrValue::Int16(tr.i16()?),
+            AttrValue::Int32(_) => AttrValue::Int32(tr.i32()?),
+            AttrValue::Int64(_) => AttrValue::Int64(tr.i64()?),
+            AttrValue::Uint8(_) => AttrValue::Uint8(tr.u8()?),
+            AttrValue::Uint16(_) => AttrValue::Uint16(tr.u16()?),
+            AttrValue::Uint32(_) => AttrValue::Uint32(tr.u32()?),
+            AttrValue::Uint64(_) => AttrValue::Uint64(tr.u64()?),
+            AttrValue::Float(_) => AttrValue::Float(tr.f32()?),
+            AttrValue::Double(_) => AttrValue::Double(tr.f64()?),
+            AttrValue::Enum8(_) => AttrValue::Enum8(tr.u8()?),
+            AttrValue::Enum16(_) => AttrValue::Enum16(tr.u16()?),
+            AttrValue::Bitmap8(_) => AttrValue::Bitmap8(tr.u8()?),
+            AttrValue::Bitmap16(_) => AttrValue::Bitmap16(tr.u16()?),
+            AttrValue::Bitmap32(_) => AttrValue::Bitmap32(tr.u32()?),
+            AttrValue::Utf8(_) => match tr.get_element_type() {
+                ElementType::Utf8l(s) | ElementType::Utf16l(s) => {
+                    AttrValue::Utf8(String::from_utf8(s.to_vec()).map_err(|_| Error::InvalidData)?)
+                }
+                _ => return Err(Error::TLVTypeMismatch),
+            },
+            AttrValue::OctetStr(_) => match tr.get_element_type() {
+                ElementType::Str8l(s) | ElementType::Str16l(s) => AttrValue::OctetStr(s.to_vec()),
+                _ => return Err(Error::TLVTypeMismatch),
+            },
+            AttrValue::Nullable { value, .. } => {
+                if tr.is_null() {
+                    AttrValue::null(value.as_ref().clone())
+                } else {
+                    AttrValue::nullable(value.decode_as(tr)?)
+                }
+            }
+            AttrValue::List { entry, .. } => {
+                let mut values = Vec::new();
+                if let Some(iter) = tr.confirm_array()?.iter() {
+                    for e in iter {
+                        values.push(entry.decode_as(&e)?);
+                    }
+                }
+                AttrValue::list(entry.as_ref().clone(), values)
+            }
+            AttrValue::Struct(v) => {
+                let tr = tr.confirm_struct()?;
+                let mut fields = Vec::with_capacity(v.len());
+                for (tag, field) in v {
+                    fields.push((*tag, field.decode_as(&tr.find_tag(*tag as u32)?)?));
+                }
+                AttrValue::Struct(fields)
+            }
+            AttrValue::Custom => {
+                error!("Custom attributes are decoded by their cluster");
                 return Err(Error::AttributeNotFound);
             }
+        };
+        Ok(value)
+    }
+}
+
+/// A constraint on the value of an attribute, that writes are checked against. The
+/// constraint applies to the value of a nullable attribute, if it isn't null
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constraint {
+    /// The integer value is at least 'min' and at most 'max'
+    Range { min: i64, max: i64 },
+    /// The integer value is one of these, e.g. the values that an enum defines
+    OneOf(&'static [u32]),
+    /// The length of a string, or the number of entries of a list, is at most this
+    MaxLength(usize),
+}
+
+impl Constraint {
+    fn is_met(&self, value: &AttrValue) -> bool {
+        match (self, value) {
+            (_, AttrValue::Nullable { null: true, .. }) => true,
+            (_, AttrValue::Nullable { value, .. }) => self.is_met(value),
+            (Constraint::Range { min, max }, v) => v
+                .as_integer()
+                .is_some_and(|v| v >= *min as i128 && v <= *max as i128),
+            (Constraint::OneOf(values), v) => v
+                .as_integer()
+                .is_some_and(|v| values.iter().any(|a| *a as i128 == v)),
+            (Constraint::MaxLength(max), AttrValue::Utf8(v)) => v.len() <= *max,
+            (Constraint::MaxLength(max), AttrValue::OctetStr(v)) => v.len() <= *max,
+            (Constraint::MaxLength(max), AttrValue::List { values, .. }) => values.len() <= *max,
+            _ => {
+                error!("Constraint {:?} doesn't apply to {:?}", self, value);
+                false
+            }
         }
-        Ok(())
     }
 }
 
@@ -114,6 +316,7 @@ pub struct Attribute {
     value: AttrValue,
     quality: Quality,
     access: Access,
+    constraint: Option<Constraint>,
 }
 
 impl Default for Attribute {
@@ -123,6 +326,7 @@ impl Default for Attribute {
             value: AttrValue::Bool(true),
             quality: Default::default(),
             access: Default::default(),
+            constraint: None,
         }
     }
 }
@@ -139,9 +343,33 @@ impl Attribute {
             value,
             access,
             quality,
+            constraint: None,
         })
     }
 
+    /// Set the constraint, that the written values must meet
+    pub fn set_constraint(mut self, constraint: Constraint) -> Self {
+        self.constraint = Some(constraint);
+        self
+    }
+
+    // Decode a written value into the type of the attribute, and check it against the
+    // constraint
+    fn decode_write(&self, data: &TLVElement) -> Result<AttrValue, IMStatusCode> {
+        let value = self.value.decode_as(data).map_err(|_| {
+            if data.is_null() {
+                // The attribute isn't nullable
+                IMStatusCode::ConstraintError
+            } else {
+                IMStatusCode::InvalidDataType
+            }
+        })?;
+        match &self.constraint {
+            Some(c) if !c.is_met(&value) => Err(IMStatusCode::ConstraintError),
+            _ => Ok(value),
+        }
+    }
+
     pub fn set_value(&mut self, value: AttrValue) -> Result<(), Error> {
         if !self.quality.contains(Quality::FIXED) {
             self.value = value;
@@ -167,10 +395,42 @@ pub enum GlobalElements {
     _ClusterRevision = 0xFFFD,
     FeatureMap = 0xFFFC,
     AttributeList = 0xFFFB,
-    _EventList = 0xFFFA,
+    EventList = 0xFFFA,
     _ClientGenCmd = 0xFFF9,
     ServerGenCmd = 0xFFF8,
-    _FabricIndex = 0xFE,
+    FabricIndex = 0xFE,
+}
+
+/// The details of an attribute read, as handed to the cluster
+pub struct AttrDetails {
+    pub attr_id: u16,
+    /// The fabric of the reader, 0 if it isn't on a fabric
+    pub fab_idx: u8,
+    /// If set, the fabric-scoped lists only report the entries of the reader's fabric
+    pub fab_filter: bool,
+    /// If set, the entries of the other fabrics only report their fabric index
+    pub fab_sensitive: bool,
+}
+
+impl AttrDetails {
+    /// Encode an entry of a fabric-scoped list, that belongs to the fabric 'fab_idx'. 'f'
+    /// encodes the complete entry, including its fabric index
+    pub fn write_fab_scoped<F>(&self, tw: &mut TLVWriter, fab_idx: u8, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut TLVWriter) -> Result<(), Error>,
+    {
+        if fab_idx == self.fab_idx {
+            f(tw)
+        } else if self.fab_filter {
+            Ok(())
+        } else if self.fab_sensitive {
+            tw.start_struct(TagType::Anonymous)?;
+            tw.u8(TagType::Context(GlobalElements::FabricIndex as u8), fab_idx)?;
+            tw.end_container()
+        } else {
+            f(tw)
+        }
+    }
 }
 
 pub trait ClusterType {
@@ -180,7 +440,7 @@ pub trait ClusterType {
         &self,
         _tag: TagType,
         _tw: &mut TLVWriter,
-        _attr_id: u16,
+        _attr: &AttrDetails,
     ) -> Result<(), IMStatusCode> {
         Err(IMStatusCode::UnsupportedAttribute)
     }
@@ -192,16 +452,38 @@ pub trait ClusterType {
         Err(IMStatusCode::UnsupportedCommand)
     }
 
-    fn write_attribute(&mut self, data: &TLVElement, attr_id: u16) -> Result<(), IMStatusCode> {
+    fn write_attribute(
+        &mut self,
+        _accessor: &Accessor,
+        data: &TLVElement,
+        attr_id: u16,
+    ) -> Result<(), IMStatusCode> {
         self.base_mut().write_attribute(data, attr_id)
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct Command {
+    id: u16,
+    access: Access,
+}
+
+impl Command {
+    pub fn new(id: u16, access: Access) -> Self {
+        Self { id, access }
+    }
+}
+
 pub struct Cluster {
     id: u32,
     attributes: Vec<Attribute>,
+    // Only the commands that need an access other than the default are listed here
+    commands: Vec<Command>,
+    events: Vec<u32>,
     feature_map: Option<u32>,
     data_ver: u32,
+    // The attributes that changed, that the application is yet to be notified of
+    changed_attrs: Vec<u16>,
 }
 
 impl Cluster {
@@ -209,8 +491,11 @@ impl Cluster {
         let mut c = Cluster {
             id,
             attributes: Vec::with_capacity(ATTRS_PER_CLUSTER),
+            commands: Vec::with_capacity(CMDS_PER_CLUSTER),
+            events: Vec::new(),
             feature_map: None,
             data_ver: rand::thread_rng().gen_range(0..0xFFFFFFFF),
+            changed_attrs: Vec::new(),
         };
         c.add_default_attributes()?;
         Ok(c)
@@ -224,6 +509,11 @@ impl Cluster {
         self.data_ver
     }
 
+    /// The data version must be bumped whenever any attribute of the cluster changes
+    pub fn bump_dataver(&mut self) {
+        self.data_ver = self.data_ver.wrapping_add(1);
+    }
+
     pub fn set_feature_map(&mut self, map: u32) -> Result<(), Error> {
         if self.feature_map.is_none() {
             self.add_attribute(Attribute::new(
@@ -258,6 +548,44 @@ impl Cluster {
         }
     }
 
+    pub fn add_command(&mut self, cmd: Command) -> Result<(), Error> {
+        if self.commands.len() < self.commands.capacity() {
+            self.commands.push(cmd);
+            Ok(())
+        } else {
+            Err(Error::NoSpace)
+        }
+    }
+
+    /// Declare an event that this cluster may emit. This also adds the EventList attribute
+    pub fn add_event(&mut self, event_id: u32) -> Result<(), Error> {
+        if self.events.is_empty() {
+            self.add_attribute(Attribute::new(
+                GlobalElements::EventList as u16,
+                AttrValue::Custom,
+                Access::RV,
+                Quality::NONE,
+            )?)?;
+        }
+        self.events.push(event_id);
+        Ok(())
+    }
+
+    /// Returns the access for the command. The default for commands, that weren't
+    /// explicitly added, is the Operate privilege
+    pub fn get_command_access(&self, cmd_id: u16) -> Access {
+        self.commands
+            .iter()
+            .find(|c| c.id == cmd_id)
+            .map_or(Access::NEED_OPERATE, |c| c.access)
+    }
+
+    pub fn get_attribute_access(&self, attr_id: u16) -> Result<Access, IMStatusCode> {
+        self.get_attribute(attr_id)
+            .map(|a| a.access)
+            .map_err(|_| IMStatusCode::UnsupportedAttribute)
+    }
+
     fn get_attribute_index(&self, attr_id: u16) -> Option<usize> {
         self.attributes.iter().position(|c| c.id == attr_id)
     }
@@ -294,20 +622,20 @@ impl Cluster {
         c: &dyn ClusterType,
         tag: TagType,
         tw: &mut TLVWriter,
-        attr_id: u16,
+        attr: &AttrDetails,
     ) -> Result<(), IMStatusCode> {
         let base = c.base();
         let a = base
-            .get_attribute(attr_id)
+            .get_attribute(attr.attr_id)
             .map_err(|_| IMStatusCode::UnsupportedAttribute)?;
         if !a.access.contains(Access::READ) {
             return Err(IMStatusCode::UnsupportedRead);
         }
 
-        if a.value != AttrValue::Custom || Attribute::is_system_attr(attr_id) {
+        if a.value != AttrValue::Custom || Attribute::is_system_attr(attr.attr_id) {
             base.read_standard_attribute(tag, tw, a)
         } else {
-            c.read_custom_attribute(tag, tw, attr_id)
+            c.read_custom_attribute(tag, tw, attr)
         }
     }
 
@@ -328,6 +656,14 @@ impl Cluster {
                     let _ = tw.end_container();
                     Ok(())
                 }
+                GlobalElements::EventList => {
+                    let _ = tw.start_array(tag);
+                    for e in &self.events {
+                        let _ = tw.u32(TagType::Anonymous, *e);
+                    }
+                    let _ = tw.end_container();
+                    Ok(())
+                }
                 GlobalElements::FeatureMap => {
                     let val = if let Some(m) = self.feature_map { m } else { 0 };
                     let _ = tw.u32(tag, val);
@@ -359,12 +695,14 @@ impl Cluster {
             return Err(IMStatusCode::UnsupportedWrite);
         }
         if a.value != AttrValue::Custom {
-            let mut value = a.value;
-            value
-                .update_from_tlv(data)
-                .map_err(|_| IMStatusCode::Failure)?;
-            a.set_value(value)
-                .map_err(|_| IMStatusCode::UnsupportedWrite)
+            let value = a.decode_write(data)?;
+            if a.value != value {
+                a.set_value(value)
+                    .map_err(|_| IMStatusCode::UnsupportedWrite)?;
+                self.bump_dataver();
+                self.attr_changed(attr_id);
+            }
+            Ok(())
         } else {
             Err(IMStatusCode::UnsupportedAttribute)
         }
@@ -372,7 +710,26 @@ impl Cluster {
 
     pub fn write_attribute_raw(&mut self, attr_id: u16, value: AttrValue) -> Result<(), Error> {
         let a = self.get_attribute_mut(attr_id)?;
-        a.set_value(value)
+        if a.value != value {
+            a.set_value(value)?;
+            self.bump_dataver();
+            self.attr_changed(attr_id);
+        }
+        Ok(())
+    }
+
+    fn attr_changed(&mut self, attr_id: u16) {
+        if !self.changed_attrs.contains(&attr_id) {
+            self.changed_attrs.push(attr_id);
+        }
+    }
+
+    /// Returns the attributes that changed since the last call, along with their new values
+    pub fn take_changes(&mut self) -> Vec<(u16, AttrValue)> {
+        std::mem::take(&mut self.changed_attrs)
+            .into_iter()
+            .filter_map(|id| Some((id, self.read_attribute_raw(id).ok()?.clone())))
+            .collect()
     }
 }
 
@@ -475,6 +832,15 @@ pub trait ChangeConsumer {
     fn endpoint_added(&self, id: u16, endpoint: &mut Endpoint) -> Result<(), Error>;
 }
 
+/// A change to the value of an attribute, that the application is notified of
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttrChange {
+    pub endpoint: u16,
+    pub cluster: u32,
+    pub attr: u16,
+    pub value: AttrValue,
+}
+
 #[derive(Default)]
 pub struct Node {
     endpoints: [Option<Box<Endpoint>>; ENDPTS_PER_ACC],
@@ -672,4 +1038,22 @@ impl Node {
             }
         });
     }
+
+    /// Returns the changes to the attribute values of all the clusters, since the last call
+    pub fn take_attr_changes(&mut self) -> Vec<AttrChange> {
+        let mut changes = Vec::new();
+        self.for_each_cluster_mut(&GenericPath::new(None, None, None), |path, c| {
+            let endpoint = path.endpoint.unwrap_or_default();
+            let cluster = c.base().id();
+            for (attr, value) in c.base_mut().take_changes() {
+                changes.push(AttrChange {
+                    endpoint,
+                    cluster,
+                    attr,
+                    value,
+                });
+            }
+        });
+        changes
+    }
 }
@@ -0,0 +1,273 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cmd_enter;
+use crate::data_model::objects::*;
+use crate::data_model::sdm::failsafe::FailSafe;
+use crate::error::*;
+use crate::fabric::FabricMgr;
+use crate::interaction_model::command::CommandReq;
+use crate::interaction_model::core::IMStatusCode;
+use crate::interaction_model::messages::ib;
+use crate::secure_channel::pake::{CommWindowStatus, PaseMgr};
+use crate::secure_channel::spake2p::VerifierData;
+use crate::tlv::{FromTLV, OctetStr, TLVElement, TLVWriter, TagType, ToTLV};
+use log::{error, info};
+use num_derive::FromPrimitive;
+
+// Administrator Commissioning Cluster
+
+pub const ID: u32 = 0x003C;
+
+// The range of the CommissioningTimeout, in seconds
+const MIN_COMM_TIMEOUT: u16 = 180;
+const MAX_COMM_TIMEOUT: u16 = 900;
+
+const MAX_DISCRIMINATOR: u16 = 0xFFF;
+
+#[derive(FromPrimitive)]
+pub enum Attributes {
+    WindowStatus = 0,
+    AdminFabricIndex = 1,
+    AdminVendorId = 2,
+}
+
+#[derive(FromPrimitive)]
+pub enum Commands {
+    OpenCommWindow = 0x00,
+    OpenBasicCommWindow = 0x01,
+    RevokeComm = 0x02,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum AdminCommStatus {
+    Busy = 2,
+    PAKEParameterError = 3,
+    WindowNotOpen = 4,
+}
+
+// The feature map bit, for support of the Basic Commissioning Window
+const FEATURE_BASIC: u32 = 0x01;
+
+fn attr_window_status_new() -> Result<Attribute, Error> {
+    Attribute::new(
+        Attributes::WindowStatus as u16,
+        AttrValue::Custom,
+        Access::RV,
+        Quality::NONE,
+    )
+}
+
+fn attr_admin_fabric_index_new() -> Result<Attribute, Error> {
+    Attribute::new(
+        Attributes::AdminFabricIndex as u16,
+        AttrValue::Custom,
+        Access::RV,
+        Quality::NULLABLE,
+    )
+}
+
+fn attr_admin_vendor_id_new() -> Result<Attribute, Error> {
+    Attribute::new(
+        Attributes::AdminVendorId as u16,
+        AttrValue::Custom,
+        Access::RV,
+        Quality::NULLABLE,
+    )
+}
+
+#[derive(FromTLV)]
+#[tlvargs(lifetime = "'a")]
+struct OpenCommWindowReq<'a> {
+    timeout: u16,
+    verifier: OctetStr<'a>,
+    discriminator: u16,
+    iterations: u32,
+    salt: OctetStr<'a>,
+}
+
+#[derive(FromTLV)]
+struct OpenBasicCommWindowReq {
+    timeout: u16,
+}
+
+pub struct AdminCommCluster {
+    base: Cluster,
+    pase_mgr: PaseMgr,
+    fabric_mgr: Arc<FabricMgr>,
+    failsafe: Arc<FailSafe>,
+}
+
+impl ClusterType for AdminCommCluster {
+    fn base(&self) -> &Cluster {
+        &self.base
+    }
+    fn base_mut(&mut self) -> &mut Cluster {
+        &mut self.base
+    }
+
+    fn read_custom_attribute(
+        &self,
+        tag: TagType,
+        tw: &mut TLVWriter,
+        attr: &AttrDetails,
+    ) -> Result<(), IMStatusCode> {
+        let (status, opener) = self.pase_mgr.window_status()?;
+        match num::FromPrimitive::from_u16(attr.attr_id)
+            .ok_or(IMStatusCode::UnsupportedAttribute)?
+        {
+            Attributes::WindowStatus => {
+                let _ = tw.u8(tag, status as u8);
+            }
+            Attributes::AdminFabricIndex => {
+                let _ = match opener {
+                    Some(fab_idx) => tw.u8(tag, fab_idx),
+                    None => tw.null(tag),
+                };
+            }
+            Attributes::AdminVendorId => {
+                let vendor_id = opener.and_then(|fab_idx| {
+                    let fabric = self.fabric_mgr.get_fabric(fab_idx as usize).ok()?;
+                    fabric.as_ref().as_ref().map(|f| f.get_vendor_id())
+                });
+                let _ = match vendor_id {
+                    Some(vendor_id) => tw.u16(tag, vendor_id),
+                    None => tw.null(tag),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_command(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+        let cmd = cmd_req
+            .cmd
+            .path
+            .leaf
+            .map(num::FromPrimitive::from_u32)
+            .ok_or(IMStatusCode::UnsupportedCommand)?
+            .ok_or(IMStatusCode::UnsupportedCommand)?;
+        match cmd {
+            Commands::OpenCommWindow => self.handle_command_opencomm_win(cmd_req),
+            Commands::OpenBasicCommWindow => self.handle_command_open_basic_comm_win(cmd_req),
+            Commands::RevokeComm => self.handle_command_revoke_comm(cmd_req),
+        }
+    }
+}
+
+// The command failed, with a cluster specific status
+fn encode_cluster_status(
+    cmd_req: &mut CommandReq,
+    status: AdminCommStatus,
+) -> Result<(), IMStatusCode> {
+    error!("Administrator Commissioning failed: {:?}", status);
+    let resp = ib::InvResp::status_new(cmd_req.cmd, IMStatusCode::Failure, status as u16);
+    let _ = resp.to_tlv(cmd_req.resp, TagType::Anonymous);
+    cmd_req.trans.complete();
+    Ok(())
+}
+
+impl AdminCommCluster {
+    pub fn new(
+        pase_mgr: PaseMgr,
+        fabric_mgr: Arc<FabricMgr>,
+        failsafe: Arc<FailSafe>,
+    ) -> Result<Box<Self>, Error> {
+        let mut c = Box::new(AdminCommCluster {
+            pase_mgr,
+            fabric_mgr,
+            failsafe,
+            base: Cluster::new(ID)?,
+        });
+        c.base.set_feature_map(FEATURE_BASIC)?;
+        c.base.add_attribute(attr_window_status_new()?)?;
+        c.base.add_attribute(attr_admin_fabric_index_new()?)?;
+        c.base.add_attribute(attr_admin_vendor_id_new()?)?;
+        for cmd in [
+            Commands::OpenCommWindow,
+            Commands::OpenBasicCommWindow,
+            Commands::RevokeComm,
+        ] {
+            c.base.add_command(Command::new(
+                cmd as u16,
+                Access::NEED_ADMIN | Access::TIMED_ONLY,
+            ))?;
+        }
+        Ok(c)
+    }
+
+    // A window can't be opened, while another one is open, or while commissioning is
+    // in progress
+    fn is_busy(&self) -> Result<bool, IMStatusCode> {
+        let (status, _) = self.pase_mgr.window_status()?;
+        Ok(status != CommWindowStatus::NotOpen || self.failsafe.is_armed())
+    }
+
+    fn comm_timeout(timeout: u16) -> Result<Duration, IMStatusCode> {
+        if (MIN_COMM_TIMEOUT..=MAX_COMM_TIMEOUT).contains(&timeout) {
+            Ok(Duration::from_secs(timeout as u64))
+        } else {
+            error!("Invalid Commissioning Timeout: {}", timeout);
+            Err(IMStatusCode::InvalidCommand)
+        }
+    }
+
+    fn handle_command_opencomm_win(
+        &mut self,
+        cmd_req: &mut CommandReq,
+    ) -> Result<(), IMStatusCode> {
+        cmd_enter!("Open Commissioning Window");
+        let req = OpenCommWindowReq::from_tlv(&cmd_req.data)?;
+        if self.is_busy()? {
+            return encode_cluster_status(cmd_req, AdminCommStatus::Busy);
+        }
+
+        let timeout = AdminCommCluster::comm_timeout(req.timeout)?;
+        if req.discriminator > MAX_DISCRIMINATOR {
+            return Err(IMStatusCode::InvalidCommand);
+        }
+        let verifier = match VerifierData::new(req.verifier.0, req.iterations, req.salt.0) {
+            Ok(v) => v,
+            Err(_) => return encode_cluster_status(cmd_req, AdminCommStatus::PAKEParameterError),
+        };
+
+        let opener = cmd_req.trans.session.get_local_fabric_idx();
+        self.pase_mgr
+            .open_enhanced_window(verifier, req.discriminator, opener, Some(timeout))?;
+        info!(
+            "Opened Enhanced Commissioning Window, discriminator {}",
+            req.discriminator
+        );
+        cmd_req.trans.complete();
+        Err(IMStatusCode::Sucess)
+    }
+
+    fn handle_command_open_basic_comm_win(
+        &mut self,
+        cmd_req: &mut CommandReq,
+    ) -> Result<(), IMStatusCode> {
+        cmd_enter!("Open Basic Commissioning Window");
+        let req = OpenBasicCommWindowReq::from_tlv(&cmd_req.data)?;
+        if self.is_busy()? {
+            return encode_cluster_status(cmd_req, AdminCommStatus::Busy);
+        }
+
+        let timeout = AdminCommCluster::comm_timeout(req.timeout)?;
+        let opener = cmd_req.trans.session.get_local_fabric_idx();
+        self.pase_mgr.open_basic_window(opener, Some(timeout))?;
+        info!("Opened Basic Commissioning Window");
+        cmd_req.trans.complete();
+        Err(IMStatusCode::Sucess)
+    }
+
+    fn handle_command_revoke_comm(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+        cmd_enter!("Revoke Commissioning");
+        // This also aborts a PASE commissioning that is in progress under the window
+        if !self.pase_mgr.revoke_window()? {
+            return encode_cluster_status(cmd_req, AdminCommStatus::WindowNotOpen);
+        }
+        info!("Revoked Commissioning Window");
+        cmd_req.trans.complete();
+        Err(IMStatusCode::Sucess)
+    }
+}
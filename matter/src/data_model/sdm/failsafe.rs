@@ -1,9 +1,32 @@
-use crate::{error::Error, transport::session::SessionMode};
-use log::error;
-use std::sync::RwLock;
+use crate::{
+    acl::AclMgr,
+    data_model::sdm::general_commissioning::RegLocationType,
+    error::Error,
+    fabric::{Fabric, FabricMgr},
+    group_keys::GroupKeys,
+    secure_channel::pake::PaseMgr,
+    transport::session::SessionMode,
+};
+use log::{error, info};
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+/* Fail-Safe
+ *
+ * - The commissioning changes are made under an armed Fail-Safe, and are only kept once
+ *   CommissioningComplete disarms it
+ * - The transport checks for the expiry, as it loops. If the Fail-Safe expired, the fabric
+ *   that was added (or updated) is rolled back, the pending Regulatory Config is dropped,
+ *   the Breadcrumb is reset, and the transport closes the PASE sessions. The CSR keypair and the root from
+ *   AddTrustedRootCert are kept in the PASE session, so they go along with it
+ * - Revoking the commissioning window, or its timeout, aborts the PASE commissioning. The
+ *   Fail-Safe that was armed over PASE expires then, and the PASE sessions are closed
+ * - Only Ethernet is supported, so there is no network config to roll back
+ */
 
 #[derive(PartialEq)]
-#[allow(dead_code)]
 enum NocState {
     NocNotRecvd,
     // This is the local fabric index
@@ -11,14 +34,16 @@ enum NocState {
     UpdateNocRecvd(u8),
 }
 
-#[derive(PartialEq)]
 pub struct ArmedCtx {
     session_mode: SessionMode,
-    timeout: u8,
+    deadline: Instant,
     noc_state: NocState,
+    // The fabric, as it was before an UpdateNOC
+    prev_fabric: Option<Box<Fabric>>,
+    // The Regulatory Config, that is applied once the Fail-Safe is disarmed
+    reg_config: Option<u8>,
 }
 
-#[derive(PartialEq)]
 pub enum State {
     Idle,
     Armed(ArmedCtx),
@@ -26,35 +51,99 @@ pub enum State {
 
 pub struct FailSafeInner {
     state: State,
+    reg_config: u8,
+    // The General Commissioning Breadcrumb, that the commissioner tracks its progress with
+    bread_crumb: u64,
+}
+
+// The state, that is rolled back on expiry
+struct Rollback {
+    fabric_mgr: Arc<FabricMgr>,
+    acl_mgr: Arc<AclMgr>,
+    group_keys: Arc<GroupKeys>,
+}
+
+impl Rollback {
+    fn run(&self, ctx: ArmedCtx) {
+        match ctx.noc_state {
+            NocState::NocNotRecvd => (),
+            NocState::AddNocRecvd(fab_idx) => {
+                info!("Fail-Safe: Removing fabric {}", fab_idx);
+                // The sessions of the fabric are evicted by the transport
+                if let Err(e) = self.fabric_mgr.remove(fab_idx) {
+                    error!("Error removing the fabric: {:?}", e);
+                }
+                if let Err(e) = self.acl_mgr.delete_for_fabric(fab_idx) {
+                    error!("Error removing the ACL entries of the fabric: {:?}", e);
+                }
+                if let Err(e) = self.group_keys.remove_fabric(fab_idx) {
+                    error!("Error removing the groups of the fabric: {:?}", e);
+                }
+            }
+            NocState::UpdateNocRecvd(fab_idx) => {
+                if let Some(prev_fabric) = ctx.prev_fabric {
+                    info!("Fail-Safe: Reverting the NOC of fabric {}", fab_idx);
+                    if let Err(e) = self.fabric_mgr.update(fab_idx, *prev_fabric) {
+                        error!("Error reverting the fabric: {:?}", e);
+                    }
+                }
+            }
+        }
+    }
 }
 
 pub struct FailSafe {
     state: RwLock<FailSafeInner>,
+    rollback: Rollback,
+    pase_mgr: PaseMgr,
 }
 
 impl FailSafe {
-    pub fn new() -> Self {
+    pub fn new(
+        fabric_mgr: Arc<FabricMgr>,
+        acl_mgr: Arc<AclMgr>,
+        group_keys: Arc<GroupKeys>,
+        pase_mgr: PaseMgr,
+    ) -> Self {
         Self {
-            state: RwLock::new(FailSafeInner { state: State::Idle }),
+            state: RwLock::new(FailSafeInner {
+                state: State::Idle,
+                // TODO: Arch-Specific
+                reg_config: RegLocationType::IndoorOutdoor as u8,
+                bread_crumb: 0,
+            }),
+            rollback: Rollback {
+                fabric_mgr,
+                acl_mgr,
+                group_keys,
+            },
+            pase_mgr,
         }
     }
 
+    /// Arm the Fail-Safe for 'timeout' seconds. Re-arming it with a timeout of 0 expires
+    /// it right away
     pub fn arm(&self, timeout: u8, session_mode: SessionMode) -> Result<(), Error> {
         let mut inner = self.state.write()?;
+        let deadline = Instant::now() + Duration::from_secs(timeout as u64);
         match &mut inner.state {
             State::Idle => {
-                inner.state = State::Armed(ArmedCtx {
-                    session_mode,
-                    timeout,
-                    noc_state: NocState::NocNotRecvd,
-                })
+                if timeout != 0 {
+                    inner.state = State::Armed(ArmedCtx {
+                        session_mode,
+                        deadline,
+                        noc_state: NocState::NocNotRecvd,
+                        prev_fabric: None,
+                        reg_config: None,
+                    })
+                }
             }
             State::Armed(c) => {
                 if c.session_mode != session_mode {
                     return Err(Error::Invalid);
                 }
                 // re-arm
-                c.timeout = timeout;
+                c.deadline = deadline;
             }
         }
         Ok(())
@@ -79,14 +168,19 @@ impl FailSafe {
                         }
                     }
                 }
+                // The changes are now committed
+                if let Some(reg_config) = c.reg_config {
+                    inner.reg_config = reg_config;
+                }
                 inner.state = State::Idle;
+                self.pase_mgr.commissioning_complete()?;
             }
         }
         Ok(())
     }
 
     pub fn is_armed(&self) -> bool {
-        self.state.read().unwrap().state != State::Idle
+        matches!(self.state.read().unwrap().state, State::Armed(_))
     }
 
     pub fn record_add_noc(&self, fabric_index: u8) -> Result<(), Error> {
@@ -104,6 +198,24 @@ impl FailSafe {
         }
     }
 
+    /// Record an UpdateNOC, along with the fabric as it was before, to which it is
+    /// reverted on expiry
+    pub fn record_update_noc(&self, fabric_index: u8, prev_fabric: Fabric) -> Result<(), Error> {
+        let mut inner = self.state.write()?;
+        match &mut inner.state {
+            State::Idle => Err(Error::Invalid),
+            State::Armed(c) => {
+                if c.noc_state == NocState::NocNotRecvd {
+                    c.noc_state = NocState::UpdateNocRecvd(fabric_index);
+                    c.prev_fabric = Some(Box::new(prev_fabric));
+                    Ok(())
+                } else {
+                    Err(Error::Invalid)
+                }
+            }
+        }
+    }
+
     pub fn allow_noc_change(&self) -> Result<bool, Error> {
         let mut inner = self.state.write()?;
         let allow = match &mut inner.state {
@@ -112,10 +224,61 @@ impl FailSafe {
         };
         Ok(allow)
     }
-}
 
-impl Default for FailSafe {
-    fn default() -> Self {
-        Self::new()
+    /// Set the Regulatory Config. If the Fail-Safe is armed, it only takes effect once
+    /// the Fail-Safe is disarmed
+    pub fn set_reg_config(&self, reg_config: u8) -> Result<(), Error> {
+        let mut inner = self.state.write()?;
+        match &mut inner.state {
+            State::Idle => inner.reg_config = reg_config,
+            State::Armed(c) => c.reg_config = Some(reg_config),
+        }
+        Ok(())
+    }
+
+    /// The Regulatory Config, including one that is pending under the Fail-Safe
+    pub fn get_reg_config(&self) -> Result<u8, Error> {
+        let inner = self.state.read()?;
+        Ok(match &inner.state {
+            State::Armed(ArmedCtx {
+                reg_config: Some(reg_config),
+                ..
+            }) => *reg_config,
+            _ => inner.reg_config,
+        })
+    }
+
+    pub fn set_bread_crumb(&self, bread_crumb: u64) -> Result<(), Error> {
+        self.state.write()?.bread_crumb = bread_crumb;
+        Ok(())
+    }
+
+    pub fn get_bread_crumb(&self) -> Result<u64, Error> {
+        Ok(self.state.read()?.bread_crumb)
+    }
+
+    /// Roll back the commissioning, if the Fail-Safe has expired, or the PASE commissioning
+    /// was aborted. Returns true if either happened, the PASE sessions must then be closed
+    pub fn check_expiry(&self) -> bool {
+        let aborted = self.pase_mgr.check_expiry();
+        let ctx = {
+            let mut inner = match self.state.write() {
+                Ok(inner) => inner,
+                Err(_) => return aborted,
+            };
+            match &inner.state {
+                State::Armed(c) if Instant::now() >= c.deadline => (),
+                State::Armed(c) if aborted && c.session_mode == SessionMode::Pase => (),
+                _ => return aborted,
+            }
+            inner.bread_crumb = 0;
+            match std::mem::replace(&mut inner.state, State::Idle) {
+                State::Armed(c) => c,
+                State::Idle => return aborted,
+            }
+        };
+        info!("Fail-Safe expired, rolling back the commissioning");
+        self.rollback.run(ctx);
+        true
     }
 }
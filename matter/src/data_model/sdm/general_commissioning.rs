@@ -1,3 +1,4 @@
+use crate::acl::Accessor;
 use crate::cmd_enter;
 use crate::data_model::objects::*;
 use crate::data_model::sdm::failsafe::FailSafe;
@@ -44,19 +45,19 @@ pub enum RegLocationType {
     IndoorOutdoor = 2,
 }
 
-fn attr_bread_crumb_new(bread_crumb: u64) -> Result<Attribute, Error> {
+fn attr_bread_crumb_new() -> Result<Attribute, Error> {
     Attribute::new(
         Attributes::BreadCrumb as u16,
-        AttrValue::Uint64(bread_crumb),
+        AttrValue::Custom,
         Access::READ | Access::WRITE | Access::NEED_ADMIN,
         Quality::NONE,
     )
 }
 
-fn attr_reg_config_new(reg_config: RegLocationType) -> Result<Attribute, Error> {
+fn attr_reg_config_new() -> Result<Attribute, Error> {
     Attribute::new(
         Attributes::RegConfig as u16,
-        AttrValue::Uint8(reg_config as u8),
+        AttrValue::Custom,
         Access::RV,
         Quality::NONE,
     )
@@ -104,21 +105,44 @@ impl ClusterType for GenCommCluster {
         &self,
         tag: TagType,
         tw: &mut TLVWriter,
-        attr_id: u16,
+        attr: &AttrDetails,
     ) -> Result<(), IMStatusCode> {
-        match num::FromPrimitive::from_u16(attr_id).ok_or(IMStatusCode::UnsupportedAttribute)? {
+        match num::FromPrimitive::from_u16(attr.attr_id)
+            .ok_or(IMStatusCode::UnsupportedAttribute)?
+        {
             Attributes::BasicCommissioningInfo => {
                 let _ = tw.start_struct(tag);
                 let _ = tw.u16(TagType::Context(0), self.expiry_len);
                 let _ = tw.end_container();
                 Ok(())
             }
+            Attributes::RegConfig => {
+                let _ = tw.u8(tag, self.failsafe.get_reg_config()?);
+                Ok(())
+            }
+            Attributes::BreadCrumb => {
+                let _ = tw.u64(tag, self.failsafe.get_bread_crumb()?);
+                Ok(())
+            }
             _ => Err(IMStatusCode::UnsupportedAttribute),
         }
     }
 
-    fn write_attribute(&mut self, data: &TLVElement, attr_id: u16) -> Result<(), IMStatusCode> {
-        self.base.write_attribute(data, attr_id)
+    fn write_attribute(
+        &mut self,
+        _accessor: &Accessor,
+        data: &TLVElement,
+        attr_id: u16,
+    ) -> Result<(), IMStatusCode> {
+        if attr_id == Attributes::BreadCrumb as u16 {
+            // The Breadcrumb is kept by the Fail-Safe, that resets it on expiry
+            let bread_crumb = data.u64().map_err(|_| IMStatusCode::InvalidDataType)?;
+            self.failsafe.set_bread_crumb(bread_crumb)?;
+            self.base.bump_dataver();
+            Ok(())
+        } else {
+            self.base.write_attribute(data, attr_id)
+        }
     }
 
     fn handle_command(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
@@ -139,32 +163,32 @@ impl ClusterType for GenCommCluster {
 }
 
 impl GenCommCluster {
-    pub fn new() -> Result<Box<Self>, Error> {
-        let failsafe = Arc::new(FailSafe::new());
-
+    pub fn new(failsafe: Arc<FailSafe>) -> Result<Box<Self>, Error> {
         let mut c = Box::new(GenCommCluster {
             // TODO: Arch-Specific
             expiry_len: 120,
             failsafe,
             base: Cluster::new(ID)?,
         });
-        c.base.add_attribute(attr_bread_crumb_new(0)?)?;
-        // TODO: Arch-Specific
-        c.base
-            .add_attribute(attr_reg_config_new(RegLocationType::IndoorOutdoor)?)?;
+        c.base.add_attribute(attr_bread_crumb_new()?)?;
+        c.base.add_attribute(attr_reg_config_new()?)?;
         // TODO: Arch-Specific
         c.base.add_attribute(attr_location_capability_new(
             RegLocationType::IndoorOutdoor,
         )?)?;
         c.base.add_attribute(attr_comm_info_new()?)?;
+        for cmd in [
+            Commands::ArmFailsafe,
+            Commands::SetRegulatoryConfig,
+            Commands::CommissioningComplete,
+        ] {
+            c.base
+                .add_command(Command::new(cmd as u16, Access::NEED_ADMIN))?;
+        }
 
         Ok(c)
     }
 
-    pub fn failsafe(&self) -> Arc<FailSafe> {
-        self.failsafe.clone()
-    }
-
     fn handle_command_armfailsafe(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
         cmd_enter!("ARM Fail Safe");
 
@@ -177,6 +201,8 @@ impl GenCommCluster {
         {
             return Err(IMStatusCode::Busy);
         }
+        self.failsafe.set_bread_crumb(p.bread_crumb as u64)?;
+        self.base.bump_dataver();
 
         let cmd_data = |t: &mut TLVWriter| {
             t.u8(TagType::Context(0), CommissioningError::Ok as u8)?;
@@ -193,6 +219,11 @@ impl GenCommCluster {
         cmd_req: &mut CommandReq,
     ) -> Result<(), IMStatusCode> {
         cmd_enter!("Set Regulatory Config");
+        let reg_config = cmd_req
+            .data
+            .find_tag(0)
+            .and_then(|t| t.u8())
+            .map_err(|_| IMStatusCode::InvalidCommand)?;
         // These data types don't match the spec
         let country_code = cmd_req
             .data
@@ -200,10 +231,21 @@ impl GenCommCluster {
             .map_err(|_| IMStatusCode::InvalidCommand)?
             .slice()
             .map_err(|_| IMStatusCode::InvalidCommand)?;
-        info!("Received country code: {:?}", country_code);
+        info!(
+            "Received regulatory config: {} country code: {:?}",
+            reg_config, country_code
+        );
+
+        // Any location is within our LocationCapability
+        let status = if reg_config > RegLocationType::IndoorOutdoor as u8 {
+            CommissioningError::ErrValueOutsideRange
+        } else {
+            self.failsafe.set_reg_config(reg_config)?;
+            CommissioningError::Ok
+        };
 
         let cmd_data = |t: &mut TLVWriter| {
-            t.u8(TagType::Context(0), 0)?;
+            t.u8(TagType::Context(0), status as u8)?;
             t.utf8(TagType::Context(1), b"")
         };
         let resp = ib::InvResp::cmd_new(0, ID, Commands::SetRegulatoryConfigResp as u16, &cmd_data);
@@ -233,6 +275,10 @@ impl GenCommCluster {
         {
             status = CommissioningError::ErrInvalidAuth as u8;
         }
+        if status == CommissioningError::Ok as u8 {
+            self.failsafe.set_bread_crumb(0)?;
+            self.base.bump_dataver();
+        }
 
         let cmd_data = |t: &mut TLVWriter| {
             t.u8(TagType::Context(0), status)?;
@@ -0,0 +1,306 @@
+use std::sync::Arc;
+
+use crate::acl::Accessor;
+use crate::cmd_enter;
+use crate::data_model::objects::*;
+use crate::error::*;
+use crate::group_keys::{self, GroupKeyMapEntry, GroupKeySet, GroupKeys, IPK_KEY_SET_ID};
+use crate::interaction_model::command::CommandReq;
+use crate::interaction_model::core::IMStatusCode;
+use crate::interaction_model::messages::ib;
+use crate::tlv::{TLVElement, TLVWriter, TagType, ToTLV};
+use log::{error, info};
+use num_derive::FromPrimitive;
+
+// Group Key Management Cluster
+
+pub const ID: u32 = 0x003F;
+
+#[derive(FromPrimitive)]
+pub enum Attributes {
+    GroupKeyMap = 0,
+    GroupTable = 1,
+    MaxGroupsPerFabric = 2,
+    MaxGroupKeysPerFabric = 3,
+}
+
+#[derive(FromPrimitive)]
+pub enum Commands {
+    KeySetWrite = 0x00,
+    KeySetRead = 0x01,
+    KeySetReadResp = 0x02,
+    KeySetRemove = 0x03,
+    KeySetReadAllIndices = 0x04,
+    KeySetReadAllIndicesResp = 0x05,
+}
+
+// The key set, or its id, in the command requests and responses
+const KEY_SET_TAG: u32 = 0;
+
+pub struct GrpKeyMgmtCluster {
+    base: Cluster,
+    group_keys: Arc<GroupKeys>,
+}
+
+impl GrpKeyMgmtCluster {
+    pub fn new(group_keys: Arc<GroupKeys>) -> Result<Box<Self>, Error> {
+        let mut c = Box::new(GrpKeyMgmtCluster {
+            base: Cluster::new(ID)?,
+            group_keys,
+        });
+        c.base.add_attribute(Attribute::new(
+            Attributes::GroupKeyMap as u16,
+            AttrValue::Custom,
+            Access::RWVM | Access::FAB_SCOPED,
+            Quality::NONE,
+        )?)?;
+        c.base.add_attribute(Attribute::new(
+            Attributes::GroupTable as u16,
+            AttrValue::Custom,
+            Access::RV | Access::FAB_SCOPED,
+            Quality::NONE,
+        )?)?;
+        c.base.add_attribute(attr_limit_new(
+            Attributes::MaxGroupsPerFabric,
+            group_keys::MAX_GROUPS_PER_FABRIC,
+        )?)?;
+        c.base.add_attribute(attr_limit_new(
+            Attributes::MaxGroupKeysPerFabric,
+            group_keys::MAX_GROUP_KEYS_PER_FABRIC,
+        )?)?;
+        for cmd in [
+            Commands::KeySetWrite,
+            Commands::KeySetRead,
+            Commands::KeySetRemove,
+            Commands::KeySetReadAllIndices,
+        ] {
+            c.base
+                .add_command(Command::new(cmd as u16, Access::NEED_ADMIN))?;
+        }
+        Ok(c)
+    }
+
+    // The key sets are fabric-scoped, so these commands need a CASE session
+    fn fab_idx(cmd_req: &CommandReq) -> Result<u8, IMStatusCode> {
+        cmd_req
+            .trans
+            .session
+            .get_local_fabric_idx()
+            .ok_or(IMStatusCode::UnsupportedAccess)
+    }
+
+    fn key_set_id(cmd_req: &CommandReq) -> Result<u16, IMStatusCode> {
+        cmd_req
+            .data
+            .find_tag(KEY_SET_TAG)
+            .and_then(|t| t.u16())
+            .map_err(|_| IMStatusCode::InvalidCommand)
+    }
+
+    fn handle_command_keyset_write(
+        &mut self,
+        cmd_req: &mut CommandReq,
+    ) -> Result<(), IMStatusCode> {
+        cmd_enter!("KeySetWrite");
+        let fab_idx = GrpKeyMgmtCluster::fab_idx(cmd_req)?;
+        let key_set = cmd_req
+            .data
+            .find_tag(KEY_SET_TAG)
+            .and_then(|t| GroupKeySet::from_tlv(&t))
+            .map_err(|_| IMStatusCode::InvalidCommand)?;
+        info!("Writing Key Set {}", key_set.id);
+
+        self.group_keys
+            .set_key_set(fab_idx, key_set)
+            .map_err(|e| match e {
+                Error::NoSpace => IMStatusCode::ResourceExhausted,
+                Error::Invalid => IMStatusCode::InvalidCommand,
+                _ => IMStatusCode::Failure,
+            })?;
+        cmd_req.trans.complete();
+        Err(IMStatusCode::Sucess)
+    }
+
+    fn handle_command_keyset_read(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+        cmd_enter!("KeySetRead");
+        let fab_idx = GrpKeyMgmtCluster::fab_idx(cmd_req)?;
+        let id = GrpKeyMgmtCluster::key_set_id(cmd_req)?;
+        let key_set = self
+            .group_keys
+            .get_key_set(fab_idx, id)
+            .map_err(|_| IMStatusCode::NotFound)?;
+
+        // The epoch keys themselves are never read back
+        let cmd_data = |t: &mut TLVWriter| key_set.to_tlv(t, TagType::Context(0), false);
+        let resp = ib::InvResp::cmd_new(0, ID, Commands::KeySetReadResp as u16, &cmd_data);
+        let _ = resp.to_tlv(cmd_req.resp, TagType::Anonymous);
+        cmd_req.trans.complete();
+        Ok(())
+    }
+
+    fn handle_command_keyset_remove(
+        &mut self,
+        cmd_req: &mut CommandReq,
+    ) -> Result<(), IMStatusCode> {
+        cmd_enter!("KeySetRemove");
+        let fab_idx = GrpKeyMgmtCluster::fab_idx(cmd_req)?;
+        let id = GrpKeyMgmtCluster::key_set_id(cmd_req)?;
+        if id == IPK_KEY_SET_ID {
+            // The IPK is removed only along with its fabric
+            return Err(IMStatusCode::InvalidCommand);
+        }
+        self.group_keys
+            .remove_key_set(fab_idx, id)
+            .map_err(|_| IMStatusCode::NotFound)?;
+        // The mappings to this key set are gone too
+        self.base.bump_dataver();
+        cmd_req.trans.complete();
+        Err(IMStatusCode::Sucess)
+    }
+
+    fn handle_command_keyset_read_all(
+        &mut self,
+        cmd_req: &mut CommandReq,
+    ) -> Result<(), IMStatusCode> {
+        cmd_enter!("KeySetReadAllIndices");
+        let fab_idx = GrpKeyMgmtCluster::fab_idx(cmd_req)?;
+        let ids = self.group_keys.get_key_set_ids(fab_idx)?;
+
+        let cmd_data = |t: &mut TLVWriter| {
+            t.start_array(TagType::Context(0))?;
+            // The IPK is always there
+            t.u16(TagType::Anonymous, IPK_KEY_SET_ID)?;
+            for id in &ids {
+                t.u16(TagType::Anonymous, *id)?;
+            }
+            t.end_container()
+        };
+        let resp =
+            ib::InvResp::cmd_new(0, ID, Commands::KeySetReadAllIndicesResp as u16, &cmd_data);
+        let _ = resp.to_tlv(cmd_req.resp, TagType::Anonymous);
+        cmd_req.trans.complete();
+        Ok(())
+    }
+
+    /// Replace all the entries of the accessor's fabric with the entries in the list
+    fn write_key_map_attr(
+        &mut self,
+        accessor: &Accessor,
+        data: &TLVElement,
+    ) -> Result<(), IMStatusCode> {
+        if accessor.fab_idx == 0 {
+            // The Group Key Map is fabric-scoped, this can only be written over a CASE session
+            return Err(IMStatusCode::UnsupportedAccess);
+        }
+
+        let mut entries = Vec::new();
+        let iter = data
+            .confirm_array()
+            .map_err(|_| IMStatusCode::InvalidDataType)?
+            .iter();
+        if let Some(iter) = iter {
+            for e in iter {
+                let mut entry =
+                    GroupKeyMapEntry::from_tlv(&e).map_err(|_| IMStatusCode::ConstraintError)?;
+                if entry.key_set_id == IPK_KEY_SET_ID {
+                    // No group may use the IPK
+                    return Err(IMStatusCode::ConstraintError);
+                }
+                entry.fab_idx = accessor.fab_idx;
+                entries.push(entry);
+            }
+        }
+
+        self.group_keys
+            .set_key_map(accessor.fab_idx, entries)
+            .map_err(|e| match e {
+                Error::NoSpace => IMStatusCode::ResourceExhausted,
+                _ => IMStatusCode::ConstraintError,
+            })?;
+        self.base.bump_dataver();
+        Ok(())
+    }
+}
+
+impl ClusterType for GrpKeyMgmtCluster {
+    fn base(&self) -> &Cluster {
+        &self.base
+    }
+    fn base_mut(&mut self) -> &mut Cluster {
+        &mut self.base
+    }
+
+    fn read_custom_attribute(
+        &self,
+        tag: TagType,
+        tw: &mut TLVWriter,
+        attr: &AttrDetails,
+    ) -> Result<(), IMStatusCode> {
+        match num::FromPrimitive::from_u16(attr.attr_id)
+            .ok_or(IMStatusCode::UnsupportedAttribute)?
+        {
+            Attributes::GroupKeyMap => {
+                let _ = tw.start_array(tag);
+                let _ = self.group_keys.for_each_key_map(|entry| {
+                    let _ = attr.write_fab_scoped(tw, entry.fab_idx, |tw| {
+                        entry.to_tlv(tw, TagType::Anonymous)
+                    });
+                });
+                let _ = tw.end_container();
+                Ok(())
+            }
+            Attributes::GroupTable => {
+                let _ = tw.start_array(tag);
+                let _ = self.group_keys.for_each_group(|group| {
+                    let _ = attr.write_fab_scoped(tw, group.fab_idx, |tw| {
+                        group.to_tlv(tw, TagType::Anonymous)
+                    });
+                });
+                let _ = tw.end_container();
+                Ok(())
+            }
+            _ => {
+                error!("Attribute not supported: this shouldn't happen");
+                Err(IMStatusCode::UnsupportedAttribute)
+            }
+        }
+    }
+
+    fn write_attribute(
+        &mut self,
+        accessor: &Accessor,
+        data: &TLVElement,
+        attr_id: u16,
+    ) -> Result<(), IMStatusCode> {
+        match num::FromPrimitive::from_u16(attr_id).ok_or(IMStatusCode::UnsupportedAttribute)? {
+            Attributes::GroupKeyMap => self.write_key_map_attr(accessor, data),
+            _ => Err(IMStatusCode::UnsupportedWrite),
+        }
+    }
+
+    fn handle_command(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+        let cmd = cmd_req
+            .cmd
+            .path
+            .leaf
+            .map(num::FromPrimitive::from_u32)
+            .ok_or(IMStatusCode::UnsupportedCommand)?
+            .ok_or(IMStatusCode::UnsupportedCommand)?;
+        match cmd {
+            Commands::KeySetWrite => self.handle_command_keyset_write(cmd_req),
+            Commands::KeySetRead => self.handle_command_keyset_read(cmd_req),
+            Commands::KeySetRemove => self.handle_command_keyset_remove(cmd_req),
+            Commands::KeySetReadAllIndices => self.handle_command_keyset_read_all(cmd_req),
+            _ => Err(IMStatusCode::UnsupportedCommand),
+        }
+    }
+}
+
+fn attr_limit_new(attr: Attributes, limit: usize) -> Result<Attribute, Error> {
+    Attribute::new(
+        attr as u16,
+        AttrValue::Uint16(limit as u16),
+        Access::RV,
+        Quality::FIXED,
+    )
+}
@@ -1,11 +1,15 @@
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::acl::{AclEntry, AclMgr, AuthMode, Privilege};
 use crate::cert::Cert;
 use crate::crypto::{self, CryptoKeyPair, KeyPair};
 use crate::data_model::objects::*;
 use crate::data_model::sdm::dev_att;
-use crate::fabric::{Fabric, FabricMgr};
+use crate::fabric::{
+    Fabric, FabricMgr, MAX_CERT_TLV_LEN, MAX_FABRIC_LABEL_LEN, MAX_SUPPORTED_FABRICS,
+};
+use crate::group_keys::GroupKeys;
 use crate::interaction_model::command::CommandReq;
 use crate::interaction_model::core::IMStatusCode;
 use crate::interaction_model::messages::ib;
@@ -47,6 +51,25 @@ const RESP_MAX: usize = 900;
 
 pub const ID: u32 = 0x003E;
 
+#[derive(FromPrimitive)]
+pub enum Attributes {
+    NOCs = 0,
+    Fabrics = 1,
+    SupportedFabrics = 2,
+    CommissionedFabrics = 3,
+    TrustedRootCertificates = 4,
+    CurrentFabricIndex = 5,
+}
+
+// The tags of the NOCStruct and the FabricDescriptorStruct
+const NOC_TAG_NOC: u8 = 1;
+const NOC_TAG_ICAC: u8 = 2;
+const FABRIC_TAG_ROOT_PUBKEY: u8 = 1;
+const FABRIC_TAG_VENDOR_ID: u8 = 2;
+const FABRIC_TAG_FABRIC_ID: u8 = 3;
+const FABRIC_TAG_NODE_ID: u8 = 4;
+const FABRIC_TAG_LABEL: u8 = 5;
+
 #[derive(FromPrimitive)]
 pub enum Commands {
     AttReq = 0x00,
@@ -56,7 +79,10 @@ pub enum Commands {
     CSRReq = 0x04,
     CSRResp = 0x05,
     AddNOC = 0x06,
+    UpdateNOC = 0x07,
     NOCResp = 0x08,
+    UpdateFabricLabel = 0x09,
+    RemoveFabric = 0x0a,
     AddTrustedRootCert = 0x0b,
 }
 
@@ -64,6 +90,8 @@ pub struct NocCluster {
     base: Cluster,
     dev_att: Box<dyn DevAttDataFetcher>,
     fabric_mgr: Arc<FabricMgr>,
+    acl_mgr: Arc<AclMgr>,
+    group_keys: Arc<GroupKeys>,
     failsafe: Arc<FailSafe>,
 }
 struct NocData {
@@ -84,17 +112,41 @@ impl NocCluster {
     pub fn new(
         dev_att: Box<dyn DevAttDataFetcher>,
         fabric_mgr: Arc<FabricMgr>,
+        acl_mgr: Arc<AclMgr>,
+        group_keys: Arc<GroupKeys>,
         failsafe: Arc<FailSafe>,
     ) -> Result<Box<Self>, Error> {
-        Ok(Box::new(Self {
+        let mut c = Box::new(Self {
             dev_att,
             fabric_mgr,
+            acl_mgr,
+            group_keys,
             failsafe,
             base: Cluster::new(ID)?,
-        }))
+        });
+        c.base.add_attribute(attr_nocs_new()?)?;
+        c.base.add_attribute(attr_fabrics_new()?)?;
+        c.base.add_attribute(attr_supported_fabrics_new()?)?;
+        c.base.add_attribute(attr_commissioned_fabrics_new()?)?;
+        c.base.add_attribute(attr_trusted_root_certs_new()?)?;
+        c.base.add_attribute(attr_current_fabric_index_new()?)?;
+        for cmd in [
+            Commands::AttReq,
+            Commands::CertChainReq,
+            Commands::CSRReq,
+            Commands::AddNOC,
+            Commands::UpdateNOC,
+            Commands::UpdateFabricLabel,
+            Commands::RemoveFabric,
+            Commands::AddTrustedRootCert,
+        ] {
+            c.base
+                .add_command(Command::new(cmd as u16, Access::NEED_ADMIN))?;
+        }
+        Ok(c)
     }
 
-    fn _handle_command_addnoc(&mut self, cmd_req: &mut CommandReq) -> Result<(), NocStatus> {
+    fn _handle_command_addnoc(&mut self, cmd_req: &mut CommandReq) -> Result<u8, NocStatus> {
         let noc_data = cmd_req
             .trans
             .session
@@ -112,10 +164,8 @@ impl NocCluster {
 
         let r = AddNocReq::from_tlv(&cmd_req.data).map_err(|_| NocStatus::InvalidNOC)?;
 
-        let noc_value = Cert::new(r.noc_value.0).map_err(|_| NocStatus::InvalidNOC)?;
-        info!("Received NOC as: {}", noc_value);
-        let icac_value = Cert::new(r.icac_value.0).map_err(|_| NocStatus::InvalidNOC)?;
-        info!("Received ICAC as: {}", icac_value);
+        let (noc_value, icac_value) = get_noc_icac(r.noc_value, r.icac_value)?;
+        validate_chain(&noc_data.root_ca, icac_value.as_ref(), &noc_value)?;
 
         let fabric = Fabric::new(
             noc_data.key_pair,
@@ -123,42 +173,185 @@ impl NocCluster {
             icac_value,
             noc_value,
             r.ipk_value.0,
+            r.vendor_id,
         )
         .map_err(|_| NocStatus::TableFull)?;
+        let fabric_id = fabric.get_fabric_id();
+        let compressed_id = fabric.get_compressed_fabric_id().to_vec();
         let fab_idx = self
             .fabric_mgr
             .add(fabric)
             .map_err(|_| NocStatus::TableFull)?;
+        self.group_keys
+            .add_fabric(fab_idx, fabric_id, &compressed_id)
+            .map_err(|_| NocStatus::TableFull)?;
+
+        // The CASE Admin Subject gets Administer privilege on the new fabric
+        let mut admin_entry = AclEntry::new(fab_idx, Privilege::Administer, AuthMode::Case);
+        admin_entry
+            .add_subject(r.case_admin_subject)
+            .and_then(|_| self.acl_mgr.add(admin_entry))
+            .map_err(|_| NocStatus::MissingAcl)?;
 
         if self.failsafe.record_add_noc(fab_idx).is_err() {
             error!("Failed to record NoC in the FailSafe, what to do?");
         }
+        Ok(fab_idx)
+    }
 
-        let cmd_data = |t: &mut TLVWriter| {
-            // Status
-            t.u8(TagType::Context(0), 0)?;
-            // Fabric Index  - hard-coded for now
-            t.u8(TagType::Context(1), fab_idx)?;
-            // Debug string
-            t.utf8(TagType::Context(2), b"")
+    fn handle_command_addnoc(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+        cmd_enter!("AddNOC");
+        let result = self._handle_command_addnoc(cmd_req);
+        if result.is_ok() {
+            self.base.bump_dataver();
+        }
+        send_noc_resp(cmd_req, result);
+        Ok(())
+    }
+
+    fn _handle_command_updatenoc(&mut self, cmd_req: &mut CommandReq) -> Result<u8, NocStatus> {
+        // The NOC of the fabric that the session is on is updated
+        let fab_idx = cmd_req
+            .trans
+            .session
+            .get_local_fabric_idx()
+            .ok_or(NocStatus::InsufficientPrivlege)?;
+        let noc_data = cmd_req
+            .trans
+            .session
+            .take_data::<NocData>()
+            .ok_or(NocStatus::MissingCsr)?;
+
+        if !self
+            .failsafe
+            .allow_noc_change()
+            .map_err(|_| NocStatus::InsufficientPrivlege)?
+        {
+            error!("UpdateNOC not allowed by Fail Safe");
+            return Err(NocStatus::InsufficientPrivlege);
+        }
+
+        let r = UpdateNocReq::from_tlv(&cmd_req.data).map_err(|_| NocStatus::InvalidNOC)?;
+        let (noc_value, icac_value) = get_noc_icac(r.noc_value, r.icac_value)?;
+
+        // The root, the IPK and the Vendor ID remain those of the existing fabric
+        let (root_ca, ipk, vendor_id) = {
+            let fabric = self
+                .fabric_mgr
+                .get_fabric(fab_idx as usize)
+                .map_err(|_| NocStatus::InvalidFabricIndex)?;
+            let fabric = fabric
+                .as_ref()
+                .as_ref()
+                .ok_or(NocStatus::InvalidFabricIndex)?;
+            if noc_value.get_fabric_id().ok() != Some(fabric.get_fabric_id()) {
+                error!("UpdateNOC can't move the node to another fabric");
+                return Err(NocStatus::InvalidNOC);
+            }
+            validate_chain(&fabric.root_ca, icac_value.as_ref(), &noc_value)?;
+            let mut buf = [0u8; MAX_CERT_TLV_LEN];
+            let len = fabric
+                .root_ca
+                .as_tlv(&mut buf)
+                .map_err(|_| NocStatus::InvalidNOC)?;
+            let root_ca = Cert::new(&buf[..len]).map_err(|_| NocStatus::InvalidNOC)?;
+            (
+                root_ca,
+                fabric.ipk.epoch_key().to_vec(),
+                fabric.get_vendor_id(),
+            )
         };
-        let resp = ib::InvResp::cmd_new(0, ID, Commands::NOCResp as u16, &cmd_data);
-        let _ = resp.to_tlv(cmd_req.resp, TagType::Anonymous);
-        cmd_req.trans.complete();
+
+        let fabric = Fabric::new(
+            noc_data.key_pair,
+            root_ca,
+            icac_value,
+            noc_value,
+            &ipk,
+            vendor_id,
+        )
+        .map_err(|_| NocStatus::InvalidNOC)?;
+        let prev_fabric = self
+            .fabric_mgr
+            .update(fab_idx, fabric)
+            .map_err(|_| NocStatus::InvalidFabricIndex)?;
+
+        if self
+            .failsafe
+            .record_update_noc(fab_idx, prev_fabric)
+            .is_err()
+        {
+            error!("Failed to record NoC in the FailSafe, what to do?");
+        }
+        Ok(fab_idx)
+    }
+
+    fn handle_command_updatenoc(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+        cmd_enter!("UpdateNOC");
+        let result = self._handle_command_updatenoc(cmd_req);
+        if result.is_ok() {
+            self.base.bump_dataver();
+        }
+        send_noc_resp(cmd_req, result);
         Ok(())
     }
 
-    fn handle_command_addnoc(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
-        cmd_enter!("AddNOC");
-        if let Err(e) = self._handle_command_addnoc(cmd_req) {
-            let cmd_data = |t: &mut TLVWriter| {
-                // Status
-                t.u8(TagType::Context(0), e as u8)
-            };
-            let invoke_resp = ib::InvResp::cmd_new(0, ID, Commands::NOCResp as u16, &cmd_data);
-            let _ = invoke_resp.to_tlv(cmd_req.resp, TagType::Anonymous);
-            cmd_req.trans.complete();
+    fn handle_command_updatefablabel(
+        &mut self,
+        cmd_req: &mut CommandReq,
+    ) -> Result<(), IMStatusCode> {
+        cmd_enter!("UpdateFabricLabel");
+        let label = cmd_req
+            .data
+            .find_tag(0)
+            .and_then(|t| t.slice())
+            .map_err(|_| IMStatusCode::InvalidCommand)?;
+        if label.len() > MAX_FABRIC_LABEL_LEN {
+            return Err(IMStatusCode::ConstraintError);
         }
+        let label = String::from_utf8_lossy(label).into_owned();
+        let fab_idx = cmd_req
+            .trans
+            .session
+            .get_local_fabric_idx()
+            .ok_or(IMStatusCode::UnsupportedAccess)?;
+
+        let result = match self.fabric_mgr.set_label(fab_idx, &label) {
+            Ok(()) => {
+                self.base.bump_dataver();
+                Ok(fab_idx)
+            }
+            Err(Error::Invalid) => Err(NocStatus::LabelConflict),
+            Err(_) => Err(NocStatus::InvalidFabricIndex),
+        };
+        send_noc_resp(cmd_req, result);
+        Ok(())
+    }
+
+    fn handle_command_rmfabric(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
+        cmd_enter!("RemoveFabric");
+        let fab_idx = cmd_req
+            .data
+            .find_tag(0)
+            .and_then(|t| t.u8())
+            .map_err(|_| IMStatusCode::InvalidCommand)?;
+
+        let result = match self.fabric_mgr.remove(fab_idx) {
+            Ok(()) => {
+                // Everything else that belongs to the fabric goes along with it. Its
+                // sessions are evicted by the transport, once this response is sent
+                if let Err(e) = self.acl_mgr.delete_for_fabric(fab_idx) {
+                    error!("Error removing the ACL entries of the fabric: {:?}", e);
+                }
+                if let Err(e) = self.group_keys.remove_fabric(fab_idx) {
+                    error!("Error removing the groups of the fabric: {:?}", e);
+                }
+                self.base.bump_dataver();
+                Ok(fab_idx)
+            }
+            Err(_) => Err(NocStatus::InvalidFabricIndex),
+        };
+        send_noc_resp(cmd_req, result);
         Ok(())
     }
 
@@ -291,6 +484,54 @@ impl ClusterType for NocCluster {
         &mut self.base
     }
 
+    fn read_custom_attribute(
+        &self,
+        tag: TagType,
+        tw: &mut TLVWriter,
+        attr: &AttrDetails,
+    ) -> Result<(), IMStatusCode> {
+        match num::FromPrimitive::from_u16(attr.attr_id)
+            .ok_or(IMStatusCode::UnsupportedAttribute)?
+        {
+            Attributes::NOCs => {
+                let _ = tw.start_array(tag);
+                let _ = self.fabric_mgr.for_each_fabric(|fab_idx, fabric| {
+                    let _ = attr.write_fab_scoped(tw, fab_idx, |tw| write_noc(tw, fab_idx, fabric));
+                });
+                let _ = tw.end_container();
+            }
+            Attributes::Fabrics => {
+                let _ = tw.start_array(tag);
+                let _ = self.fabric_mgr.for_each_fabric(|fab_idx, fabric| {
+                    let _ = attr.write_fab_scoped(tw, fab_idx, |tw| {
+                        write_fabric_descriptor(tw, fab_idx, fabric)
+                    });
+                });
+                let _ = tw.end_container();
+            }
+            Attributes::CommissionedFabrics => {
+                let mut count = 0;
+                let _ = self.fabric_mgr.for_each_fabric(|_, _| count += 1);
+                let _ = tw.u8(tag, count);
+            }
+            Attributes::TrustedRootCertificates => {
+                let _ = tw.start_array(tag);
+                let _ = self.fabric_mgr.for_each_fabric(|_, fabric| {
+                    let _ = write_cert(tw, TagType::Anonymous, &fabric.root_ca);
+                });
+                let _ = tw.end_container();
+            }
+            Attributes::CurrentFabricIndex => {
+                let _ = tw.u8(tag, attr.fab_idx);
+            }
+            _ => {
+                error!("Attribute not supported: this shouldn't happen");
+                return Err(IMStatusCode::UnsupportedAttribute);
+            }
+        }
+        Ok(())
+    }
+
     fn handle_command(&mut self, cmd_req: &mut CommandReq) -> Result<(), IMStatusCode> {
         let cmd = cmd_req
             .cmd
@@ -301,6 +542,9 @@ impl ClusterType for NocCluster {
             .ok_or(IMStatusCode::UnsupportedCommand)?;
         match cmd {
             Commands::AddNOC => self.handle_command_addnoc(cmd_req),
+            Commands::UpdateNOC => self.handle_command_updatenoc(cmd_req),
+            Commands::UpdateFabricLabel => self.handle_command_updatefablabel(cmd_req),
+            Commands::RemoveFabric => self.handle_command_rmfabric(cmd_req),
             Commands::CSRReq => self.handle_command_csrrequest(cmd_req),
             Commands::AddTrustedRootCert => self.handle_command_addtrustedrootcert(cmd_req),
             Commands::AttReq => self.handle_command_attrequest(cmd_req),
@@ -310,6 +554,145 @@ impl ClusterType for NocCluster {
     }
 }
 
+fn attr_nocs_new() -> Result<Attribute, Error> {
+    Attribute::new(
+        Attributes::NOCs as u16,
+        AttrValue::Custom,
+        Access::READ | Access::NEED_ADMIN | Access::FAB_SCOPED | Access::FAB_SENSITIVE,
+        Quality::NONE,
+    )
+}
+
+fn attr_fabrics_new() -> Result<Attribute, Error> {
+    Attribute::new(
+        Attributes::Fabrics as u16,
+        AttrValue::Custom,
+        Access::RV | Access::FAB_SCOPED,
+        Quality::NONE,
+    )
+}
+
+fn attr_supported_fabrics_new() -> Result<Attribute, Error> {
+    // The first slot of the Fabric Manager isn't available to fabrics
+    Attribute::new(
+        Attributes::SupportedFabrics as u16,
+        AttrValue::Uint8((MAX_SUPPORTED_FABRICS - 1) as u8),
+        Access::RV,
+        Quality::FIXED,
+    )
+}
+
+fn attr_commissioned_fabrics_new() -> Result<Attribute, Error> {
+    Attribute::new(
+        Attributes::CommissionedFabrics as u16,
+        AttrValue::Custom,
+        Access::RV,
+        Quality::NONE,
+    )
+}
+
+fn attr_trusted_root_certs_new() -> Result<Attribute, Error> {
+    Attribute::new(
+        Attributes::TrustedRootCertificates as u16,
+        AttrValue::Custom,
+        Access::RV,
+        Quality::NONE,
+    )
+}
+
+fn attr_current_fabric_index_new() -> Result<Attribute, Error> {
+    Attribute::new(
+        Attributes::CurrentFabricIndex as u16,
+        AttrValue::Custom,
+        Access::RV,
+        Quality::NONE,
+    )
+}
+
+// The NOC and the optional ICAC of AddNOC and UpdateNOC
+fn get_noc_icac(noc: OctetStr, icac: Option<OctetStr>) -> Result<(Cert, Option<Cert>), NocStatus> {
+    let noc = Cert::new(noc.0).map_err(|_| NocStatus::InvalidNOC)?;
+    info!("Received NOC as: {}", noc);
+    let icac = match icac {
+        Some(icac) => {
+            let icac = Cert::new(icac.0).map_err(|_| NocStatus::InvalidNOC)?;
+            info!("Received ICAC as: {}", icac);
+            Some(icac)
+        }
+        None => None,
+    };
+    Ok((noc, icac))
+}
+
+// The NOC, and the ICAC if any, must chain up to the root of the fabric
+fn validate_chain(root_ca: &Cert, icac: Option<&Cert>, noc: &Cert) -> Result<(), NocStatus> {
+    Fabric::validate_chain(root_ca, icac, noc).map_err(|e| {
+        error!("The NOC doesn't chain up to the root: {:?}", e);
+        NocStatus::InvalidNOC
+    })
+}
+
+// A certificate, in its Matter TLV encoding
+fn write_cert(tw: &mut TLVWriter, tag: TagType, cert: &Cert) -> Result<(), Error> {
+    let mut buf = [0u8; MAX_CERT_TLV_LEN];
+    let len = cert.as_tlv(&mut buf)?;
+    tw.str16(tag, &buf[..len])
+}
+
+// The NOCStruct of a fabric
+fn write_noc(tw: &mut TLVWriter, fab_idx: u8, fabric: &Fabric) -> Result<(), Error> {
+    tw.start_struct(TagType::Anonymous)?;
+    write_cert(tw, TagType::Context(NOC_TAG_NOC), &fabric.noc)?;
+    if let Some(icac) = &fabric.icac {
+        write_cert(tw, TagType::Context(NOC_TAG_ICAC), icac)?;
+    } else {
+        tw.null(TagType::Context(NOC_TAG_ICAC))?;
+    }
+    tw.u8(TagType::Context(GlobalElements::FabricIndex as u8), fab_idx)?;
+    tw.end_container()
+}
+
+fn write_fabric_descriptor(tw: &mut TLVWriter, fab_idx: u8, fabric: &Fabric) -> Result<(), Error> {
+    tw.start_struct(TagType::Anonymous)?;
+    tw.str8(
+        TagType::Context(FABRIC_TAG_ROOT_PUBKEY),
+        fabric.root_ca.get_pubkey(),
+    )?;
+    tw.u16(
+        TagType::Context(FABRIC_TAG_VENDOR_ID),
+        fabric.get_vendor_id(),
+    )?;
+    tw.u64(
+        TagType::Context(FABRIC_TAG_FABRIC_ID),
+        fabric.get_fabric_id(),
+    )?;
+    tw.u64(TagType::Context(FABRIC_TAG_NODE_ID), fabric.get_node_id())?;
+    tw.utf8(
+        TagType::Context(FABRIC_TAG_LABEL),
+        fabric.get_label().as_bytes(),
+    )?;
+    tw.u8(TagType::Context(GlobalElements::FabricIndex as u8), fab_idx)?;
+    tw.end_container()
+}
+
+// The NOCResponse, with the fabric index on success
+fn send_noc_resp(cmd_req: &mut CommandReq, result: Result<u8, NocStatus>) {
+    let cmd_data = |t: &mut TLVWriter| {
+        match result {
+            Ok(fab_idx) => {
+                t.u8(TagType::Context(0), NocStatus::Ok as u8)?;
+                t.u8(TagType::Context(1), fab_idx)?;
+            }
+            Err(e) => t.u8(TagType::Context(0), e as u8)?,
+        }
+        // Debug string
+        t.utf8(TagType::Context(2), b"")
+    };
+    let resp = ib::InvResp::cmd_new(0, ID, Commands::NOCResp as u16, &cmd_data);
+    let _ = resp.to_tlv(cmd_req.resp, TagType::Anonymous);
+    cmd_req.trans.complete();
+}
+
 fn add_attestation_element(
     dev_att: &dyn DevAttDataFetcher,
     att_nonce: &[u8],
@@ -373,10 +756,17 @@ fn add_nocsrelement(
 #[tlvargs(lifetime = "'a")]
 struct AddNocReq<'a> {
     noc_value: OctetStr<'a>,
-    icac_value: OctetStr<'a>,
+    icac_value: Option<OctetStr<'a>>,
     ipk_value: OctetStr<'a>,
-    _case_admin_node_id: u32,
-    _vendor_id: u16,
+    case_admin_subject: u64,
+    vendor_id: u16,
+}
+
+#[derive(FromTLV)]
+#[tlvargs(lifetime = "'a")]
+struct UpdateNocReq<'a> {
+    noc_value: OctetStr<'a>,
+    icac_value: Option<OctetStr<'a>>,
 }
 
 #[derive(FromTLV)]
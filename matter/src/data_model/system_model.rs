@@ -1 +1,2 @@
+pub mod access_control;
 pub mod descriptor;
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use num_derive::FromPrimitive;
+
+use crate::acl::{self, Accessor, AclEntry, AclMgr, AuthMode};
+use crate::data_model::objects::*;
+use crate::error::*;
+use crate::interaction_model::core::IMStatusCode;
+use crate::tlv::{TLVElement, TLVWriter, TagType};
+use log::error;
+
+pub const ID: u32 = 0x001F;
+
+#[derive(FromPrimitive)]
+pub enum Attributes {
+    Acl = 0,
+    SubjectsPerEntry = 2,
+    TargetsPerEntry = 3,
+    EntriesPerFabric = 4,
+}
+
+pub struct AccessControlCluster {
+    base: Cluster,
+    acl_mgr: Arc<AclMgr>,
+}
+
+impl AccessControlCluster {
+    pub fn new(acl_mgr: Arc<AclMgr>) -> Result<Box<Self>, Error> {
+        let mut c = Box::new(AccessControlCluster {
+            base: Cluster::new(ID)?,
+            acl_mgr,
+        });
+        c.base.add_attribute(attr_acl_new()?)?;
+        c.base.add_attribute(attr_limit_new(
+            Attributes::SubjectsPerEntry,
+            acl::SUBJECTS_PER_ENTRY,
+        )?)?;
+        c.base.add_attribute(attr_limit_new(
+            Attributes::TargetsPerEntry,
+            acl::TARGETS_PER_ENTRY,
+        )?)?;
+        c.base.add_attribute(attr_limit_new(
+            Attributes::EntriesPerFabric,
+            acl::ENTRIES_PER_FABRIC,
+        )?)?;
+        Ok(c)
+    }
+
+    /// Replace all the entries of the accessor's fabric with the entries in the list
+    fn write_acl_attr(
+        &mut self,
+        accessor: &Accessor,
+        data: &TLVElement,
+    ) -> Result<(), IMStatusCode> {
+        if accessor.fab_idx == 0 {
+            // The ACL is fabric-scoped, this can only be written over a CASE session
+            return Err(IMStatusCode::UnsupportedAccess);
+        }
+
+        let mut entries = Vec::new();
+        let iter = data
+            .confirm_array()
+            .map_err(|_| IMStatusCode::InvalidDataType)?
+            .iter();
+        if let Some(iter) = iter {
+            for e in iter {
+                let mut entry =
+                    AclEntry::from_tlv(&e).map_err(|_| IMStatusCode::ConstraintError)?;
+                if entry.auth_mode == AuthMode::Pase {
+                    // PASE is granted implicitly, it can't be configured in the ACL
+                    return Err(IMStatusCode::ConstraintError);
+                }
+                entry.fab_idx = accessor.fab_idx;
+                entries.push(entry);
+            }
+        }
+
+        self.acl_mgr
+            .set_for_fabric(accessor.fab_idx, entries)
+            .map_err(|_| IMStatusCode::ResourceExhausted)?;
+        self.base.bump_dataver();
+        Ok(())
+    }
+}
+
+impl ClusterType for AccessControlCluster {
+    fn base(&self) -> &Cluster {
+        &self.base
+    }
+    fn base_mut(&mut self) -> &mut Cluster {
+        &mut self.base
+    }
+
+    fn read_custom_attribute(
+        &self,
+        tag: TagType,
+        tw: &mut TLVWriter,
+        attr: &AttrDetails,
+    ) -> Result<(), IMStatusCode> {
+        match num::FromPrimitive::from_u16(attr.attr_id)
+            .ok_or(IMStatusCode::UnsupportedAttribute)?
+        {
+            Attributes::Acl => {
+                let _ = tw.start_array(tag);
+                let _ = self.acl_mgr.for_each_acl(|entry| {
+                    let _ = attr.write_fab_scoped(tw, entry.fab_idx, |tw| {
+                        entry.to_tlv(tw, TagType::Anonymous)
+                    });
+                });
+                let _ = tw.end_container();
+                Ok(())
+            }
+            _ => {
+                error!("Attribute not supported: this shouldn't happen");
+                Err(IMStatusCode::UnsupportedAttribute)
+            }
+        }
+    }
+
+    fn write_attribute(
+        &mut self,
+        accessor: &Accessor,
+        data: &TLVElement,
+        attr_id: u16,
+    ) -> Result<(), IMStatusCode> {
+        match num::FromPrimitive::from_u16(attr_id).ok_or(IMStatusCode::UnsupportedAttribute)? {
+            Attributes::Acl => self.write_acl_attr(accessor, data),
+            _ => Err(IMStatusCode::UnsupportedWrite),
+        }
+    }
+}
+
+fn attr_acl_new() -> Result<Attribute, Error> {
+    Attribute::new(
+        Attributes::Acl as u16,
+        AttrValue::Custom,
+        Access::READ
+            | Access::WRITE
+            | Access::NEED_ADMIN
+            | Access::FAB_SCOPED
+            | Access::FAB_SENSITIVE,
+        Quality::NONE,
+    )
+}
+
+fn attr_limit_new(attr: Attributes, limit: usize) -> Result<Attribute, Error> {
+    Attribute::new(
+        attr as u16,
+        AttrValue::Uint16(limit as u16),
+        Access::RV,
+        Quality::FIXED,
+    )
+}
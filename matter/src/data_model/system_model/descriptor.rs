@@ -48,9 +48,11 @@ impl ClusterType for DescriptorCluster {
         &self,
         tag: TagType,
         tw: &mut TLVWriter,
-        attr_id: u16,
+        attr: &AttrDetails,
     ) -> Result<(), IMStatusCode> {
-        match num::FromPrimitive::from_u16(attr_id).ok_or(IMStatusCode::UnsupportedAttribute)? {
+        match num::FromPrimitive::from_u16(attr.attr_id)
+            .ok_or(IMStatusCode::UnsupportedAttribute)?
+        {
             Attributes::ServerList => {
                 let path = GenericPath {
                     endpoint: Some(self.endpoint_id),
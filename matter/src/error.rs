@@ -9,6 +9,8 @@ pub enum Error {
     AttributeIsCustom,
     ClusterNotFound,
     CommandNotFound,
+    // A message that was already received
+    Duplicate,
     EndpointNotFound,
     Crypto,
     TLSStack,
@@ -30,6 +32,7 @@ pub enum Error {
     PacketPoolExhaust,
     StdIoError,
     SysTimeFail,
+    Timeout,
     Invalid,
     InvalidAAD,
     InvalidData,
@@ -1,4 +1,4 @@
-use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::sync::{Arc, RwLock};
 
 use byteorder::{BigEndian, ByteOrder, LittleEndian};
 use log::info;
@@ -9,10 +9,11 @@ use crate::{
     crypto::{self, crypto_dummy::KeyPairDummy, hkdf_sha256, CryptoKeyPair, HmacSha256, KeyPair},
     error::Error,
     group_keys::KeySet,
-    sys::{Mdns, MdnsService, Psm},
+    persist::KvStore,
+    sys::{Mdns, MdnsService, ServiceMode},
 };
 
-const MAX_CERT_TLV_LEN: usize = 300;
+pub const MAX_CERT_TLV_LEN: usize = 300;
 const COMPRESSED_FABRIC_ID_LEN: usize = 8;
 
 macro_rules! fb_key {
@@ -27,6 +28,23 @@ const ST_NOC: &str = "noc";
 const ST_IPK: &str = "ipk";
 const ST_PBKEY: &str = "pubkey";
 const ST_PRKEY: &str = "privkey";
+const ST_LABEL: &str = "label";
+const ST_VID: &str = "vid";
+const ST_RESUMPTION: &str = "resumption";
+const ST_ALL: [&str; 9] = [
+    ST_RCA,
+    ST_ICA,
+    ST_NOC,
+    ST_IPK,
+    ST_PBKEY,
+    ST_PRKEY,
+    ST_LABEL,
+    ST_VID,
+    ST_RESUMPTION,
+];
+
+// The longest Fabric Label that is accepted
+pub const MAX_FABRIC_LABEL_LEN: usize = 32;
 
 #[allow(dead_code)]
 pub struct Fabric {
@@ -34,10 +52,14 @@ pub struct Fabric {
     fabric_id: u64,
     key_pair: Box<dyn CryptoKeyPair>,
     pub root_ca: Cert,
-    pub icac: Cert,
+    // The NOC may be issued directly by the root
+    pub icac: Option<Cert>,
     pub noc: Cert,
     pub ipk: KeySet,
     compressed_id: [u8; COMPRESSED_FABRIC_ID_LEN],
+    label: String,
+    // The Vendor ID of the administrator, that commissioned the node into this fabric
+    vendor_id: u16,
     mdns_service: Option<MdnsService>,
 }
 
@@ -45,9 +67,10 @@ impl Fabric {
     pub fn new(
         key_pair: KeyPair,
         root_ca: Cert,
-        icac: Cert,
+        icac: Option<Cert>,
         noc: Cert,
         ipk: &[u8],
+        vendor_id: u16,
     ) -> Result<Self, Error> {
         let node_id = noc.get_node_id()?;
         let fabric_id = noc.get_fabric_id()?;
@@ -61,6 +84,8 @@ impl Fabric {
             noc,
             ipk: KeySet::default(),
             compressed_id: [0; COMPRESSED_FABRIC_ID_LEN],
+            label: String::new(),
+            vendor_id,
             mdns_service: None,
         };
         Fabric::get_compressed_id(f.root_ca.get_pubkey(), fabric_id, &mut f.compressed_id)?;
@@ -77,7 +102,10 @@ impl Fabric {
             mdns_service_name.push_str(&format!("{:02X}", c));
         }
         info!("MDNS Service Name: {}", mdns_service_name);
-        f.mdns_service = Some(Mdns::publish_service(&mdns_service_name)?);
+        f.mdns_service = Some(Mdns::publish_service(
+            &mdns_service_name,
+            ServiceMode::Commissioned,
+        )?);
         Ok(f)
     }
 
@@ -87,14 +115,33 @@ impl Fabric {
             fabric_id: 0,
             key_pair: Box::new(KeyPairDummy::new()?),
             root_ca: Cert::default(),
-            icac: Cert::default(),
+            icac: None,
             noc: Cert::default(),
             ipk: KeySet::default(),
             compressed_id: [0; COMPRESSED_FABRIC_ID_LEN],
+            label: String::new(),
+            vendor_id: 0,
             mdns_service: None,
         })
     }
 
+    /// Verify that the NOC, and the ICAC if any, chain up to the root, and that they are of
+    /// the same fabric
+    pub fn validate_chain(root_ca: &Cert, icac: Option<&Cert>, noc: &Cert) -> Result<(), Error> {
+        let verifier = noc.verify_chain_start();
+        let verifier = if let Some(icac) = icac {
+            if let Ok(fid) = icac.get_fabric_id() {
+                if fid != noc.get_fabric_id()? {
+                    return Err(Error::Invalid);
+                }
+            }
+            verifier.add_cert(icac)?
+        } else {
+            verifier
+        };
+        verifier.add_cert(root_ca)?.finalise()
+    }
+
     fn get_compressed_id(root_pubkey: &[u8], fabric_id: u64, out: &mut [u8]) -> Result<(), Error> {
         let root_pubkey = &root_pubkey[1..];
         let mut fabric_id_be: [u8; 8] = [0; 8];
@@ -107,7 +154,9 @@ impl Fabric {
             .map_err(|_| Error::NoSpace)
     }
 
-    pub fn match_dest_id(&self, random: &[u8], target: &[u8]) -> Result<(), Error> {
+    /// Computes the destination id, with which a CASE initiator identifies the node
+    /// 'node_id' on this fabric
+    pub fn get_dest_id(&self, random: &[u8], node_id: u64, out: &mut [u8]) -> Result<(), Error> {
         let mut mac = HmacSha256::new(self.ipk.op_key())?;
 
         mac.update(random)?;
@@ -117,11 +166,15 @@ impl Fabric {
         LittleEndian::write_u64(&mut buf, self.fabric_id);
         mac.update(&buf)?;
 
-        LittleEndian::write_u64(&mut buf, self.node_id);
+        LittleEndian::write_u64(&mut buf, node_id);
         mac.update(&buf)?;
 
+        mac.finish(out)
+    }
+
+    pub fn match_dest_id(&self, random: &[u8], target: &[u8]) -> Result<(), Error> {
         let mut id = [0_u8; crypto::SHA256_HASH_LEN_BYTES];
-        mac.finish(&mut id)?;
+        self.get_dest_id(random, self.node_id, &mut id)?;
         if id.as_slice() == target {
             Ok(())
         } else {
@@ -141,56 +194,106 @@ impl Fabric {
         self.fabric_id
     }
 
-    fn store(&self, index: usize, psm: &MutexGuard<Psm>) -> Result<(), Error> {
+    pub fn get_compressed_fabric_id(&self) -> &[u8] {
+        &self.compressed_id
+    }
+
+    pub fn get_label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn get_vendor_id(&self) -> u16 {
+        self.vendor_id
+    }
+
+    fn store(&self, index: usize, store: &dyn KvStore) -> Result<(), Error> {
         let mut key = [0u8; MAX_CERT_TLV_LEN];
         let len = self.root_ca.as_tlv(&mut key)?;
-        psm.set_kv_slice(fb_key!(index, ST_RCA), &key[..len])?;
-        let len = self.icac.as_tlv(&mut key)?;
-        psm.set_kv_slice(fb_key!(index, ST_ICA), &key[..len])?;
+        store.set_kv_slice(fb_key!(index, ST_RCA), &key[..len])?;
+        if let Some(icac) = &self.icac {
+            let len = icac.as_tlv(&mut key)?;
+            store.set_kv_slice(fb_key!(index, ST_ICA), &key[..len])?;
+        } else {
+            store.remove_kv(fb_key!(index, ST_ICA))?;
+        }
         let len = self.noc.as_tlv(&mut key)?;
-        psm.set_kv_slice(fb_key!(index, ST_NOC), &key[..len])?;
-        psm.set_kv_slice(fb_key!(index, ST_IPK), self.ipk.epoch_key())?;
+        store.set_kv_slice(fb_key!(index, ST_NOC), &key[..len])?;
+        store.set_kv_slice(fb_key!(index, ST_IPK), self.ipk.epoch_key())?;
 
         let mut key = [0_u8; crypto::EC_POINT_LEN_BYTES];
         let len = self.key_pair.get_public_key(&mut key)?;
         let key = &key[..len];
-        psm.set_kv_slice(fb_key!(index, ST_PBKEY), key)?;
+        store.set_kv_slice(fb_key!(index, ST_PBKEY), key)?;
 
         let mut key = [0_u8; crypto::BIGNUM_LEN_BYTES];
         let len = self.key_pair.get_private_key(&mut key)?;
         let key = &key[..len];
-        psm.set_kv_slice(fb_key!(index, ST_PRKEY), key)?;
+        store.set_kv_slice(fb_key!(index, ST_PRKEY), key)?;
+        store.set_kv_slice(fb_key!(index, ST_LABEL), self.label.as_bytes())?;
+        store.set_kv_u64(fb_key!(index, ST_VID), self.vendor_id as u64)?;
+
+        Ok(())
+    }
 
+    fn remove(index: usize, store: &dyn KvStore) -> Result<(), Error> {
+        for key in ST_ALL {
+            store.remove_kv(fb_key!(index, key))?;
+        }
         Ok(())
     }
 
-    fn load(index: usize, psm: &MutexGuard<Psm>) -> Result<Self, Error> {
+    fn load(index: usize, store: &dyn KvStore) -> Result<Self, Error> {
         let mut root_ca = Vec::new();
-        psm.get_kv_slice(fb_key!(index, ST_RCA), &mut root_ca)?;
+        store.get_kv_slice(fb_key!(index, ST_RCA), &mut root_ca)?;
         let root_ca = Cert::new(root_ca.as_slice())?;
 
         let mut icac = Vec::new();
-        psm.get_kv_slice(fb_key!(index, ST_ICA), &mut icac)?;
-        let icac = Cert::new(icac.as_slice())?;
+        let icac = if store
+            .get_kv_slice(fb_key!(index, ST_ICA), &mut icac)
+            .is_ok()
+        {
+            Some(Cert::new(icac.as_slice())?)
+        } else {
+            None
+        };
 
         let mut noc = Vec::new();
-        psm.get_kv_slice(fb_key!(index, ST_NOC), &mut noc)?;
+        store.get_kv_slice(fb_key!(index, ST_NOC), &mut noc)?;
         let noc = Cert::new(noc.as_slice())?;
 
         let mut ipk = Vec::new();
-        psm.get_kv_slice(fb_key!(index, ST_IPK), &mut ipk)?;
+        store.get_kv_slice(fb_key!(index, ST_IPK), &mut ipk)?;
 
         let mut pub_key = Vec::new();
-        psm.get_kv_slice(fb_key!(index, ST_PBKEY), &mut pub_key)?;
+        store.get_kv_slice(fb_key!(index, ST_PBKEY), &mut pub_key)?;
         let mut priv_key = Vec::new();
-        psm.get_kv_slice(fb_key!(index, ST_PRKEY), &mut priv_key)?;
+        store.get_kv_slice(fb_key!(index, ST_PRKEY), &mut priv_key)?;
         let keypair = KeyPair::new_from_components(pub_key.as_slice(), priv_key.as_slice())?;
 
-        Fabric::new(keypair, root_ca, icac, noc, ipk.as_slice())
+        // The label and the vendor id were only stored by later versions
+        let mut vendor_id = 0;
+        let _ = store.get_kv_u64(fb_key!(index, ST_VID), &mut vendor_id);
+
+        let mut fabric = Fabric::new(
+            keypair,
+            root_ca,
+            icac,
+            noc,
+            ipk.as_slice(),
+            vendor_id as u16,
+        )?;
+        let mut label = Vec::new();
+        if store
+            .get_kv_slice(fb_key!(index, ST_LABEL), &mut label)
+            .is_ok()
+        {
+            fabric.label = String::from_utf8_lossy(&label).into_owned();
+        }
+        Ok(fabric)
     }
 }
 
-const MAX_SUPPORTED_FABRICS: usize = 3;
+pub const MAX_SUPPORTED_FABRICS: usize = 3;
 #[derive(Default)]
 pub struct FabricMgrInner {
     // The outside world expects Fabric Index to be one more than the actual one
@@ -200,32 +303,35 @@ pub struct FabricMgrInner {
 
 pub struct FabricMgr {
     inner: RwLock<FabricMgrInner>,
-    psm: Arc<Mutex<Psm>>,
+    store: Arc<dyn KvStore>,
+    // The indices of the fabrics that were removed, oldest first. The sessions and the
+    // other state of a fabric are dropped by its users, as they catch up with this list
+    removed: RwLock<Vec<u8>>,
 }
 
 impl FabricMgr {
-    pub fn new() -> Result<Self, Error> {
+    /// Create the Fabric Manager, that persists the fabrics in 'store'
+    pub fn new(store: Arc<dyn KvStore>) -> Result<Self, Error> {
         let dummy_fabric = Fabric::dummy()?;
         let mut mgr = FabricMgrInner::default();
         mgr.fabrics[0] = Some(dummy_fabric);
         let mut fm = Self {
             inner: RwLock::new(mgr),
-            psm: Psm::get()?,
+            store,
+            removed: RwLock::new(Vec::new()),
         };
         fm.load()?;
         Ok(fm)
     }
 
     fn store(&self, index: usize, fabric: &Fabric) -> Result<(), Error> {
-        let psm = self.psm.lock().unwrap();
-        fabric.store(index, &psm)
+        fabric.store(index, self.store.as_ref())
     }
 
     fn load(&mut self) -> Result<(), Error> {
         let mut mgr = self.inner.write()?;
-        let psm = self.psm.lock().unwrap();
         for i in 0..MAX_SUPPORTED_FABRICS {
-            let result = Fabric::load(i, &psm);
+            let result = Fabric::load(i, self.store.as_ref());
             if let Ok(fabric) = result {
                 info!("Adding new fabric at index {}", i);
                 mgr.fabrics[i] = Some(fabric);
@@ -248,6 +354,89 @@ impl FabricMgr {
         Ok(index as u8)
     }
 
+    /// Replace the fabric at 'index', as with an UpdateNOC, and return the previous one.
+    /// The label of the fabric is retained
+    pub fn update(&self, index: u8, mut f: Fabric) -> Result<Fabric, Error> {
+        let mut mgr = self.inner.write()?;
+        let old = mgr
+            .fabrics
+            .get_mut(index as usize)
+            .and_then(|f| f.as_mut())
+            .ok_or(Error::NotFound)?;
+        f.label = old.label.clone();
+        self.store(index as usize, &f)?;
+        Ok(std::mem::replace(old, f))
+    }
+
+    /// Remove the fabric at 'index', along with its stored data. Its mDNS service is
+    /// withdrawn as the fabric is dropped
+    pub fn remove(&self, index: u8) -> Result<(), Error> {
+        if index == 0 {
+            return Err(Error::NotFound);
+        }
+        let mut mgr = self.inner.write()?;
+        mgr.fabrics
+            .get_mut(index as usize)
+            .and_then(|f| f.take())
+            .ok_or(Error::NotFound)?;
+        info!("Removing fabric at index {}", index);
+        Fabric::remove(index as usize, self.store.as_ref())?;
+        self.removed.write()?.push(index);
+        Ok(())
+    }
+
+    /// The indices of the fabrics that were removed since 'cursor', which is then
+    /// advanced past them
+    pub fn get_removed_since(&self, cursor: &mut usize) -> Vec<u8> {
+        let removed = match self.removed.read() {
+            Ok(r) => r,
+            Err(_) => return Vec::new(),
+        };
+        let new = removed.get(*cursor..).unwrap_or_default().to_vec();
+        *cursor = removed.len();
+        new
+    }
+
+    /// Set the label of the fabric at 'index'. Labels must be unique, unless empty
+    pub fn set_label(&self, index: u8, label: &str) -> Result<(), Error> {
+        if label.len() > MAX_FABRIC_LABEL_LEN {
+            return Err(Error::Invalid);
+        }
+        let mut mgr = self.inner.write()?;
+        let conflict = mgr.fabrics.iter().enumerate().any(|(i, f)| {
+            i != index as usize
+                && !label.is_empty()
+                && f.as_ref().map(|f| f.label.as_str()) == Some(label)
+        });
+        if conflict {
+            return Err(Error::Invalid);
+        }
+        let fabric = mgr
+            .fabrics
+            .get_mut(index as usize)
+            .and_then(|f| f.as_mut())
+            .ok_or(Error::NotFound)?;
+        fabric.label = label.to_owned();
+        self.store
+            .set_kv_slice(fb_key!(index, ST_LABEL), label.as_bytes())
+    }
+
+    /// Persist the CASE session resumption records of the fabric at 'index'. They are
+    /// removed from the storage along with the fabric
+    pub fn store_resumption(&self, index: u8, val: &[u8]) -> Result<(), Error> {
+        // The fabric may have been removed while the session was being established
+        let mgr = self.inner.read()?;
+        if !matches!(mgr.fabrics.get(index as usize), Some(Some(_))) {
+            return Err(Error::NotFound);
+        }
+        self.store.set_kv_slice(fb_key!(index, ST_RESUMPTION), val)
+    }
+
+    /// Append the stored CASE session resumption records of the fabric at 'index' to 'val'
+    pub fn load_resumption(&self, index: u8, val: &mut Vec<u8>) -> Result<usize, Error> {
+        self.store.get_kv_slice(fb_key!(index, ST_RESUMPTION), val)
+    }
+
     pub fn match_dest_id(&self, random: &[u8], target: &[u8]) -> Result<usize, Error> {
         let mgr = self.inner.read()?;
         for i in 0..MAX_SUPPORTED_FABRICS {
@@ -267,6 +456,20 @@ impl FabricMgr {
         Ok(RwLockReadGuardRef::new(self.inner.read()?).map(|fm| &fm.fabrics[idx]))
     }
 
+    /// Call 'f' for each of the fabrics, with its index
+    pub fn for_each_fabric<T>(&self, mut f: T) -> Result<(), Error>
+    where
+        T: FnMut(u8, &Fabric),
+    {
+        let mgr = self.inner.read()?;
+        for (i, fabric) in mgr.fabrics.iter().enumerate().skip(1) {
+            if let Some(fabric) = fabric {
+                f(i as u8, fabric);
+            }
+        }
+        Ok(())
+    }
+
     pub fn is_empty(&self) -> bool {
         let mgr = self.inner.read().unwrap();
         for i in 1..MAX_SUPPORTED_FABRICS {
@@ -1,31 +1,43 @@
-use std::sync::{Arc, Mutex, Once};
+use std::{
+    net::Ipv6Addr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+};
 
-use crate::{crypto, error::Error};
+use crate::{
+    crypto,
+    error::Error,
+    fabric::MAX_SUPPORTED_FABRICS,
+    persist::KvStore,
+    tlv::{self, nullable, TLVElement, TLVWriter, TagType},
+    utils::writebuf::WriteBuf,
+};
+use byteorder::{BigEndian, ByteOrder};
+use log::error;
+use num_derive::FromPrimitive;
 
-// This is just makeshift implementation for now, not used anywhere
-pub struct GroupKeys {}
+/* Group Keys
+ *
+ * - The keys of a fabric's groups are organised in key sets, each of which holds up to 3
+ *   epoch keys. A group message is encrypted with an operational key, that is derived
+ *   from one of the epoch keys and the compressed fabric id
+ * - The Group Key Map maps a group to the key set that its messages are encrypted with
+ * - The Group Table records the endpoints that are members of a group
+ * - Key set 0 is the fabric's IPK, which is managed along with the fabric itself
+ */
 
-static mut G_GRP_KEYS: Option<Arc<Mutex<GroupKeys>>> = None;
-static INIT: Once = Once::new();
+// The minimum values as required by the spec, the key sets include the IPK
+pub const MAX_GROUP_KEYS_PER_FABRIC: usize = 3;
+pub const MAX_GROUPS_PER_FABRIC: usize = 4;
+pub const EPOCH_KEYS_PER_SET: usize = 3;
+pub const IPK_KEY_SET_ID: u16 = 0;
+const MAX_GROUPS_TLV_LEN: usize = 4096;
+const ST_GROUPS: &str = "groups";
 
-impl GroupKeys {
-    fn new() -> Self {
-        Self {}
-    }
-
-    pub fn get() -> Result<Arc<Mutex<Self>>, Error> {
-        unsafe {
-            INIT.call_once(|| {
-                G_GRP_KEYS = Some(Arc::new(Mutex::new(GroupKeys::new())));
-            });
-            Ok(G_GRP_KEYS.as_ref().ok_or(Error::Invalid)?.clone())
-        }
-    }
-
-    pub fn insert_key() -> Result<(), Error> {
-        Ok(())
-    }
-}
+const GROUP_KEY_INFO: [u8; 13] = *b"GroupKey v1.0";
+const GROUP_KEY_HASH_INFO: [u8; 12] = *b"GroupKeyHash";
 
 #[derive(Debug, Default)]
 pub struct KeySet {
@@ -42,11 +54,7 @@ impl KeySet {
     }
 
     fn op_key_from_ipk(ipk: &[u8], compressed_id: &[u8], opkey: &mut [u8]) -> Result<(), Error> {
-        const GRP_KEY_INFO: [u8; 13] = [
-            0x47, 0x72, 0x6f, 0x75, 0x70, 0x4b, 0x65, 0x79, 0x20, 0x76, 0x31, 0x2e, 0x30,
-        ];
-
-        crypto::hkdf_sha256(compressed_id, ipk, &GRP_KEY_INFO, opkey).map_err(|_| Error::NoSpace)
+        crypto::hkdf_sha256(compressed_id, ipk, &GROUP_KEY_INFO, opkey).map_err(|_| Error::NoSpace)
     }
 
     pub fn op_key(&self) -> &[u8] {
@@ -57,3 +65,811 @@ impl KeySet {
         &self.epoch_key
     }
 }
+
+/// The Group Session ID, that a message encrypted with the operational key carries
+pub fn group_session_id(op_key: &[u8]) -> Result<u16, Error> {
+    let mut hash = [0_u8; 2];
+    crypto::hkdf_sha256(&[], op_key, &GROUP_KEY_HASH_INFO, &mut hash)
+        .map_err(|_| Error::NoSpace)?;
+    Ok(BigEndian::read_u16(&hash))
+}
+
+/// The IPv6 multicast address of a group: FF35:0040:FD<Fabric ID>00:<Group ID>
+pub fn group_multicast_addr(fabric_id: u64, group_id: u16) -> Ipv6Addr {
+    let mut addr = [0_u8; 16];
+    addr[..5].copy_from_slice(&[0xff, 0x35, 0x00, 0x40, 0xfd]);
+    BigEndian::write_u64(&mut addr[5..13], fabric_id);
+    BigEndian::write_u16(&mut addr[14..], group_id);
+    Ipv6Addr::from(addr)
+}
+
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq)]
+pub enum KeySetPolicy {
+    TrustFirst = 0,
+    CacheAndSync = 1,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpochKey {
+    pub key: [u8; crypto::SYMM_KEY_LEN_BYTES],
+    // In microseconds since the Matter epoch
+    pub start_time: u64,
+}
+
+enum KeySetTag {
+    Id = 0,
+    Policy = 1,
+    // Followed by the other epoch keys and start times, alternately
+    EpochKey0 = 2,
+    EpochStartTime0 = 3,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupKeySet {
+    pub id: u16,
+    pub policy: KeySetPolicy,
+    pub epoch_keys: Vec<EpochKey>,
+}
+
+impl GroupKeySet {
+    /// Decode a key set. The first epoch key is mandatory, the others, if present,
+    /// must start after the previous one
+    pub fn from_tlv(t: &TLVElement) -> Result<Self, Error> {
+        let id = t.find_tag(KeySetTag::Id as u32)?.u16()?;
+        let policy = t.find_tag(KeySetTag::Policy as u32)?.u8()?;
+        let policy = num::FromPrimitive::from_u8(policy).ok_or(Error::Invalid)?;
+
+        let mut epoch_keys: Vec<EpochKey> = Vec::with_capacity(EPOCH_KEYS_PER_SET);
+        for i in 0..EPOCH_KEYS_PER_SET as u32 {
+            let key_tag = KeySetTag::EpochKey0 as u32 + 2 * i;
+            let time_tag = KeySetTag::EpochStartTime0 as u32 + 2 * i;
+            let key = nullable(t, key_tag, |e| e.slice())?;
+            let start_time = nullable(t, time_tag, |e| e.u64())?;
+            let (key, start_time) = match (key, start_time) {
+                (Some(k), Some(s)) => (k, s),
+                (None, None) if i > 0 => break,
+                _ => return Err(Error::Invalid),
+            };
+            if key.len() != crypto::SYMM_KEY_LEN_BYTES
+                || start_time <= epoch_keys.last().map(|e| e.start_time).unwrap_or(0)
+            {
+                return Err(Error::Invalid);
+            }
+            let mut epoch_key = EpochKey {
+                key: [0; crypto::SYMM_KEY_LEN_BYTES],
+                start_time,
+            };
+            epoch_key.key.copy_from_slice(key);
+            epoch_keys.push(epoch_key);
+        }
+
+        Ok(Self {
+            id,
+            policy,
+            epoch_keys,
+        })
+    }
+
+    /// Encode the key set, the epoch keys themselves are only included if 'with_keys'
+    pub fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType, with_keys: bool) -> Result<(), Error> {
+        tw.start_struct(tag)?;
+        tw.u16(TagType::Context(KeySetTag::Id as u8), self.id)?;
+        tw.u8(TagType::Context(KeySetTag::Policy as u8), self.policy as u8)?;
+        for i in 0..EPOCH_KEYS_PER_SET {
+            let key_tag = TagType::Context(KeySetTag::EpochKey0 as u8 + 2 * i as u8);
+            let time_tag = TagType::Context(KeySetTag::EpochStartTime0 as u8 + 2 * i as u8);
+            match self.epoch_keys.get(i) {
+                Some(e) => {
+                    if with_keys {
+                        tw.str8(key_tag, &e.key)?;
+                    } else {
+                        tw.null(key_tag)?;
+                    }
+                    tw.u64(time_tag, e.start_time)?;
+                }
+                None => {
+                    tw.null(key_tag)?;
+                    tw.null(time_tag)?;
+                }
+            }
+        }
+        tw.end_container()
+    }
+}
+
+enum KeyMapTag {
+    GroupId = 1,
+    KeySetId = 2,
+    FabricIndex = 0xFE,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GroupKeyMapEntry {
+    pub group_id: u16,
+    pub key_set_id: u16,
+    pub fab_idx: u8,
+}
+
+impl GroupKeyMapEntry {
+    /// Decode an entry, the fabric index is not part of the encoding when the entry is
+    /// written, it should be set by the caller
+    pub fn from_tlv(t: &TLVElement) -> Result<Self, Error> {
+        Ok(Self {
+            group_id: t.find_tag(KeyMapTag::GroupId as u32)?.u16()?,
+            key_set_id: t.find_tag(KeyMapTag::KeySetId as u32)?.u16()?,
+            fab_idx: nullable(t, KeyMapTag::FabricIndex as u32, |e| e.u8())?.unwrap_or_default(),
+        })
+    }
+
+    pub fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        tw.start_struct(tag)?;
+        tw.u16(TagType::Context(KeyMapTag::GroupId as u8), self.group_id)?;
+        tw.u16(TagType::Context(KeyMapTag::KeySetId as u8), self.key_set_id)?;
+        tw.u8(TagType::Context(KeyMapTag::FabricIndex as u8), self.fab_idx)?;
+        tw.end_container()
+    }
+}
+
+enum GroupInfoTag {
+    GroupId = 1,
+    Endpoints = 2,
+    GroupName = 3,
+    FabricIndex = 0xFE,
+}
+
+/// An entry of the Group Table
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupInfo {
+    pub group_id: u16,
+    pub endpoints: Vec<u16>,
+    pub name: String,
+    pub fab_idx: u8,
+}
+
+impl GroupInfo {
+    fn from_tlv(t: &TLVElement) -> Result<Self, Error> {
+        let mut endpoints = Vec::new();
+        if let Some(iter) = t
+            .find_tag(GroupInfoTag::Endpoints as u32)?
+            .confirm_array()?
+            .iter()
+        {
+            for e in iter {
+                endpoints.push(e.u16()?);
+            }
+        }
+        let name = nullable(t, GroupInfoTag::GroupName as u32, |e| e.slice())?.unwrap_or(&[]);
+        Ok(Self {
+            group_id: t.find_tag(GroupInfoTag::GroupId as u32)?.u16()?,
+            endpoints,
+            name: String::from_utf8_lossy(name).into_owned(),
+            fab_idx: t.find_tag(GroupInfoTag::FabricIndex as u32)?.u8()?,
+        })
+    }
+
+    pub fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        tw.start_struct(tag)?;
+        tw.u16(TagType::Context(GroupInfoTag::GroupId as u8), self.group_id)?;
+        tw.start_array(TagType::Context(GroupInfoTag::Endpoints as u8))?;
+        for e in &self.endpoints {
+            tw.u16(TagType::Anonymous, *e)?;
+        }
+        tw.end_container()?;
+        tw.utf8(
+            TagType::Context(GroupInfoTag::GroupName as u8),
+            self.name.as_bytes(),
+        )?;
+        tw.u8(
+            TagType::Context(GroupInfoTag::FabricIndex as u8),
+            self.fab_idx,
+        )?;
+        tw.end_container()
+    }
+}
+
+// An operational key, that was derived from one of the epoch keys of a key set
+struct OpKey {
+    key: [u8; crypto::SYMM_KEY_LEN_BYTES],
+    session_id: u16,
+}
+
+struct KeySetEntry {
+    key_set: GroupKeySet,
+    op_keys: Vec<OpKey>,
+}
+
+impl KeySetEntry {
+    fn new(key_set: GroupKeySet, compressed_id: &[u8]) -> Result<Self, Error> {
+        let mut op_keys = Vec::with_capacity(key_set.epoch_keys.len());
+        for e in &key_set.epoch_keys {
+            let mut op_key = OpKey {
+                key: [0; crypto::SYMM_KEY_LEN_BYTES],
+                session_id: 0,
+            };
+            KeySet::op_key_from_ipk(&e.key, compressed_id, &mut op_key.key)?;
+            op_key.session_id = group_session_id(&op_key.key)?;
+            op_keys.push(op_key);
+        }
+        Ok(Self { key_set, op_keys })
+    }
+}
+
+enum FabricGroupsTag {
+    FabricIndex = 0,
+    FabricId = 1,
+    CompressedId = 2,
+    KeySets = 3,
+    KeyMap = 4,
+    Groups = 5,
+}
+
+// The group keys and the groups of a fabric
+struct FabricGroups {
+    fab_idx: u8,
+    fabric_id: u64,
+    compressed_id: Vec<u8>,
+    key_sets: Vec<KeySetEntry>,
+    key_map: Vec<GroupKeyMapEntry>,
+    groups: Vec<GroupInfo>,
+}
+
+impl FabricGroups {
+    fn new(fab_idx: u8, fabric_id: u64, compressed_id: &[u8]) -> Self {
+        Self {
+            fab_idx,
+            fabric_id,
+            compressed_id: compressed_id.to_vec(),
+            key_sets: Vec::new(),
+            key_map: Vec::new(),
+            groups: Vec::new(),
+        }
+    }
+
+    fn from_tlv(t: &TLVElement) -> Result<Self, Error> {
+        let mut f = FabricGroups::new(
+            t.find_tag(FabricGroupsTag::FabricIndex as u32)?.u8()?,
+            t.find_tag(FabricGroupsTag::FabricId as u32)?.u64()?,
+            t.find_tag(FabricGroupsTag::CompressedId as u32)?.slice()?,
+        );
+        let list = |tag: FabricGroupsTag| t.find_tag(tag as u32)?.confirm_array();
+        if let Some(iter) = list(FabricGroupsTag::KeySets)?.iter() {
+            for k in iter {
+                let key_set = GroupKeySet::from_tlv(&k)?;
+                f.key_sets
+                    .push(KeySetEntry::new(key_set, &f.compressed_id)?);
+            }
+        }
+        if let Some(iter) = list(FabricGroupsTag::KeyMap)?.iter() {
+            for m in iter {
+                f.key_map.push(GroupKeyMapEntry::from_tlv(&m)?);
+            }
+        }
+        if let Some(iter) = list(FabricGroupsTag::Groups)?.iter() {
+            for g in iter {
+                f.groups.push(GroupInfo::from_tlv(&g)?);
+            }
+        }
+        Ok(f)
+    }
+
+    fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        tw.start_struct(tag)?;
+        tw.u8(
+            TagType::Context(FabricGroupsTag::FabricIndex as u8),
+            self.fab_idx,
+        )?;
+        tw.u64(
+            TagType::Context(FabricGroupsTag::FabricId as u8),
+            self.fabric_id,
+        )?;
+        tw.str8(
+            TagType::Context(FabricGroupsTag::CompressedId as u8),
+            &self.compressed_id,
+        )?;
+        tw.start_array(TagType::Context(FabricGroupsTag::KeySets as u8))?;
+        for k in &self.key_sets {
+            k.key_set.to_tlv(tw, TagType::Anonymous, true)?;
+        }
+        tw.end_container()?;
+        tw.start_array(TagType::Context(FabricGroupsTag::KeyMap as u8))?;
+        for m in &self.key_map {
+            m.to_tlv(tw, TagType::Anonymous)?;
+        }
+        tw.end_container()?;
+        tw.start_array(TagType::Context(FabricGroupsTag::Groups as u8))?;
+        for g in &self.groups {
+            g.to_tlv(tw, TagType::Anonymous)?;
+        }
+        tw.end_container()?;
+        tw.end_container()
+    }
+
+    fn key_set_mut(&mut self, id: u16) -> Option<&mut KeySetEntry> {
+        self.key_sets.iter_mut().find(|k| k.key_set.id == id)
+    }
+}
+
+/// The group keys and the groups of all the fabrics, that are shared between the Group
+/// Key Management cluster, and the transport, that decrypts the group messages
+pub struct GroupKeys {
+    fabrics: RwLock<Vec<FabricGroups>>,
+    // Set when the groups change, so that the transport can update its multicast
+    // memberships
+    changed: AtomicBool,
+    store: Arc<dyn KvStore>,
+}
+
+impl GroupKeys {
+    /// Create the Group Keys, that persist in 'store'
+    pub fn new(store: Arc<dyn KvStore>) -> Result<Self, Error> {
+        let gk = Self {
+            fabrics: RwLock::new(Vec::with_capacity(MAX_SUPPORTED_FABRICS)),
+            changed: AtomicBool::new(true),
+            store,
+        };
+        gk.load()?;
+        Ok(gk)
+    }
+
+    /// Start tracking the groups of a fabric, any previous groups at this index are
+    /// dropped
+    pub fn add_fabric(
+        &self,
+        fab_idx: u8,
+        fabric_id: u64,
+        compressed_id: &[u8],
+    ) -> Result<(), Error> {
+        let mut fabrics = self.fabrics.write()?;
+        fabrics.retain(|f| f.fab_idx != fab_idx);
+        fabrics.push(FabricGroups::new(fab_idx, fabric_id, compressed_id));
+        self.store(&fabrics)
+    }
+
+    pub fn remove_fabric(&self, fab_idx: u8) -> Result<(), Error> {
+        let mut fabrics = self.fabrics.write()?;
+        fabrics.retain(|f| f.fab_idx != fab_idx);
+        self.store(&fabrics)?;
+        self.changed.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    // Update the groups of a fabric, and persist them
+    fn update<T>(&self, fab_idx: u8, f: T) -> Result<(), Error>
+    where
+        T: FnOnce(&mut FabricGroups) -> Result<(), Error>,
+    {
+        let mut fabrics = self.fabrics.write()?;
+        let fabric = fabrics
+            .iter_mut()
+            .find(|f| f.fab_idx == fab_idx)
+            .ok_or(Error::NotFound)?;
+        f(fabric)?;
+        self.store(&fabrics)
+    }
+
+    /// Add a key set, or replace the one with the same id
+    pub fn set_key_set(&self, fab_idx: u8, key_set: GroupKeySet) -> Result<(), Error> {
+        if key_set.id == IPK_KEY_SET_ID {
+            return Err(Error::Invalid);
+        }
+        self.update(fab_idx, |f| {
+            let entry = KeySetEntry::new(key_set, &f.compressed_id)?;
+            if let Some(k) = f.key_set_mut(entry.key_set.id) {
+                *k = entry;
+            } else if f.key_sets.len() < MAX_GROUP_KEYS_PER_FABRIC - 1 {
+                f.key_sets.push(entry);
+            } else {
+                return Err(Error::NoSpace);
+            }
+            Ok(())
+        })
+    }
+
+    pub fn get_key_set(&self, fab_idx: u8, id: u16) -> Result<GroupKeySet, Error> {
+        let fabrics = self.fabrics.read()?;
+        fabrics
+            .iter()
+            .find(|f| f.fab_idx == fab_idx)
+            .and_then(|f| f.key_sets.iter().find(|k| k.key_set.id == id))
+            .map(|k| k.key_set.clone())
+            .ok_or(Error::NotFound)
+    }
+
+    /// Remove a key set, along with the groups that are mapped to it
+    pub fn remove_key_set(&self, fab_idx: u8, id: u16) -> Result<(), Error> {
+        self.update(fab_idx, |f| {
+            let count = f.key_sets.len();
+            f.key_sets.retain(|k| k.key_set.id != id);
+            if f.key_sets.len() == count {
+                return Err(Error::NotFound);
+            }
+            f.key_map.retain(|m| m.key_set_id != id);
+            Ok(())
+        })
+    }
+
+    pub fn get_key_set_ids(&self, fab_idx: u8) -> Result<Vec<u16>, Error> {
+        let fabrics = self.fabrics.read()?;
+        Ok(fabrics
+            .iter()
+            .filter(|f| f.fab_idx == fab_idx)
+            .flat_map(|f| f.key_sets.iter().map(|k| k.key_set.id))
+            .collect())
+    }
+
+    /// Replace the Group Key Map of a fabric. A group can be mapped to only one key set,
+    /// that must exist
+    pub fn set_key_map(&self, fab_idx: u8, entries: Vec<GroupKeyMapEntry>) -> Result<(), Error> {
+        if entries.len() > MAX_GROUPS_PER_FABRIC {
+            return Err(Error::NoSpace);
+        }
+        self.update(fab_idx, |f| {
+            for (i, e) in entries.iter().enumerate() {
+                if e.fab_idx != fab_idx
+                    || e.group_id == 0
+                    || entries[..i].iter().any(|x| x.group_id == e.group_id)
+                    || !f.key_sets.iter().any(|k| k.key_set.id == e.key_set_id)
+                {
+                    return Err(Error::Invalid);
+                }
+            }
+            f.key_map = entries;
+            Ok(())
+        })
+    }
+
+    pub fn for_each_key_map<T>(&self, mut f: T) -> Result<(), Error>
+    where
+        T: FnMut(&GroupKeyMapEntry),
+    {
+        let fabrics = self.fabrics.read()?;
+        for entry in fabrics.iter().flat_map(|f| f.key_map.iter()) {
+            f(entry)
+        }
+        Ok(())
+    }
+
+    /// Make the endpoint a member of the group
+    pub fn add_group_endpoint(
+        &self,
+        fab_idx: u8,
+        group_id: u16,
+        endpoint: u16,
+        name: &str,
+    ) -> Result<(), Error> {
+        if group_id == 0 {
+            return Err(Error::Invalid);
+        }
+        self.update(fab_idx, |f| {
+            let full = f.groups.len() >= MAX_GROUPS_PER_FABRIC;
+            match f.groups.iter_mut().find(|g| g.group_id == group_id) {
+                Some(g) => {
+                    if !g.endpoints.contains(&endpoint) {
+                        g.endpoints.push(endpoint);
+                    }
+                    g.name = name.to_owned();
+                }
+                None if full => return Err(Error::NoSpace),
+                None => f.groups.push(GroupInfo {
+                    group_id,
+                    endpoints: vec![endpoint],
+                    name: name.to_owned(),
+                    fab_idx,
+                }),
+            }
+            Ok(())
+        })?;
+        self.changed.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Remove the endpoint from the group, the group is removed along with its last
+    /// endpoint
+    pub fn remove_group_endpoint(
+        &self,
+        fab_idx: u8,
+        group_id: u16,
+        endpoint: u16,
+    ) -> Result<(), Error> {
+        self.update(fab_idx, |f| {
+            let g = f
+                .groups
+                .iter_mut()
+                .find(|g| g.group_id == group_id && g.endpoints.contains(&endpoint))
+                .ok_or(Error::NotFound)?;
+            g.endpoints.retain(|e| *e != endpoint);
+            f.groups.retain(|g| !g.endpoints.is_empty());
+            Ok(())
+        })?;
+        self.changed.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Remove the endpoint from all the groups of the fabric
+    pub fn remove_all_group_endpoint(&self, fab_idx: u8, endpoint: u16) -> Result<(), Error> {
+        self.update(fab_idx, |f| {
+            for g in f.groups.iter_mut() {
+                g.endpoints.retain(|e| *e != endpoint);
+            }
+            f.groups.retain(|g| !g.endpoints.is_empty());
+            Ok(())
+        })?;
+        self.changed.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// The groups of the fabric
+    pub fn get_groups(&self, fab_idx: u8) -> Result<Vec<GroupInfo>, Error> {
+        let fabrics = self.fabrics.read()?;
+        Ok(fabrics
+            .iter()
+            .filter(|f| f.fab_idx == fab_idx)
+            .flat_map(|f| f.groups.iter())
+            .cloned()
+            .collect())
+    }
+
+    /// Whether a key set is mapped to the group
+    pub fn is_group_mapped(&self, fab_idx: u8, group_id: u16) -> Result<bool, Error> {
+        let fabrics = self.fabrics.read()?;
+        Ok(fabrics
+            .iter()
+            .filter(|f| f.fab_idx == fab_idx)
+            .flat_map(|f| f.key_map.iter())
+            .any(|e| e.group_id == group_id))
+    }
+
+    pub fn for_each_group<T>(&self, mut f: T) -> Result<(), Error>
+    where
+        T: FnMut(&GroupInfo),
+    {
+        let fabrics = self.fabrics.read()?;
+        for group in fabrics.iter().flat_map(|f| f.groups.iter()) {
+            f(group)
+        }
+        Ok(())
+    }
+
+    /// The endpoints that are members of the group
+    pub fn get_group_endpoints(&self, fab_idx: u8, group_id: u16) -> Result<Vec<u16>, Error> {
+        let fabrics = self.fabrics.read()?;
+        Ok(fabrics
+            .iter()
+            .filter(|f| f.fab_idx == fab_idx)
+            .flat_map(|f| f.groups.iter())
+            .find(|g| g.group_id == group_id)
+            .map(|g| g.endpoints.clone())
+            .unwrap_or_default())
+    }
+
+    /// The operational keys, along with their fabric index, that a message to the group
+    /// with this Group Session ID may be encrypted with
+    pub fn get_op_keys(
+        &self,
+        session_id: u16,
+        group_id: u16,
+    ) -> Result<Vec<(u8, [u8; crypto::SYMM_KEY_LEN_BYTES])>, Error> {
+        let fabrics = self.fabrics.read()?;
+        let mut keys = Vec::new();
+        for f in fabrics.iter() {
+            for m in f.key_map.iter().filter(|m| m.group_id == group_id) {
+                let key_set = f.key_sets.iter().find(|k| k.key_set.id == m.key_set_id);
+                for op_key in key_set.iter().flat_map(|k| k.op_keys.iter()) {
+                    if op_key.session_id == session_id {
+                        keys.push((f.fab_idx, op_key.key));
+                    }
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    /// The multicast addresses of all the groups that have member endpoints, if they
+    /// changed since the last call
+    pub fn get_changed_multicast_addrs(&self) -> Result<Option<Vec<Ipv6Addr>>, Error> {
+        if !self.changed.swap(false, Ordering::SeqCst) {
+            return Ok(None);
+        }
+        let fabrics = self.fabrics.read()?;
+        let mut addrs = Vec::new();
+        for f in fabrics.iter() {
+            for g in f.groups.iter() {
+                addrs.push(group_multicast_addr(f.fabric_id, g.group_id));
+            }
+        }
+        Ok(Some(addrs))
+    }
+
+    fn store(&self, fabrics: &[FabricGroups]) -> Result<(), Error> {
+        let mut buf = [0u8; MAX_GROUPS_TLV_LEN];
+        let buf_len = buf.len();
+        let mut wb = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut wb);
+        tw.start_array(TagType::Anonymous)?;
+        for f in fabrics {
+            f.to_tlv(&mut tw, TagType::Anonymous)?;
+        }
+        tw.end_container()?;
+        self.store.set_kv_slice(ST_GROUPS, wb.as_borrow_slice())
+    }
+
+    fn load(&self) -> Result<(), Error> {
+        let mut buf = Vec::new();
+        if self.store.get_kv_slice(ST_GROUPS, &mut buf).is_err() {
+            // Nothing stored yet
+            return Ok(());
+        }
+        let root = tlv::get_root_node(&buf)?;
+        let mut fabrics = self.fabrics.write()?;
+        if let Some(iter) = root.confirm_array()?.iter() {
+            for t in iter {
+                match FabricGroups::from_tlv(&t) {
+                    Ok(f) => fabrics.push(f),
+                    Err(e) => error!("Ignoring invalid stored groups: {:?}", e),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persist::MemKvStore;
+
+    const FAB_IDX: u8 = 1;
+    const FABRIC_ID: u64 = 0x2906_c908_d115_d362;
+    const COMPRESSED_ID: [u8; 8] = [0x87, 0xe1, 0xb0, 0x04, 0xe2, 0x35, 0xa1, 0x30];
+
+    fn key_set(id: u16, keys: &[u8]) -> GroupKeySet {
+        GroupKeySet {
+            id,
+            policy: KeySetPolicy::TrustFirst,
+            epoch_keys: keys
+                .iter()
+                .enumerate()
+                .map(|(i, k)| EpochKey {
+                    key: [*k; crypto::SYMM_KEY_LEN_BYTES],
+                    start_time: 1000 * (i as u64 + 1),
+                })
+                .collect(),
+        }
+    }
+
+    fn init() -> GroupKeys {
+        let gk = GroupKeys::new(Arc::new(MemKvStore::new())).unwrap();
+        gk.add_fabric(FAB_IDX, FABRIC_ID, &COMPRESSED_ID).unwrap();
+        gk
+    }
+
+    #[test]
+    fn test_multicast_addr() {
+        assert_eq!(
+            group_multicast_addr(FABRIC_ID, 0x1234),
+            "ff35:40:fd29:6c9:8d1:15d3:6200:1234"
+                .parse::<Ipv6Addr>()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_key_set_tlv() {
+        let ks = key_set(0x1a1, &[1, 2]);
+        let mut buf = [0; 128];
+        let mut wb = WriteBuf::new(&mut buf, 128);
+        let mut tw = TLVWriter::new(&mut wb);
+        ks.to_tlv(&mut tw, TagType::Anonymous, true).unwrap();
+        let root = tlv::get_root_node(wb.as_borrow_slice()).unwrap();
+        assert_eq!(GroupKeySet::from_tlv(&root).unwrap(), ks);
+
+        // Without the keys, the key set can't be decoded back
+        let mut buf = [0; 128];
+        let mut wb = WriteBuf::new(&mut buf, 128);
+        let mut tw = TLVWriter::new(&mut wb);
+        ks.to_tlv(&mut tw, TagType::Anonymous, false).unwrap();
+        let root = tlv::get_root_node(wb.as_borrow_slice()).unwrap();
+        assert_eq!(GroupKeySet::from_tlv(&root), Err(Error::Invalid));
+    }
+
+    #[test]
+    fn test_key_sets() {
+        let gk = init();
+        // The IPK can't be written
+        assert_eq!(
+            gk.set_key_set(FAB_IDX, key_set(0, &[1])),
+            Err(Error::Invalid)
+        );
+        // Neither can a fabric that isn't tracked
+        assert_eq!(gk.set_key_set(2, key_set(1, &[1])), Err(Error::NotFound));
+
+        gk.set_key_set(FAB_IDX, key_set(1, &[1])).unwrap();
+        gk.set_key_set(FAB_IDX, key_set(2, &[2])).unwrap();
+        assert_eq!(
+            gk.set_key_set(FAB_IDX, key_set(3, &[3])),
+            Err(Error::NoSpace)
+        );
+        // Replacing an existing one is fine
+        gk.set_key_set(FAB_IDX, key_set(2, &[4, 5])).unwrap();
+        assert_eq!(gk.get_key_set(FAB_IDX, 2).unwrap(), key_set(2, &[4, 5]));
+        assert_eq!(gk.get_key_set_ids(FAB_IDX).unwrap(), vec![1, 2]);
+
+        // Removing a key set removes its mappings
+        let map = |group_id, key_set_id| GroupKeyMapEntry {
+            group_id,
+            key_set_id,
+            fab_idx: FAB_IDX,
+        };
+        assert_eq!(
+            gk.set_key_map(FAB_IDX, vec![map(0x10, 3)]),
+            Err(Error::Invalid)
+        );
+        gk.set_key_map(FAB_IDX, vec![map(0x10, 1), map(0x20, 2)])
+            .unwrap();
+        gk.remove_key_set(FAB_IDX, 1).unwrap();
+        assert_eq!(gk.remove_key_set(FAB_IDX, 1), Err(Error::NotFound));
+        let mut entries = Vec::new();
+        gk.for_each_key_map(|e| entries.push(*e)).unwrap();
+        assert_eq!(entries, vec![map(0x20, 2)]);
+    }
+
+    #[test]
+    fn test_op_keys() {
+        let gk = init();
+        gk.set_key_set(FAB_IDX, key_set(1, &[1, 2])).unwrap();
+        let keys = [
+            KeySet::new(&[1; 16], &COMPRESSED_ID).unwrap(),
+            KeySet::new(&[2; 16], &COMPRESSED_ID).unwrap(),
+        ];
+        let session_id = group_session_id(keys[1].op_key()).unwrap();
+
+        // Only once the group is mapped to the key set
+        assert!(gk.get_op_keys(session_id, 0x10).unwrap().is_empty());
+        let entry = GroupKeyMapEntry {
+            group_id: 0x10,
+            key_set_id: 1,
+            fab_idx: FAB_IDX,
+        };
+        gk.set_key_map(FAB_IDX, vec![entry]).unwrap();
+        let op_keys = gk.get_op_keys(session_id, 0x10).unwrap();
+        assert!(!op_keys.is_empty());
+        assert!(op_keys
+            .iter()
+            .all(|(f, k)| *f == FAB_IDX && group_session_id(k).unwrap() == session_id));
+        assert!(op_keys.iter().any(|(_, k)| k == keys[1].op_key()));
+        assert!(gk.get_op_keys(session_id, 0x20).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_groups() {
+        let gk = init();
+        assert!(gk.get_changed_multicast_addrs().unwrap().is_some());
+        assert!(gk.get_changed_multicast_addrs().unwrap().is_none());
+
+        gk.add_group_endpoint(FAB_IDX, 0x10, 1, "Kitchen").unwrap();
+        gk.add_group_endpoint(FAB_IDX, 0x10, 2, "Kitchen").unwrap();
+        assert_eq!(gk.get_group_endpoints(FAB_IDX, 0x10).unwrap(), vec![1, 2]);
+        assert_eq!(
+            gk.get_changed_multicast_addrs().unwrap(),
+            Some(vec![group_multicast_addr(FABRIC_ID, 0x10)])
+        );
+
+        gk.remove_group_endpoint(FAB_IDX, 0x10, 1).unwrap();
+        assert_eq!(gk.get_group_endpoints(FAB_IDX, 0x10).unwrap(), vec![2]);
+        gk.remove_group_endpoint(FAB_IDX, 0x10, 2).unwrap();
+        assert_eq!(
+            gk.remove_group_endpoint(FAB_IDX, 0x10, 2),
+            Err(Error::NotFound)
+        );
+        assert_eq!(gk.get_changed_multicast_addrs().unwrap(), Some(vec![]));
+
+        gk.add_group_endpoint(FAB_IDX, 0x10, 1, "").unwrap();
+        gk.add_group_endpoint(FAB_IDX, 0x20, 1, "").unwrap();
+        gk.add_group_endpoint(FAB_IDX, 0x20, 2, "").unwrap();
+        gk.remove_all_group_endpoint(FAB_IDX, 1).unwrap();
+        let groups = gk.get_groups(FAB_IDX).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].group_id, 0x20);
+        assert_eq!(groups[0].endpoints, vec![2]);
+    }
+}
@@ -0,0 +1,564 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use heapless::LinearMap;
+use log::{error, info};
+
+use crate::{
+    error::Error,
+    interaction_model::core::{create_status_response, IMStatusCode, OpCode},
+    tlv::{get_root_node_struct, FromTLV, TLVElement, TLVWriter, TagType, ToTLV},
+    transport::{
+        exchange::Exchange,
+        packet::{Packet, MAX_RX_BUF_SIZE},
+        proto_demux::ResponseRequired,
+    },
+    utils::writebuf::WriteBuf,
+};
+
+use super::{
+    messages::{
+        ib::{AttrData, AttrPath, AttrResp, AttrStatus, CmdPath, InvResp},
+        msg::{self, ReadReq, StatusResp, SubscribeReq, SubscribeResp, TimedReq, WriteReq},
+    },
+    InteractionModel, Transaction,
+};
+
+/* Interaction Model Client
+ *
+ * - Application code queues a Read/Write/Invoke/Subscribe Request through the ImClient,
+ *   and blocks until the response arrives
+ * - The transport picks up the queued request as an unsolicited message, and sends it
+ *   on a new exchange, initiated on the requested (CASE/PASE) session
+ * - The responses on that exchange are collected (including all the chunks of a report),
+ *   and handed back to the application, once the interaction is complete
+ * - Reports for the subscriptions that we created, arrive on exchanges that are initiated
+ *   by the peer, and are passed on to the corresponding ClientSubscription
+ */
+
+// The max number of requests that may be queued, but not yet sent out
+const MAX_CLIENT_REQS: usize = 8;
+// The max number of subscriptions that we can create on peers
+const MAX_CLIENT_SUBSCRIPTIONS: usize = 4;
+// How long we wait for the peer to complete the interaction
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The final response of an interaction initiated by the ImClient
+#[derive(Debug)]
+pub struct Response {
+    opcode: OpCode,
+    // The payloads of all the messages that make up the response, e.g. the chunks of
+    // a report
+    payloads: Vec<Vec<u8>>,
+}
+
+impl Response {
+    pub fn get_opcode(&self) -> OpCode {
+        self.opcode
+    }
+
+    /// The status, if the peer responded with a Status Response
+    pub fn status(&self) -> Option<IMStatusCode> {
+        if self.opcode != OpCode::StatusResponse {
+            return None;
+        }
+        let root = get_root_node_struct(self.payloads.last()?).ok()?;
+        StatusResp::from_tlv(&root).ok().map(|s| s.status)
+    }
+
+    /// The attribute data/status of a Read Request, or of a subscription report
+    pub fn attr_reports(&self) -> Result<Vec<AttrResp<'_>>, Error> {
+        if self.opcode != OpCode::ReportData {
+            return Err(Error::InvalidOpcode);
+        }
+        let mut reports = Vec::new();
+        for payload in &self.payloads {
+            let root = get_root_node_struct(payload)?;
+            // The attribute reports are absent, if there was nothing to report
+            if let Ok(attr_reports) = root.find_tag(msg::ReportDataTag::AttributeReports as u32) {
+                for report in attr_reports.confirm_array()?.iter().ok_or(Error::Invalid)? {
+                    reports.push(AttrResp::from_tlv(&report)?);
+                }
+            }
+        }
+        Ok(reports)
+    }
+
+    /// The status of every attribute of a Write Request
+    pub fn write_statuses(&self) -> Result<Vec<AttrStatus>, Error> {
+        if self.opcode != OpCode::WriteResponse {
+            return Err(Error::InvalidOpcode);
+        }
+        let root = get_root_node_struct(self.payloads.last().ok_or(Error::Invalid)?)?;
+        let statuses = root.find_tag(msg::WriteRespTag::WriteResponses as u32)?;
+        statuses
+            .confirm_array()?
+            .iter()
+            .ok_or(Error::Invalid)?
+            .map(|s| AttrStatus::from_tlv(&s))
+            .collect()
+    }
+
+    /// The command data/status of an Invoke Request
+    pub fn inv_responses(&self) -> Result<Vec<InvResp<'_>>, Error> {
+        if self.opcode != OpCode::InvokeResponse {
+            return Err(Error::InvalidOpcode);
+        }
+        let root = get_root_node_struct(self.payloads.last().ok_or(Error::Invalid)?)?;
+        let responses = root.find_tag(msg::InvRespTag::InvokeResponses as u32)?;
+        responses
+            .confirm_array()?
+            .iter()
+            .ok_or(Error::Invalid)?
+            .map(|r| InvResp::from_tlv(&r))
+            .collect()
+    }
+}
+
+/// A subscription that we created on a peer
+pub struct ClientSubscription {
+    pub id: u32,
+    pub max_int: u16,
+    /// The priming report, with the values at the time of subscription
+    pub priming: Response,
+    reports: Receiver<Response>,
+}
+
+impl ClientSubscription {
+    /// Wait for the next report on this subscription. Reports arrive at least every
+    /// 'max_int' seconds, if they don't, the subscription should be considered lost.
+    ///
+    /// Dropping the ClientSubscription cancels the subscription on the peer, with the
+    /// next report.
+    pub fn recv_report(&self, timeout: Duration) -> Result<Response, Error> {
+        self.reports.recv_timeout(timeout).map_err(|e| match e {
+            RecvTimeoutError::Timeout => Error::Timeout,
+            RecvTimeoutError::Disconnected => Error::NoExchange,
+        })
+    }
+}
+
+struct ClientReq {
+    // The local session id of the session to send the request on
+    sess_id: u16,
+    opcode: OpCode,
+    payload: Vec<u8>,
+    // The timeout of the Timed Request that has to precede the request, if any
+    timed: Option<u16>,
+    resp_tx: Sender<Result<Response, Error>>,
+    // Where the reports go, for Subscribe Requests
+    reports_tx: Option<Sender<Response>>,
+}
+
+impl ClientReq {
+    fn fail(self, e: Error) {
+        error!("Client request {:?} failed: {:?}", self.opcode, e);
+        let _ = self.resp_tx.send(Err(e));
+    }
+}
+
+type ReqQueue = Arc<Mutex<VecDeque<ClientReq>>>;
+
+/// The handle that application code uses to interact with other nodes
+#[derive(Clone)]
+pub struct ImClient {
+    queue: ReqQueue,
+}
+
+impl ImClient {
+    /// Read the attributes from the peer on the session with the local session id
+    pub fn read(
+        &self,
+        sess_id: u16,
+        attrs: &[AttrPath],
+        fabric_filtered: bool,
+    ) -> Result<Response, Error> {
+        let req = ReadReq::new(fabric_filtered).set_attr_requests(attrs);
+        let payload = encode(|tw| req.to_tlv(tw, TagType::Anonymous))?;
+        self.request(sess_id, OpCode::ReadRequest, payload, None, None)
+    }
+
+    /// Write the attributes on the peer. If 'timed' is set, the write is preceded by a
+    /// Timed Request with this timeout (in milliseconds)
+    pub fn write(
+        &self,
+        sess_id: u16,
+        attrs: &[AttrData],
+        timed: Option<u16>,
+    ) -> Result<Response, Error> {
+        let mut req = WriteReq::new(false, attrs);
+        if timed.is_some() {
+            req.timed_request = Some(true);
+        }
+        let payload = encode(|tw| req.to_tlv(tw, TagType::Anonymous))?;
+        self.request(sess_id, OpCode::WriteRequest, payload, timed, None)
+    }
+
+    /// Invoke the command on the peer, with the command fields encoded by 'data'. If
+    /// 'timed' is set, the invoke is preceded by a Timed Request with this timeout (in
+    /// milliseconds)
+    pub fn invoke(
+        &self,
+        sess_id: u16,
+        cmd: CmdPath,
+        data: &dyn Fn(&mut TLVWriter) -> Result<(), Error>,
+        timed: Option<u16>,
+    ) -> Result<Response, Error> {
+        let payload = encode(|tw| {
+            tw.start_struct(TagType::Anonymous)?;
+            tw.bool(
+                TagType::Context(msg::InvReqTag::SupressResponse as u8),
+                false,
+            )?;
+            tw.bool(
+                TagType::Context(msg::InvReqTag::TimedReq as u8),
+                timed.is_some(),
+            )?;
+            tw.start_array(TagType::Context(msg::InvReqTag::InvokeRequests as u8))?;
+            tw.start_struct(TagType::Anonymous)?;
+            cmd.to_tlv(tw, TagType::Context(0))?;
+            tw.start_struct(TagType::Context(1))?;
+            data(tw)?;
+            tw.end_container()?;
+            tw.end_container()?;
+            tw.end_container()?;
+            tw.end_container()
+        })?;
+        self.request(sess_id, OpCode::InvokeRequest, payload, timed, None)
+    }
+
+    /// Subscribe to the attributes on the peer. Any existing subscriptions on the
+    /// session are retained.
+    pub fn subscribe(
+        &self,
+        sess_id: u16,
+        attrs: &[AttrPath],
+        min_int: u16,
+        max_int: u16,
+        fabric_filtered: bool,
+    ) -> Result<ClientSubscription, Error> {
+        let mut req = SubscribeReq::new(fabric_filtered, min_int, max_int).set_attr_requests(attrs);
+        req.keep_subs = true;
+        let payload = encode(|tw| req.to_tlv(tw, TagType::Anonymous))?;
+        let (reports_tx, reports) = mpsc::channel();
+        let mut resp = self.request(
+            sess_id,
+            OpCode::SubscribeRequest,
+            payload,
+            None,
+            Some(reports_tx),
+        )?;
+        if resp.opcode != OpCode::SubscriptResponse {
+            error!("Subscription failed with {:?}", resp.status());
+            return Err(Error::Invalid);
+        }
+
+        let subs_resp = resp.payloads.pop().ok_or(Error::Invalid)?;
+        let subs_resp = SubscribeResp::from_tlv(&get_root_node_struct(&subs_resp)?)?;
+        resp.opcode = OpCode::ReportData;
+        Ok(ClientSubscription {
+            id: subs_resp.subs_id,
+            max_int: subs_resp.max_int,
+            priming: resp,
+            reports,
+        })
+    }
+
+    fn request(
+        &self,
+        sess_id: u16,
+        opcode: OpCode,
+        payload: Vec<u8>,
+        timed: Option<u16>,
+        reports_tx: Option<Sender<Response>>,
+    ) -> Result<Response, Error> {
+        let (resp_tx, resp_rx) = mpsc::channel();
+        {
+            let mut queue = self.queue.lock()?;
+            if queue.len() >= MAX_CLIENT_REQS {
+                return Err(Error::NoSpace);
+            }
+            queue.push_back(ClientReq {
+                sess_id,
+                opcode,
+                payload,
+                timed,
+                resp_tx,
+                reports_tx,
+            });
+        }
+        // The request is dropped, if its exchange or session is closed before completion
+        resp_rx.recv_timeout(CLIENT_TIMEOUT).map_err(|e| match e {
+            RecvTimeoutError::Timeout => Error::Timeout,
+            RecvTimeoutError::Disconnected => Error::NoExchange,
+        })?
+    }
+}
+
+fn encode<F>(f: F) -> Result<Vec<u8>, Error>
+where
+    F: FnOnce(&mut TLVWriter) -> Result<(), Error>,
+{
+    let mut buf = vec![0; MAX_RX_BUF_SIZE];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+    f(&mut tw)?;
+    let len = wb.as_borrow_slice().len();
+    buf.truncate(len);
+    Ok(buf)
+}
+
+pub struct ClientMgr {
+    queue: ReqQueue,
+    // The request that was selected to be sent out next
+    selected: Option<ClientReq>,
+    // The subscription ids are only unique per peer, the subscriptions are keyed by
+    // the local session id of the session they were created on, and their id
+    subs: LinearMap<(u16, u32), Sender<Response>, MAX_CLIENT_SUBSCRIPTIONS>,
+}
+
+impl Default for ClientMgr {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientMgr {
+    pub fn new() -> Self {
+        Self {
+            queue: Arc::new(Mutex::new(VecDeque::new())),
+            selected: None,
+            subs: LinearMap::new(),
+        }
+    }
+
+    pub fn is_selected(&self) -> bool {
+        self.selected.is_some()
+    }
+
+    /// Remove all the subscriptions that we created on this session, their
+    /// ClientSubscriptions are then disconnected
+    pub fn remove_for_session(&mut self, sess_id: u16) {
+        let to_remove: Vec<(u16, u32)> = self
+            .subs
+            .keys()
+            .filter(|(s, _)| *s == sess_id)
+            .copied()
+            .collect();
+        for key in to_remove {
+            info!(
+                "Dropping subscription {} on the peer, the session is gone",
+                key.1
+            );
+            self.subs.remove(&key);
+        }
+    }
+}
+
+/// The context stored in the exchange, while the client interaction is in progress
+pub struct ClientCtx {
+    req: ClientReq,
+    // Set while we wait for the Status Response to the Timed Request
+    timed_pending: bool,
+    payloads: Vec<Vec<u8>>,
+}
+
+// The chunks of a subscription report received so far
+struct ReportCtx {
+    payloads: Vec<Vec<u8>>,
+}
+
+fn write_request(req: &ClientReq, proto_tx: &mut Packet) -> Result<(), Error> {
+    proto_tx.set_proto_opcode(req.opcode as u8);
+    proto_tx.get_writebuf()?.append(&req.payload)
+}
+
+// Returns the (MoreChunkedMsgs, SupressResponse) flags of a Report Data
+fn report_flags(root: &TLVElement) -> (bool, bool) {
+    let flag = |tag: msg::ReportDataTag| {
+        root.find_tag(tag as u32)
+            .and_then(|t| t.bool())
+            .unwrap_or(false)
+    };
+    (
+        flag(msg::ReportDataTag::MoreChunkedMsgs),
+        flag(msg::ReportDataTag::SupressResponse),
+    )
+}
+
+impl InteractionModel {
+    /// Returns the handle for initiating interactions with other nodes
+    pub fn get_client(&self) -> ImClient {
+        ImClient {
+            queue: self.client.queue.clone(),
+        }
+    }
+
+    /// Select the next queued client request, and return the local session id of the
+    /// session that it should be sent on
+    pub fn get_pending_client_req(&mut self) -> Option<u16> {
+        if let Some(req) = self.client.selected.take() {
+            // The exchange couldn't be initiated for the previously selected request
+            req.fail(Error::NoSession);
+        }
+        let req = self.client.queue.lock().ok()?.pop_front()?;
+        let sess_id = req.sess_id;
+        self.client.selected = Some(req);
+        Some(sess_id)
+    }
+
+    /// Encode the client request selected in get_pending_client_req()
+    pub fn handle_pending_client_req(
+        &mut self,
+        exch: &mut Exchange,
+        proto_tx: &mut Packet,
+    ) -> Result<(), Error> {
+        let req = self.client.selected.take().ok_or(Error::InvalidState)?;
+        let timed_pending = if let Some(timeout) = req.timed {
+            proto_tx.set_proto_opcode(OpCode::TimedRequest as u8);
+            let mut tw = TLVWriter::new(proto_tx.get_writebuf()?);
+            TimedReq::new(timeout).to_tlv(&mut tw, TagType::Anonymous)?;
+            true
+        } else {
+            write_request(&req, proto_tx)?;
+            false
+        };
+        info!("Sending client request {:?}", req.opcode);
+        exch.set_exchange_data(Box::new(ClientCtx {
+            req,
+            timed_pending,
+            payloads: Vec::new(),
+        }));
+        Ok(())
+    }
+
+    /// Handle the peer's response on an exchange that we initiated for a client request
+    pub fn handle_client_resp(
+        &mut self,
+        trans: &mut Transaction,
+        exch: &mut Exchange,
+        opcode: OpCode,
+        rx_buf: &[u8],
+        proto_tx: &mut Packet,
+    ) -> Result<ResponseRequired, Error> {
+        let mut ctx = exch
+            .take_exchange_data::<ClientCtx>()
+            .ok_or(Error::InvalidState)?;
+
+        if ctx.timed_pending && opcode == OpCode::StatusResponse {
+            let status = StatusResp::from_tlv(&get_root_node_struct(rx_buf)?)?.status;
+            if status == IMStatusCode::Sucess {
+                // The timed window is open, send the actual request
+                write_request(&ctx.req, proto_tx)?;
+                ctx.timed_pending = false;
+                exch.set_exchange_data(ctx);
+                return Ok(ResponseRequired::Yes);
+            }
+        }
+
+        ctx.payloads.push(rx_buf.to_vec());
+        let mut result = ResponseRequired::No;
+        let complete = if opcode == OpCode::ReportData {
+            let (more_chunks, supress_response) = report_flags(&get_root_node_struct(rx_buf)?);
+            if !supress_response {
+                create_status_response(proto_tx, IMStatusCode::Sucess)?;
+                result = ResponseRequired::Yes;
+            }
+            // The priming report of a subscription, is followed by the Subscribe Response
+            !more_chunks && ctx.req.opcode != OpCode::SubscribeRequest
+        } else {
+            true
+        };
+        if !complete {
+            exch.set_exchange_data(ctx);
+            return Ok(result);
+        }
+
+        trans.complete();
+        if opcode == OpCode::SubscriptResponse {
+            let subs_resp = SubscribeResp::from_tlv(&get_root_node_struct(rx_buf)?)?;
+            let key = (trans.session.get_local_sess_id(), subs_resp.subs_id);
+            let reports_tx = ctx.req.reports_tx.take().ok_or(Error::InvalidState)?;
+            if self.client.subs.contains_key(&key) {
+                error!(
+                    "Subscription {} already exists on the peer",
+                    subs_resp.subs_id
+                );
+                ctx.req.fail(Error::Invalid);
+                return Ok(result);
+            }
+            if self.client.subs.insert(key, reports_tx).is_err() {
+                ctx.req.fail(Error::NoSpace);
+                return Ok(result);
+            }
+            info!("Created subscription {} on the peer", subs_resp.subs_id);
+        }
+        let _ = ctx.req.resp_tx.send(Ok(Response {
+            opcode,
+            payloads: ctx.payloads,
+        }));
+        Ok(result)
+    }
+
+    /// Handle a report, from the peer, for a subscription that we created
+    pub fn handle_subs_report(
+        &mut self,
+        trans: &mut Transaction,
+        exch: &mut Exchange,
+        rx_buf: &[u8],
+        proto_tx: &mut Packet,
+    ) -> Result<ResponseRequired, Error> {
+        let root = get_root_node_struct(rx_buf)?;
+        let id = root
+            .find_tag(msg::ReportDataTag::SubscriptionId as u32)?
+            .u32()?;
+        let (more_chunks, supress_response) = report_flags(&root);
+        let mut payloads = exch
+            .take_exchange_data::<ReportCtx>()
+            .map(|c| c.payloads)
+            .unwrap_or_default();
+        payloads.push(rx_buf.to_vec());
+
+        let key = (trans.session.get_local_sess_id(), id);
+        let status = match self.client.subs.get(&key) {
+            Some(reports_tx) => {
+                if more_chunks {
+                    exch.set_exchange_data(Box::new(ReportCtx { payloads }));
+                    IMStatusCode::Sucess
+                } else {
+                    let report = Response {
+                        opcode: OpCode::ReportData,
+                        payloads,
+                    };
+                    if reports_tx.send(report).is_ok() {
+                        IMStatusCode::Sucess
+                    } else {
+                        // The application is no longer interested in this subscription
+                        info!("Cancelling subscription {} on the peer", id);
+                        self.client.subs.remove(&key);
+                        IMStatusCode::InvalidSubscription
+                    }
+                }
+            }
+            None => {
+                error!("Report for unknown subscription {}", id);
+                IMStatusCode::InvalidSubscription
+            }
+        };
+
+        if status != IMStatusCode::Sucess || !more_chunks {
+            trans.complete();
+        }
+        if supress_response && status == IMStatusCode::Sucess {
+            return Ok(ResponseRequired::No);
+        }
+        create_status_response(proto_tx, status)
+    }
+}
@@ -1,4 +1,4 @@
-use super::core::OpCode;
+use super::core::{create_status_response, OpCode};
 use super::messages::ib;
 use super::messages::msg;
 use super::InteractionModel;
@@ -36,6 +36,15 @@ impl InteractionModel {
 
         let mut tw = TLVWriter::new(proto_tx.get_writebuf()?);
         let root = get_root_node_struct(rx_buf)?;
+        let timed_req = root
+            .find_tag(msg::InvReqTag::TimedReq as u32)
+            .and_then(|t| t.bool())
+            .unwrap_or(false);
+        if let Err(status) = trans.validate_timed(timed_req) {
+            trans.complete();
+            return create_status_response(proto_tx, status);
+        }
+
         // Spec says tag should be 2, but CHIP Tool sends the tag as 0
         let cmd_list_iter = root
             .find_tag(msg::InvReqTag::InvokeRequests as u32)?
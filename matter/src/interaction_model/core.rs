@@ -2,7 +2,9 @@ use crate::{
     error::*,
     tlv::{self, FromTLV, TLVElement, TLVWriter, TagType, ToTLV},
     transport::{
-        proto_demux::{self, ProtoCtx, ResponseRequired},
+        exchange::ExchangeCtx,
+        packet::Packet,
+        proto_demux::{self, ProtoCtx, ResponseRequired, SessionEvent},
         session::Session,
     },
 };
@@ -11,6 +13,10 @@ use log::{error, info};
 use num;
 use num_derive::FromPrimitive;
 
+use super::client::{ClientCtx, ClientMgr};
+use super::messages::msg::StatusResp;
+use super::read::ReportReq;
+use super::subscribe::SubsMgr;
 use super::InteractionConsumer;
 use super::InteractionModel;
 use super::Transaction;
@@ -22,7 +28,7 @@ use super::TransactionState;
 /* Interaction Model ID as per the Matter Spec */
 const PROTO_ID_INTERACTION_MODEL: usize = 0x01;
 
-#[derive(FromPrimitive, Debug)]
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq)]
 pub enum OpCode {
     Reserved = 0,
     StatusResponse = 1,
@@ -43,6 +49,7 @@ impl<'a> Transaction<'a> {
             state: TransactionState::Ongoing,
             data: None,
             session,
+            timed_expiry: None,
         }
     }
 
@@ -57,13 +64,19 @@ impl<'a> Transaction<'a> {
 
 impl InteractionModel {
     pub fn new(consumer: Box<dyn InteractionConsumer>) -> InteractionModel {
-        InteractionModel { consumer }
+        InteractionModel {
+            consumer,
+            subs: SubsMgr::new(),
+            client: ClientMgr::new(),
+        }
     }
 }
 
 impl proto_demux::HandleProto for InteractionModel {
     fn handle_proto_id(&mut self, ctx: &mut ProtoCtx) -> Result<ResponseRequired, Error> {
+        let is_group = ctx.exch_ctx.sess.is_group();
         let mut trans = Transaction::new(&mut ctx.exch_ctx.sess);
+        trans.set_timed_window(ctx.exch_ctx.exch);
         let proto_opcode: OpCode =
             num::FromPrimitive::from_u8(ctx.rx.get_proto_opcode()).ok_or(Error::Invalid)?;
         ctx.tx.set_proto_id(PROTO_ID_INTERACTION_MODEL as u16);
@@ -71,14 +84,65 @@ impl proto_demux::HandleProto for InteractionModel {
         let buf = ctx.rx.as_borrow_slice();
         info!("{} {:?}", "Received command".cyan(), proto_opcode);
         tlv::print_tlv_list(buf);
-        let result = match proto_opcode {
-            OpCode::InvokeRequest => self.handle_invoke_req(&mut trans, buf, &mut ctx.tx)?,
-            OpCode::ReadRequest => self.handle_read_req(&mut trans, buf, &mut ctx.tx)?,
-            OpCode::WriteRequest => self.handle_write_req(&mut trans, buf, &mut ctx.tx)?,
-            _ => {
-                error!("Opcode Not Handled: {:?}", proto_opcode);
+        if is_group {
+            // Only commands can be multicast, and they are never responded to
+            if proto_opcode != OpCode::InvokeRequest {
+                error!("Opcode Not Handled on a group session: {:?}", proto_opcode);
                 return Err(Error::InvalidOpcode);
             }
+            self.handle_invoke_req(&mut trans, buf, &mut ctx.tx)?;
+            return Ok(ResponseRequired::No);
+        }
+        let result = if ctx.exch_ctx.exch.get_exchange_data::<ClientCtx>().is_some() {
+            self.handle_client_resp(
+                &mut trans,
+                ctx.exch_ctx.exch,
+                proto_opcode,
+                buf,
+                &mut ctx.tx,
+            )?
+        } else {
+            match proto_opcode {
+                OpCode::InvokeRequest => self.handle_invoke_req(&mut trans, buf, &mut ctx.tx)?,
+                OpCode::WriteRequest => self.handle_write_req(&mut trans, buf, &mut ctx.tx)?,
+                _ if trans.is_timed() => {
+                    // Only a Write or an Invoke can follow a TimedRequest on the exchange
+                    error!(
+                        "Opcode {:?} not allowed in a timed interaction",
+                        proto_opcode
+                    );
+                    trans.complete();
+                    create_status_response(&mut ctx.tx, IMStatusCode::TimedRequestMismatch)?
+                }
+                OpCode::ReadRequest => {
+                    self.handle_read_req(&mut trans, ctx.exch_ctx.exch, buf, &mut ctx.tx)?
+                }
+                OpCode::SubscribeRequest => {
+                    self.handle_subscribe_req(&mut trans, ctx.exch_ctx.exch, buf, &mut ctx.tx)?
+                }
+                OpCode::TimedRequest => {
+                    self.handle_timed_req(ctx.exch_ctx.exch, buf, &mut ctx.tx)?
+                }
+                OpCode::StatusResponse => {
+                    if ctx.exch_ctx.exch.get_exchange_data::<ReportReq>().is_some() {
+                        self.handle_read_status_resp(
+                            &mut trans,
+                            ctx.exch_ctx.exch,
+                            buf,
+                            &mut ctx.tx,
+                        )?
+                    } else {
+                        self.handle_status_resp(&mut trans, ctx.exch_ctx.exch, buf, &mut ctx.tx)?
+                    }
+                }
+                OpCode::ReportData => {
+                    self.handle_subs_report(&mut trans, ctx.exch_ctx.exch, buf, &mut ctx.tx)?
+                }
+                _ => {
+                    error!("Opcode Not Handled: {:?}", proto_opcode);
+                    return Err(Error::InvalidOpcode);
+                }
+            }
         };
 
         if result == ResponseRequired::Yes {
@@ -94,6 +158,37 @@ impl proto_demux::HandleProto for InteractionModel {
     fn get_proto_id(&self) -> usize {
         PROTO_ID_INTERACTION_MODEL as usize
     }
+
+    fn handle_session_event(&mut self, event: &SessionEvent) -> Result<(), Error> {
+        match event {
+            SessionEvent::Evicted(sess_id) => {
+                self.subs.remove_for_session(*sess_id);
+                self.client.remove_for_session(*sess_id);
+            }
+        }
+        Ok(())
+    }
+
+    fn get_unsolicited_sess(&mut self) -> Option<u16> {
+        self.get_pending_client_req()
+            .or_else(|| self.get_pending_report())
+    }
+
+    fn handle_unsolicited(
+        &mut self,
+        exch_ctx: &mut ExchangeCtx,
+        tx: &mut Packet,
+    ) -> Result<(), Error> {
+        tx.set_proto_id(PROTO_ID_INTERACTION_MODEL as u16);
+        if self.client.is_selected() {
+            self.handle_pending_client_req(exch_ctx.exch, tx)?;
+        } else {
+            self.handle_pending_report(exch_ctx.exch, tx)?;
+        }
+        info!("Sending unsolicited message");
+        tlv::print_tlv_list(tx.as_borrow_slice());
+        Ok(())
+    }
 }
 
 #[derive(FromPrimitive, Debug, Clone, Copy, PartialEq)]
@@ -120,6 +215,17 @@ pub enum IMStatusCode {
     UnsupportedCluster = 0xc3,
     NoUpstreamSubscription = 0xc5,
     NeedsTimedInteraction = 0xc6,
+    TimedRequestMismatch = 0xc9,
+}
+
+pub fn create_status_response(
+    proto_tx: &mut Packet,
+    status: IMStatusCode,
+) -> Result<ResponseRequired, Error> {
+    proto_tx.set_proto_opcode(OpCode::StatusResponse as u8);
+    let mut tw = TLVWriter::new(proto_tx.get_writebuf()?);
+    StatusResp::new(status).to_tlv(&mut tw, TagType::Anonymous)?;
+    Ok(ResponseRequired::Yes)
 }
 
 impl From<Error> for IMStatusCode {
@@ -39,10 +39,97 @@ pub mod msg {
 
     use crate::{
         error::Error,
+        interaction_model::core::IMStatusCode,
         tlv::{FromTLV, TLVArray, TLVElement, TLVWriter, TagType, ToTLV},
     };
 
-    use super::ib::{AttrData, AttrPath};
+    use super::ib::{AttrData, AttrPath, DataVersionFilter, EventFilter, EventPath};
+
+    #[derive(Debug, FromTLV, ToTLV)]
+    pub struct StatusResp {
+        pub status: IMStatusCode,
+    }
+
+    impl StatusResp {
+        pub fn new(status: IMStatusCode) -> Self {
+            Self { status }
+        }
+    }
+
+    #[derive(Debug, FromTLV, ToTLV)]
+    pub struct TimedReq {
+        pub timeout: u16,
+    }
+
+    impl TimedReq {
+        pub fn new(timeout: u16) -> Self {
+            Self { timeout }
+        }
+    }
+
+    #[derive(Default, FromTLV, ToTLV)]
+    #[tlvargs(lifetime = "'a")]
+    pub struct SubscribeReq<'a> {
+        pub keep_subs: bool,
+        pub min_int_floor: u16,
+        pub max_int_ceil: u16,
+        pub attr_requests: Option<TLVArray<'a, AttrPath>>,
+        pub event_requests: Option<TLVArray<'a, EventPath>>,
+        pub event_filters: Option<TLVArray<'a, EventFilter>>,
+        // The Context Tags are discontiguous for some reason
+        _dummy: Option<bool>,
+        pub fabric_filtered: bool,
+        pub dataver_filters: Option<TLVArray<'a, DataVersionFilter>>,
+    }
+
+    impl<'a> SubscribeReq<'a> {
+        pub fn new(fabric_filtered: bool, min_int_floor: u16, max_int_ceil: u16) -> Self {
+            Self {
+                fabric_filtered,
+                min_int_floor,
+                max_int_ceil,
+                ..Default::default()
+            }
+        }
+
+        pub fn set_attr_requests(mut self, requests: &'a [AttrPath]) -> Self {
+            self.attr_requests = Some(TLVArray::new(requests));
+            self
+        }
+
+        pub fn set_event_requests(mut self, requests: &'a [EventPath]) -> Self {
+            self.event_requests = Some(TLVArray::new(requests));
+            self
+        }
+
+        pub fn set_event_filters(mut self, filters: &'a [EventFilter]) -> Self {
+            self.event_filters = Some(TLVArray::new(filters));
+            self
+        }
+
+        pub fn set_dataver_filters(mut self, filters: &'a [DataVersionFilter]) -> Self {
+            self.dataver_filters = Some(TLVArray::new(filters));
+            self
+        }
+    }
+
+    #[derive(Debug, FromTLV, ToTLV)]
+    pub struct SubscribeResp {
+        pub subs_id: u32,
+        // The Context Tags are discontiguous for some reason
+        _dummy: Option<u32>,
+        pub max_int: u16,
+    }
+
+    impl SubscribeResp {
+        pub fn new(subs_id: u32, max_int: u16) -> Self {
+            Self {
+                subs_id,
+                _dummy: None,
+                max_int,
+            }
+        }
+    }
 
     pub enum InvRespTag {
         SupressResponse = 0,
@@ -67,10 +154,10 @@ pub mod msg {
     #[tlvargs(lifetime = "'a")]
     pub struct ReadReq<'a> {
         pub attr_requests: Option<TLVArray<'a, AttrPath>>,
-        event_requests: Option<bool>,
-        event_filters: Option<bool>,
+        pub event_requests: Option<TLVArray<'a, EventPath>>,
+        pub event_filters: Option<TLVArray<'a, EventFilter>>,
         pub fabric_filtered: bool,
-        pub dataver_filters: Option<TLVArray<'a, bool>>,
+        pub dataver_filters: Option<TLVArray<'a, DataVersionFilter>>,
     }
 
     impl<'a> ReadReq<'a> {
@@ -85,6 +172,21 @@ pub mod msg {
             self.attr_requests = Some(TLVArray::new(requests));
             self
         }
+
+        pub fn set_event_requests(mut self, requests: &'a [EventPath]) -> Self {
+            self.event_requests = Some(TLVArray::new(requests));
+            self
+        }
+
+        pub fn set_event_filters(mut self, filters: &'a [EventFilter]) -> Self {
+            self.event_filters = Some(TLVArray::new(filters));
+            self
+        }
+
+        pub fn set_dataver_filters(mut self, filters: &'a [DataVersionFilter]) -> Self {
+            self.dataver_filters = Some(TLVArray::new(filters));
+            self
+        }
     }
 
     pub enum WriteReqTag {
@@ -98,7 +200,7 @@ pub mod msg {
     #[tlvargs(lifetime = "'b")]
     pub struct WriteReq<'a, 'b> {
         pub supress_response: Option<bool>,
-        timed_request: Option<bool>,
+        pub timed_request: Option<bool>,
         pub write_requests: TLVArray<'a, AttrData<'b>>,
         more_chunked: Option<bool>,
     }
@@ -121,10 +223,10 @@ pub mod msg {
     // Report Data
     // TODO: Differs from spec
     pub enum ReportDataTag {
-        _SubscriptionId = 0,
+        SubscriptionId = 0,
         AttributeReports = 1,
-        _EventReport = 2,
-        _MoreChunkedMsgs = 3,
+        EventReports = 2,
+        MoreChunkedMsgs = 3,
         SupressResponse = 4,
     }
 
@@ -140,7 +242,7 @@ pub mod ib {
     use crate::{
         error::Error,
         interaction_model::core::IMStatusCode,
-        tlv::{FromTLV, TLVElement, TLVWriter, TagType, ToTLV},
+        tlv::{FromTLV, Nullable, TLVElement, TLVWriter, TagType, ToTLV},
     };
     use log::error;
     use num_derive::FromPrimitive;
@@ -474,8 +576,8 @@ pub mod ib {
 
     #[derive(Debug, Clone, Copy, PartialEq, FromTLV, ToTLV)]
     pub struct AttrStatus {
-        path: AttrPath,
-        status: Status,
+        pub path: AttrPath,
+        pub status: Status,
     }
 
     impl AttrStatus {
@@ -496,7 +598,8 @@ pub mod ib {
         pub endpoint: Option<u16>,
         pub cluster: Option<u32>,
         pub attr: Option<u16>,
-        pub list_index: Option<u16>,
+        // A null list index appends the data to the list
+        pub list_index: Option<Nullable<u16>>,
     }
 
     impl AttrPath {
@@ -514,6 +617,111 @@ pub mod ib {
         }
     }
 
+    // Cluster Path
+    #[derive(Default, Clone, Copy, Debug, PartialEq, FromTLV, ToTLV)]
+    #[tlvargs(datatype = "list")]
+    pub struct ClusterPath {
+        pub node: Option<u64>,
+        pub endpoint: u16,
+        pub cluster: u32,
+    }
+
+    // Data Version Filter
+    #[derive(Default, Clone, Copy, Debug, PartialEq, FromTLV, ToTLV)]
+    pub struct DataVersionFilter {
+        pub path: ClusterPath,
+        pub data_ver: u32,
+    }
+
+    impl DataVersionFilter {
+        pub fn new(endpoint: u16, cluster: u32, data_ver: u32) -> Self {
+            Self {
+                path: ClusterPath {
+                    node: None,
+                    endpoint,
+                    cluster,
+                },
+                data_ver,
+            }
+        }
+    }
+
+    // Event Path
+    #[derive(Default, Clone, Copy, Debug, PartialEq, FromTLV, ToTLV)]
+    #[tlvargs(datatype = "list")]
+    pub struct EventPath {
+        pub node: Option<u64>,
+        pub endpoint: Option<u16>,
+        pub cluster: Option<u32>,
+        pub event: Option<u32>,
+        pub is_urgent: Option<bool>,
+    }
+
+    impl EventPath {
+        pub fn new(path: &GenericPath) -> Self {
+            Self {
+                endpoint: path.endpoint,
+                cluster: path.cluster,
+                event: path.leaf,
+                ..Default::default()
+            }
+        }
+
+        pub fn to_gp(&self) -> GenericPath {
+            GenericPath::new(self.endpoint, self.cluster, self.event)
+        }
+    }
+
+    // Event Filter
+    #[derive(Default, Clone, Copy, Debug, PartialEq, FromTLV, ToTLV)]
+    pub struct EventFilter {
+        pub node: Option<u64>,
+        pub event_min: Option<u64>,
+    }
+
+    impl EventFilter {
+        pub fn new(event_min: u64) -> Self {
+            Self {
+                node: None,
+                event_min: Some(event_min),
+            }
+        }
+    }
+
+    // Event Response
+    pub enum EventRespTag {
+        Status = 0,
+        Data = 1,
+    }
+
+    // Event Data
+    pub enum EventDataTag {
+        Path = 0,
+        EventNumber = 1,
+        Priority = 2,
+        EpochTimestamp = 3,
+        SystemTimestamp = 4,
+        _DeltaEpochTimestamp = 5,
+        _DeltaSystemTimestamp = 6,
+        Data = 7,
+    }
+
+    // Event Status
+    #[derive(Debug, Clone, Copy, PartialEq, FromTLV, ToTLV)]
+    pub struct EventStatus {
+        pub path: EventPath,
+        pub status: Status,
+    }
+
+    impl EventStatus {
+        pub fn new(path: &GenericPath, status: IMStatusCode, cluster_status: u16) -> Self {
+            Self {
+                path: EventPath::new(path),
+                status: Status::new(status, cluster_status),
+            }
+        }
+    }
+
     // Command Path
     #[derive(Default, Debug, Copy, Clone, PartialEq)]
     pub struct CmdPath {
@@ -1,14 +1,19 @@
-use std::any::Any;
+use std::{any::Any, time::SystemTime};
 
 use crate::{
+    acl::Accessor,
     error::Error,
     tlv::{TLVElement, TLVWriter},
     transport::session::Session,
 };
 
-use self::messages::{
-    ib,
-    msg::{ReadReq, WriteReq},
+use self::{
+    messages::{
+        ib,
+        msg::{ReadReq, WriteReq},
+    },
+    client::ClientMgr,
+    subscribe::SubsMgr,
 };
 
 #[derive(PartialEq)]
@@ -20,6 +25,21 @@ pub struct Transaction<'a> {
     pub state: TransactionState,
     pub data: Option<Box<dyn Any>>,
     pub session: &'a mut Session,
+    // The expiry of the timed interaction window, if any
+    timed_expiry: Option<SystemTime>,
+}
+
+/// Where a read, whose reports don't fit in a single ReportData, left off. The next
+/// ReportData resumes from here
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub struct ReadCursor {
+    /// The index of the path in the request, the attribute paths followed by the event paths
+    pub path_idx: usize,
+    /// The number of leaves (attributes or events) of the wildcard path, that are already done
+    pub leaf_idx: usize,
+    /// The number of entries already reported, of a list attribute that is split as it
+    /// doesn't fit in a ReportData of its own
+    pub list_idx: Option<usize>,
 }
 
 pub trait InteractionConsumer {
@@ -31,16 +51,47 @@ pub trait InteractionConsumer {
         tlvwriter: &mut TLVWriter,
     ) -> Result<(), Error>;
 
-    fn consume_read_attr(&self, req: &ReadReq, tlvwriter: &mut TLVWriter) -> Result<(), Error>;
+    /// Encode the reports of the read, starting from the cursor. If they don't all fit,
+    /// returns the cursor that the next ReportData must resume from
+    fn consume_read_attr(
+        &self,
+        accessor: &Accessor,
+        req: &ReadReq,
+        cursor: ReadCursor,
+        tlvwriter: &mut TLVWriter,
+    ) -> Result<Option<ReadCursor>, Error>;
+
+    fn consume_write_attr(
+        &self,
+        accessor: &Accessor,
+        req: &WriteReq,
+        tlvwriter: &mut TLVWriter,
+    ) -> Result<(), Error>;
+
+    /// Returns a digest of the data versions of all the clusters that match the attribute
+    /// paths. A change in the digest indicates that the data has changed, and subscribers
+    /// must be reported. Consumers that don't track data versions, only get periodic reports.
+    fn get_dataver_digest(&self, _attr_paths: &[ib::AttrPath]) -> u32 {
+        0
+    }
 
-    fn consume_write_attr(&self, req: &WriteReq, tlvwriter: &mut TLVWriter) -> Result<(), Error>;
+    /// Returns the event number that will be allocated to the next event. Subscribers to
+    /// events must be reported, once this moves past what they have already seen.
+    fn get_next_event_no(&self) -> u64 {
+        0
+    }
 }
 
 pub struct InteractionModel {
     consumer: Box<dyn InteractionConsumer>,
+    subs: SubsMgr,
+    client: ClientMgr,
 }
+pub mod client;
 pub mod command;
 pub mod core;
 pub mod messages;
 pub mod read;
+pub mod subscribe;
+pub mod timed;
 pub mod write;
@@ -1,38 +1,195 @@
 use crate::{
+    acl::Accessor,
     error::Error,
-    interaction_model::core::OpCode,
+    interaction_model::core::{IMStatusCode, OpCode},
     tlv::{get_root_node_struct, FromTLV, TLVWriter, TagType},
-    transport::{packet::Packet, proto_demux::ResponseRequired},
+    transport::{exchange::Exchange, packet::Packet, proto_demux::ResponseRequired},
 };
+use log::error;
 
 use super::{
-    messages::msg::{self, ReadReq},
-    InteractionModel, Transaction,
+    messages::{
+        ib::{AttrPath, DataVersionFilter, EventFilter, EventPath},
+        msg::{self, ReadReq, StatusResp},
+    },
+    InteractionConsumer, InteractionModel, ReadCursor, Transaction,
 };
 
+/* Chunked reports
+ *
+ * - The reports of a read (or of a subscription) that don't fit in a single ReportData,
+ *   are split across several of them, on the same exchange. All but the last one have
+ *   MoreChunkedMsgs set, and the peer asks for the next one with a StatusResponse
+ * - The consumer encodes the reports from a cursor, and returns the cursor that the next
+ *   ReportData resumes from. A list attribute that doesn't fit in a ReportData of its own,
+ *   is reported as an empty list, followed by its entries that are appended one at a time
+ */
+
+// The space that is held back for the end of the ReportData: MoreChunkedMsgs or
+// SupressResponse, and the end of the structure
+const REPORT_TRAILER_LEN: usize = 3;
+
+/// A read, that is reported in one or more ReportData messages. This owns the paths of
+/// the read, so that it can resume once the peer asks for the next chunk
+pub struct ReportReq {
+    subs_id: Option<u32>,
+    fabric_filtered: bool,
+    attr_paths: Vec<AttrPath>,
+    dataver_filters: Vec<DataVersionFilter>,
+    event_paths: Vec<EventPath>,
+    event_min: u64,
+    cursor: ReadCursor,
+}
+
+impl ReportReq {
+    pub fn new(
+        subs_id: Option<u32>,
+        fabric_filtered: bool,
+        attr_paths: Vec<AttrPath>,
+        dataver_filters: Vec<DataVersionFilter>,
+        event_paths: Vec<EventPath>,
+        event_min: u64,
+    ) -> Self {
+        Self {
+            subs_id,
+            fabric_filtered,
+            attr_paths,
+            dataver_filters,
+            event_paths,
+            event_min,
+            cursor: Default::default(),
+        }
+    }
+
+    /// Encode the next ReportData. Returns true if more of them follow
+    pub fn write_chunk(
+        &mut self,
+        consumer: &dyn InteractionConsumer,
+        accessor: &Accessor,
+        proto_tx: &mut Packet,
+    ) -> Result<bool, Error> {
+        proto_tx.set_proto_opcode(OpCode::ReportData as u8);
+
+        let wb = proto_tx.get_writebuf()?;
+        wb.shrink(REPORT_TRAILER_LEN)?;
+        let mut tw = TLVWriter::new(wb);
+        tw.start_struct(TagType::Anonymous)?;
+        if let Some(subs_id) = self.subs_id {
+            tw.u32(
+                TagType::Context(msg::ReportDataTag::SubscriptionId as u8),
+                subs_id,
+            )?;
+        }
+        let event_filters = [EventFilter::new(self.event_min)];
+        let mut read_req = ReadReq::new(self.fabric_filtered)
+            .set_attr_requests(&self.attr_paths)
+            .set_dataver_filters(&self.dataver_filters);
+        if !self.event_paths.is_empty() {
+            read_req = read_req
+                .set_event_requests(&self.event_paths)
+                .set_event_filters(&event_filters);
+        }
+        let next = consumer.consume_read_attr(accessor, &read_req, self.cursor, &mut tw)?;
+        tw.expand(REPORT_TRAILER_LEN)?;
+
+        if let Some(cursor) = next {
+            self.cursor = cursor;
+            tw.bool(
+                TagType::Context(msg::ReportDataTag::MoreChunkedMsgs as u8),
+                true,
+            )?;
+        } else if self.subs_id.is_none() {
+            // Supress response is true for the last report of a read. Subscription reports
+            // always expect a StatusResponse
+            tw.bool(
+                TagType::Context(msg::ReportDataTag::SupressResponse as u8),
+                true,
+            )?;
+        }
+        tw.end_container()?;
+        Ok(next.is_some())
+    }
+}
+
 impl InteractionModel {
     pub fn handle_read_req(
         &mut self,
         trans: &mut Transaction,
+        exch: &mut Exchange,
         rx_buf: &[u8],
         proto_tx: &mut Packet,
     ) -> Result<ResponseRequired, Error> {
-        proto_tx.set_proto_opcode(OpCode::ReportData as u8);
-
-        let mut tw = TLVWriter::new(proto_tx.get_writebuf()?);
         let root = get_root_node_struct(rx_buf)?;
         let read_req = ReadReq::from_tlv(&root)?;
 
-        tw.start_struct(TagType::Anonymous)?;
-        self.consumer.consume_read_attr(&read_req, &mut tw)?;
-        // Supress response always true for read interaction
-        tw.bool(
-            TagType::Context(msg::ReportDataTag::SupressResponse as u8),
-            true,
-        )?;
-        tw.end_container()?;
+        let attr_paths = read_req
+            .attr_requests
+            .as_ref()
+            .map_or_else(Vec::new, |r| r.iter().collect());
+        let dataver_filters = read_req
+            .dataver_filters
+            .as_ref()
+            .map_or_else(Vec::new, |f| f.iter().collect());
+        let event_paths = read_req
+            .event_requests
+            .as_ref()
+            .map_or_else(Vec::new, |r| r.iter().collect());
+        let event_min = read_req
+            .event_filters
+            .as_ref()
+            .and_then(|f| f.iter().filter_map(|f| f.event_min).max())
+            .unwrap_or(0);
+        let report = ReportReq::new(
+            None,
+            read_req.fabric_filtered,
+            attr_paths,
+            dataver_filters,
+            event_paths,
+            event_min,
+        );
+        self.write_read_chunk(trans, exch, Box::new(report), proto_tx)
+    }
 
-        trans.complete();
+    /// Send the next chunk of a read, once the peer acknowledges the previous one
+    pub fn handle_read_status_resp(
+        &mut self,
+        trans: &mut Transaction,
+        exch: &mut Exchange,
+        rx_buf: &[u8],
+        proto_tx: &mut Packet,
+    ) -> Result<ResponseRequired, Error> {
+        let root = get_root_node_struct(rx_buf)?;
+        let status = StatusResp::from_tlv(&root)?.status;
+        let report = exch
+            .take_exchange_data::<ReportReq>()
+            .ok_or(Error::InvalidState)?;
+
+        if status != IMStatusCode::Sucess {
+            error!("Reader returned status {:?}, ending the read", status);
+            trans.complete();
+            return Ok(ResponseRequired::No);
+        }
+        self.write_read_chunk(trans, exch, report, proto_tx)
+    }
+
+    // The read is kept in the exchange, until its last chunk is sent
+    fn write_read_chunk(
+        &mut self,
+        trans: &mut Transaction,
+        exch: &mut Exchange,
+        mut report: Box<ReportReq>,
+        proto_tx: &mut Packet,
+    ) -> Result<ResponseRequired, Error> {
+        let accessor = Accessor::new(trans.session);
+        match report.write_chunk(self.consumer.as_ref(), &accessor, proto_tx) {
+            Ok(true) => exch.set_exchange_data(report),
+            Ok(false) => trans.complete(),
+            Err(e) => {
+                error!("Error in encoding the report {:?}", e);
+                trans.complete();
+                return Err(e);
+            }
+        }
         Ok(ResponseRequired::Yes)
     }
 }
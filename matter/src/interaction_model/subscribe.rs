@@ -0,0 +1,371 @@
+use std::time::{Duration, SystemTime};
+
+use heapless::LinearMap;
+use log::{error, info};
+use rand::Rng;
+
+use crate::{
+    acl::Accessor,
+    error::Error,
+    interaction_model::core::{create_status_response, IMStatusCode, OpCode},
+    tlv::{get_root_node_struct, FromTLV, TLVWriter, TagType, ToTLV},
+    transport::{exchange::Exchange, packet::Packet, proto_demux::ResponseRequired},
+};
+
+use super::{
+    messages::{
+        ib::{AttrPath, DataVersionFilter, EventPath},
+        msg::{StatusResp, SubscribeReq, SubscribeResp},
+    },
+    read::ReportReq,
+    InteractionConsumer, InteractionModel, Transaction,
+};
+
+/* Subscription handling
+ *
+ * - A SubscribeRequest is answered with a 'priming' ReportData that carries the
+ *   current value of all the requested attributes, and the requested events. The
+ *   clusters that match one of the Data Version Filters are left out of it. Like all the
+ *   reports, it may be split across several ReportData messages
+ * - Once the subscriber acknowledges the priming report with a StatusResponse, we
+ *   send out the SubscribeResponse, which completes the transaction
+ * - After this, ReportData messages are sent on a new exchange (on the same session)
+ *   whenever the data has changed or new events were emitted (subject to the min
+ *   interval), or when the max interval expires without any change
+ */
+
+pub const MAX_SUBSCRIPTIONS: usize = 4;
+
+pub struct Subscription {
+    id: u32,
+    // The local session id of the session on which the subscription was created
+    sess_id: u16,
+    // The subject that created the subscription, reports are subject to its access
+    accessor: Accessor,
+    min_int: u16,
+    max_int: u16,
+    fabric_filtered: bool,
+    attr_paths: Vec<AttrPath>,
+    dataver_digest: u32,
+    event_paths: Vec<EventPath>,
+    // Only events with an event number of at least this, are yet to be reported
+    event_min: u64,
+    last_report: SystemTime,
+    // Set when we are waiting for a StatusResponse to a report
+    report_pending: bool,
+    // The rest of the report, if it didn't fit in a single ReportData
+    report_chunks: Option<ReportReq>,
+}
+
+impl Subscription {
+    fn elapsed(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.last_report).unwrap_or_default()
+    }
+
+    fn has_new_events(&self, next_event_no: u64) -> bool {
+        !self.event_paths.is_empty() && next_event_no > self.event_min
+    }
+
+    // Encode the first ReportData of a report, the rest of the report is sent as the
+    // subscriber acknowledges each chunk. The Data Version Filters only apply to the
+    // priming report
+    fn write_report(
+        &mut self,
+        consumer: &dyn InteractionConsumer,
+        dataver_filters: Vec<DataVersionFilter>,
+        proto_tx: &mut Packet,
+    ) -> Result<(), Error> {
+        let mut report = ReportReq::new(
+            Some(self.id),
+            self.fabric_filtered,
+            self.attr_paths.clone(),
+            dataver_filters,
+            self.event_paths.clone(),
+            self.event_min,
+        );
+        if report.write_chunk(consumer, &self.accessor, proto_tx)? {
+            self.report_chunks = Some(report);
+        }
+        Ok(())
+    }
+}
+
+/// The context stored in the exchange, while we wait for the subscriber's StatusResponse
+pub enum SubsCtx {
+    Priming(u32),
+    Report(u32),
+}
+
+pub struct SubsMgr {
+    subs: LinearMap<u32, Subscription, MAX_SUBSCRIPTIONS>,
+    next_id: u32,
+    // The subscription that was selected for an unsolicited report
+    selected: Option<u32>,
+}
+
+impl Default for SubsMgr {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SubsMgr {
+    pub fn new() -> Self {
+        Self {
+            subs: LinearMap::new(),
+            next_id: rand::thread_rng().gen_range(1..0xFFFF),
+            selected: None,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.subs.len()
+    }
+
+    fn get_next_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        id
+    }
+
+    fn add(&mut self, subs: Subscription) -> Result<u32, Error> {
+        let id = subs.id;
+        self.subs.insert(id, subs).map_err(|_| Error::NoSpace)?;
+        Ok(id)
+    }
+
+    fn remove(&mut self, id: u32) {
+        info!("Removing subscription {}", id);
+        self.subs.remove(&id);
+    }
+
+    /// Remove all the subscriptions that were created on this session
+    pub fn remove_for_session(&mut self, sess_id: u16) {
+        let to_remove: Vec<u32> = self
+            .subs
+            .values()
+            .filter(|s| s.sess_id == sess_id)
+            .map(|s| s.id)
+            .collect();
+        for id in to_remove {
+            self.remove(id);
+        }
+    }
+
+    fn report_done(&mut self, id: u32) {
+        if let Some(s) = self.subs.get_mut(&id) {
+            s.report_pending = false;
+        }
+    }
+}
+
+impl InteractionModel {
+    pub fn handle_subscribe_req(
+        &mut self,
+        trans: &mut Transaction,
+        exch: &mut Exchange,
+        rx_buf: &[u8],
+        proto_tx: &mut Packet,
+    ) -> Result<ResponseRequired, Error> {
+        let root = get_root_node_struct(rx_buf)?;
+        let req = SubscribeReq::from_tlv(&root)?;
+        let sess_id = trans.session.get_local_sess_id();
+
+        if !req.keep_subs {
+            self.subs.remove_for_session(sess_id);
+        }
+
+        if req.min_int_floor > req.max_int_ceil {
+            error!(
+                "Invalid subscription intervals: min {} max {}",
+                req.min_int_floor, req.max_int_ceil
+            );
+            trans.complete();
+            return create_status_response(proto_tx, IMStatusCode::InvalidAction);
+        }
+        if self.subs.count() >= MAX_SUBSCRIPTIONS {
+            error!("No space for new subscriptions");
+            trans.complete();
+            return create_status_response(proto_tx, IMStatusCode::ResourceExhausted);
+        }
+
+        let attr_paths = if let Some(attr_requests) = &req.attr_requests {
+            attr_requests.iter().collect()
+        } else {
+            Vec::new()
+        };
+        let event_paths = if let Some(event_requests) = &req.event_requests {
+            event_requests.iter().collect()
+        } else {
+            Vec::new()
+        };
+        let event_min = req
+            .event_filters
+            .as_ref()
+            .and_then(|f| f.iter().filter_map(|f| f.event_min).max())
+            .unwrap_or(0);
+        let dataver_filters: Vec<DataVersionFilter> = req
+            .dataver_filters
+            .as_ref()
+            .map_or_else(Vec::new, |f| f.iter().collect());
+        let dataver_digest = self.consumer.get_dataver_digest(&attr_paths);
+        let next_event_no = self.consumer.get_next_event_no();
+        let mut subs = Subscription {
+            id: self.subs.get_next_id(),
+            sess_id,
+            accessor: Accessor::new(trans.session),
+            min_int: req.min_int_floor,
+            max_int: req.max_int_ceil,
+            fabric_filtered: req.fabric_filtered,
+            attr_paths,
+            dataver_digest,
+            event_paths,
+            event_min,
+            last_report: SystemTime::now(),
+            // We wait for the StatusResponse to the priming report
+            report_pending: true,
+            report_chunks: None,
+        };
+        if let Err(e) = subs.write_report(self.consumer.as_ref(), dataver_filters, proto_tx) {
+            error!("Error in encoding priming report {:?}", e);
+            trans.complete();
+            return Err(e);
+        }
+        subs.event_min = next_event_no;
+
+        let id = self.subs.add(subs)?;
+        info!("Created subscription {} on session {}", id, sess_id);
+        exch.set_exchange_data(Box::new(SubsCtx::Priming(id)));
+        Ok(ResponseRequired::Yes)
+    }
+
+    pub fn handle_status_resp(
+        &mut self,
+        trans: &mut Transaction,
+        exch: &mut Exchange,
+        rx_buf: &[u8],
+        proto_tx: &mut Packet,
+    ) -> Result<ResponseRequired, Error> {
+        let root = get_root_node_struct(rx_buf)?;
+        let status = StatusResp::from_tlv(&root)?.status;
+
+        let ctx = match exch.take_exchange_data::<SubsCtx>() {
+            Some(ctx) => ctx,
+            None => {
+                trans.complete();
+                return Err(Error::InvalidState);
+            }
+        };
+        let id = match *ctx {
+            SubsCtx::Priming(id) | SubsCtx::Report(id) => id,
+        };
+
+        if status != IMStatusCode::Sucess {
+            error!("Subscriber returned status {:?}, for {}", status, id);
+            trans.complete();
+            self.subs.remove(id);
+            return Ok(ResponseRequired::No);
+        }
+
+        if let Some(subs) = self.subs.subs.get_mut(&id) {
+            if let Some(mut report) = subs.report_chunks.take() {
+                // The next chunk of the report, on the same exchange
+                let result = report.write_chunk(self.consumer.as_ref(), &subs.accessor, proto_tx);
+                match result {
+                    Ok(more) => {
+                        if more {
+                            subs.report_chunks = Some(report);
+                        }
+                        exch.set_exchange_data(ctx);
+                        return Ok(ResponseRequired::Yes);
+                    }
+                    Err(e) => {
+                        error!("Error in encoding the report for {}: {:?}", id, e);
+                        trans.complete();
+                        self.subs.remove(id);
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        // Once the whole report is acknowledged, this exchange is done with
+        trans.complete();
+        self.subs.report_done(id);
+
+        match *ctx {
+            SubsCtx::Priming(id) => {
+                let max_int = match self.subs.subs.get(&id) {
+                    Some(s) => s.max_int,
+                    None => return Ok(ResponseRequired::No),
+                };
+                proto_tx.set_proto_opcode(OpCode::SubscriptResponse as u8);
+                let mut tw = TLVWriter::new(proto_tx.get_writebuf()?);
+                SubscribeResp::new(id, max_int).to_tlv(&mut tw, TagType::Anonymous)?;
+                Ok(ResponseRequired::Yes)
+            }
+            SubsCtx::Report(_) => Ok(ResponseRequired::No),
+        }
+    }
+
+    /// Select a subscription that needs to be reported, and return the local session
+    /// id of the session that the report should be sent on
+    pub fn get_pending_report(&mut self) -> Option<u16> {
+        let now = SystemTime::now();
+        let next_event_no = self.consumer.get_next_event_no();
+        let mut dead = Vec::new();
+        let mut selected = None;
+
+        for s in self.subs.subs.values_mut() {
+            let elapsed = s.elapsed(now);
+            let max_int_expired = elapsed >= Duration::from_secs(s.max_int as u64);
+            if s.report_pending {
+                if max_int_expired {
+                    // The subscriber hasn't responded to our previous report
+                    dead.push(s.id);
+                }
+                continue;
+            }
+            if elapsed < Duration::from_secs(s.min_int as u64) {
+                continue;
+            }
+
+            let dataver_digest = self.consumer.get_dataver_digest(&s.attr_paths);
+            if max_int_expired
+                || dataver_digest != s.dataver_digest
+                || s.has_new_events(next_event_no)
+            {
+                s.dataver_digest = dataver_digest;
+                s.last_report = now;
+                s.report_pending = true;
+                selected = Some((s.id, s.sess_id));
+                break;
+            }
+        }
+
+        for id in dead {
+            self.subs.remove(id);
+        }
+
+        let (id, sess_id) = selected?;
+        self.subs.selected = Some(id);
+        Some(sess_id)
+    }
+
+    /// Encode the report for the subscription selected in get_pending_report()
+    pub fn handle_pending_report(
+        &mut self,
+        exch: &mut Exchange,
+        proto_tx: &mut Packet,
+    ) -> Result<(), Error> {
+        let id = self.subs.selected.take().ok_or(Error::InvalidState)?;
+        let subs = self.subs.subs.get_mut(&id).ok_or(Error::NotFound)?;
+        info!("Sending report for subscription {}", id);
+        // Any event emitted while the report is being encoded, will be reported next time
+        let next_event_no = self.consumer.get_next_event_no();
+        subs.write_report(self.consumer.as_ref(), Vec::new(), proto_tx)?;
+        subs.event_min = next_event_no;
+        exch.set_exchange_data(Box::new(SubsCtx::Report(id)));
+        Ok(())
+    }
+}
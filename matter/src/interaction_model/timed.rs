@@ -0,0 +1,79 @@
+use std::time::{Duration, SystemTime};
+
+use log::error;
+
+use crate::{
+    error::Error,
+    tlv::{get_root_node_struct, FromTLV},
+    transport::{exchange::Exchange, packet::Packet, proto_demux::ResponseRequired},
+};
+
+use super::{
+    core::{create_status_response, IMStatusCode},
+    messages::msg::TimedReq,
+    InteractionModel, Transaction,
+};
+
+/* Timed Interactions
+ *
+ * - A TimedRequest opens a window (of 'timeout' milliseconds) on the exchange
+ * - The Write/Invoke Request that follows on the same exchange must indicate that it is a
+ *   timed request, and must arrive before the window expires
+ * - Attributes and Commands with Access::TIMED_ONLY can only be written/invoked as
+ *   part of such an interaction
+ */
+
+/// The context stored in the exchange, between the TimedRequest and the action that follows
+pub struct TimedCtx {
+    expiry: SystemTime,
+}
+
+impl<'a> Transaction<'a> {
+    /// Pick up the timed window, if the exchange had one open
+    pub fn set_timed_window(&mut self, exch: &mut Exchange) {
+        if exch.get_exchange_data::<TimedCtx>().is_some() {
+            self.timed_expiry = exch.take_exchange_data::<TimedCtx>().map(|t| t.expiry);
+        }
+    }
+
+    /// Validate the timed window against the 'timed request' flag of the Write/Invoke Request
+    pub fn validate_timed(&mut self, timed_req: bool) -> Result<(), IMStatusCode> {
+        match (self.timed_expiry, timed_req) {
+            (None, false) => Ok(()),
+            (Some(expiry), true) => {
+                if SystemTime::now() > expiry {
+                    error!("Timed interaction window expired");
+                    self.timed_expiry = None;
+                    Err(IMStatusCode::Timeout)
+                } else {
+                    Ok(())
+                }
+            }
+            _ => {
+                error!("Mismatch between timed request flag and the timed window");
+                self.timed_expiry = None;
+                Err(IMStatusCode::TimedRequestMismatch)
+            }
+        }
+    }
+
+    pub fn is_timed(&self) -> bool {
+        self.timed_expiry.is_some()
+    }
+}
+
+impl InteractionModel {
+    pub fn handle_timed_req(
+        &mut self,
+        exch: &mut Exchange,
+        rx_buf: &[u8],
+        proto_tx: &mut Packet,
+    ) -> Result<ResponseRequired, Error> {
+        let root = get_root_node_struct(rx_buf)?;
+        let req = TimedReq::from_tlv(&root)?;
+
+        let expiry = SystemTime::now() + Duration::from_millis(req.timeout as u64);
+        exch.set_exchange_data(Box::new(TimedCtx { expiry }));
+        create_status_response(proto_tx, IMStatusCode::Sucess)
+    }
+}
@@ -1,12 +1,17 @@
 use log::error;
 
 use crate::{
+    acl::Accessor,
     error::Error,
     tlv::{get_root_node_struct, FromTLV, TLVWriter, TagType},
     transport::{packet::Packet, proto_demux::ResponseRequired},
 };
 
-use super::{core::OpCode, messages::msg::WriteReq, InteractionModel, Transaction};
+use super::{
+    core::{create_status_response, OpCode},
+    messages::msg::WriteReq,
+    InteractionModel, Transaction,
+};
 
 impl InteractionModel {
     pub fn handle_write_req(
@@ -20,12 +25,18 @@ impl InteractionModel {
         let mut tw = TLVWriter::new(proto_tx.get_writebuf()?);
         let root = get_root_node_struct(rx_buf)?;
         let write_req = WriteReq::from_tlv(&root)?;
+        if let Err(status) = trans.validate_timed(write_req.timed_request == Some(true)) {
+            trans.complete();
+            return create_status_response(proto_tx, status);
+        }
         // TODO: This is found in the spec, but not in the C++ implementation
         let _fab_scoped = false;
         let supress_response = write_req.supress_response.is_some();
 
         tw.start_struct(TagType::Anonymous)?;
-        self.consumer.consume_write_attr(&write_req, &mut tw)?;
+        let accessor = Accessor::new(trans.session);
+        self.consumer
+            .consume_write_attr(&accessor, &write_req, &mut tw)?;
         tw.end_container()?;
 
         trans.complete();
@@ -7,9 +7,12 @@
 //!
 //! # Examples
 //! ```
-//! use matter::Matter;
+//! use matter::{Matter, CommissioningData};
 //! use matter::data_model::device_types::device_type_add_on_off_light;
 //! use matter::data_model::cluster_basic_information::BasicInfoConfig;
+//! use matter::secure_channel::spake2p::VerifierData;
+//! use matter::sys::FileKvStore;
+//! use std::sync::Arc;
 //!
 //! # use matter::data_model::sdm::dev_att::{DataType, DevAttDataFetcher};
 //! # use matter::error::Error;
@@ -27,20 +30,30 @@
 //!     sw_ver: 1,
 //! };
 //!
+//! /// The passcode and the discriminator, that a commissioner uses to pair with the device
+//! let comm_data = CommissioningData {
+//!     verifier: VerifierData::new_with_pw(20202021).unwrap(),
+//!     discriminator: 250,
+//! };
+//!
+//! /// The storage for the fabrics, each device on a host needs its own directory
+//! let store = Arc::new(FileKvStore::new("/var/tmp/matter-doc").unwrap());
+//!
 //! /// Get the Matter Object
 //! /// The dev_att is an object that implements the DevAttDataFetcher trait.
-//! let mut matter = Matter::new(dev_info, dev_att).unwrap();
+//! let mut matter = Matter::new(dev_info, dev_att, comm_data, store).unwrap();
 //! let dm = matter.get_data_model();
 //! {
 //!     let mut node = dm.node.write().unwrap();
 //!     /// Add our device-types
-//!     let endpoint = device_type_add_on_off_light(&mut node).unwrap();
+//!     let endpoint = device_type_add_on_off_light(&mut node, dm.group_keys.clone()).unwrap();
 //! }
 //! // Start the Matter Daemon
 //! // matter.start_daemon().unwrap();
 //! ```
 //! Start off exploring by going to the [Matter] object.
 
+pub mod acl;
 pub mod cert;
 pub mod core;
 pub mod crypto;
@@ -49,6 +62,8 @@ pub mod error;
 pub mod fabric;
 pub mod group_keys;
 pub mod interaction_model;
+pub mod pairing;
+pub mod persist;
 pub mod secure_channel;
 pub mod sys;
 pub mod tlv;
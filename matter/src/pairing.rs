@@ -0,0 +1,83 @@
+//! The Onboarding Payload, that a commissioner uses to discover and pair with a device
+//!
+//! The payload can be encoded as a QR code ("MT:" followed by Base38) or as an
+//! 11 or 21-digit Manual Pairing Code.
+
+pub mod code;
+pub mod qr;
+
+mod base38;
+mod verhoeff;
+
+use bitflags::bitflags;
+
+use crate::data_model::cluster_basic_information::BasicInfoConfig;
+
+bitflags! {
+    /// The ways in which the device can be discovered, while commissionable
+    pub struct DiscoveryCapabilities: u8 {
+        const SOFT_AP = 0x01;
+        const BLE = 0x02;
+        const IP = 0x04;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommissioningFlow {
+    /// The device is commissionable as soon as it is powered on
+    Standard = 0,
+    /// The user must take some action on the device, before it is commissionable
+    UserIntent = 1,
+    /// The commissioner must follow the vendor's instructions
+    Custom = 2,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionalValue {
+    String(String),
+    Int(u32),
+}
+
+/// The tag of the Serial Number, in the optional data of the QR code. Tags 0x80 to 0xFF
+/// are available for vendor specific data
+pub const SERIAL_NUMBER_TAG: u8 = 0x00;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OnboardingPayload {
+    pub vid: u16,
+    pub pid: u16,
+    pub flow: CommissioningFlow,
+    pub discovery: DiscoveryCapabilities,
+    /// The 12-bit discriminator. The Manual Pairing Code only carries the upper 4 bits
+    pub discriminator: u16,
+    pub passcode: u32,
+    /// The optional data of the QR code, as (tag, value) pairs
+    pub optional: Vec<(u8, OptionalValue)>,
+}
+
+impl OnboardingPayload {
+    pub fn new(
+        dev_det: &BasicInfoConfig,
+        discriminator: u16,
+        passcode: u32,
+        discovery: DiscoveryCapabilities,
+    ) -> Self {
+        Self {
+            vid: dev_det.vid,
+            pid: dev_det.pid,
+            flow: CommissioningFlow::Standard,
+            discovery,
+            discriminator,
+            passcode,
+            optional: Vec::new(),
+        }
+    }
+
+    pub fn set_serial_no(&mut self, serial_no: &str) {
+        self.optional.retain(|(tag, _)| *tag != SERIAL_NUMBER_TAG);
+        self.optional.push((
+            SERIAL_NUMBER_TAG,
+            OptionalValue::String(serial_no.to_owned()),
+        ));
+    }
+}
@@ -0,0 +1,90 @@
+use crate::error::Error;
+
+// Base38 encoding, as defined in the Matter spec. Every 3 bytes are encoded as 5
+// characters, a trailing chunk of 2 bytes as 4 characters, and of 1 byte as 2 characters.
+// The characters of a chunk are emitted from the least significant digit.
+
+const BASE38_CHARS: &[u8; 38] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ-.";
+const RADIX: u32 = BASE38_CHARS.len() as u32;
+
+// The number of characters, that a chunk of 'n' bytes is encoded in
+fn chars_per_chunk(n: usize) -> usize {
+    match n {
+        1 => 2,
+        2 => 4,
+        _ => 5,
+    }
+}
+
+pub fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for chunk in bytes.chunks(3) {
+        let mut value = chunk
+            .iter()
+            .rev()
+            .fold(0u32, |acc, b| (acc << 8) | *b as u32);
+        for _ in 0..chars_per_chunk(chunk.len()) {
+            out.push(BASE38_CHARS[(value % RADIX) as usize] as char);
+            value /= RADIX;
+        }
+    }
+    out
+}
+
+pub fn decode(s: &str) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::with_capacity(s.len() / 5 * 3 + 2);
+    for chunk in s.as_bytes().chunks(5) {
+        let num_bytes = match chunk.len() {
+            2 => 1,
+            4 => 2,
+            5 => 3,
+            _ => return Err(Error::InvalidData),
+        };
+        let mut value = 0u32;
+        for c in chunk.iter().rev() {
+            let digit = BASE38_CHARS
+                .iter()
+                .position(|b| b == c)
+                .ok_or(Error::InvalidData)?;
+            value = value * RADIX + digit as u32;
+        }
+        if value >> (8 * num_bytes) != 0 {
+            // The chunk encodes more bits, than fit in its bytes
+            return Err(Error::InvalidData);
+        }
+        for _ in 0..num_bytes {
+            out.push(value as u8);
+            value >>= 8;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base38_encode_decode() {
+        let vectors: [(&[u8], &str); 6] = [
+            (b"", ""),
+            (&[10], "A0"),
+            (&[10, 10], "OT10"),
+            (&[10, 10, 10], "-N.B0"),
+            (b"Hello World!", "KKHF3W2S013OPM3EJX11"),
+            (&[0xff, 0xff, 0xff], "PLS18"),
+        ];
+        for (bytes, s) in vectors {
+            assert_eq!(encode(bytes), s);
+            assert_eq!(decode(s).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_base38_decode_invalid() {
+        // Invalid length, invalid character, and a value that overflows the chunk
+        assert!(decode("A").is_err());
+        assert!(decode("A0a").is_err());
+        assert!(decode("ZZZZZ").is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use std::convert::TryFrom;
+
+use crate::error::Error;
+
+use super::{verhoeff, CommissioningFlow, DiscoveryCapabilities, OnboardingPayload};
+
+const SHORT_CODE_LEN: usize = 11;
+const LONG_CODE_LEN: usize = 21;
+
+// The Manual Pairing Code carries the upper 4 bits of the discriminator
+const SHORT_DISCRIMINATOR_SHIFT: u16 = 8;
+const PASSCODE_LSB_BITS: u32 = 14;
+const PASSCODE_LSB_MASK: u32 = (1 << PASSCODE_LSB_BITS) - 1;
+const VID_PID_PRESENT: u32 = 1 << 2;
+
+impl OnboardingPayload {
+    /// The Manual Pairing Code, e.g. "34970112332". It is 21 digits long, including the
+    /// Vendor ID and Product ID, if the commissioning flow isn't the standard one.
+    pub fn to_manual_code(&self) -> String {
+        let short_discriminator = (self.discriminator >> SHORT_DISCRIMINATOR_SHIFT) as u32 & 0x0F;
+        let vid_pid_present = self.flow != CommissioningFlow::Standard;
+
+        let mut chunk1 = short_discriminator >> 2;
+        if vid_pid_present {
+            chunk1 |= VID_PID_PRESENT;
+        }
+        let chunk2 = ((short_discriminator & 0x03) << PASSCODE_LSB_BITS)
+            | (self.passcode & PASSCODE_LSB_MASK);
+        let chunk3 = self.passcode >> PASSCODE_LSB_BITS;
+
+        let mut code = format!("{}{:05}{:04}", chunk1, chunk2, chunk3);
+        if vid_pid_present {
+            code += &format!("{:05}{:05}", self.vid, self.pid);
+        }
+        // The code consists of decimal digits only, so the check digit always exists
+        if let Some(c) = verhoeff::check_digit(&code) {
+            code.push(c);
+        }
+        code
+    }
+
+    /// Parse a Manual Pairing Code. Only the upper 4 bits of the discriminator are set in
+    /// the result, and the discovery capabilities are unknown.
+    pub fn from_manual_code(code: &str) -> Result<Self, Error> {
+        let code: String = code.chars().filter(|c| *c != '-' && *c != ' ').collect();
+        if (code.len() != SHORT_CODE_LEN && code.len() != LONG_CODE_LEN)
+            || !verhoeff::is_valid(&code)
+        {
+            return Err(Error::InvalidData);
+        }
+
+        let field = |start: usize, end: usize| -> Result<u32, Error> {
+            code[start..end].parse().map_err(|_| Error::InvalidData)
+        };
+        let chunk1 = field(0, 1)?;
+        let chunk2 = field(1, 6)?;
+        let chunk3 = field(6, 10)?;
+
+        let vid_pid_present = chunk1 & VID_PID_PRESENT != 0;
+        if vid_pid_present != (code.len() == LONG_CODE_LEN) {
+            return Err(Error::InvalidData);
+        }
+        let short_discriminator = ((chunk1 & 0x03) << 2) | (chunk2 >> PASSCODE_LSB_BITS);
+        if short_discriminator > 0x0F {
+            return Err(Error::InvalidData);
+        }
+        let passcode = (chunk3 << PASSCODE_LSB_BITS) | (chunk2 & PASSCODE_LSB_MASK);
+
+        let (flow, vid, pid) = if vid_pid_present {
+            let vid = u16::try_from(field(10, 15)?).map_err(|_| Error::InvalidData)?;
+            let pid = u16::try_from(field(15, 20)?).map_err(|_| Error::InvalidData)?;
+            (CommissioningFlow::Custom, vid, pid)
+        } else {
+            (CommissioningFlow::Standard, 0, 0)
+        };
+
+        Ok(Self {
+            vid,
+            pid,
+            flow,
+            discovery: DiscoveryCapabilities::empty(),
+            discriminator: (short_discriminator as u16) << SHORT_DISCRIMINATOR_SHIFT,
+            passcode,
+            optional: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(flow: CommissioningFlow) -> OnboardingPayload {
+        OnboardingPayload {
+            vid: 0xFFF1,
+            pid: 0x8000,
+            flow,
+            discovery: DiscoveryCapabilities::empty(),
+            discriminator: 3840,
+            passcode: 20202021,
+            optional: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_short_manual_code() {
+        let p = payload(CommissioningFlow::Standard);
+        let code = p.to_manual_code();
+        assert_eq!(code, "34970112332");
+
+        let parsed = OnboardingPayload::from_manual_code("3497-011-2332").unwrap();
+        assert_eq!(parsed.discriminator, 3840);
+        assert_eq!(parsed.passcode, 20202021);
+        assert_eq!(parsed.flow, CommissioningFlow::Standard);
+    }
+
+    #[test]
+    fn test_long_manual_code() {
+        let p = payload(CommissioningFlow::Custom);
+        let code = p.to_manual_code();
+        assert_eq!(code.len(), LONG_CODE_LEN);
+        assert_eq!(&code[10..20], "6552132768");
+        assert_eq!(OnboardingPayload::from_manual_code(&code).unwrap(), p);
+    }
+
+    #[test]
+    fn test_manual_code_invalid() {
+        // Wrong check digit, and wrong length
+        assert!(OnboardingPayload::from_manual_code("34970112333").is_err());
+        assert!(OnboardingPayload::from_manual_code("3497011233").is_err());
+    }
+}
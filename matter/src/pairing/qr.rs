@@ -0,0 +1,205 @@
+use crate::{
+    error::Error,
+    tlv::{self, ElementType, TLVWriter, TagType},
+    utils::writebuf::WriteBuf,
+};
+
+use super::{base38, CommissioningFlow, DiscoveryCapabilities, OnboardingPayload, OptionalValue};
+
+const QR_CODE_PREFIX: &str = "MT:";
+const PAYLOAD_VERSION: u32 = 0;
+
+// The packed payload, that precedes the optional TLV data
+const PACKED_LEN: usize = 11;
+const MAX_TLV_LEN: usize = 256;
+
+// The fields of the packed payload in order, with their width in bits
+const VERSION_BITS: usize = 3;
+const VID_BITS: usize = 16;
+const PID_BITS: usize = 16;
+const FLOW_BITS: usize = 2;
+const DISCOVERY_BITS: usize = 8;
+const DISCRIMINATOR_BITS: usize = 12;
+const PASSCODE_BITS: usize = 27;
+const PADDING_BITS: usize = 4;
+
+// Writes values into a byte buffer, from the least significant bit onwards
+struct BitWriter<'a> {
+    buf: &'a mut [u8],
+    offset: usize,
+}
+
+impl<'a> BitWriter<'a> {
+    fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, offset: 0 }
+    }
+
+    fn put(&mut self, value: u32, bits: usize) {
+        for i in 0..bits {
+            if value & (1 << i) != 0 {
+                self.buf[self.offset / 8] |= 1 << (self.offset % 8);
+            }
+            self.offset += 1;
+        }
+    }
+}
+
+struct BitReader<'a> {
+    buf: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, offset: 0 }
+    }
+
+    fn get(&mut self, bits: usize) -> u32 {
+        let mut value = 0;
+        for i in 0..bits {
+            if self.buf[self.offset / 8] & (1 << (self.offset % 8)) != 0 {
+                value |= 1 << i;
+            }
+            self.offset += 1;
+        }
+        value
+    }
+}
+
+impl OnboardingPayload {
+    /// The payload of the QR code, e.g. "MT:Y.K9042C00KA0648G00"
+    pub fn to_qr_code(&self) -> Result<String, Error> {
+        if self.discriminator >> DISCRIMINATOR_BITS != 0 || self.passcode >> PASSCODE_BITS != 0 {
+            return Err(Error::Invalid);
+        }
+
+        let mut packed = [0u8; PACKED_LEN];
+        let mut bw = BitWriter::new(&mut packed);
+        bw.put(PAYLOAD_VERSION, VERSION_BITS);
+        bw.put(self.vid as u32, VID_BITS);
+        bw.put(self.pid as u32, PID_BITS);
+        bw.put(self.flow as u32, FLOW_BITS);
+        bw.put(self.discovery.bits() as u32, DISCOVERY_BITS);
+        bw.put(self.discriminator as u32, DISCRIMINATOR_BITS);
+        bw.put(self.passcode, PASSCODE_BITS);
+        bw.put(0, PADDING_BITS);
+
+        let mut payload = packed.to_vec();
+        if !self.optional.is_empty() {
+            let mut buf = [0u8; MAX_TLV_LEN];
+            let buf_len = buf.len();
+            let mut wb = WriteBuf::new(&mut buf, buf_len);
+            let mut tw = TLVWriter::new(&mut wb);
+            tw.start_struct(TagType::Anonymous)?;
+            for (tag, value) in &self.optional {
+                match value {
+                    OptionalValue::String(s) => tw.utf8(TagType::Context(*tag), s.as_bytes())?,
+                    OptionalValue::Int(i) => tw.u32(TagType::Context(*tag), *i)?,
+                }
+            }
+            tw.end_container()?;
+            payload.extend_from_slice(wb.as_borrow_slice());
+        }
+
+        Ok(format!("{}{}", QR_CODE_PREFIX, base38::encode(&payload)))
+    }
+
+    pub fn from_qr_code(qr_code: &str) -> Result<Self, Error> {
+        let encoded = qr_code
+            .strip_prefix(QR_CODE_PREFIX)
+            .ok_or(Error::InvalidData)?;
+        let payload = base38::decode(encoded)?;
+        if payload.len() < PACKED_LEN {
+            return Err(Error::InvalidData);
+        }
+
+        let mut br = BitReader::new(&payload[..PACKED_LEN]);
+        if br.get(VERSION_BITS) != PAYLOAD_VERSION {
+            return Err(Error::InvalidData);
+        }
+        let vid = br.get(VID_BITS) as u16;
+        let pid = br.get(PID_BITS) as u16;
+        let flow = match br.get(FLOW_BITS) {
+            0 => CommissioningFlow::Standard,
+            1 => CommissioningFlow::UserIntent,
+            2 => CommissioningFlow::Custom,
+            _ => return Err(Error::InvalidData),
+        };
+        let discovery = DiscoveryCapabilities::from_bits(br.get(DISCOVERY_BITS) as u8)
+            .ok_or(Error::InvalidData)?;
+        let discriminator = br.get(DISCRIMINATOR_BITS) as u16;
+        let passcode = br.get(PASSCODE_BITS);
+
+        let mut optional = Vec::new();
+        if payload.len() > PACKED_LEN {
+            let root = tlv::get_root_node_struct(&payload[PACKED_LEN..])?;
+            for e in root.iter().ok_or(Error::InvalidData)? {
+                let tag = match e.get_tag() {
+                    TagType::Context(t) => t,
+                    _ => return Err(Error::InvalidData),
+                };
+                let value = match e.get_element_type() {
+                    ElementType::Utf8l(_) | ElementType::Utf16l(_) => OptionalValue::String(
+                        String::from_utf8(e.slice()?.to_vec()).map_err(|_| Error::InvalidData)?,
+                    ),
+                    _ => OptionalValue::Int(e.u32()?),
+                };
+                optional.push((tag, value));
+            }
+        }
+
+        Ok(Self {
+            vid,
+            pid,
+            flow,
+            discovery,
+            discriminator,
+            passcode,
+            optional,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload() -> OnboardingPayload {
+        OnboardingPayload {
+            vid: 12,
+            pid: 1,
+            flow: CommissioningFlow::Standard,
+            discovery: DiscoveryCapabilities::SOFT_AP,
+            discriminator: 128,
+            passcode: 2048,
+            optional: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_qr_code() {
+        let p = payload();
+        let qr = p.to_qr_code().unwrap();
+        assert_eq!(qr, "MT:M5L90MP500K64J00000");
+        assert_eq!(OnboardingPayload::from_qr_code(&qr).unwrap(), p);
+    }
+
+    #[test]
+    fn test_qr_code_optional_data() {
+        let mut p = payload();
+        p.set_serial_no("123456789");
+        p.optional.push((0x80, OptionalValue::Int(12)));
+        let qr = p.to_qr_code().unwrap();
+        assert_eq!(OnboardingPayload::from_qr_code(&qr).unwrap(), p);
+    }
+
+    #[test]
+    fn test_qr_code_invalid() {
+        assert!(OnboardingPayload::from_qr_code("M5L90MP500K64J00000").is_err());
+        assert!(OnboardingPayload::from_qr_code("MT:M5L90MP500K64J").is_err());
+
+        let mut p = payload();
+        p.passcode = 1 << PASSCODE_BITS;
+        assert!(p.to_qr_code().is_err());
+    }
+}
@@ -0,0 +1,63 @@
+// The Verhoeff check digit, that is part of the Manual Pairing Code
+
+const D: [[u8; 10]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+    [1, 2, 3, 4, 0, 6, 7, 8, 9, 5],
+    [2, 3, 4, 0, 1, 7, 8, 9, 5, 6],
+    [3, 4, 0, 1, 2, 8, 9, 5, 6, 7],
+    [4, 0, 1, 2, 3, 9, 5, 6, 7, 8],
+    [5, 9, 8, 7, 6, 0, 4, 3, 2, 1],
+    [6, 5, 9, 8, 7, 1, 0, 4, 3, 2],
+    [7, 6, 5, 9, 8, 2, 1, 0, 4, 3],
+    [8, 7, 6, 5, 9, 3, 2, 1, 0, 4],
+    [9, 8, 7, 6, 5, 4, 3, 2, 1, 0],
+];
+
+const P: [[u8; 10]; 8] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+    [1, 5, 7, 6, 2, 8, 3, 0, 9, 4],
+    [5, 8, 0, 3, 7, 9, 6, 1, 4, 2],
+    [8, 9, 1, 6, 0, 4, 3, 5, 2, 7],
+    [9, 4, 5, 3, 1, 2, 6, 8, 7, 0],
+    [4, 2, 8, 6, 5, 7, 3, 9, 0, 1],
+    [2, 7, 9, 3, 8, 0, 6, 4, 1, 5],
+    [7, 0, 4, 6, 9, 1, 3, 2, 5, 8],
+];
+
+const INV: [u8; 10] = [0, 4, 3, 2, 1, 5, 6, 7, 8, 9];
+
+// 'offset' is 1 when computing the check digit, that will be appended to 'digits'
+fn checksum(digits: &str, offset: usize) -> Option<u8> {
+    let mut c = 0;
+    for (i, d) in digits.bytes().rev().enumerate() {
+        let d = (d as char).to_digit(10)? as usize;
+        c = D[c as usize][P[(i + offset) % 8][d] as usize];
+    }
+    Some(c)
+}
+
+/// The check digit for a string of decimal digits
+pub fn check_digit(digits: &str) -> Option<char> {
+    let c = checksum(digits, 1)?;
+    std::char::from_digit(INV[c as usize] as u32, 10)
+}
+
+/// Validate a string of decimal digits, whose last digit is the check digit
+pub fn is_valid(digits: &str) -> bool {
+    checksum(digits, 0) == Some(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verhoeff() {
+        assert_eq!(check_digit("236"), Some('3'));
+        assert!(is_valid("2363"));
+        assert!(!is_valid("2364"));
+        assert_eq!(check_digit("3497011233"), Some('2'));
+        assert!(is_valid("34970112332"));
+        assert_eq!(check_digit("12a"), None);
+    }
+}
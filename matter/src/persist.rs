@@ -0,0 +1,94 @@
+use std::{collections::HashMap, convert::TryInto, sync::Mutex};
+
+use crate::error::Error;
+
+/* Persistent Storage
+ *
+ * - The fabrics, the ACLs and the group keys are persisted through a key-value store, so
+ *   that they survive a reboot
+ * - The store is handed to Matter::new(), so each device on a host can use its own
+ * - A file-backed store is in sys (FileKvStore), the in-memory one below is mostly
+ *   useful for tests
+ */
+
+/// A key-value store for the data that must survive a reboot
+///
+/// A missing key is reported as [Error::NotFound]
+pub trait KvStore: Send + Sync {
+    fn set_kv_slice(&self, key: &str, val: &[u8]) -> Result<(), Error>;
+
+    /// Append the value of the key to 'val', and return its length
+    fn get_kv_slice(&self, key: &str, val: &mut Vec<u8>) -> Result<usize, Error>;
+
+    /// Remove the key, removing a missing key is not an error
+    fn remove_kv(&self, key: &str) -> Result<(), Error>;
+
+    fn set_kv_u64(&self, key: &str, val: u64) -> Result<(), Error> {
+        self.set_kv_slice(key, &val.to_be_bytes())
+    }
+
+    fn get_kv_u64(&self, key: &str, val: &mut u64) -> Result<(), Error> {
+        let mut vec = Vec::new();
+        self.get_kv_slice(key, &mut vec)?;
+        *val = u64::from_be_bytes(vec.as_slice().try_into()?);
+        Ok(())
+    }
+}
+
+/// A [KvStore] that is only kept in memory, everything is lost on reboot
+#[derive(Default)]
+pub struct MemKvStore {
+    entries: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemKvStore {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl KvStore for MemKvStore {
+    fn set_kv_slice(&self, key: &str, val: &[u8]) -> Result<(), Error> {
+        self.entries.lock()?.insert(key.to_owned(), val.to_vec());
+        Ok(())
+    }
+
+    fn get_kv_slice(&self, key: &str, val: &mut Vec<u8>) -> Result<usize, Error> {
+        let entries = self.entries.lock()?;
+        let v = entries.get(key).ok_or(Error::NotFound)?;
+        val.extend_from_slice(v);
+        Ok(v.len())
+    }
+
+    fn remove_kv(&self, key: &str) -> Result<(), Error> {
+        self.entries.lock()?.remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mem_kv_store() {
+        let store = MemKvStore::new();
+        let mut val = Vec::new();
+        assert_eq!(store.get_kv_slice("a", &mut val), Err(Error::NotFound));
+
+        store.set_kv_slice("a", &[1, 2, 3]).unwrap();
+        assert_eq!(store.get_kv_slice("a", &mut val), Ok(3));
+        assert_eq!(val, vec![1, 2, 3]);
+
+        store.set_kv_u64("b", 0x1122).unwrap();
+        let mut v = 0;
+        store.get_kv_u64("b", &mut v).unwrap();
+        assert_eq!(v, 0x1122);
+        // Not a u64
+        assert!(store.get_kv_u64("a", &mut v).is_err());
+
+        store.remove_kv("a").unwrap();
+        store.remove_kv("a").unwrap();
+        assert_eq!(store.get_kv_slice("a", &mut val), Err(Error::NotFound));
+    }
+}
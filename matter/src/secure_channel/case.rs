@@ -1,19 +1,32 @@
-use std::sync::Arc;
+use std::{
+    collections::VecDeque,
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
-use log::{error, trace};
-use owning_ref::RwLockReadGuardRef;
+use log::{error, info, trace};
 use rand::prelude::*;
 
 use crate::{
+    acl::NocCatIds,
     cert::Cert,
     crypto::{self, CryptoKeyPair, KeyPair, Sha256},
     error::Error,
-    fabric::{Fabric, FabricMgr, FabricMgrInner},
+    fabric::{Fabric, FabricMgr, MAX_SUPPORTED_FABRICS},
     secure_channel::common,
-    secure_channel::common::SCStatusCodes,
-    tlv::{get_root_node_struct, FromTLV, OctetStr, TLVElement, TLVWriter, TagType},
+    secure_channel::common::{OpCode, SCStatusCodes, SessionParams},
+    secure_channel::status_report,
+    tlv::{
+        get_root_node, get_root_node_struct, FromTLV, OctetStr, TLVElement, TLVWriter, TagType,
+        ToTLV,
+    },
     transport::{
+        exchange::ExchangeCtx,
         network::Address,
+        packet::Packet,
         proto_demux::ProtoCtx,
         queue::{Msg, WorkQ},
         session::{CloneData, SessionMode},
@@ -21,10 +34,231 @@ use crate::{
     utils::writebuf::WriteBuf,
 };
 
+// The max number of session establishment requests that may be queued
+const MAX_CASE_REQS: usize = 4;
+// How long we wait for the peer to complete the session establishment
+const CASE_TIMEOUT: Duration = Duration::from_secs(30);
+
+const MAX_ENCRYPTED_SIZE: usize = 800;
+
+const SIGMA2_NONCE: [u8; crypto::AEAD_NONCE_LEN_BYTES] = [
+    0x4e, 0x43, 0x41, 0x53, 0x45, 0x5f, 0x53, 0x69, 0x67, 0x6d, 0x61, 0x32, 0x4e,
+];
+const SIGMA3_NONCE: [u8; crypto::AEAD_NONCE_LEN_BYTES] = [
+    0x4e, 0x43, 0x41, 0x53, 0x45, 0x5f, 0x53, 0x69, 0x67, 0x6d, 0x61, 0x33, 0x4e,
+];
+
+// The max number of peers, whose sessions we can resume
+const MAX_RESUMPTION_RECORDS: usize = 8;
+const RESUMPTION_ID_LEN: usize = 16;
+const MAX_RESUMPTION_TLV_LEN: usize = 1024;
+
+const SIGMA1_RESUME_INFO: [u8; 13] = *b"Sigma1_Resume";
+const SIGMA2_RESUME_INFO: [u8; 13] = *b"Sigma2_Resume";
+const SIGMA1_RESUME_NONCE: [u8; crypto::AEAD_NONCE_LEN_BYTES] = *b"NCASE_SigmaS1";
+const SIGMA2_RESUME_NONCE: [u8; crypto::AEAD_NONCE_LEN_BYTES] = *b"NCASE_SigmaS2";
+
 #[derive(PartialEq)]
 enum State {
+    // Responder
     Sigma1Rx,
     Sigma3Rx,
+    Sigma2ResumeTx,
+    // Initiator
+    Sigma1Tx,
+    Sigma3Tx,
+}
+
+// The state that is retained once a session is established, so that the next session
+// with the same peer can be resumed without the certificate exchange
+#[derive(Clone, Copy)]
+struct ResumptionRecord {
+    resumption_id: [u8; RESUMPTION_ID_LEN],
+    shared_secret: [u8; crypto::ECDH_SHARED_SECRET_LEN_BYTES],
+    local_fabric_idx: usize,
+    peer_nodeid: u64,
+    peer_cat_ids: NocCatIds,
+}
+
+impl ResumptionRecord {
+    fn from_tlv(t: &TLVElement, local_fabric_idx: usize) -> Result<Self, Error> {
+        let mut record = Self {
+            resumption_id: [0; RESUMPTION_ID_LEN],
+            shared_secret: [0; crypto::ECDH_SHARED_SECRET_LEN_BYTES],
+            local_fabric_idx,
+            peer_nodeid: t.find_tag(3)?.u64()?,
+            peer_cat_ids: Default::default(),
+        };
+        let resumption_id = t.find_tag(1)?.slice()?;
+        let shared_secret = t.find_tag(2)?.slice()?;
+        if resumption_id.len() != RESUMPTION_ID_LEN
+            || shared_secret.len() != crypto::ECDH_SHARED_SECRET_LEN_BYTES
+        {
+            return Err(Error::Invalid);
+        }
+        record.resumption_id.copy_from_slice(resumption_id);
+        record.shared_secret.copy_from_slice(shared_secret);
+        if let Some(iter) = t.find_tag(4)?.confirm_array()?.iter() {
+            for (cat_id, t) in record.peer_cat_ids.iter_mut().zip(iter) {
+                *cat_id = t.u32()?;
+            }
+        }
+        Ok(record)
+    }
+}
+
+// The fabric index isn't encoded, the records are stored with their fabric
+impl ToTLV for ResumptionRecord {
+    fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        tw.start_struct(tag)?;
+        tw.str8(TagType::Context(1), &self.resumption_id)?;
+        tw.str8(TagType::Context(2), &self.shared_secret)?;
+        tw.u64(TagType::Context(3), self.peer_nodeid)?;
+        tw.start_array(TagType::Context(4))?;
+        for cat_id in self.peer_cat_ids {
+            tw.u32(TagType::Anonymous, cat_id)?;
+        }
+        tw.end_container()?;
+        tw.end_container()
+    }
+}
+
+// The records are persisted along with their fabrics, so the sessions can be resumed after
+// a reboot, and the records of a fabric are gone once the fabric is removed
+struct ResumptionStore {
+    fabric_mgr: Arc<FabricMgr>,
+    // The most recently established sessions are at the back
+    records: VecDeque<ResumptionRecord>,
+}
+
+impl ResumptionStore {
+    fn new(fabric_mgr: Arc<FabricMgr>) -> Self {
+        let mut store = Self {
+            fabric_mgr,
+            records: VecDeque::with_capacity(MAX_RESUMPTION_RECORDS),
+        };
+        for fab_idx in 1..MAX_SUPPORTED_FABRICS {
+            if let Err(e) = store.load(fab_idx) {
+                error!("Ignoring invalid stored resumption records: {:?}", e);
+            }
+        }
+        store
+    }
+
+    fn load(&mut self, fab_idx: usize) -> Result<(), Error> {
+        let mut buf = Vec::new();
+        if self
+            .fabric_mgr
+            .load_resumption(fab_idx as u8, &mut buf)
+            .is_err()
+        {
+            // Nothing stored yet
+            return Ok(());
+        }
+        let root = get_root_node(&buf)?;
+        if let Some(iter) = root.confirm_array()?.iter() {
+            for t in iter {
+                if self.records.len() < MAX_RESUMPTION_RECORDS {
+                    self.records
+                        .push_back(ResumptionRecord::from_tlv(&t, fab_idx)?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Failing to persist the records only means that the sessions can't be resumed after
+    // a reboot, so the errors are just logged
+    fn store(&self, fab_idx: usize) {
+        let mut buf = [0u8; MAX_RESUMPTION_TLV_LEN];
+        let buf_len = buf.len();
+        let mut wb = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut wb);
+        let result = tw
+            .start_array(TagType::Anonymous)
+            .and_then(|_| {
+                self.records
+                    .iter()
+                    .filter(|r| r.local_fabric_idx == fab_idx)
+                    .try_for_each(|r| r.to_tlv(&mut tw, TagType::Anonymous))
+            })
+            .and_then(|_| tw.end_container())
+            .and_then(|_| {
+                self.fabric_mgr
+                    .store_resumption(fab_idx as u8, wb.as_borrow_slice())
+            });
+        if let Err(e) = result {
+            error!("Error storing the resumption records: {:?}", e);
+        }
+    }
+
+    fn save(&mut self, record: ResumptionRecord) {
+        self.records.retain(|r| {
+            r.local_fabric_idx != record.local_fabric_idx || r.peer_nodeid != record.peer_nodeid
+        });
+        if self.records.len() >= MAX_RESUMPTION_RECORDS {
+            if let Some(evicted) = self.records.pop_front() {
+                if evicted.local_fabric_idx != record.local_fabric_idx {
+                    self.store(evicted.local_fabric_idx);
+                }
+            }
+        }
+        self.records.push_back(record);
+        self.store(record.local_fabric_idx);
+    }
+
+    fn remove(&mut self, local_fabric_idx: usize, peer_nodeid: u64) {
+        self.records
+            .retain(|r| r.local_fabric_idx != local_fabric_idx || r.peer_nodeid != peer_nodeid);
+        self.store(local_fabric_idx);
+    }
+
+    // The stored records were already removed along with the fabric
+    fn remove_fabric(&mut self, local_fabric_idx: usize) {
+        self.records
+            .retain(|r| r.local_fabric_idx != local_fabric_idx);
+    }
+
+    fn get_by_id(&self, resumption_id: &[u8]) -> Option<ResumptionRecord> {
+        self.records
+            .iter()
+            .find(|r| r.resumption_id == resumption_id)
+            .copied()
+    }
+
+    fn get_by_peer(&self, local_fabric_idx: usize, peer_nodeid: u64) -> Option<ResumptionRecord> {
+        self.records
+            .iter()
+            .find(|r| r.local_fabric_idx == local_fabric_idx && r.peer_nodeid == peer_nodeid)
+            .copied()
+    }
+}
+
+// The session that the responder adds, once the initiator confirms the resumption
+struct ResumedSession {
+    record: ResumptionRecord,
+    clone_data: CloneData,
+}
+
+// The state that only the initiator of the session maintains
+struct InitiatorCtx {
+    // The node that we are establishing the session with
+    peer_nodeid: u64,
+    // The CASE Authenticated Tags from the peer's NOC
+    peer_cat_ids: NocCatIds,
+    // Our ephemeral key pair, until the shared secret is derived
+    key_pair: Option<KeyPair>,
+    initiator_random: [u8; 32],
+    // The previous session with the peer, that we are trying to resume
+    resumption: Option<ResumptionRecord>,
+    resp_tx: Sender<Result<u16, Error>>,
+}
+
+impl InitiatorCtx {
+    fn fail(self, e: Error) {
+        error!("CASE session establishment failed: {:?}", e);
+        let _ = self.resp_tx.send(Err(e));
+    }
 }
 
 pub struct CaseSession {
@@ -36,6 +270,11 @@ pub struct CaseSession {
     our_pub_key: [u8; crypto::EC_POINT_LEN_BYTES],
     peer_pub_key: [u8; crypto::EC_POINT_LEN_BYTES],
     local_fabric_idx: usize,
+    resumption_id: [u8; RESUMPTION_ID_LEN],
+    // The session parameters that the peer asked for
+    peer_params: SessionParams,
+    initiator: Option<InitiatorCtx>,
+    resumed: Option<ResumedSession>,
 }
 impl CaseSession {
     pub fn new(peer_sessid: u16, local_sessid: u16) -> Result<Self, Error> {
@@ -48,17 +287,205 @@ impl CaseSession {
             our_pub_key: [0; crypto::EC_POINT_LEN_BYTES],
             peer_pub_key: [0; crypto::EC_POINT_LEN_BYTES],
             local_fabric_idx: 0,
+            resumption_id: [0; RESUMPTION_ID_LEN],
+            peer_params: Default::default(),
+            initiator: None,
+            resumed: None,
         })
     }
+
+    fn is_initiator(&self) -> bool {
+        matches!(self.state, State::Sigma1Tx | State::Sigma3Tx)
+    }
+}
+
+struct CaseReq {
+    fabric_idx: u8,
+    peer_nodeid: u64,
+    peer_addr: Address,
+    resp_tx: Sender<Result<u16, Error>>,
+}
+
+impl CaseReq {
+    fn fail(self, e: Error) {
+        error!("CASE session establishment failed: {:?}", e);
+        let _ = self.resp_tx.send(Err(e));
+    }
+}
+
+type ReqQueue = Arc<Mutex<VecDeque<CaseReq>>>;
+
+/// The handle that application code uses to establish CASE sessions with other nodes
+#[derive(Clone)]
+pub struct CaseClient {
+    queue: ReqQueue,
+}
+
+impl CaseClient {
+    /// Establish a CASE session with the node 'peer_nodeid', on our fabric with the index
+    /// 'fabric_idx', that is reachable at 'peer_addr'
+    ///
+    /// Returns the local session id of the new session, once the peer has accepted it.
+    pub fn establish(
+        &self,
+        fabric_idx: u8,
+        peer_nodeid: u64,
+        peer_addr: Address,
+    ) -> Result<u16, Error> {
+        let (resp_tx, resp_rx) = mpsc::channel();
+        {
+            let mut queue = self.queue.lock()?;
+            if queue.len() >= MAX_CASE_REQS {
+                return Err(Error::NoSpace);
+            }
+            queue.push_back(CaseReq {
+                fabric_idx,
+                peer_nodeid,
+                peer_addr,
+                resp_tx,
+            });
+        }
+        // The request is dropped, if its exchange is closed before completion
+        resp_rx.recv_timeout(CASE_TIMEOUT).map_err(|e| match e {
+            RecvTimeoutError::Timeout => Error::Timeout,
+            RecvTimeoutError::Disconnected => Error::NoExchange,
+        })?
+    }
 }
 
 pub struct Case {
     fabric_mgr: Arc<FabricMgr>,
+    queue: ReqQueue,
+    // The request that was selected to be sent out next
+    selected: Option<CaseReq>,
+    resumption: ResumptionStore,
+    // How far we have caught up with the fabrics that were removed
+    removed_cursor: usize,
 }
 
 impl Case {
     pub fn new(fabric_mgr: Arc<FabricMgr>) -> Self {
-        Self { fabric_mgr }
+        Self {
+            resumption: ResumptionStore::new(fabric_mgr.clone()),
+            fabric_mgr,
+            queue: Arc::new(Mutex::new(VecDeque::new())),
+            selected: None,
+            removed_cursor: 0,
+        }
+    }
+
+    // A session with a removed fabric must not be resumed
+    fn purge_removed_fabrics(&mut self) {
+        for fab_idx in self.fabric_mgr.get_removed_since(&mut self.removed_cursor) {
+            self.resumption.remove_fabric(fab_idx as usize);
+        }
+    }
+
+    pub fn get_client(&self) -> CaseClient {
+        CaseClient {
+            queue: self.queue.clone(),
+        }
+    }
+
+    /// Select the next queued request, and return the address of the peer to send
+    /// the Sigma1 to
+    pub fn get_pending_req(&mut self) -> Option<Address> {
+        if let Some(req) = self.selected.take() {
+            // The exchange couldn't be initiated for the previously selected request
+            req.fail(Error::NoSession);
+        }
+        let req = self.queue.lock().ok()?.pop_front()?;
+        let peer_addr = req.peer_addr;
+        self.selected = Some(req);
+        Some(peer_addr)
+    }
+
+    /// Encode the Sigma1 for the request selected in get_pending_req()
+    pub fn handle_pending_req(
+        &mut self,
+        exch_ctx: &mut ExchangeCtx,
+        proto_tx: &mut Packet,
+    ) -> Result<(), Error> {
+        let req = self.selected.take().ok_or(Error::InvalidState)?;
+        self.purge_removed_fabrics();
+        let mut initiator = InitiatorCtx {
+            peer_nodeid: req.peer_nodeid,
+            peer_cat_ids: Default::default(),
+            key_pair: None,
+            initiator_random: [0; 32],
+            resumption: self
+                .resumption
+                .get_by_peer(req.fabric_idx as usize, req.peer_nodeid),
+            resp_tx: req.resp_tx,
+        };
+        match self.write_sigma1(exch_ctx, proto_tx, req.fabric_idx, &mut initiator) {
+            Ok(mut case_session) => {
+                case_session.initiator = Some(initiator);
+                exch_ctx.exch.set_exchange_data(case_session);
+                Ok(())
+            }
+            Err(e) => {
+                initiator.fail(e);
+                Err(e)
+            }
+        }
+    }
+
+    fn write_sigma1(
+        &self,
+        exch_ctx: &mut ExchangeCtx,
+        proto_tx: &mut Packet,
+        fabric_idx: u8,
+        initiator: &mut InitiatorCtx,
+    ) -> Result<Box<CaseSession>, Error> {
+        if fabric_idx as usize >= MAX_SUPPORTED_FABRICS {
+            return Err(Error::Invalid);
+        }
+        let fabric = self.fabric_mgr.get_fabric(fabric_idx as usize)?;
+        let fabric = fabric.as_ref().as_ref().ok_or(Error::NotFound)?;
+
+        rand::thread_rng().fill_bytes(&mut initiator.initiator_random);
+        let mut dest_id = [0_u8; crypto::SHA256_HASH_LEN_BYTES];
+        fabric.get_dest_id(
+            &initiator.initiator_random,
+            initiator.peer_nodeid,
+            &mut dest_id,
+        )?;
+
+        let local_sessid = exch_ctx.sess.reserve_new_sess_id();
+        let mut case_session = Box::new(CaseSession::new(0, local_sessid)?);
+        case_session.state = State::Sigma1Tx;
+        case_session.local_fabric_idx = fabric_idx as usize;
+
+        // Create an ephemeral Key Pair
+        let key_pair = KeyPair::new()?;
+        let _ = key_pair.get_public_key(&mut case_session.our_pub_key)?;
+        initiator.key_pair = Some(key_pair);
+
+        let mut tw = TLVWriter::new(proto_tx.get_writebuf()?);
+        tw.start_struct(TagType::Anonymous)?;
+        tw.str8(TagType::Context(1), &initiator.initiator_random)?;
+        tw.u16(TagType::Context(2), local_sessid)?;
+        tw.str8(TagType::Context(3), &dest_id)?;
+        tw.str8(TagType::Context(4), &case_session.our_pub_key)?;
+        SessionParams::local().to_tlv(&mut tw, TagType::Context(5))?;
+        if let Some(record) = &initiator.resumption {
+            // The peer falls back to the full handshake, if it can't resume the session
+            let mut mic = [0_u8; crypto::AEAD_MIC_LEN_BYTES];
+            Case::get_resume_mic(
+                &SIGMA1_RESUME_INFO,
+                &SIGMA1_RESUME_NONCE,
+                &record.shared_secret,
+                &initiator.initiator_random,
+                &record.resumption_id,
+                &mut mic,
+            )?;
+            tw.str8(TagType::Context(6), &record.resumption_id)?;
+            tw.str8(TagType::Context(7), &mic)?;
+        }
+        tw.end_container()?;
+        case_session.tt_hash.update(proto_tx.as_borrow_slice())?;
+        Ok(case_session)
     }
 
     pub fn handle_casesigma3(&mut self, ctx: &mut ProtoCtx) -> Result<(), Error> {
@@ -88,7 +515,7 @@ impl Case {
         let root = get_root_node_struct(ctx.rx.as_borrow_slice())?;
         let encrypted = root.find_tag(1)?.slice()?;
 
-        let mut decrypted: [u8; 800] = [0; 800];
+        let mut decrypted: [u8; MAX_ENCRYPTED_SIZE] = [0; MAX_ENCRYPTED_SIZE];
         if encrypted.len() > decrypted.len() {
             error!("Data too large");
             return Err(Error::NoSpace);
@@ -103,8 +530,8 @@ impl Case {
         let d = Sigma3Decrypt::from_tlv(&root)?;
 
         let initiator_noc = Cert::new(d.initiator_noc.0)?;
-        let initiator_icac = Cert::new(d.initiator_icac.0)?;
-        if let Err(e) = Case::validate_certs(fabric, &initiator_noc, &initiator_icac) {
+        let initiator_icac = d.initiator_icac.map(|icac| Cert::new(icac.0)).transpose()?;
+        if let Err(e) = Case::validate_certs(fabric, &initiator_noc, initiator_icac.as_ref()) {
             error!("Certificate Chain doesn't match: {}", e);
             common::create_sc_status_report(
                 &mut ctx.tx,
@@ -115,9 +542,9 @@ impl Case {
             return Ok(());
         }
 
-        if Case::validate_sigma3_sign(
+        if Case::validate_tbs_sign(
             d.initiator_noc.0,
-            d.initiator_icac.0,
+            d.initiator_icac.map(|icac| icac.0),
             &initiator_noc,
             d.signature.0,
             &case_session,
@@ -136,15 +563,32 @@ impl Case {
 
         // Only now do we add this message to the TT Hash
         case_session.tt_hash.update(ctx.rx.as_borrow_slice())?;
-        let clone_data = Case::get_session_clone_data(
+        let mut session_keys = [0_u8; 3 * crypto::SYMM_KEY_LEN_BYTES];
+        Case::get_session_keys(
             fabric.ipk.op_key(),
+            &case_session.tt_hash,
+            &case_session.shared_secret,
+            &mut session_keys,
+        )?;
+        let peer_nodeid = initiator_noc.get_node_id()?;
+        let mut clone_data = Case::get_session_clone_data(
+            &session_keys,
             fabric.get_node_id(),
-            initiator_noc.get_node_id()?,
+            peer_nodeid,
             ctx.exch_ctx.sess.get_peer_addr(),
             &case_session,
-        )?;
+        );
+        initiator_noc.get_cat_ids(&mut clone_data.peer_cat_ids);
+        let record = ResumptionRecord {
+            resumption_id: case_session.resumption_id,
+            shared_secret: case_session.shared_secret,
+            local_fabric_idx: case_session.local_fabric_idx,
+            peer_nodeid,
+            peer_cat_ids: clone_data.peer_cat_ids,
+        };
         // Queue a transport mgr request to add a new session
         WorkQ::get()?.sync_send(Msg::NewSession(clone_data))?;
+        self.resumption.save(record);
 
         common::create_sc_status_report(
             &mut ctx.tx,
@@ -162,6 +606,23 @@ impl Case {
         let root = get_root_node_struct(rx_buf)?;
         let r = Sigma1Req::from_tlv(&root)?;
 
+        self.purge_removed_fabrics();
+        if let (Some(resumption_id), Some(resume_mic)) = (r.resumption_id, r.initiator_resume_mic) {
+            match self.resumption.get_by_id(resumption_id.0) {
+                Some(record)
+                    if Case::validate_sigma1_resume(
+                        &record,
+                        r.initiator_random.0,
+                        resume_mic.0,
+                    )
+                    .is_ok() =>
+                {
+                    return self.write_sigma2_resume(&mut ctx.exch_ctx, &mut ctx.tx, &r, record);
+                }
+                _ => info!("Can't resume the session, continuing with the full handshake"),
+            }
+        }
+
         let local_fabric_idx = self
             .fabric_mgr
             .match_dest_id(r.initiator_random.0, r.dest_id.0);
@@ -180,6 +641,10 @@ impl Case {
         let mut case_session = Box::new(CaseSession::new(r.initiator_sessid, local_sessid)?);
         case_session.tt_hash.update(rx_buf)?;
         case_session.local_fabric_idx = local_fabric_idx?;
+        case_session.peer_params = r.session_params.unwrap_or_default();
+        ctx.exch_ctx
+            .sess
+            .set_peer_mrp_params(case_session.peer_params.mrp_params());
         if r.peer_pub_key.0.len() != crypto::EC_POINT_LEN_BYTES {
             error!("Invalid public key length");
             return Err(Error::Invalid);
@@ -206,8 +671,6 @@ impl Case {
         rand::thread_rng().fill_bytes(&mut our_random);
 
         // Derive the Encrypted Part
-        const MAX_ENCRYPTED_SIZE: usize = 800;
-
         let mut encrypted: [u8; MAX_ENCRYPTED_SIZE] = [0; MAX_ENCRYPTED_SIZE];
         let encrypted_len = {
             let mut signature = [0u8; crypto::EC_SIGNATURE_LEN_BYTES];
@@ -222,8 +685,10 @@ impl Case {
                 return Ok(());
             }
 
-            let sign_len = Case::get_sigma2_sign(
-                &fabric,
+            // We are guaranteed this unwrap will work
+            let fabric = fabric.as_ref().as_ref().unwrap();
+            let sign_len = Case::get_tbs_sign(
+                fabric,
                 &case_session.our_pub_key,
                 &case_session.peer_pub_key,
                 &mut signature,
@@ -231,7 +696,8 @@ impl Case {
             let signature = &signature[..sign_len];
 
             Case::get_sigma2_encryption(
-                &fabric,
+                fabric.ipk.op_key(),
+                fabric,
                 &our_random,
                 &mut case_session,
                 signature,
@@ -247,136 +713,606 @@ impl Case {
         tw.u16(TagType::Context(2), local_sessid)?;
         tw.str8(TagType::Context(3), &case_session.our_pub_key)?;
         tw.str16(TagType::Context(4), encrypted)?;
+        SessionParams::local().to_tlv(&mut tw, TagType::Context(5))?;
         tw.end_container()?;
         case_session.tt_hash.update(ctx.tx.as_borrow_slice())?;
         ctx.exch_ctx.exch.set_exchange_data(case_session);
         Ok(())
     }
 
-    fn get_session_clone_data(
-        ipk: &[u8],
-        local_nodeid: u64,
-        peer_nodeid: u64,
-        peer_addr: Address,
-        case_session: &CaseSession,
-    ) -> Result<CloneData, Error> {
+    fn validate_sigma1_resume(
+        record: &ResumptionRecord,
+        initiator_random: &[u8],
+        resume_mic: &[u8],
+    ) -> Result<(), Error> {
+        let mut mic = [0_u8; crypto::AEAD_MIC_LEN_BYTES];
+        Case::get_resume_mic(
+            &SIGMA1_RESUME_INFO,
+            &SIGMA1_RESUME_NONCE,
+            &record.shared_secret,
+            initiator_random,
+            &record.resumption_id,
+            &mut mic,
+        )?;
+        if mic.as_slice() == resume_mic {
+            Ok(())
+        } else {
+            error!("Sigma1 Resume MIC doesn't match");
+            Err(Error::Invalid)
+        }
+    }
+
+    fn write_sigma2_resume(
+        &self,
+        exch_ctx: &mut ExchangeCtx,
+        proto_tx: &mut Packet,
+        sigma1: &Sigma1Req,
+        mut record: ResumptionRecord,
+    ) -> Result<(), Error> {
+        let fabric = self.fabric_mgr.get_fabric(record.local_fabric_idx)?;
+        let fabric = fabric.as_ref().as_ref().ok_or(Error::NotFound)?;
+        let initiator_random = sigma1.initiator_random.0;
+
+        let local_sessid = exch_ctx.sess.reserve_new_sess_id();
+        let mut case_session = Box::new(CaseSession::new(sigma1.initiator_sessid, local_sessid)?);
+        case_session.state = State::Sigma2ResumeTx;
+        case_session.local_fabric_idx = record.local_fabric_idx;
+        case_session.peer_params = sigma1.session_params.unwrap_or_default();
+        exch_ctx
+            .sess
+            .set_peer_mrp_params(case_session.peer_params.mrp_params());
+
+        // The resumed session gets a new resumption id
+        rand::thread_rng().fill_bytes(&mut record.resumption_id);
+        let mut mic = [0_u8; crypto::AEAD_MIC_LEN_BYTES];
+        Case::get_resume_mic(
+            &SIGMA2_RESUME_INFO,
+            &SIGMA2_RESUME_NONCE,
+            &record.shared_secret,
+            initiator_random,
+            &record.resumption_id,
+            &mut mic,
+        )?;
+
         let mut session_keys = [0_u8; 3 * crypto::SYMM_KEY_LEN_BYTES];
-        Case::get_session_keys(
-            ipk,
-            &case_session.tt_hash,
-            &case_session.shared_secret,
+        Case::get_resumption_session_keys(
+            &record.shared_secret,
+            initiator_random,
+            &record.resumption_id,
             &mut session_keys,
         )?;
-
-        let mut clone_data = CloneData::new(
-            local_nodeid,
-            peer_nodeid,
-            case_session.peer_sessid,
-            case_session.local_sessid,
-            peer_addr,
-            SessionMode::Case(case_session.local_fabric_idx as u8),
+        let mut clone_data = Case::get_session_clone_data(
+            &session_keys,
+            fabric.get_node_id(),
+            record.peer_nodeid,
+            exch_ctx.sess.get_peer_addr(),
+            &case_session,
         );
+        clone_data.peer_cat_ids = record.peer_cat_ids;
 
-        clone_data.dec_key.copy_from_slice(&session_keys[0..16]);
-        clone_data.enc_key.copy_from_slice(&session_keys[16..32]);
-        clone_data
-            .att_challenge
-            .copy_from_slice(&session_keys[32..48]);
-        Ok(clone_data)
-    }
-
-    fn validate_sigma3_sign(
-        initiator_noc: &[u8],
-        initiator_icac: &[u8],
-        initiator_noc_cert: &Cert,
-        sign: &[u8],
-        case_session: &CaseSession,
-    ) -> Result<(), Error> {
-        const MAX_TBS_SIZE: usize = 800;
-        let mut buf: [u8; MAX_TBS_SIZE] = [0; MAX_TBS_SIZE];
-        let mut write_buf = WriteBuf::new(&mut buf, MAX_TBS_SIZE);
-        let mut tw = TLVWriter::new(&mut write_buf);
+        proto_tx.set_proto_opcode(OpCode::CASESigma2Resume as u8);
+        let mut tw = TLVWriter::new(proto_tx.get_writebuf()?);
         tw.start_struct(TagType::Anonymous)?;
-        tw.str8(TagType::Context(1), initiator_noc)?;
-        tw.str8(TagType::Context(2), initiator_icac)?;
-        tw.str8(TagType::Context(3), &case_session.peer_pub_key)?;
-        tw.str8(TagType::Context(4), &case_session.our_pub_key)?;
+        tw.str8(TagType::Context(1), &record.resumption_id)?;
+        tw.str8(TagType::Context(2), &mic)?;
+        tw.u16(TagType::Context(3), local_sessid)?;
+        SessionParams::local().to_tlv(&mut tw, TagType::Context(4))?;
         tw.end_container()?;
 
-        let key = KeyPair::new_from_public(initiator_noc_cert.get_pubkey())?;
-        key.verify_msg(write_buf.as_slice(), sign)?;
+        // The session is added once the initiator confirms it
+        case_session.resumed = Some(ResumedSession { record, clone_data });
+        exch_ctx.exch.set_exchange_data(case_session);
         Ok(())
     }
 
-    fn validate_certs(fabric: &Fabric, noc: &Cert, icac: &Cert) -> Result<(), Error> {
-        if let Ok(fid) = icac.get_fabric_id() {
-            if fid != fabric.get_fabric_id() {
-                return Err(Error::Invalid);
-            }
-        }
-
-        if fabric.get_fabric_id() != noc.get_fabric_id()? {
+    pub fn handle_casesigma2(&mut self, ctx: &mut ProtoCtx) -> Result<(), Error> {
+        let mut case_session = ctx
+            .exch_ctx
+            .exch
+            .take_exchange_data::<CaseSession>()
+            .ok_or(Error::InvalidState)?;
+        if case_session.state != State::Sigma1Tx {
             return Err(Error::Invalid);
         }
+        let mut initiator = case_session.initiator.take().ok_or(Error::InvalidState)?;
 
-        noc.verify_chain_start()
-            .add_cert(icac)?
-            .add_cert(&fabric.root_ca)?
-            .finalise()?;
-
+        match self.process_sigma2(ctx, &mut case_session, &mut initiator) {
+            Ok(()) => {
+                case_session.state = State::Sigma3Tx;
+                case_session.initiator = Some(initiator);
+                ctx.exch_ctx.exch.set_exchange_data(case_session);
+            }
+            Err(e) => {
+                initiator.fail(e);
+                common::create_sc_status_report(
+                    &mut ctx.tx,
+                    common::SCStatusCodes::InvalidParameter,
+                    None,
+                )?;
+                ctx.exch_ctx.exch.close();
+            }
+        }
         Ok(())
     }
 
-    fn get_session_keys(
-        ipk: &[u8],
-        tt: &Sha256,
-        shared_secret: &[u8],
-        key: &mut [u8],
+    // Validate the responder's Sigma2, and write our Sigma3
+    fn process_sigma2(
+        &self,
+        ctx: &mut ProtoCtx,
+        case_session: &mut CaseSession,
+        initiator: &mut InitiatorCtx,
     ) -> Result<(), Error> {
-        const SEKEYS_INFO: [u8; 11] = [
-            0x53, 0x65, 0x73, 0x73, 0x69, 0x6f, 0x6e, 0x4b, 0x65, 0x79, 0x73,
-        ];
-        if key.len() < 48 {
-            return Err(Error::NoSpace);
+        let fabric = self.fabric_mgr.get_fabric(case_session.local_fabric_idx)?;
+        let fabric = fabric.as_ref().as_ref().ok_or(Error::NotFound)?;
+
+        let rx_buf = ctx.rx.as_borrow_slice();
+        let root = get_root_node_struct(rx_buf)?;
+        let r = Sigma2Resp::from_tlv(&root)?;
+        if r.responder_pub_key.0.len() != crypto::EC_POINT_LEN_BYTES {
+            error!("Invalid public key length");
+            return Err(Error::Invalid);
         }
-        let mut salt = Vec::<u8>::with_capacity(256);
-        salt.extend_from_slice(ipk);
-        let tt = tt.clone();
-        let mut tt_hash = [0u8; crypto::SHA256_HASH_LEN_BYTES];
-        tt.finish(&mut tt_hash)?;
-        salt.extend_from_slice(&tt_hash);
-        //        println!("Session Key: salt: {:x?}, len: {}", salt, salt.len());
+        case_session
+            .peer_pub_key
+            .copy_from_slice(r.responder_pub_key.0);
+        case_session.peer_sessid = r.responder_sessid;
+        case_session.peer_params = r.responder_session_params.unwrap_or_default();
+        ctx.exch_ctx
+            .sess
+            .set_peer_mrp_params(case_session.peer_params.mrp_params());
 
-        crypto::hkdf_sha256(salt.as_slice(), shared_secret, &SEKEYS_INFO, key)
-            .map_err(|_x| Error::NoSpace)?;
-        //        println!("Session Key: key: {:x?}", key);
+        // Derive the Shared Secret
+        let key_pair = initiator.key_pair.take().ok_or(Error::InvalidState)?;
+        let len = key_pair.derive_secret(r.responder_pub_key.0, &mut case_session.shared_secret)?;
+        if len != 32 {
+            error!("Derived secret length incorrect");
+            return Err(Error::Invalid);
+        }
 
-        Ok(())
-    }
+        let encrypted = r.encrypted.0;
+        let mut decrypted: [u8; MAX_ENCRYPTED_SIZE] = [0; MAX_ENCRYPTED_SIZE];
+        if encrypted.len() > decrypted.len() {
+            error!("Data too large");
+            return Err(Error::NoSpace);
+        }
+        let decrypted = &mut decrypted[..encrypted.len()];
+        decrypted.copy_from_slice(encrypted);
 
-    fn get_sigma3_decryption(
-        ipk: &[u8],
-        case_session: &CaseSession,
-        encrypted: &mut [u8],
-    ) -> Result<usize, Error> {
-        let mut sigma3_key = [0_u8; crypto::SYMM_KEY_LEN_BYTES];
-        Case::get_sigma3_key(
-            ipk,
-            &case_session.tt_hash,
-            &case_session.shared_secret,
-            &mut sigma3_key,
+        let len = Case::get_sigma2_decryption(
+            fabric.ipk.op_key(),
+            r.responder_random.0,
+            case_session,
+            decrypted,
         )?;
-        // println!("Sigma3 Key: {:x?}", sigma3_key);
+        let decrypted = &decrypted[..len];
 
-        let nonce: [u8; 13] = [
-            0x4e, 0x43, 0x41, 0x53, 0x45, 0x5f, 0x53, 0x69, 0x67, 0x6d, 0x61, 0x33, 0x4e,
-        ];
+        let root = get_root_node_struct(decrypted)?;
+        let d = Sigma2Decrypt::from_tlv(&root)?;
 
-        let encrypted_len = encrypted.len();
-        crypto::decrypt_in_place(&sigma3_key, &nonce, &[], encrypted)?;
+        let responder_noc = Cert::new(d.responder_noc.0)?;
+        let responder_icac = d.responder_icac.map(|icac| Cert::new(icac.0)).transpose()?;
+        if let Err(e) = Case::validate_certs(fabric, &responder_noc, responder_icac.as_ref()) {
+            error!("Certificate Chain doesn't match: {}", e);
+            return Err(e);
+        }
+        if responder_noc.get_node_id()? != initiator.peer_nodeid {
+            error!("Responder isn't the node we wanted to reach");
+            return Err(Error::Invalid);
+        }
+
+        if let Err(e) = Case::validate_tbs_sign(
+            d.responder_noc.0,
+            d.responder_icac.map(|icac| icac.0),
+            &responder_noc,
+            d.signature.0,
+            case_session,
+        ) {
+            error!("Sigma2 Signature doesn't match");
+            return Err(e);
+        }
+        responder_noc.get_cat_ids(&mut initiator.peer_cat_ids);
+        if d.resumption_id.0.len() != RESUMPTION_ID_LEN {
+            error!("Invalid resumption id length");
+            return Err(Error::Invalid);
+        }
+        case_session
+            .resumption_id
+            .copy_from_slice(d.resumption_id.0);
+
+        // Only now do we add this message to the TT Hash
+        case_session.tt_hash.update(rx_buf)?;
+
+        // Derive the Encrypted Part
+        let mut encrypted: [u8; MAX_ENCRYPTED_SIZE] = [0; MAX_ENCRYPTED_SIZE];
+        let encrypted_len = {
+            let mut signature = [0u8; crypto::EC_SIGNATURE_LEN_BYTES];
+            let sign_len = Case::get_tbs_sign(
+                fabric,
+                &case_session.our_pub_key,
+                &case_session.peer_pub_key,
+                &mut signature,
+            )?;
+            let signature = &signature[..sign_len];
+
+            Case::get_sigma3_encryption(fabric, case_session, signature, &mut encrypted)?
+        };
+        let encrypted = &encrypted[0..encrypted_len];
+
+        // Generate our Sigma3
+        let mut tw = TLVWriter::new(ctx.tx.get_writebuf()?);
+        tw.start_struct(TagType::Anonymous)?;
+        tw.str16(TagType::Context(1), encrypted)?;
+        tw.end_container()?;
+        case_session.tt_hash.update(ctx.tx.as_borrow_slice())?;
+        Ok(())
+    }
+
+    pub fn handle_casesigma2_resume(&mut self, ctx: &mut ProtoCtx) -> Result<(), Error> {
+        let mut case_session = ctx
+            .exch_ctx
+            .exch
+            .take_exchange_data::<CaseSession>()
+            .ok_or(Error::InvalidState)?;
+        if case_session.state != State::Sigma1Tx {
+            return Err(Error::Invalid);
+        }
+        let initiator = case_session.initiator.take().ok_or(Error::InvalidState)?;
+        ctx.exch_ctx.exch.close();
+
+        match self.process_sigma2_resume(ctx, &mut case_session, &initiator) {
+            Ok(record) => {
+                common::create_sc_status_report(
+                    &mut ctx.tx,
+                    SCStatusCodes::SessionEstablishmentSuccess,
+                    None,
+                )?;
+                self.resumption.save(record);
+                let _ = initiator.resp_tx.send(Ok(case_session.local_sessid));
+            }
+            Err(e) => {
+                // The next attempt will use the full handshake
+                self.resumption
+                    .remove(case_session.local_fabric_idx, initiator.peer_nodeid);
+                common::create_sc_status_report(
+                    &mut ctx.tx,
+                    common::SCStatusCodes::InvalidParameter,
+                    None,
+                )?;
+                initiator.fail(e);
+            }
+        }
+        Ok(())
+    }
+
+    // Validate the responder's Sigma2_Resume, and add the resumed session
+    fn process_sigma2_resume(
+        &self,
+        ctx: &mut ProtoCtx,
+        case_session: &mut CaseSession,
+        initiator: &InitiatorCtx,
+    ) -> Result<ResumptionRecord, Error> {
+        // The responder can't resume a session that we didn't ask to resume
+        let mut record = initiator.resumption.ok_or(Error::Invalid)?;
+
+        let root = get_root_node_struct(ctx.rx.as_borrow_slice())?;
+        let r = Sigma2ResumeResp::from_tlv(&root)?;
+        if r.resumption_id.0.len() != RESUMPTION_ID_LEN {
+            error!("Invalid resumption id length");
+            return Err(Error::Invalid);
+        }
+        record.resumption_id.copy_from_slice(r.resumption_id.0);
+        case_session.peer_sessid = r.responder_sessid;
+        case_session.peer_params = r.responder_session_params.unwrap_or_default();
+        ctx.exch_ctx
+            .sess
+            .set_peer_mrp_params(case_session.peer_params.mrp_params());
+
+        let mut mic = [0_u8; crypto::AEAD_MIC_LEN_BYTES];
+        Case::get_resume_mic(
+            &SIGMA2_RESUME_INFO,
+            &SIGMA2_RESUME_NONCE,
+            &record.shared_secret,
+            &initiator.initiator_random,
+            &record.resumption_id,
+            &mut mic,
+        )?;
+        if mic.as_slice() != r.resume_mic.0 {
+            error!("Sigma2 Resume MIC doesn't match");
+            return Err(Error::Invalid);
+        }
+
+        let mut session_keys = [0_u8; 3 * crypto::SYMM_KEY_LEN_BYTES];
+        Case::get_resumption_session_keys(
+            &record.shared_secret,
+            &initiator.initiator_random,
+            &record.resumption_id,
+            &mut session_keys,
+        )?;
+        let fabric = self.fabric_mgr.get_fabric(case_session.local_fabric_idx)?;
+        let fabric = fabric.as_ref().as_ref().ok_or(Error::NotFound)?;
+        let mut clone_data = Case::get_session_clone_data(
+            &session_keys,
+            fabric.get_node_id(),
+            record.peer_nodeid,
+            ctx.exch_ctx.sess.get_peer_addr(),
+            case_session,
+        );
+        clone_data.peer_cat_ids = record.peer_cat_ids;
+        // Queue a transport mgr request to add a new session
+        WorkQ::get()?.sync_send(Msg::NewSession(clone_data))?;
+        Ok(record)
+    }
+
+    pub fn handle_status_report(&mut self, ctx: &mut ProtoCtx) -> Result<(), Error> {
+        let report = status_report::parse_status_report(ctx.rx.as_borrow_slice())?;
+        let mut case_session = match ctx.exch_ctx.exch.take_exchange_data::<CaseSession>() {
+            Some(c) => c,
+            None => {
+                info!(
+                    "Status Report outside of session establishment: {:?}",
+                    report
+                );
+                return Ok(());
+            }
+        };
+        ctx.exch_ctx.exch.close();
+
+        if let Some(resumed) = case_session.resumed.take() {
+            if report.is_success() {
+                // Queue a transport mgr request to add a new session
+                WorkQ::get()?.sync_send(Msg::NewSession(resumed.clone_data))?;
+                self.resumption.save(resumed.record);
+            } else {
+                error!("Peer rejected the resumed session: {:?}", report);
+            }
+            return Ok(());
+        }
+
+        let initiator = case_session.initiator.take().ok_or(Error::InvalidState)?;
+        if !report.is_success() {
+            error!("Peer rejected the session: {:?}", report);
+            initiator.fail(Error::Invalid);
+            return Ok(());
+        }
+        if case_session.state != State::Sigma3Tx {
+            initiator.fail(Error::InvalidState);
+            return Ok(());
+        }
+
+        let peer_addr = ctx.exch_ctx.sess.get_peer_addr();
+        match self.add_initiator_session(&case_session, &initiator, peer_addr) {
+            Ok(()) => {
+                self.resumption.save(ResumptionRecord {
+                    resumption_id: case_session.resumption_id,
+                    shared_secret: case_session.shared_secret,
+                    local_fabric_idx: case_session.local_fabric_idx,
+                    peer_nodeid: initiator.peer_nodeid,
+                    peer_cat_ids: initiator.peer_cat_ids,
+                });
+                let _ = initiator.resp_tx.send(Ok(case_session.local_sessid));
+            }
+            Err(e) => initiator.fail(e),
+        }
+        Ok(())
+    }
+
+    fn add_initiator_session(
+        &self,
+        case_session: &CaseSession,
+        initiator: &InitiatorCtx,
+        peer_addr: Address,
+    ) -> Result<(), Error> {
+        let fabric = self.fabric_mgr.get_fabric(case_session.local_fabric_idx)?;
+        let fabric = fabric.as_ref().as_ref().ok_or(Error::NotFound)?;
+        let mut session_keys = [0_u8; 3 * crypto::SYMM_KEY_LEN_BYTES];
+        Case::get_session_keys(
+            fabric.ipk.op_key(),
+            &case_session.tt_hash,
+            &case_session.shared_secret,
+            &mut session_keys,
+        )?;
+        let mut clone_data = Case::get_session_clone_data(
+            &session_keys,
+            fabric.get_node_id(),
+            initiator.peer_nodeid,
+            peer_addr,
+            case_session,
+        );
+        clone_data.peer_cat_ids = initiator.peer_cat_ids;
+        // Queue a transport mgr request to add a new session
+        WorkQ::get()?.sync_send(Msg::NewSession(clone_data))
+    }
+
+    fn get_session_clone_data(
+        session_keys: &[u8],
+        local_nodeid: u64,
+        peer_nodeid: u64,
+        peer_addr: Address,
+        case_session: &CaseSession,
+    ) -> CloneData {
+        let mut clone_data = CloneData::new(
+            local_nodeid,
+            peer_nodeid,
+            case_session.peer_sessid,
+            case_session.local_sessid,
+            peer_addr,
+            SessionMode::Case(case_session.local_fabric_idx as u8),
+        );
+
+        // The I2R key is the initiator's encryption key, and the responder's decryption key
+        let (i2r_key, r2i_key) = (&session_keys[0..16], &session_keys[16..32]);
+        if case_session.is_initiator() {
+            clone_data.enc_key.copy_from_slice(i2r_key);
+            clone_data.dec_key.copy_from_slice(r2i_key);
+        } else {
+            clone_data.dec_key.copy_from_slice(i2r_key);
+            clone_data.enc_key.copy_from_slice(r2i_key);
+        }
+        clone_data
+            .att_challenge
+            .copy_from_slice(&session_keys[32..48]);
+        clone_data.peer_mrp = case_session.peer_params.mrp_params();
+        clone_data
+    }
+
+    // Both Sigma2 and Sigma3 sign the sender's certificates, followed by the sender's and
+    // the receiver's ephemeral public keys
+    fn validate_tbs_sign(
+        peer_noc: &[u8],
+        peer_icac: Option<&[u8]>,
+        peer_noc_cert: &Cert,
+        sign: &[u8],
+        case_session: &CaseSession,
+    ) -> Result<(), Error> {
+        const MAX_TBS_SIZE: usize = 800;
+        let mut buf: [u8; MAX_TBS_SIZE] = [0; MAX_TBS_SIZE];
+        let mut write_buf = WriteBuf::new(&mut buf, MAX_TBS_SIZE);
+        let mut tw = TLVWriter::new(&mut write_buf);
+        tw.start_struct(TagType::Anonymous)?;
+        tw.str8(TagType::Context(1), peer_noc)?;
+        if let Some(peer_icac) = peer_icac {
+            tw.str8(TagType::Context(2), peer_icac)?;
+        }
+        tw.str8(TagType::Context(3), &case_session.peer_pub_key)?;
+        tw.str8(TagType::Context(4), &case_session.our_pub_key)?;
+        tw.end_container()?;
+
+        let key = KeyPair::new_from_public(peer_noc_cert.get_pubkey())?;
+        key.verify_msg(write_buf.as_slice(), sign)?;
+        Ok(())
+    }
+
+    fn validate_certs(fabric: &Fabric, noc: &Cert, icac: Option<&Cert>) -> Result<(), Error> {
+        if fabric.get_fabric_id() != noc.get_fabric_id()? {
+            return Err(Error::Invalid);
+        }
+        Fabric::validate_chain(&fabric.root_ca, icac, noc)
+    }
+
+    fn get_session_keys(
+        ipk: &[u8],
+        tt: &Sha256,
+        shared_secret: &[u8],
+        key: &mut [u8],
+    ) -> Result<(), Error> {
+        const SEKEYS_INFO: [u8; 11] = [
+            0x53, 0x65, 0x73, 0x73, 0x69, 0x6f, 0x6e, 0x4b, 0x65, 0x79, 0x73,
+        ];
+        if key.len() < 48 {
+            return Err(Error::NoSpace);
+        }
+        let mut salt = Vec::<u8>::with_capacity(256);
+        salt.extend_from_slice(ipk);
+        let tt = tt.clone();
+        let mut tt_hash = [0u8; crypto::SHA256_HASH_LEN_BYTES];
+        tt.finish(&mut tt_hash)?;
+        salt.extend_from_slice(&tt_hash);
+        //        println!("Session Key: salt: {:x?}, len: {}", salt, salt.len());
+
+        crypto::hkdf_sha256(salt.as_slice(), shared_secret, &SEKEYS_INFO, key)
+            .map_err(|_x| Error::NoSpace)?;
+        //        println!("Session Key: key: {:x?}", key);
+
+        Ok(())
+    }
+
+    fn get_resumption_session_keys(
+        shared_secret: &[u8],
+        initiator_random: &[u8],
+        resumption_id: &[u8],
+        key: &mut [u8],
+    ) -> Result<(), Error> {
+        const SERKEYS_INFO: [u8; 21] = *b"SessionResumptionKeys";
+        if key.len() < 48 {
+            return Err(Error::NoSpace);
+        }
+        let mut salt = Vec::<u8>::with_capacity(64);
+        salt.extend_from_slice(initiator_random);
+        salt.extend_from_slice(resumption_id);
+
+        crypto::hkdf_sha256(salt.as_slice(), shared_secret, &SERKEYS_INFO, key)
+            .map_err(|_x| Error::NoSpace)
+    }
+
+    // The Resume MIC is the tag of an empty message, encrypted with a key that is derived
+    // from the shared secret of the session that is being resumed
+    fn get_resume_mic(
+        info: &[u8],
+        nonce: &[u8],
+        shared_secret: &[u8],
+        initiator_random: &[u8],
+        resumption_id: &[u8],
+        mic: &mut [u8],
+    ) -> Result<(), Error> {
+        let mut salt = Vec::<u8>::with_capacity(64);
+        salt.extend_from_slice(initiator_random);
+        salt.extend_from_slice(resumption_id);
+        let mut key = [0_u8; crypto::SYMM_KEY_LEN_BYTES];
+        crypto::hkdf_sha256(salt.as_slice(), shared_secret, info, &mut key)
+            .map_err(|_x| Error::NoSpace)?;
+
+        let mut tag = [0_u8; crypto::AEAD_MIC_LEN_BYTES];
+        crypto::encrypt_in_place(&key, nonce, &[], &mut tag, 0)?;
+        mic.copy_from_slice(&tag);
+        Ok(())
+    }
+
+    fn get_sigma3_decryption(
+        ipk: &[u8],
+        case_session: &CaseSession,
+        encrypted: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mut sigma3_key = [0_u8; crypto::SYMM_KEY_LEN_BYTES];
+        Case::get_sigma3_key(
+            ipk,
+            &case_session.tt_hash,
+            &case_session.shared_secret,
+            &mut sigma3_key,
+        )?;
+        // println!("Sigma3 Key: {:x?}", sigma3_key);
+
+        let encrypted_len = encrypted.len();
+        crypto::decrypt_in_place(&sigma3_key, &SIGMA3_NONCE, &[], encrypted)?;
         Ok(encrypted_len - crypto::AEAD_MIC_LEN_BYTES)
     }
 
+    fn get_sigma3_encryption(
+        fabric: &Fabric,
+        case_session: &CaseSession,
+        signature: &[u8],
+        out: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mut sigma3_key = [0_u8; crypto::SYMM_KEY_LEN_BYTES];
+        Case::get_sigma3_key(
+            fabric.ipk.op_key(),
+            &case_session.tt_hash,
+            &case_session.shared_secret,
+            &mut sigma3_key,
+        )?;
+
+        let mut write_buf = WriteBuf::new(out, out.len());
+        let mut tw = TLVWriter::new(&mut write_buf);
+        tw.start_struct(TagType::Anonymous)?;
+        tw.str16_as(TagType::Context(1), |buf| fabric.noc.as_tlv(buf))?;
+        if let Some(icac) = &fabric.icac {
+            tw.str16_as(TagType::Context(2), |buf| icac.as_tlv(buf))?;
+        }
+        tw.str8(TagType::Context(3), signature)?;
+        tw.end_container()?;
+        let tag = [0u8; crypto::AEAD_MIC_LEN_BYTES];
+        write_buf.append(&tag)?;
+        let cipher_text = write_buf.as_mut_slice();
+
+        crypto::encrypt_in_place(
+            &sigma3_key,
+            &SIGMA3_NONCE,
+            &[],
+            cipher_text,
+            cipher_text.len() - crypto::AEAD_MIC_LEN_BYTES,
+        )?;
+        Ok(write_buf.as_slice().len())
+    }
+
     fn get_sigma3_key(
         ipk: &[u8],
         tt: &Sha256,
@@ -406,8 +1342,9 @@ impl Case {
 
     fn get_sigma2_key(
         ipk: &[u8],
-        our_random: &[u8],
-        case_session: &mut CaseSession,
+        responder_random: &[u8],
+        responder_pub_key: &[u8],
+        case_session: &CaseSession,
         key: &mut [u8],
     ) -> Result<(), Error> {
         const S2K_INFO: [u8; 6] = [0x53, 0x69, 0x67, 0x6d, 0x61, 0x32];
@@ -416,8 +1353,8 @@ impl Case {
         }
         let mut salt = Vec::<u8>::with_capacity(256);
         salt.extend_from_slice(ipk);
-        salt.extend_from_slice(our_random);
-        salt.extend_from_slice(&case_session.our_pub_key);
+        salt.extend_from_slice(responder_random);
+        salt.extend_from_slice(responder_pub_key);
 
         let tt = case_session.tt_hash.clone();
 
@@ -433,23 +1370,41 @@ impl Case {
         Ok(())
     }
 
+    fn get_sigma2_decryption(
+        ipk: &[u8],
+        responder_random: &[u8],
+        case_session: &CaseSession,
+        encrypted: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mut sigma2_key = [0_u8; crypto::SYMM_KEY_LEN_BYTES];
+        Case::get_sigma2_key(
+            ipk,
+            responder_random,
+            &case_session.peer_pub_key,
+            case_session,
+            &mut sigma2_key,
+        )?;
+
+        let encrypted_len = encrypted.len();
+        crypto::decrypt_in_place(&sigma2_key, &SIGMA2_NONCE, &[], encrypted)?;
+        Ok(encrypted_len - crypto::AEAD_MIC_LEN_BYTES)
+    }
+
     fn get_sigma2_encryption(
-        fabric: &RwLockReadGuardRef<FabricMgrInner, Option<Fabric>>,
+        ipk: &[u8],
+        fabric: &Fabric,
         our_random: &[u8],
         case_session: &mut CaseSession,
         signature: &[u8],
         out: &mut [u8],
     ) -> Result<usize, Error> {
-        let mut resumption_id: [u8; 16] = [0; 16];
-        rand::thread_rng().fill_bytes(&mut resumption_id);
-
-        // We are guaranteed this unwrap will work
-        let fabric = fabric.as_ref().as_ref().unwrap();
+        rand::thread_rng().fill_bytes(&mut case_session.resumption_id);
 
         let mut sigma2_key = [0_u8; crypto::SYMM_KEY_LEN_BYTES];
         Case::get_sigma2_key(
-            fabric.ipk.op_key(),
+            ipk,
             our_random,
+            &case_session.our_pub_key,
             case_session,
             &mut sigma2_key,
         )?;
@@ -458,14 +1413,13 @@ impl Case {
         let mut tw = TLVWriter::new(&mut write_buf);
         tw.start_struct(TagType::Anonymous)?;
         tw.str16_as(TagType::Context(1), |buf| fabric.noc.as_tlv(buf))?;
-        tw.str16_as(TagType::Context(2), |buf| fabric.icac.as_tlv(buf))?;
+        if let Some(icac) = &fabric.icac {
+            tw.str16_as(TagType::Context(2), |buf| icac.as_tlv(buf))?;
+        }
         tw.str8(TagType::Context(3), signature)?;
-        tw.str8(TagType::Context(4), &resumption_id)?;
+        tw.str8(TagType::Context(4), &case_session.resumption_id)?;
         tw.end_container()?;
         //println!("TBE is {:x?}", write_buf.as_borrow_slice());
-        let nonce: [u8; crypto::AEAD_NONCE_LEN_BYTES] = [
-            0x4e, 0x43, 0x41, 0x53, 0x45, 0x5f, 0x53, 0x69, 0x67, 0x6d, 0x61, 0x32, 0x4e,
-        ];
         //        let nonce = GenericArray::from_slice(&nonce);
         //        type AesCcm = Ccm<Aes128, U16, U13>;
         //        let cipher = AesCcm::new(GenericArray::from_slice(key));
@@ -476,7 +1430,7 @@ impl Case {
 
         crypto::encrypt_in_place(
             &sigma2_key,
-            &nonce,
+            &SIGMA2_NONCE,
             &[],
             cipher_text,
             cipher_text.len() - TAG_LEN,
@@ -484,21 +1438,21 @@ impl Case {
         Ok(write_buf.as_slice().len())
     }
 
-    fn get_sigma2_sign(
-        fabric: &RwLockReadGuardRef<FabricMgrInner, Option<Fabric>>,
+    fn get_tbs_sign(
+        fabric: &Fabric,
         our_pub_key: &[u8],
         peer_pub_key: &[u8],
         signature: &mut [u8],
     ) -> Result<usize, Error> {
-        // We are guaranteed this unwrap will work
-        let fabric = fabric.as_ref().as_ref().unwrap();
         const MAX_TBS_SIZE: usize = 800;
         let mut buf: [u8; MAX_TBS_SIZE] = [0; MAX_TBS_SIZE];
         let mut write_buf = WriteBuf::new(&mut buf, MAX_TBS_SIZE);
         let mut tw = TLVWriter::new(&mut write_buf);
         tw.start_struct(TagType::Anonymous)?;
         tw.str16_as(TagType::Context(1), |buf| fabric.noc.as_tlv(buf))?;
-        tw.str16_as(TagType::Context(2), |buf| fabric.icac.as_tlv(buf))?;
+        if let Some(icac) = &fabric.icac {
+            tw.str16_as(TagType::Context(2), |buf| icac.as_tlv(buf))?;
+        }
         tw.str8(TagType::Context(3), our_pub_key)?;
         tw.str8(TagType::Context(4), peer_pub_key)?;
         tw.end_container()?;
@@ -514,12 +1468,728 @@ struct Sigma1Req<'a> {
     initiator_sessid: u16,
     dest_id: OctetStr<'a>,
     peer_pub_key: OctetStr<'a>,
+    session_params: Option<SessionParams>,
+    resumption_id: Option<OctetStr<'a>>,
+    initiator_resume_mic: Option<OctetStr<'a>>,
+}
+
+#[derive(FromTLV)]
+#[tlvargs(start = 1, lifetime = "'a")]
+struct Sigma2Resp<'a> {
+    responder_random: OctetStr<'a>,
+    responder_sessid: u16,
+    responder_pub_key: OctetStr<'a>,
+    encrypted: OctetStr<'a>,
+    responder_session_params: Option<SessionParams>,
+}
+
+#[derive(FromTLV)]
+#[tlvargs(start = 1, lifetime = "'a")]
+struct Sigma2ResumeResp<'a> {
+    resumption_id: OctetStr<'a>,
+    resume_mic: OctetStr<'a>,
+    responder_sessid: u16,
+    responder_session_params: Option<SessionParams>,
+}
+
+#[derive(FromTLV)]
+#[tlvargs(start = 1, lifetime = "'a")]
+struct Sigma2Decrypt<'a> {
+    responder_noc: OctetStr<'a>,
+    responder_icac: Option<OctetStr<'a>>,
+    signature: OctetStr<'a>,
+    resumption_id: OctetStr<'a>,
 }
 
 #[derive(FromTLV)]
 #[tlvargs(start = 1, lifetime = "'a")]
 struct Sigma3Decrypt<'a> {
     initiator_noc: OctetStr<'a>,
-    initiator_icac: OctetStr<'a>,
+    initiator_icac: Option<OctetStr<'a>>,
     signature: OctetStr<'a>,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::thread::{self, JoinHandle};
+
+    use async_channel::Receiver;
+    use boxslab::Slab;
+
+    use super::*;
+    use crate::{
+        persist::MemKvStore,
+        transport::{
+            exchange::{Exchange, Role},
+            packet::PacketPool,
+            queue::{test_new_session as new_session, test_work_q as work_q},
+            session::{Session, SessionMgr},
+        },
+    };
+
+    const IPK: [u8; 16] = [0x11; 16];
+
+    // The initiator and responder sessions, as they are after Sigma1 and Sigma2
+    fn session_pair() -> (CaseSession, CaseSession) {
+        let mut initiator = CaseSession::new(2, 1).unwrap();
+        initiator.state = State::Sigma3Tx;
+        let mut responder = CaseSession::new(1, 2).unwrap();
+
+        let i_key_pair = KeyPair::new().unwrap();
+        let r_key_pair = KeyPair::new().unwrap();
+        i_key_pair
+            .get_public_key(&mut initiator.our_pub_key)
+            .unwrap();
+        r_key_pair
+            .get_public_key(&mut responder.our_pub_key)
+            .unwrap();
+        initiator.peer_pub_key = responder.our_pub_key;
+        responder.peer_pub_key = initiator.our_pub_key;
+        i_key_pair
+            .derive_secret(&initiator.peer_pub_key, &mut initiator.shared_secret)
+            .unwrap();
+        r_key_pair
+            .derive_secret(&responder.peer_pub_key, &mut responder.shared_secret)
+            .unwrap();
+
+        initiator.tt_hash.update(b"sigma1").unwrap();
+        responder.tt_hash.update(b"sigma1").unwrap();
+        (initiator, responder)
+    }
+
+    #[test]
+    fn test_sigma2_decryption() {
+        let (initiator, responder) = session_pair();
+        let responder_random = [0x22; 32];
+
+        // Encrypt as the responder does
+        let mut sigma2_key = [0_u8; crypto::SYMM_KEY_LEN_BYTES];
+        Case::get_sigma2_key(
+            &IPK,
+            &responder_random,
+            &responder.our_pub_key,
+            &responder,
+            &mut sigma2_key,
+        )
+        .unwrap();
+        let plain_text = [0x33_u8; 40];
+        let mut buf = [0_u8; 40 + crypto::AEAD_MIC_LEN_BYTES];
+        buf[..40].copy_from_slice(&plain_text);
+        crypto::encrypt_in_place(&sigma2_key, &SIGMA2_NONCE, &[], &mut buf, 40).unwrap();
+
+        let len =
+            Case::get_sigma2_decryption(&IPK, &responder_random, &initiator, &mut buf).unwrap();
+        assert_eq!(&buf[..len], &plain_text);
+    }
+
+    #[test]
+    fn test_session_keys() {
+        let (mut initiator, mut responder) = session_pair();
+        initiator.tt_hash.update(b"sigma2 sigma3").unwrap();
+        responder.tt_hash.update(b"sigma2 sigma3").unwrap();
+
+        let mut i_keys = [0_u8; 3 * crypto::SYMM_KEY_LEN_BYTES];
+        let mut r_keys = [0_u8; 3 * crypto::SYMM_KEY_LEN_BYTES];
+        Case::get_session_keys(
+            &IPK,
+            &initiator.tt_hash,
+            &initiator.shared_secret,
+            &mut i_keys,
+        )
+        .unwrap();
+        Case::get_session_keys(
+            &IPK,
+            &responder.tt_hash,
+            &responder.shared_secret,
+            &mut r_keys,
+        )
+        .unwrap();
+        let i = Case::get_session_clone_data(&i_keys, 1, 2, Address::default(), &initiator);
+        let r = Case::get_session_clone_data(&r_keys, 2, 1, Address::default(), &responder);
+        assert_eq!(i.enc_key, r.dec_key);
+        assert_eq!(i.dec_key, r.enc_key);
+        assert_ne!(i.enc_key, i.dec_key);
+        assert_eq!(i.att_challenge, r.att_challenge);
+    }
+
+    fn resumption_record(peer_nodeid: u64, id: u8) -> ResumptionRecord {
+        ResumptionRecord {
+            resumption_id: [id; RESUMPTION_ID_LEN],
+            shared_secret: [0x44; crypto::ECDH_SHARED_SECRET_LEN_BYTES],
+            local_fabric_idx: 1,
+            peer_nodeid,
+            peer_cat_ids: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_sigma1_resume_mic() {
+        let record = resumption_record(2, 0x55);
+        let initiator_random = [0x66; 32];
+        let mut mic = [0_u8; crypto::AEAD_MIC_LEN_BYTES];
+        Case::get_resume_mic(
+            &SIGMA1_RESUME_INFO,
+            &SIGMA1_RESUME_NONCE,
+            &record.shared_secret,
+            &initiator_random,
+            &record.resumption_id,
+            &mut mic,
+        )
+        .unwrap();
+        assert!(Case::validate_sigma1_resume(&record, &initiator_random, &mic).is_ok());
+
+        // A different random, or a different shared secret, doesn't match
+        assert!(Case::validate_sigma1_resume(&record, &[0x67; 32], &mic).is_err());
+        let mut other = record;
+        other.shared_secret[0] = 0;
+        assert!(Case::validate_sigma1_resume(&other, &initiator_random, &mic).is_err());
+    }
+
+    #[test]
+    fn test_resumption_store() {
+        let fabric_mgr = Arc::new(FabricMgr::new(Arc::new(MemKvStore::new())).unwrap());
+        fabric_mgr.add(node1()).unwrap();
+        let mut store = ResumptionStore::new(fabric_mgr.clone());
+        for peer in 0..MAX_RESUMPTION_RECORDS as u64 {
+            store.save(resumption_record(peer, peer as u8));
+        }
+        // A new session with a peer replaces its previous record
+        store.save(resumption_record(3, 0x33));
+        assert!(store.get_by_id(&[3; RESUMPTION_ID_LEN]).is_none());
+        assert_eq!(store.get_by_peer(1, 3).unwrap().resumption_id, [0x33; 16]);
+
+        // The least recently established session is dropped, once the store is full
+        store.save(resumption_record(100, 100));
+        assert!(store.get_by_peer(1, 0).is_none());
+        assert!(store.get_by_peer(1, 1).is_some());
+        assert!(store.get_by_peer(1, 100).is_some());
+        assert!(store.get_by_peer(2, 100).is_none());
+
+        store.remove(1, 100);
+        assert!(store.get_by_id(&[100; RESUMPTION_ID_LEN]).is_none());
+
+        // The records are reloaded from the fabric's storage
+        let store = ResumptionStore::new(fabric_mgr.clone());
+        assert_eq!(store.records.len(), MAX_RESUMPTION_RECORDS - 1);
+        assert_eq!(store.get_by_peer(1, 3).unwrap().resumption_id, [0x33; 16]);
+        assert!(store.get_by_peer(1, 0).is_none());
+        assert!(store.get_by_peer(1, 100).is_none());
+
+        // And are removed along with the fabric
+        fabric_mgr.remove(1).unwrap();
+        let store = ResumptionStore::new(fabric_mgr);
+        assert!(store.records.is_empty());
+    }
+
+    // A node of the test vectors' fabric, with its NOC and the key pair of the NOC
+    fn fabric(noc: &[u8], pub_key: &[u8], priv_key: &[u8]) -> Fabric {
+        Fabric::new(
+            KeyPair::new_from_components(pub_key, priv_key).unwrap(),
+            Cert::new(&test_vectors::RCAC).unwrap(),
+            Some(Cert::new(&test_vectors::ICAC).unwrap()),
+            Cert::new(noc).unwrap(),
+            &IPK,
+            0xfff1,
+        )
+        .unwrap()
+    }
+
+    fn node1() -> Fabric {
+        fabric(
+            &test_vectors::NOC_NODE1,
+            &test_vectors::NOC_NODE1_PUBKEY,
+            &test_vectors::NOC_NODE1_PRIVKEY,
+        )
+    }
+
+    fn node2() -> Fabric {
+        fabric(
+            &test_vectors::NOC_NODE2,
+            &test_vectors::NOC_NODE2_PUBKEY,
+            &test_vectors::NOC_NODE2_PRIVKEY,
+        )
+    }
+
+    // One end of a CASE exchange, that runs the handlers as the Secure Channel does
+    struct Node {
+        case: Case,
+        sess_mgr: SessionMgr,
+        sess_idx: usize,
+        exch: Exchange,
+    }
+
+    impl Node {
+        fn new(fabric: Fabric) -> (Self, u8) {
+            let fabric_mgr = Arc::new(FabricMgr::new(Arc::new(MemKvStore::new())).unwrap());
+            let fab_idx = fabric_mgr.add(fabric).unwrap();
+            let mut sess_mgr = SessionMgr::new();
+            let sess_idx = sess_mgr.add(Address::default(), None).unwrap();
+            let node = Self {
+                case: Case::new(fabric_mgr),
+                sess_mgr,
+                sess_idx,
+                exch: Exchange::new(1, sess_idx, Role::Responder),
+            };
+            (node, fab_idx)
+        }
+
+        // As after a reboot, only what was persisted remains
+        fn restart(&mut self) {
+            self.case = Case::new(self.case.fabric_mgr.clone());
+        }
+
+        // Queue a request for a session with 'peer_nodeid', and return the Sigma1 for it
+        fn establish(
+            &mut self,
+            fab_idx: u8,
+            peer_nodeid: u64,
+        ) -> (JoinHandle<Result<u16, Error>>, Vec<u8>) {
+            let client = self.case.get_client();
+            let req =
+                thread::spawn(move || client.establish(fab_idx, peer_nodeid, Address::default()));
+            while self.case.get_pending_req().is_none() {
+                thread::sleep(Duration::from_millis(10));
+            }
+            self.exch = Exchange::new(1, self.sess_idx, Role::Initiator);
+            let mut tx = Packet::new_tx().unwrap();
+            let mut exch_ctx = ExchangeCtx {
+                exch: &mut self.exch,
+                sess: self.sess_mgr.get_session_handle(self.sess_idx),
+            };
+            self.case
+                .handle_pending_req(&mut exch_ctx, &mut tx)
+                .unwrap();
+            (req, tx.as_borrow_slice().to_vec())
+        }
+
+        // Handle a message from the peer, and return our response to it
+        fn handle(&mut self, opcode: OpCode, msg: &[u8]) -> (OpCode, Vec<u8>) {
+            let mut rx = Slab::<PacketPool>::new(Packet::new_rx().unwrap()).unwrap();
+            let tx = Slab::<PacketPool>::new(Packet::new_tx().unwrap()).unwrap();
+            rx.as_borrow_slice()[..msg.len()].copy_from_slice(msg);
+            rx.get_parsebuf().unwrap().set_len(msg.len());
+            if opcode == OpCode::CASESigma1 {
+                self.exch = Exchange::new(1, self.sess_idx, Role::Responder);
+            }
+            let exch_ctx = ExchangeCtx {
+                exch: &mut self.exch,
+                sess: self.sess_mgr.get_session_handle(self.sess_idx),
+            };
+            let mut ctx = ProtoCtx::new(exch_ctx, rx, tx);
+            match opcode {
+                OpCode::CASESigma1 => {
+                    ctx.tx.set_proto_opcode(OpCode::CASESigma2 as u8);
+                    self.case.handle_casesigma1(&mut ctx)
+                }
+                OpCode::CASESigma2 => {
+                    ctx.tx.set_proto_opcode(OpCode::CASESigma3 as u8);
+                    self.case.handle_casesigma2(&mut ctx)
+                }
+                OpCode::CASESigma2Resume => self.case.handle_casesigma2_resume(&mut ctx),
+                OpCode::CASESigma3 => self.case.handle_casesigma3(&mut ctx),
+                OpCode::StatusReport => self.case.handle_status_report(&mut ctx),
+                _ => panic!("Unexpected opcode {:?}", opcode),
+            }
+            .unwrap();
+            let opcode = num::FromPrimitive::from_u8(ctx.tx.get_proto_opcode()).unwrap();
+            (opcode, ctx.tx.as_borrow_slice().to_vec())
+        }
+    }
+
+    // A Sigma2 in answer to 'sigma1', from a responder that owns the NOC of 'signer'. It is
+    // encrypted with 'ipk', so that the initiator gets as far as checking the NOC
+    fn sigma2_from(ipk: &[u8], signer: &Fabric, sigma1: &[u8]) -> Vec<u8> {
+        let root = get_root_node_struct(sigma1).unwrap();
+        let r = Sigma1Req::from_tlv(&root).unwrap();
+        let mut case_session = CaseSession::new(r.initiator_sessid, 10).unwrap();
+        case_session.tt_hash.update(sigma1).unwrap();
+        case_session.peer_pub_key.copy_from_slice(r.peer_pub_key.0);
+        let key_pair = KeyPair::new().unwrap();
+        key_pair
+            .get_public_key(&mut case_session.our_pub_key)
+            .unwrap();
+        key_pair
+            .derive_secret(r.peer_pub_key.0, &mut case_session.shared_secret)
+            .unwrap();
+
+        let mut signature = [0u8; crypto::EC_SIGNATURE_LEN_BYTES];
+        let sign_len = Case::get_tbs_sign(
+            signer,
+            &case_session.our_pub_key,
+            &case_session.peer_pub_key,
+            &mut signature,
+        )
+        .unwrap();
+        let our_random = [0x77; 32];
+        let mut encrypted = [0u8; MAX_ENCRYPTED_SIZE];
+        let encrypted_len = Case::get_sigma2_encryption(
+            ipk,
+            signer,
+            &our_random,
+            &mut case_session,
+            &signature[..sign_len],
+            &mut encrypted,
+        )
+        .unwrap();
+
+        let mut buf = [0u8; 1024];
+        let mut wb = WriteBuf::new(&mut buf, 1024);
+        let mut tw = TLVWriter::new(&mut wb);
+        tw.start_struct(TagType::Anonymous).unwrap();
+        tw.str8(TagType::Context(1), &our_random).unwrap();
+        tw.u16(TagType::Context(2), 10).unwrap();
+        tw.str8(TagType::Context(3), &case_session.our_pub_key)
+            .unwrap();
+        tw.str16(TagType::Context(4), &encrypted[..encrypted_len])
+            .unwrap();
+        tw.end_container().unwrap();
+        wb.as_slice().to_vec()
+    }
+
+    // Pass the messages between the two, starting with the initiator's Sigma1, until the
+    // session is established or rejected. Returns the opcodes of the messages
+    fn run_case(initiator: &mut Node, responder: &mut Node, sigma1: Vec<u8>) -> Vec<OpCode> {
+        let mut opcodes = Vec::new();
+        let mut msg = (OpCode::CASESigma1, sigma1);
+        let nodes = [responder, initiator];
+        for i in 0.. {
+            opcodes.push(msg.0);
+            let resp = nodes[i % 2].handle(msg.0, &msg.1);
+            if opcodes.last() == Some(&OpCode::StatusReport) {
+                break;
+            }
+            msg = resp;
+        }
+        opcodes
+    }
+
+    // The sessions that the initiator (node 1) and the responder (node 2) added
+    fn case_sessions(q: &Option<Receiver<Msg>>) -> (Session, Session) {
+        let a = Session::clone(&new_session(q).unwrap());
+        let b = Session::clone(&new_session(q).unwrap());
+        assert!(new_session(q).is_none());
+        if a.get_peer_node_id() == Some(2) {
+            (a, b)
+        } else {
+            (b, a)
+        }
+    }
+
+    fn assert_session_pair(i: &Session, r: &Session) {
+        assert_eq!(i.get_peer_sess_id(), r.get_local_sess_id());
+        assert_eq!(r.get_peer_sess_id(), i.get_local_sess_id());
+        assert_eq!(i.get_peer_node_id(), Some(2));
+        assert_eq!(r.get_peer_node_id(), Some(1));
+
+        // Each side decrypts what the other encrypts
+        assert_eq!(i.get_enc_key(), r.get_dec_key());
+        assert_eq!(i.get_dec_key(), r.get_enc_key());
+        assert_ne!(i.get_enc_key(), i.get_dec_key());
+        assert_eq!(i.get_att_challenge(), r.get_att_challenge());
+    }
+
+    const FULL_CASE: [OpCode; 4] = [
+        OpCode::CASESigma1,
+        OpCode::CASESigma2,
+        OpCode::CASESigma3,
+        OpCode::StatusReport,
+    ];
+    const RESUMED_CASE: [OpCode; 3] = [
+        OpCode::CASESigma1,
+        OpCode::CASESigma2Resume,
+        OpCode::StatusReport,
+    ];
+
+    #[test]
+    fn test_case_loopback() {
+        let q = work_q();
+        let (mut initiator, fab_idx) = Node::new(node1());
+        let (mut responder, _) = Node::new(node2());
+
+        let (req, sigma1) = initiator.establish(fab_idx, 2);
+        assert_eq!(run_case(&mut initiator, &mut responder, sigma1), FULL_CASE);
+        let (i, r) = case_sessions(&q);
+        // The initiator is told the id of its session with the responder
+        assert_eq!(req.join().unwrap(), Ok(i.get_local_sess_id()));
+        assert_eq!(i.get_local_fabric_idx(), Some(fab_idx));
+        assert_session_pair(&i, &r);
+    }
+
+    #[test]
+    fn test_case_resumption() {
+        let q = work_q();
+        let (mut initiator, fab_idx) = Node::new(node1());
+        let (mut responder, _) = Node::new(node2());
+        let (req, sigma1) = initiator.establish(fab_idx, 2);
+        assert_eq!(run_case(&mut initiator, &mut responder, sigma1), FULL_CASE);
+        req.join().unwrap().unwrap();
+        let (first, _) = case_sessions(&q);
+
+        // The records are persisted, both sides can resume the session after a reboot
+        initiator.restart();
+        responder.restart();
+        let (req, sigma1) = initiator.establish(fab_idx, 2);
+        assert_eq!(
+            run_case(&mut initiator, &mut responder, sigma1),
+            RESUMED_CASE
+        );
+        let (i, r) = case_sessions(&q);
+        assert_eq!(req.join().unwrap(), Ok(i.get_local_sess_id()));
+        assert_session_pair(&i, &r);
+        assert_ne!(i.get_enc_key(), first.get_enc_key());
+
+        // The resumed session can be resumed too, with its new resumption id
+        let (req, sigma1) = initiator.establish(fab_idx, 2);
+        assert_eq!(
+            run_case(&mut initiator, &mut responder, sigma1),
+            RESUMED_CASE
+        );
+        let (i, r) = case_sessions(&q);
+        assert_eq!(req.join().unwrap(), Ok(i.get_local_sess_id()));
+        assert_session_pair(&i, &r);
+    }
+
+    #[test]
+    fn test_case_resumption_fallback() {
+        let q = work_q();
+        let (mut initiator, fab_idx) = Node::new(node1());
+        let (mut responder, _) = Node::new(node2());
+        let (req, sigma1) = initiator.establish(fab_idx, 2);
+        assert_eq!(run_case(&mut initiator, &mut responder, sigma1), FULL_CASE);
+        req.join().unwrap().unwrap();
+        case_sessions(&q);
+
+        // A responder that doesn't know the resumption id
+        let (mut other, _) = Node::new(node2());
+        let (req, sigma1) = initiator.establish(fab_idx, 2);
+        assert_eq!(run_case(&mut initiator, &mut other, sigma1), FULL_CASE);
+        let (i, r) = case_sessions(&q);
+        assert_eq!(req.join().unwrap(), Ok(i.get_local_sess_id()));
+        assert_session_pair(&i, &r);
+
+        // A responder, for which the initiator's Resume MIC doesn't match
+        for record in other.case.resumption.records.iter_mut() {
+            record.shared_secret[0] ^= 0xff;
+        }
+        let (req, sigma1) = initiator.establish(fab_idx, 2);
+        assert_eq!(run_case(&mut initiator, &mut other, sigma1), FULL_CASE);
+        let (i, r) = case_sessions(&q);
+        assert_eq!(req.join().unwrap(), Ok(i.get_local_sess_id()));
+        assert_session_pair(&i, &r);
+    }
+
+    // The initiator's response, to a Sigma2 that carries the NOC of 'signer'
+    fn sigma2_with_noc(signer: Fabric) -> (Result<u16, Error>, OpCode, Vec<u8>) {
+        let (mut initiator, fab_idx) = Node::new(node1());
+        let ipk = node2().ipk.op_key().to_vec();
+        let (req, sigma1) = initiator.establish(fab_idx, 2);
+        let sigma2 = sigma2_from(&ipk, &signer, &sigma1);
+        let (opcode, resp) = initiator.handle(OpCode::CASESigma2, &sigma2);
+        if opcode == OpCode::StatusReport {
+            (req.join().unwrap(), opcode, resp)
+        } else {
+            // Drop the pending request
+            initiator.exch.close();
+            (Ok(0), opcode, resp)
+        }
+    }
+
+    #[test]
+    fn test_sigma2_noc() {
+        // The responder that we asked for
+        let (_, opcode, _) = sigma2_with_noc(node2());
+        assert_eq!(opcode, OpCode::CASESigma3);
+
+        // A node on the same fabric, that isn't the one we asked for
+        let (result, opcode, resp) = sigma2_with_noc(fabric(
+            &test_vectors::NOC_NODE3,
+            &test_vectors::NOC_NODE3_PUBKEY,
+            &test_vectors::NOC_NODE3_PRIVKEY,
+        ));
+        assert_eq!(result, Err(Error::Invalid));
+        assert_eq!(opcode, OpCode::StatusReport);
+        assert!(!status_report::parse_status_report(&resp)
+            .unwrap()
+            .is_success());
+
+        // The node that we asked for, but on another fabric
+        let (result, opcode, _) = sigma2_with_noc(fabric(
+            &test_vectors::NOC_NODE2_FAB2,
+            &test_vectors::NOC_NODE2_FAB2_PUBKEY,
+            &test_vectors::NOC_NODE2_FAB2_PRIVKEY,
+        ));
+        assert_eq!(result, Err(Error::Invalid));
+        assert_eq!(opcode, OpCode::StatusReport);
+    }
+
+    mod test_vectors {
+        // A root, and an intermediate CA for fabric 1, that issues the NOCs of nodes 1, 2
+        // and 3 on fabric 1, and of node 2 on fabric 2
+        pub const RCAC: [u8; 231] = [
+            0x15, 0x30, 0x01, 0x01, 0x01, 0x24, 0x02, 0x01, 0x37, 0x03, 0x24, 0x14, 0x01, 0x18,
+            0x26, 0x04, 0x80, 0x22, 0x81, 0x27, 0x26, 0x05, 0x80, 0x25, 0x4d, 0x3a, 0x37, 0x06,
+            0x24, 0x14, 0x01, 0x18, 0x24, 0x07, 0x01, 0x24, 0x08, 0x01, 0x30, 0x09, 0x41, 0x04,
+            0x31, 0x8d, 0xb2, 0xb8, 0xbf, 0x9c, 0x28, 0x95, 0x8b, 0x76, 0x4f, 0xb4, 0x7a, 0x07,
+            0xc0, 0x98, 0x56, 0x75, 0x16, 0xd5, 0x26, 0xc6, 0x84, 0xcb, 0xc8, 0xd3, 0x78, 0x45,
+            0x79, 0x67, 0x9a, 0xf7, 0x8e, 0x70, 0x63, 0x29, 0x2d, 0x81, 0xd4, 0x65, 0xf1, 0x8a,
+            0x63, 0x21, 0xfb, 0x3c, 0x99, 0xc0, 0xf9, 0x57, 0x8f, 0xd1, 0x07, 0x3c, 0x38, 0x59,
+            0x29, 0x1a, 0xe7, 0xdc, 0xc2, 0x16, 0x6f, 0xe8, 0x37, 0x0a, 0x35, 0x01, 0x29, 0x01,
+            0x18, 0x24, 0x02, 0x60, 0x30, 0x04, 0x14, 0x11, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1,
+            0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0x30,
+            0x05, 0x14, 0x11, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1,
+            0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0x18, 0x30, 0x0b, 0x40, 0xb4, 0x38,
+            0xa4, 0x1f, 0xe2, 0xfe, 0xe5, 0xb9, 0xce, 0xd1, 0xe8, 0x0c, 0x8a, 0x96, 0x3a, 0xfc,
+            0x38, 0xec, 0x5b, 0xfe, 0x09, 0x56, 0xe0, 0x97, 0xe7, 0x63, 0x5d, 0xaa, 0x61, 0xb8,
+            0xc7, 0x75, 0x5d, 0x43, 0x1f, 0x8e, 0x07, 0xb3, 0x93, 0x38, 0x94, 0xe0, 0xbb, 0x9a,
+            0x26, 0x45, 0x83, 0xb1, 0x2a, 0x0b, 0x6d, 0x1b, 0x44, 0x4c, 0x8f, 0xd2, 0xf1, 0xcb,
+            0x07, 0x9d, 0x61, 0x67, 0xa4, 0xa8, 0x18,
+        ];
+        pub const ICAC: [u8; 237] = [
+            0x15, 0x30, 0x01, 0x01, 0x02, 0x24, 0x02, 0x01, 0x37, 0x03, 0x24, 0x14, 0x01, 0x24,
+            0x15, 0x01, 0x18, 0x26, 0x04, 0x80, 0x22, 0x81, 0x27, 0x26, 0x05, 0x80, 0x25, 0x4d,
+            0x3a, 0x37, 0x06, 0x24, 0x13, 0x02, 0x24, 0x15, 0x01, 0x18, 0x24, 0x07, 0x01, 0x24,
+            0x08, 0x01, 0x30, 0x09, 0x41, 0x04, 0xac, 0xdf, 0x40, 0x8e, 0x2b, 0x01, 0x2c, 0x95,
+            0x03, 0x04, 0xc3, 0x4c, 0xe3, 0x2d, 0x98, 0x13, 0xe8, 0x98, 0x5e, 0x2f, 0xbe, 0xb2,
+            0xd1, 0x2d, 0x10, 0xe1, 0x5a, 0x9b, 0x41, 0x9e, 0xc9, 0xec, 0xb2, 0x33, 0xfb, 0xab,
+            0x9e, 0xf6, 0x78, 0x55, 0x88, 0x94, 0x47, 0x62, 0x77, 0x96, 0x68, 0x8a, 0xa6, 0x96,
+            0x19, 0xda, 0x83, 0x38, 0xff, 0x06, 0x84, 0x17, 0x00, 0x47, 0xcd, 0x74, 0x52, 0x3d,
+            0x37, 0x0a, 0x35, 0x01, 0x29, 0x01, 0x18, 0x24, 0x02, 0x60, 0x30, 0x04, 0x14, 0x11,
+            0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33,
+            0x33, 0x33, 0x33, 0x33, 0x33, 0x30, 0x05, 0x14, 0x11, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1,
+            0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1,
+            0x18, 0x30, 0x0b, 0x40, 0x36, 0x5f, 0x3b, 0x6f, 0x49, 0x04, 0xc1, 0x7d, 0xd0, 0x78,
+            0x37, 0x28, 0xb3, 0x0e, 0x08, 0xd4, 0xc2, 0x93, 0xbe, 0x1e, 0x0d, 0xf4, 0x6e, 0x86,
+            0x57, 0x9f, 0xcf, 0xba, 0xb9, 0xda, 0x4c, 0xa2, 0xae, 0x3d, 0x8c, 0xee, 0x11, 0xb4,
+            0x01, 0x6e, 0x63, 0x89, 0xd7, 0x38, 0x64, 0x72, 0x85, 0x1d, 0x15, 0x68, 0x44, 0xd4,
+            0x06, 0xdc, 0x42, 0x0a, 0xef, 0x43, 0x6d, 0x81, 0xf9, 0xa9, 0xb8, 0xf8, 0x18,
+        ];
+        pub const NOC_NODE1: [u8; 244] = [
+            0x15, 0x30, 0x01, 0x01, 0x03, 0x24, 0x02, 0x01, 0x37, 0x03, 0x24, 0x13, 0x02, 0x24,
+            0x15, 0x01, 0x18, 0x26, 0x04, 0x80, 0x22, 0x81, 0x27, 0x26, 0x05, 0x80, 0x25, 0x4d,
+            0x3a, 0x37, 0x06, 0x24, 0x11, 0x01, 0x24, 0x15, 0x01, 0x18, 0x24, 0x07, 0x01, 0x24,
+            0x08, 0x01, 0x30, 0x09, 0x41, 0x04, 0x6b, 0x3a, 0xc4, 0x67, 0xf9, 0x94, 0xc9, 0x71,
+            0x49, 0x4f, 0xb0, 0x60, 0x02, 0x88, 0x86, 0xbc, 0xc0, 0x1e, 0xdb, 0x92, 0xf5, 0x1b,
+            0x21, 0x1b, 0xd8, 0xa8, 0xd1, 0xb4, 0x3b, 0xd4, 0xff, 0xad, 0x0d, 0x1f, 0x39, 0x8d,
+            0xb5, 0x5c, 0xc5, 0x0a, 0xee, 0x4e, 0x8a, 0x05, 0x50, 0x01, 0x70, 0xc2, 0x97, 0x0f,
+            0x19, 0xc8, 0xbc, 0x9d, 0xad, 0x64, 0x31, 0x95, 0x4e, 0x09, 0x49, 0xf5, 0xc0, 0x25,
+            0x37, 0x0a, 0x35, 0x01, 0x28, 0x01, 0x18, 0x24, 0x02, 0x01, 0x36, 0x03, 0x04, 0x02,
+            0x04, 0x01, 0x18, 0x30, 0x04, 0x14, 0x11, 0x00, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44,
+            0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x30, 0x05,
+            0x14, 0x11, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33,
+            0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x18, 0x30, 0x0b, 0x40, 0xf1, 0x5e, 0xf3,
+            0x7b, 0xd9, 0x12, 0x9c, 0x48, 0x19, 0xc2, 0x62, 0x9a, 0x2d, 0xfd, 0x4b, 0xcd, 0xa2,
+            0xdc, 0x40, 0x9b, 0xf0, 0xcc, 0xa7, 0x38, 0xa3, 0x4d, 0x17, 0xc1, 0x05, 0x37, 0x21,
+            0x02, 0x37, 0xc1, 0x44, 0xec, 0x88, 0xc7, 0x57, 0x6f, 0x55, 0x6b, 0x73, 0x00, 0xfa,
+            0xb3, 0x33, 0x2c, 0x47, 0x3e, 0x06, 0xfd, 0xdf, 0xed, 0xc0, 0x98, 0xc5, 0x5d, 0xbd,
+            0xd8, 0xfa, 0x9b, 0x31, 0x6e, 0x18,
+        ];
+        pub const NOC_NODE1_PUBKEY: [u8; 65] = [
+            0x04, 0x6b, 0x3a, 0xc4, 0x67, 0xf9, 0x94, 0xc9, 0x71, 0x49, 0x4f, 0xb0, 0x60, 0x02,
+            0x88, 0x86, 0xbc, 0xc0, 0x1e, 0xdb, 0x92, 0xf5, 0x1b, 0x21, 0x1b, 0xd8, 0xa8, 0xd1,
+            0xb4, 0x3b, 0xd4, 0xff, 0xad, 0x0d, 0x1f, 0x39, 0x8d, 0xb5, 0x5c, 0xc5, 0x0a, 0xee,
+            0x4e, 0x8a, 0x05, 0x50, 0x01, 0x70, 0xc2, 0x97, 0x0f, 0x19, 0xc8, 0xbc, 0x9d, 0xad,
+            0x64, 0x31, 0x95, 0x4e, 0x09, 0x49, 0xf5, 0xc0, 0x25,
+        ];
+        pub const NOC_NODE1_PRIVKEY: [u8; 32] = [
+            0x16, 0xdb, 0x1b, 0x5f, 0xe1, 0x41, 0xcc, 0x18, 0x5f, 0x32, 0x84, 0x3f, 0x54, 0x73,
+            0x94, 0x0a, 0x88, 0x05, 0x24, 0x95, 0x0d, 0x68, 0x43, 0x43, 0x06, 0xf0, 0x4b, 0x8a,
+            0x66, 0x61, 0xad, 0xe5,
+        ];
+        pub const NOC_NODE2: [u8; 244] = [
+            0x15, 0x30, 0x01, 0x01, 0x04, 0x24, 0x02, 0x01, 0x37, 0x03, 0x24, 0x13, 0x02, 0x24,
+            0x15, 0x01, 0x18, 0x26, 0x04, 0x80, 0x22, 0x81, 0x27, 0x26, 0x05, 0x80, 0x25, 0x4d,
+            0x3a, 0x37, 0x06, 0x24, 0x11, 0x02, 0x24, 0x15, 0x01, 0x18, 0x24, 0x07, 0x01, 0x24,
+            0x08, 0x01, 0x30, 0x09, 0x41, 0x04, 0x03, 0x35, 0xac, 0x19, 0xb6, 0x52, 0x2e, 0x90,
+            0xfb, 0x3b, 0x63, 0x9a, 0x67, 0xe0, 0x84, 0xcd, 0x0e, 0xce, 0xc9, 0xca, 0xd3, 0x97,
+            0x30, 0xfa, 0xf5, 0x47, 0xf9, 0x78, 0x94, 0x7d, 0x17, 0xf9, 0x1f, 0x33, 0x05, 0x6b,
+            0xf0, 0xd6, 0xec, 0x86, 0x7c, 0x0c, 0x56, 0x7c, 0x47, 0x19, 0xa4, 0x8c, 0x6f, 0xc4,
+            0x35, 0x82, 0x1e, 0x8c, 0x26, 0x29, 0xaf, 0x45, 0x7b, 0x53, 0xae, 0xc5, 0x60, 0x8a,
+            0x37, 0x0a, 0x35, 0x01, 0x28, 0x01, 0x18, 0x24, 0x02, 0x01, 0x36, 0x03, 0x04, 0x02,
+            0x04, 0x01, 0x18, 0x30, 0x04, 0x14, 0x11, 0x01, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44,
+            0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x30, 0x05,
+            0x14, 0x11, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33,
+            0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x18, 0x30, 0x0b, 0x40, 0x33, 0x20, 0x1e,
+            0x4b, 0x8a, 0x85, 0x6f, 0xf0, 0x63, 0x69, 0x0b, 0x77, 0x62, 0x7c, 0x20, 0x04, 0xb6,
+            0x15, 0xff, 0x4f, 0x3b, 0x6f, 0xb1, 0x23, 0x13, 0x0b, 0x73, 0xaa, 0xae, 0x66, 0xc8,
+            0x9e, 0x5c, 0xd1, 0xa7, 0x74, 0x69, 0xdd, 0x65, 0xdb, 0xce, 0x2a, 0xb6, 0xa4, 0xe3,
+            0x45, 0x37, 0xb3, 0x51, 0x85, 0xa7, 0xae, 0xcf, 0x53, 0xaf, 0x22, 0x9d, 0xf6, 0x06,
+            0xda, 0xaa, 0xfd, 0x5f, 0x19, 0x18,
+        ];
+        pub const NOC_NODE2_PUBKEY: [u8; 65] = [
+            0x04, 0x03, 0x35, 0xac, 0x19, 0xb6, 0x52, 0x2e, 0x90, 0xfb, 0x3b, 0x63, 0x9a, 0x67,
+            0xe0, 0x84, 0xcd, 0x0e, 0xce, 0xc9, 0xca, 0xd3, 0x97, 0x30, 0xfa, 0xf5, 0x47, 0xf9,
+            0x78, 0x94, 0x7d, 0x17, 0xf9, 0x1f, 0x33, 0x05, 0x6b, 0xf0, 0xd6, 0xec, 0x86, 0x7c,
+            0x0c, 0x56, 0x7c, 0x47, 0x19, 0xa4, 0x8c, 0x6f, 0xc4, 0x35, 0x82, 0x1e, 0x8c, 0x26,
+            0x29, 0xaf, 0x45, 0x7b, 0x53, 0xae, 0xc5, 0x60, 0x8a,
+        ];
+        pub const NOC_NODE2_PRIVKEY: [u8; 32] = [
+            0xd8, 0x05, 0x49, 0x11, 0x12, 0x26, 0x8d, 0x3b, 0x40, 0xc8, 0x68, 0xcb, 0xaf, 0x34,
+            0x48, 0x0f, 0xc8, 0x6d, 0x7e, 0x8f, 0x60, 0x7f, 0xba, 0xd7, 0x34, 0x4c, 0x47, 0x4d,
+            0x35, 0x03, 0x4e, 0x8a,
+        ];
+        pub const NOC_NODE3: [u8; 244] = [
+            0x15, 0x30, 0x01, 0x01, 0x05, 0x24, 0x02, 0x01, 0x37, 0x03, 0x24, 0x13, 0x02, 0x24,
+            0x15, 0x01, 0x18, 0x26, 0x04, 0x80, 0x22, 0x81, 0x27, 0x26, 0x05, 0x80, 0x25, 0x4d,
+            0x3a, 0x37, 0x06, 0x24, 0x11, 0x03, 0x24, 0x15, 0x01, 0x18, 0x24, 0x07, 0x01, 0x24,
+            0x08, 0x01, 0x30, 0x09, 0x41, 0x04, 0x26, 0x88, 0x8e, 0x77, 0xde, 0x9d, 0xe8, 0x2a,
+            0x4f, 0xd3, 0x5e, 0x18, 0x17, 0x57, 0xf6, 0x7f, 0xc7, 0x3a, 0xec, 0x2f, 0x7e, 0x97,
+            0xfa, 0xd6, 0xf1, 0x0e, 0x54, 0x77, 0xcf, 0xb6, 0x73, 0x54, 0x9d, 0x0e, 0x69, 0xe2,
+            0xe8, 0xd8, 0xe5, 0xa0, 0x71, 0x6b, 0x86, 0xca, 0x0b, 0x11, 0xeb, 0xb9, 0x4e, 0x78,
+            0xbe, 0xf0, 0xf6, 0x36, 0xe4, 0x17, 0x7d, 0x6d, 0x4b, 0x31, 0xff, 0xc1, 0x6a, 0x91,
+            0x37, 0x0a, 0x35, 0x01, 0x28, 0x01, 0x18, 0x24, 0x02, 0x01, 0x36, 0x03, 0x04, 0x02,
+            0x04, 0x01, 0x18, 0x30, 0x04, 0x14, 0x11, 0x02, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44,
+            0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x30, 0x05,
+            0x14, 0x11, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33,
+            0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x18, 0x30, 0x0b, 0x40, 0x21, 0x31, 0x77,
+            0x09, 0xb3, 0xe1, 0xf8, 0x46, 0x7e, 0x37, 0x3b, 0xcd, 0x45, 0xe8, 0xf1, 0xfd, 0x2e,
+            0x82, 0x10, 0x7f, 0xb0, 0x01, 0x0e, 0x9e, 0x18, 0x06, 0xba, 0xee, 0xf4, 0xa9, 0xa8,
+            0x19, 0x3a, 0x3b, 0x42, 0x38, 0x6d, 0x5c, 0x57, 0x53, 0xf2, 0xa8, 0x3d, 0xdb, 0x62,
+            0x28, 0x8e, 0xaf, 0x7f, 0xfa, 0x20, 0xfa, 0x1d, 0xc7, 0x6e, 0x99, 0x93, 0xe4, 0x74,
+            0xa5, 0xe7, 0xe1, 0x1c, 0xf1, 0x18,
+        ];
+        pub const NOC_NODE3_PUBKEY: [u8; 65] = [
+            0x04, 0x26, 0x88, 0x8e, 0x77, 0xde, 0x9d, 0xe8, 0x2a, 0x4f, 0xd3, 0x5e, 0x18, 0x17,
+            0x57, 0xf6, 0x7f, 0xc7, 0x3a, 0xec, 0x2f, 0x7e, 0x97, 0xfa, 0xd6, 0xf1, 0x0e, 0x54,
+            0x77, 0xcf, 0xb6, 0x73, 0x54, 0x9d, 0x0e, 0x69, 0xe2, 0xe8, 0xd8, 0xe5, 0xa0, 0x71,
+            0x6b, 0x86, 0xca, 0x0b, 0x11, 0xeb, 0xb9, 0x4e, 0x78, 0xbe, 0xf0, 0xf6, 0x36, 0xe4,
+            0x17, 0x7d, 0x6d, 0x4b, 0x31, 0xff, 0xc1, 0x6a, 0x91,
+        ];
+        pub const NOC_NODE3_PRIVKEY: [u8; 32] = [
+            0x3c, 0xe4, 0x2f, 0xb2, 0x0a, 0xd5, 0xd7, 0x5c, 0x56, 0x96, 0x5f, 0xd8, 0x46, 0x34,
+            0x48, 0xfe, 0x18, 0xcf, 0x55, 0xa0, 0x30, 0x97, 0x98, 0x6f, 0x87, 0x9e, 0x0f, 0x5b,
+            0x0c, 0x70, 0x0c, 0x18,
+        ];
+        pub const NOC_NODE2_FAB2: [u8; 244] = [
+            0x15, 0x30, 0x01, 0x01, 0x06, 0x24, 0x02, 0x01, 0x37, 0x03, 0x24, 0x13, 0x02, 0x24,
+            0x15, 0x01, 0x18, 0x26, 0x04, 0x80, 0x22, 0x81, 0x27, 0x26, 0x05, 0x80, 0x25, 0x4d,
+            0x3a, 0x37, 0x06, 0x24, 0x11, 0x02, 0x24, 0x15, 0x02, 0x18, 0x24, 0x07, 0x01, 0x24,
+            0x08, 0x01, 0x30, 0x09, 0x41, 0x04, 0x3f, 0xff, 0x6e, 0x46, 0x87, 0x35, 0x4c, 0xa1,
+            0x18, 0xc5, 0xd5, 0x32, 0x25, 0x69, 0x93, 0x30, 0x40, 0xee, 0x43, 0x85, 0x31, 0x77,
+            0x27, 0x33, 0x23, 0xbb, 0xe3, 0x3f, 0xa4, 0x60, 0xbb, 0xf0, 0x93, 0x0a, 0x08, 0xd3,
+            0x90, 0x75, 0xf5, 0x73, 0x6e, 0x83, 0x4a, 0x9e, 0xf5, 0x43, 0x84, 0x47, 0xc9, 0xf0,
+            0xe7, 0x7f, 0x8f, 0x7c, 0xbe, 0x27, 0xbe, 0xf3, 0x42, 0x51, 0x65, 0x7a, 0xc5, 0x2f,
+            0x37, 0x0a, 0x35, 0x01, 0x28, 0x01, 0x18, 0x24, 0x02, 0x01, 0x36, 0x03, 0x04, 0x02,
+            0x04, 0x01, 0x18, 0x30, 0x04, 0x14, 0x11, 0x03, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44,
+            0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x30, 0x05,
+            0x14, 0x11, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33,
+            0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x18, 0x30, 0x0b, 0x40, 0x11, 0x58, 0x2e,
+            0x12, 0x32, 0x19, 0x3f, 0x86, 0x86, 0x6f, 0x9d, 0x35, 0x60, 0x13, 0x58, 0x79, 0x5d,
+            0x9c, 0xfb, 0xe5, 0xa8, 0x92, 0x1a, 0x60, 0x99, 0xfb, 0x60, 0x52, 0x9a, 0x63, 0x02,
+            0x67, 0xbc, 0x43, 0x04, 0x2b, 0x27, 0x59, 0x58, 0xa0, 0x78, 0xaf, 0x17, 0xea, 0x6d,
+            0xd9, 0x2e, 0xe3, 0x23, 0x93, 0xf1, 0xec, 0xcb, 0x3a, 0xb5, 0xea, 0x76, 0xe2, 0x99,
+            0x1e, 0xfb, 0xdf, 0x78, 0x75, 0x18,
+        ];
+        pub const NOC_NODE2_FAB2_PUBKEY: [u8; 65] = [
+            0x04, 0x3f, 0xff, 0x6e, 0x46, 0x87, 0x35, 0x4c, 0xa1, 0x18, 0xc5, 0xd5, 0x32, 0x25,
+            0x69, 0x93, 0x30, 0x40, 0xee, 0x43, 0x85, 0x31, 0x77, 0x27, 0x33, 0x23, 0xbb, 0xe3,
+            0x3f, 0xa4, 0x60, 0xbb, 0xf0, 0x93, 0x0a, 0x08, 0xd3, 0x90, 0x75, 0xf5, 0x73, 0x6e,
+            0x83, 0x4a, 0x9e, 0xf5, 0x43, 0x84, 0x47, 0xc9, 0xf0, 0xe7, 0x7f, 0x8f, 0x7c, 0xbe,
+            0x27, 0xbe, 0xf3, 0x42, 0x51, 0x65, 0x7a, 0xc5, 0x2f,
+        ];
+        pub const NOC_NODE2_FAB2_PRIVKEY: [u8; 32] = [
+            0x8e, 0x0d, 0xef, 0xde, 0xe6, 0x64, 0xa6, 0x67, 0xae, 0xfa, 0x07, 0x16, 0xeb, 0x20,
+            0xb7, 0x28, 0x78, 0xa9, 0xdb, 0x51, 0x55, 0x95, 0x0e, 0x53, 0x60, 0xa2, 0x8d, 0x93,
+            0x05, 0x77, 0x20, 0xf3,
+        ];
+    }
+}
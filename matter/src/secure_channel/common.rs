@@ -1,13 +1,19 @@
+use std::time::Duration;
+
 use num_derive::FromPrimitive;
 
-use crate::{error::Error, transport::packet::Packet};
+use crate::{
+    error::Error,
+    tlv::{FromTLV, TLVElement, TLVWriter, TagType, ToTLV},
+    transport::{mrp::MrpParams, packet::Packet},
+};
 
 use super::status_report::{create_status_report, GeneralCode};
 
 /* Interaction Model ID as per the Matter Spec */
 pub const PROTO_ID_SECURE_CHANNEL: usize = 0x00;
 
-#[derive(FromPrimitive, Debug)]
+#[derive(FromPrimitive, Debug, PartialEq, Clone, Copy)]
 pub enum OpCode {
     MsgCounterSyncReq = 0x00,
     MsgCounterSyncResp = 0x01,
@@ -66,3 +72,63 @@ pub fn create_mrp_standalone_ack(proto_tx: &mut Packet) {
     proto_tx.set_proto_opcode(OpCode::MRPStandAloneAck as u8);
     proto_tx.unset_reliable();
 }
+
+/// The Session Parameter Struct, exchanged by both the nodes during PASE and CASE. The
+/// intervals are in milliseconds
+#[derive(FromTLV, ToTLV, Debug, Default, Clone, Copy, PartialEq)]
+#[tlvargs(start = 1)]
+pub struct SessionParams {
+    pub idle_interval: Option<u32>,
+    pub active_interval: Option<u32>,
+    pub active_threshold: Option<u16>,
+}
+
+impl SessionParams {
+    /// The parameters that we advertise to the peer
+    pub fn local() -> Self {
+        let params = MrpParams::default();
+        Self {
+            idle_interval: Some(params.idle_interval.as_millis() as u32),
+            active_interval: Some(params.active_interval.as_millis() as u32),
+            active_threshold: Some(params.active_threshold.as_millis() as u16),
+        }
+    }
+
+    pub fn mrp_params(&self) -> MrpParams {
+        let millis = |x: u64| Duration::from_millis(x);
+        MrpParams::new(
+            self.idle_interval.map(|x| millis(x as u64)),
+            self.active_interval.map(|x| millis(x as u64)),
+            self.active_threshold.map(|x| millis(x as u64)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tlv, utils::writebuf::WriteBuf};
+
+    #[test]
+    fn test_session_params() {
+        let mut buf = [0; 32];
+        let mut wb = WriteBuf::new(&mut buf, 32);
+        let mut tw = TLVWriter::new(&mut wb);
+        SessionParams::local()
+            .to_tlv(&mut tw, TagType::Anonymous)
+            .unwrap();
+        let root = tlv::get_root_node(wb.as_borrow_slice()).unwrap();
+        let params = SessionParams::from_tlv(&root).unwrap();
+        assert_eq!(params, SessionParams::local());
+        assert_eq!(params.mrp_params(), MrpParams::default());
+
+        // A sleepy end device, that only reports its idle interval
+        let params = SessionParams {
+            idle_interval: Some(15000),
+            ..Default::default()
+        };
+        let mrp = params.mrp_params();
+        assert_eq!(mrp.idle_interval, Duration::from_secs(15));
+        assert_eq!(mrp.active_interval, MrpParams::default().active_interval);
+    }
+}
@@ -3,13 +3,21 @@ use std::sync::Arc;
 use crate::{
     error::*,
     fabric::FabricMgr,
-    secure_channel::{common::*, pake::PAKE},
-    transport::proto_demux::{self, ProtoCtx, ResponseRequired},
+    secure_channel::{
+        common::*,
+        pake::{PaseMgr, PAKE},
+    },
+    transport::{
+        exchange::ExchangeCtx,
+        network::Address,
+        packet::Packet,
+        proto_demux::{self, ProtoCtx, ResponseRequired},
+    },
 };
 use log::{error, info};
 use num;
 
-use super::case::Case;
+use super::case::{Case, CaseClient};
 
 /* Handle messages related to the Secure Channel
  */
@@ -20,19 +28,15 @@ pub struct SecureChannel {
 }
 
 impl SecureChannel {
-    pub fn new(fabric_mgr: Arc<FabricMgr>) -> SecureChannel {
+    pub fn new(fabric_mgr: Arc<FabricMgr>, pase_mgr: PaseMgr) -> SecureChannel {
         SecureChannel {
-            pake: PAKE::new(),
+            pake: PAKE::new(pase_mgr),
             case: Case::new(fabric_mgr),
         }
     }
 
-    pub fn open_comm_window(&mut self) {
-        self.pake.enable();
-    }
-
-    pub fn close_comm_window(&mut self) {
-        self.pake.disable();
+    pub fn get_case_client(&self) -> CaseClient {
+        self.case.get_client()
     }
 
     fn mrpstandaloneack_handler(&mut self, _ctx: &mut ProtoCtx) -> Result<ResponseRequired, Error> {
@@ -67,11 +71,30 @@ impl SecureChannel {
         Ok(ResponseRequired::Yes)
     }
 
+    fn casesigma2_handler(&mut self, ctx: &mut ProtoCtx) -> Result<ResponseRequired, Error> {
+        info!("In CASE Sigma2 Handler");
+        ctx.tx.set_proto_opcode(OpCode::CASESigma3 as u8);
+        self.case.handle_casesigma2(ctx)?;
+        Ok(ResponseRequired::Yes)
+    }
+
+    fn casesigma2resume_handler(&mut self, ctx: &mut ProtoCtx) -> Result<ResponseRequired, Error> {
+        info!("In CASE Sigma2 Resume Handler");
+        self.case.handle_casesigma2_resume(ctx)?;
+        Ok(ResponseRequired::Yes)
+    }
+
     fn casesigma3_handler(&mut self, ctx: &mut ProtoCtx) -> Result<ResponseRequired, Error> {
         info!("In CASE Sigma3 Handler");
         self.case.handle_casesigma3(ctx)?;
         Ok(ResponseRequired::Yes)
     }
+
+    fn statusreport_handler(&mut self, ctx: &mut ProtoCtx) -> Result<ResponseRequired, Error> {
+        info!("In Status Report Handler");
+        self.case.handle_status_report(ctx)?;
+        Ok(ResponseRequired::No)
+    }
 }
 
 impl proto_demux::HandleProto for SecureChannel {
@@ -85,7 +108,10 @@ impl proto_demux::HandleProto for SecureChannel {
             OpCode::PASEPake1 => self.pasepake1_handler(ctx),
             OpCode::PASEPake3 => self.pasepake3_handler(ctx),
             OpCode::CASESigma1 => self.casesigma1_handler(ctx),
+            OpCode::CASESigma2 => self.casesigma2_handler(ctx),
+            OpCode::CASESigma2Resume => self.casesigma2resume_handler(ctx),
             OpCode::CASESigma3 => self.casesigma3_handler(ctx),
+            OpCode::StatusReport => self.statusreport_handler(ctx),
             _ => {
                 error!("OpCode Not Handled: {:?}", proto_opcode);
                 Err(Error::InvalidOpcode)
@@ -96,4 +122,19 @@ impl proto_demux::HandleProto for SecureChannel {
     fn get_proto_id(&self) -> usize {
         PROTO_ID_SECURE_CHANNEL as usize
     }
+
+    fn get_unsolicited_peer(&mut self) -> Option<Address> {
+        self.case.get_pending_req()
+    }
+
+    fn handle_unsolicited(
+        &mut self,
+        exch_ctx: &mut ExchangeCtx,
+        tx: &mut Packet,
+    ) -> Result<(), Error> {
+        tx.set_proto_id(PROTO_ID_SECURE_CHANNEL as u16);
+        tx.set_proto_opcode(OpCode::CASESigma1 as u8);
+        info!("Sending CASE Sigma1");
+        self.case.handle_pending_req(exch_ctx, tx)
+    }
 }
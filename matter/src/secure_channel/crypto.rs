@@ -7,6 +7,9 @@ use crate::error::Error;
 // Step 1: w0 and L
 //      set_w0_from_w0s
 //      set_L
+//   or, with a pre-computed verifier
+//      set_w0
+//      set_L_direct
 // Step 2: get_pB
 // Step 3: get_TT_as_verifier(pA)
 // Step 4: Computation of cA and cB happens outside since it doesn't use either BigNum or EcPoint
@@ -23,6 +26,8 @@ pub trait CryptoSpake2 {
     #[allow(non_snake_case)]
     fn set_L(&mut self, w1s: &[u8]) -> Result<(), Error>;
     #[allow(non_snake_case)]
+    fn set_L_direct(&mut self, l: &[u8]) -> Result<(), Error>;
+    #[allow(non_snake_case)]
     fn get_pB(&mut self, pB: &mut [u8]) -> Result<(), Error>;
     #[allow(non_snake_case)]
     fn get_TT_as_verifier(
@@ -61,6 +61,11 @@ impl CryptoSpake2 for CryptoEspMbedTls {
         Ok(())
     }
 
+    #[allow(non_snake_case)]
+    fn set_L_direct(&mut self, l: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+
     #[allow(non_snake_case)]
     fn get_pB(&mut self, pB: &mut [u8]) -> Result<(), Error> {
         // From the SPAKE2+ spec (https://datatracker.ietf.org/doc/draft-bar-cfrg-spake2plus/)
@@ -109,6 +109,13 @@ impl CryptoSpake2 for CryptoMbedTLS {
         Ok(())
     }
 
+    #[allow(non_snake_case)]
+    fn set_L_direct(&mut self, l: &[u8]) -> Result<(), Error> {
+        // L is an uncompressed point, as part of a pre-computed verifier
+        self.L = EcPoint::from_binary(&self.group, l)?;
+        Ok(())
+    }
+
     #[allow(non_snake_case)]
     fn get_pB(&mut self, pB: &mut [u8]) -> Result<(), Error> {
         // From the SPAKE2+ spec (https://datatracker.ietf.org/doc/draft-bar-cfrg-spake2plus/)
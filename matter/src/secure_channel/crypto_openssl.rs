@@ -112,6 +112,13 @@ impl CryptoSpake2 for CryptoOpenSSL {
         Ok(())
     }
 
+    #[allow(non_snake_case)]
+    fn set_L_direct(&mut self, l: &[u8]) -> Result<(), Error> {
+        // L is an uncompressed point, as part of a pre-computed verifier
+        self.L = EcPoint::from_bytes(&self.group, l, &mut self.bn_ctx)?;
+        Ok(())
+    }
+
     #[allow(non_snake_case)]
     fn get_pB(&mut self, pB: &mut [u8]) -> Result<(), Error> {
         // From the SPAKE2+ spec (https://datatracker.ietf.org/doc/draft-bar-cfrg-spake2plus/)
@@ -150,6 +157,32 @@ impl CryptoSpake2 for CryptoOpenSSL {
         pA: &[u8],
         pB: &[u8],
         TT_hash: &mut [u8],
+    ) -> Result<(), Error> {
+        let X = EcPoint::from_bytes(&self.group, pA, &mut self.bn_ctx)?;
+        let (Z, V) = CryptoOpenSSL::get_ZV_as_verifier(
+            &self.w0,
+            &self.L,
+            &mut self.M,
+            &X,
+            &self.xy,
+            &self.order,
+            &self.group,
+            &mut self.bn_ctx,
+        )?;
+        self.get_TT(context, pA, pB, &Z, &V, TT_hash)
+    }
+}
+
+impl CryptoOpenSSL {
+    #[allow(non_snake_case)]
+    fn get_TT(
+        &mut self,
+        context: &[u8],
+        pA: &[u8],
+        pB: &[u8],
+        Z: &EcPoint,
+        V: &EcPoint,
+        TT_hash: &mut [u8],
     ) -> Result<(), Error> {
         let mut TT = Hasher::new(MessageDigest::sha256())?;
         // context
@@ -166,18 +199,6 @@ impl CryptoSpake2 for CryptoOpenSSL {
         // Y = pB
         CryptoOpenSSL::add_to_tt(&mut TT, pB)?;
 
-        let X = EcPoint::from_bytes(&self.group, pA, &mut self.bn_ctx)?;
-        let (Z, V) = CryptoOpenSSL::get_ZV_as_verifier(
-            &self.w0,
-            &self.L,
-            &mut self.M,
-            &X,
-            &self.xy,
-            &self.order,
-            &self.group,
-            &mut self.bn_ctx,
-        )?;
-
         // Z
         let tmp = Z.to_bytes(
             &self.group,
@@ -205,9 +226,7 @@ impl CryptoSpake2 for CryptoOpenSSL {
         TT_hash.copy_from_slice(h.as_ref());
         Ok(())
     }
-}
 
-impl CryptoOpenSSL {
     fn add_to_tt(tt: &mut Hasher, buf: &[u8]) -> Result<(), Error> {
         let mut len_buf: [u8; 8] = [0; 8];
         LittleEndian::write_u64(&mut len_buf, buf.len() as u64);
@@ -308,6 +327,54 @@ impl CryptoOpenSSL {
     }
 }
 
+// The prover side, that the tests run as the commissioner
+#[cfg(test)]
+impl CryptoOpenSSL {
+    #[allow(non_snake_case)]
+    pub fn get_pA(&mut self, pA: &mut [u8]) -> Result<(), Error> {
+        //   X = x*P + w0*M
+        self.order.rand_range(&mut self.xy)?;
+        let P = self.group.generator();
+        let X = CryptoOpenSSL::do_add_mul(
+            P,
+            &self.xy,
+            &self.M,
+            &self.w0,
+            &self.group,
+            &mut self.bn_ctx,
+        )?;
+        let X = X.to_bytes(
+            &self.group,
+            PointConversionForm::UNCOMPRESSED,
+            &mut self.bn_ctx,
+        )?;
+        pA.copy_from_slice(X.as_slice());
+        Ok(())
+    }
+
+    #[allow(non_snake_case)]
+    pub fn get_TT_as_prover(
+        &mut self,
+        context: &[u8],
+        pA: &[u8],
+        pB: &[u8],
+        TT_hash: &mut [u8],
+    ) -> Result<(), Error> {
+        let Y = EcPoint::from_bytes(&self.group, pB, &mut self.bn_ctx)?;
+        let (Z, V) = CryptoOpenSSL::get_ZV_as_prover(
+            &self.w0,
+            &self.w1,
+            &mut self.N,
+            &Y,
+            &self.xy,
+            &self.order,
+            &self.group,
+            &mut self.bn_ctx,
+        )?;
+        self.get_TT(context, pA, pB, &Z, &V, TT_hash)
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
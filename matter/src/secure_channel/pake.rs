@@ -1,12 +1,16 @@
-use std::time::{Duration, SystemTime};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
 
 use super::{
-    common::{create_sc_status_report, SCStatusCodes},
-    spake2p::Spake2P,
+    common::{create_sc_status_report, SCStatusCodes, SessionParams},
+    spake2p::{Spake2P, VerifierData},
 };
 use crate::{
     crypto,
     error::Error,
+    sys::{Mdns, ServiceMode},
     tlv::{self, get_root_node_struct, FromTLV, OctetStr, TLVElement, TLVWriter, TagType, ToTLV},
     transport::{
         exchange::ExchangeCtx,
@@ -24,20 +28,21 @@ use rand::prelude::*;
 // We create a Spake2p object and set it up in the exchange-data. This object then
 // handles Spake2+ specific stuff.
 
-// As per the spec the iteration count should be between 1000 and 100000
-const ITERATION_COUNT: u32 = 2000;
-
-// TODO: Password should be passed inside
-const SPAKE2_PASSWORD: u32 = 123456;
-
 const PASE_DISCARD_TIMEOUT_SECS: Duration = Duration::from_secs(60);
 
 const SPAKE2_SESSION_KEYS_INFO: [u8; 11] = *b"SessionKeys";
 
+// The window is closed after this many failed PASE attempts, the spec requires at least 20
+const MAX_PASE_FAILED_ATTEMPTS: u8 = 20;
+
 struct SessionData {
     start_time: SystemTime,
+    // The commissioning window that the session is established under
+    window_id: u64,
     exch_id: u16,
     peer_addr: Address,
+    // The session parameters that the initiator asked for
+    peer_params: SessionParams,
     spake2p: Box<Spake2P>,
 }
 
@@ -79,9 +84,17 @@ impl PakeState {
         }
     }
 
-    fn make_in_progress(&mut self, spake2p: Box<Spake2P>, exch_ctx: &ExchangeCtx) {
+    fn make_in_progress(
+        &mut self,
+        spake2p: Box<Spake2P>,
+        peer_params: SessionParams,
+        window_id: u64,
+        exch_ctx: &ExchangeCtx,
+    ) {
         *self = PakeState::InProgress(SessionData {
             start_time: SystemTime::now(),
+            window_id,
+            peer_params,
             spake2p,
             exch_id: exch_ctx.exch.get_id(),
             peer_addr: exch_ctx.sess.get_peer_addr(),
@@ -99,43 +112,246 @@ impl Default for PakeState {
     }
 }
 
-#[derive(Default)]
-pub struct PAKE {
-    // As per the spec the salt should be between 16 to 32 bytes
-    salt: [u8; 16],
-    passwd: u32,
-    // Whether commissioning window/PASE session is enabled or not
-    enabled: bool,
-    state: PakeState,
+/// The status of the commissioning window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommWindowStatus {
+    NotOpen = 0,
+    /// Open, with a verifier supplied by an administrator
+    EnhancedOpen = 1,
+    /// Open, with the device's own passcode
+    BasicOpen = 2,
 }
 
-impl PAKE {
-    pub fn new() -> Self {
-        // TODO: Can any PBKDF2 calculation be pre-computed here
-        let mut pake = PAKE {
-            passwd: SPAKE2_PASSWORD,
-            ..Default::default()
+/// Publishes the commissionable service, it is withdrawn once the returned handle is
+/// dropped. The device publishes it through [Mdns], tests can stub it out
+pub type CommPublisher = fn(&str, ServiceMode) -> Result<Box<dyn Send>, Error>;
+
+fn mdns_publish(name: &str, mode: ServiceMode) -> Result<Box<dyn Send>, Error> {
+    Ok(Box::new(Mdns::publish_service(name, mode)?))
+}
+
+struct CommWindow {
+    id: u64,
+    verifier: VerifierData,
+    enhanced: bool,
+    // The fabric of the administrator that opened the window, if any
+    opener: Option<u8>,
+    // The commissionable service is advertised only while the window is open
+    _service: Box<dyn Send>,
+}
+
+struct PaseMgrInner {
+    // The verifier and discriminator of the device's onboarding payload
+    verifier: VerifierData,
+    discriminator: u16,
+    publisher: CommPublisher,
+    // PASE sessions are accepted only while the window is open
+    window: Option<CommWindow>,
+    next_window_id: u64,
+    // The window times out at the deadline. A PASE session that was established under it
+    // is aborted then too, unless its commissioning is complete
+    deadline: Option<Instant>,
+    // A PASE session was established under the window, and closed it
+    pase_session: bool,
+    failed_attempts: u8,
+    // The window was revoked, or it timed out, the commissioning under it is to be aborted
+    aborted: bool,
+}
+
+impl PaseMgrInner {
+    fn open_window(
+        &mut self,
+        verifier: VerifierData,
+        discriminator: u16,
+        enhanced: bool,
+        opener: Option<u8>,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        // The instance name is a random 64-bit number
+        let name = format!("{:016X}", rand::thread_rng().next_u64());
+        let mode = ServiceMode::Commissionable {
+            discriminator,
+            enhanced,
+        };
+        let service = (self.publisher)(&name, mode)?;
+
+        let id = self.next_window_id;
+        self.next_window_id += 1;
+        self.window = Some(CommWindow {
+            id,
+            verifier,
+            enhanced,
+            opener,
+            _service: service,
+        });
+        self.deadline = timeout.map(|t| Instant::now() + t);
+        self.pase_session = false;
+        self.failed_attempts = 0;
+        Ok(())
+    }
+
+    fn close_window(&mut self) {
+        self.window = None;
+        self.deadline = None;
+    }
+
+    // Close the window, along with the PASE session that was established under it, if any.
+    // Returns whether the window was open
+    fn abort_window(&mut self) -> bool {
+        let was_open = self.window.is_some();
+        if was_open || self.pase_session {
+            self.aborted = true;
+        }
+        self.close_window();
+        self.pase_session = false;
+        was_open
+    }
+}
+
+/// The commissioning window, that is shared between the Secure Channel and the
+/// Administrator Commissioning cluster, which opens and closes it
+#[derive(Clone)]
+pub struct PaseMgr(Arc<Mutex<PaseMgrInner>>);
+
+impl PaseMgr {
+    pub fn new(verifier: VerifierData, discriminator: u16) -> Self {
+        Self::new_with_publisher(verifier, discriminator, mdns_publish)
+    }
+
+    /// Create the PASE Manager, that publishes the commissionable service with 'publisher'
+    pub fn new_with_publisher(
+        verifier: VerifierData,
+        discriminator: u16,
+        publisher: CommPublisher,
+    ) -> Self {
+        Self(Arc::new(Mutex::new(PaseMgrInner {
+            verifier,
+            discriminator,
+            publisher,
+            window: None,
+            next_window_id: 0,
+            deadline: None,
+            pase_session: false,
+            failed_attempts: 0,
+            aborted: false,
+        })))
+    }
+
+    /// Open a Basic Commissioning Window, with the passcode of the onboarding payload
+    pub fn open_basic_window(
+        &self,
+        opener: Option<u8>,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        let mut inner = self.0.lock()?;
+        let verifier = inner.verifier.clone();
+        let discriminator = inner.discriminator;
+        inner.open_window(verifier, discriminator, false, opener, timeout)
+    }
+
+    /// Open an Enhanced Commissioning Window, with a verifier and discriminator that
+    /// were supplied by the administrator of fabric 'opener'
+    pub fn open_enhanced_window(
+        &self,
+        verifier: VerifierData,
+        discriminator: u16,
+        opener: Option<u8>,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.0
+            .lock()?
+            .open_window(verifier, discriminator, true, opener, timeout)
+    }
+
+    /// Revoke the window. The PASE session that was established under it, if any, is
+    /// aborted too. Returns whether the window was open
+    pub fn revoke_window(&self) -> Result<bool, Error> {
+        Ok(self.0.lock()?.abort_window())
+    }
+
+    /// Close the window, if it has timed out. Returns true once, after the window was
+    /// revoked or timed out. The PASE sessions must then be closed, and a Fail-Safe that was
+    /// armed on them expires
+    pub fn check_expiry(&self) -> bool {
+        let mut inner = match self.0.lock() {
+            Ok(inner) => inner,
+            Err(_) => return false,
         };
-        rand::thread_rng().fill_bytes(&mut pake.salt);
-        pake
+        if inner.deadline.is_some_and(|d| Instant::now() >= d) {
+            info!("Commissioning window timed out");
+            inner.abort_window();
+        }
+        std::mem::replace(&mut inner.aborted, false)
+    }
+
+    // The window is closed, once a PASE session is established under it. The session is
+    // still aborted, if the window times out before the commissioning completes
+    fn close_window_for_session(&self) -> Result<(), Error> {
+        let mut inner = self.0.lock()?;
+        inner.pase_session = inner.window.take().is_some();
+        Ok(())
+    }
+
+    // The window stays open after a failed attempt, until there are too many of them
+    fn attempt_failed(&self) -> Result<(), Error> {
+        let mut inner = self.0.lock()?;
+        inner.failed_attempts += 1;
+        if inner.failed_attempts >= MAX_PASE_FAILED_ATTEMPTS {
+            error!("Too many failed PASE attempts, closing the commissioning window");
+            inner.close_window();
+        }
+        Ok(())
+    }
+
+    /// The commissioning is complete, it isn't aborted by the timeout of its window any more
+    pub fn commissioning_complete(&self) -> Result<(), Error> {
+        let mut inner = self.0.lock()?;
+        if inner.window.is_none() {
+            inner.deadline = None;
+        }
+        inner.pase_session = false;
+        Ok(())
+    }
+
+    fn window_id(&self) -> Result<Option<u64>, Error> {
+        Ok(self.0.lock()?.window.as_ref().map(|w| w.id))
     }
 
-    pub fn enable(&mut self) {
-        self.enabled = true;
+    /// The status of the window, along with the fabric that opened it
+    pub fn window_status(&self) -> Result<(CommWindowStatus, Option<u8>), Error> {
+        let inner = self.0.lock()?;
+        Ok(match &inner.window {
+            None => (CommWindowStatus::NotOpen, None),
+            Some(w) if w.enhanced => (CommWindowStatus::EnhancedOpen, w.opener),
+            Some(w) => (CommWindowStatus::BasicOpen, w.opener),
+        })
     }
+}
+
+pub struct PAKE {
+    pase_mgr: PaseMgr,
+    state: PakeState,
+}
 
-    pub fn disable(&mut self) {
-        self.enabled = false;
+impl PAKE {
+    pub fn new(pase_mgr: PaseMgr) -> Self {
+        // TODO: Can any PBKDF2 calculation be pre-computed here
+        PAKE {
+            pase_mgr,
+            state: PakeState::Idle,
+        }
     }
 
     #[allow(non_snake_case)]
     pub fn handle_pasepake3(&mut self, ctx: &mut ProtoCtx) -> Result<(), Error> {
         let mut sd = self.state.take_sess_data(&ctx.exch_ctx)?;
+        self.check_window(&sd)?;
 
         let cA = extract_pasepake_1_or_3_params(ctx.rx.as_borrow_slice())?;
         let (status_code, Ke) = sd.spake2p.handle_cA(cA);
+        let established = status_code == SCStatusCodes::SessionEstablishmentSuccess;
 
-        if status_code == SCStatusCodes::SessionEstablishmentSuccess {
+        if established {
             // Get the keys
             let Ke = Ke.ok_or(Error::Invalid)?;
             let mut session_keys: [u8; 48] = [0; 48];
@@ -159,6 +375,7 @@ impl PAKE {
             clone_data
                 .att_challenge
                 .copy_from_slice(&session_keys[32..48]);
+            clone_data.peer_mrp = sd.peer_params.mrp_params();
 
             // Queue a transport mgr request to add a new session
             WorkQ::get()?.sync_send(Msg::NewSession(clone_data))?;
@@ -166,22 +383,37 @@ impl PAKE {
 
         create_sc_status_report(&mut ctx.tx, status_code, None)?;
         ctx.exch_ctx.exch.close();
-        // Disable PASE for subsequent sessions
         self.state = PakeState::Idle;
-        self.disable();
+        if established {
+            // Disable PASE for subsequent sessions
+            self.pase_mgr.close_window_for_session()?;
+        } else {
+            self.pase_mgr.attempt_failed()?;
+        }
+
+        Ok(())
+    }
 
+    // The handshake is aborted, if its window was revoked, or timed out, in the meantime
+    fn check_window(&self, sd: &SessionData) -> Result<(), Error> {
+        if self.pase_mgr.window_id()? != Some(sd.window_id) {
+            error!("The commissioning window was closed, aborting PASE");
+            return Err(Error::InvalidState);
+        }
         Ok(())
     }
 
     #[allow(non_snake_case)]
     pub fn handle_pasepake1(&mut self, ctx: &mut ProtoCtx) -> Result<(), Error> {
         let mut sd = self.state.take_sess_data(&ctx.exch_ctx)?;
+        self.check_window(&sd)?;
 
         let pA = extract_pasepake_1_or_3_params(ctx.rx.as_borrow_slice())?;
         let mut pB: [u8; 65] = [0; 65];
         let mut cB: [u8; 32] = [0; 32];
-        sd.spake2p
-            .start_verifier(self.passwd, ITERATION_COUNT, &self.salt)?;
+        let pase = self.pase_mgr.0.lock()?;
+        let window = pase.window.as_ref().ok_or(Error::InvalidState)?;
+        sd.spake2p.start_verifier(&window.verifier)?;
         sd.spake2p.handle_pA(pA, &mut pB, &mut cB)?;
 
         let mut tw = TLVWriter::new(ctx.tx.get_writebuf()?);
@@ -197,11 +429,15 @@ impl PAKE {
     }
 
     pub fn handle_pbkdfparamrequest(&mut self, ctx: &mut ProtoCtx) -> Result<(), Error> {
-        if !self.enabled {
-            error!("PASE Not enabled");
-            create_sc_status_report(&mut ctx.tx, SCStatusCodes::InvalidParameter, None)?;
-            return Ok(());
-        }
+        let pase = self.pase_mgr.0.lock()?;
+        let window = match &pase.window {
+            Some(w) => w,
+            None => {
+                error!("PASE Not enabled");
+                create_sc_status_report(&mut ctx.tx, SCStatusCodes::InvalidParameter, None)?;
+                return Ok(());
+            }
+        };
 
         if !self.state.is_idle() {
             let sd = self.state.take()?;
@@ -222,6 +458,10 @@ impl PAKE {
             error!("Can't yet handle passcode_id != 0");
             return Err(Error::Invalid);
         }
+        let peer_params = a.session_params.unwrap_or_default();
+        ctx.exch_ctx
+            .sess
+            .set_peer_mrp_params(peer_params.mrp_params());
 
         let mut our_random: [u8; 32] = [0; 32];
         rand::thread_rng().fill_bytes(&mut our_random);
@@ -238,18 +478,20 @@ impl PAKE {
             our_random: OctetStr(&our_random),
             local_sessid,
             params: None,
+            session_params: SessionParams::local(),
         };
         if !a.has_params {
             let params_resp = PBKDFParamRespParams {
-                count: ITERATION_COUNT,
-                salt: OctetStr(&self.salt),
+                count: window.verifier.count,
+                salt: OctetStr(window.verifier.salt()),
             };
             resp.params = Some(params_resp);
         }
         resp.to_tlv(&mut tw, TagType::Anonymous)?;
 
         spake2p.set_context(ctx.rx.as_borrow_slice(), ctx.tx.as_borrow_slice())?;
-        self.state.make_in_progress(spake2p, &ctx.exch_ctx);
+        self.state
+            .make_in_progress(spake2p, peer_params, window.id, &ctx.exch_ctx);
 
         Ok(())
     }
@@ -276,6 +518,7 @@ struct PBKDFParamResp<'a> {
     our_random: OctetStr<'a>,
     local_sessid: u16,
     params: Option<PBKDFParamRespParams<'a>>,
+    session_params: SessionParams,
 }
 
 #[allow(non_snake_case)]
@@ -292,4 +535,207 @@ struct PBKDFParamReq<'a> {
     initiator_ssid: u16,
     passcode_id: u16,
     has_params: bool,
+    session_params: Option<SessionParams>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pase_mgr() -> PaseMgr {
+        PaseMgr::new_with_publisher(VerifierData::new_with_pw(123456).unwrap(), 250, |_, _| {
+            Ok(Box::new(()))
+        })
+    }
+
+    #[test]
+    fn test_timeout_aborts_pase_session() {
+        let pase_mgr = pase_mgr();
+        pase_mgr
+            .open_basic_window(None, Some(Duration::from_millis(50)))
+            .unwrap();
+        pase_mgr.close_window_for_session().unwrap();
+        assert_eq!(pase_mgr.window_id().unwrap(), None);
+        assert!(!pase_mgr.check_expiry());
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(pase_mgr.check_expiry());
+        assert!(!pase_mgr.check_expiry());
+    }
+
+    #[test]
+    fn test_commissioning_complete() {
+        // The commissioning is no longer aborted, once it is complete
+        let pase_mgr = pase_mgr();
+        pase_mgr
+            .open_basic_window(None, Some(Duration::from_millis(50)))
+            .unwrap();
+        pase_mgr.close_window_for_session().unwrap();
+        pase_mgr.commissioning_complete().unwrap();
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(!pase_mgr.check_expiry());
+    }
+
+    #[test]
+    fn test_reopened_window_is_kept() {
+        // The timeout of a previous window doesn't affect the next one
+        let pase_mgr = pase_mgr();
+        pase_mgr
+            .open_basic_window(None, Some(Duration::from_millis(50)))
+            .unwrap();
+        assert!(pase_mgr.revoke_window().unwrap());
+        assert!(pase_mgr.check_expiry());
+        pase_mgr.open_basic_window(None, None).unwrap();
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(!pase_mgr.check_expiry());
+        assert!(pase_mgr.window_id().unwrap().is_some());
+        assert!(pase_mgr.revoke_window().unwrap());
+        assert!(pase_mgr.check_expiry());
+        assert!(!pase_mgr.revoke_window().unwrap());
+    }
+
+    #[test]
+    fn test_failed_attempts_close_window() {
+        let pase_mgr = pase_mgr();
+        pase_mgr.open_basic_window(None, None).unwrap();
+        for _ in 1..MAX_PASE_FAILED_ATTEMPTS {
+            pase_mgr.attempt_failed().unwrap();
+        }
+        assert!(pase_mgr.window_id().unwrap().is_some());
+        pase_mgr.attempt_failed().unwrap();
+        assert_eq!(pase_mgr.window_id().unwrap(), None);
+
+        // The count starts over with the next window
+        pase_mgr.open_basic_window(None, None).unwrap();
+        pase_mgr.attempt_failed().unwrap();
+        assert!(pase_mgr.window_id().unwrap().is_some());
+    }
+
+    #[cfg(feature = "crypto_openssl")]
+    mod handshake {
+        use super::*;
+        use crate::{
+            secure_channel::{
+                spake2p::Spake2Prover,
+                status_report::{parse_status_report, StatusReport},
+            },
+            transport::{
+                exchange::{Exchange, Role},
+                packet::{Packet, PacketPool},
+                queue::{test_new_session, test_work_q},
+                session::{Session, SessionMgr},
+            },
+            utils::writebuf::WriteBuf,
+        };
+        use boxslab::Slab;
+
+        type Handler = fn(&mut PAKE, &mut ProtoCtx) -> Result<(), Error>;
+
+        // Handle a message of the commissioner, and return our response to it
+        fn handle(
+            pake: &mut PAKE,
+            sess_mgr: &mut SessionMgr,
+            exch: &mut Exchange,
+            handler: Handler,
+            msg: &[u8],
+        ) -> Vec<u8> {
+            let mut rx = Slab::<PacketPool>::new(Packet::new_rx().unwrap()).unwrap();
+            let tx = Slab::<PacketPool>::new(Packet::new_tx().unwrap()).unwrap();
+            rx.as_borrow_slice()[..msg.len()].copy_from_slice(msg);
+            rx.get_parsebuf().unwrap().set_len(msg.len());
+            let exch_ctx = ExchangeCtx {
+                exch,
+                sess: sess_mgr.get_session_handle(0),
+            };
+            let mut ctx = ProtoCtx::new(exch_ctx, rx, tx);
+            handler(pake, &mut ctx).unwrap();
+            ctx.tx.as_borrow_slice().to_vec()
+        }
+
+        fn encode(f: impl FnOnce(&mut TLVWriter)) -> Vec<u8> {
+            let mut buf = [0u8; 200];
+            let mut wb = WriteBuf::new(&mut buf, 200);
+            let mut tw = TLVWriter::new(&mut wb);
+            tw.start_struct(TagType::Anonymous).unwrap();
+            f(&mut tw);
+            tw.end_container().unwrap();
+            wb.as_slice().to_vec()
+        }
+
+        // Run the handshake as a commissioner with the passcode 'pw' does, and return the
+        // status of the Pake3
+        #[allow(non_snake_case)]
+        fn run_pase(pake: &mut PAKE, pw: u32) -> StatusReport {
+            let mut sess_mgr = SessionMgr::new();
+            let sess_idx = sess_mgr.add(Address::default(), None).unwrap();
+            let mut exch = Exchange::new(1, sess_idx, Role::Responder);
+
+            let req = encode(|tw| {
+                tw.str8(TagType::Context(1), &[0x11; 32]).unwrap();
+                tw.u16(TagType::Context(2), 5).unwrap();
+                tw.u16(TagType::Context(3), 0).unwrap();
+                tw.bool(TagType::Context(4), false).unwrap();
+            });
+            let resp = handle(
+                pake,
+                &mut sess_mgr,
+                &mut exch,
+                PAKE::handle_pbkdfparamrequest,
+                &req,
+            );
+            let root = get_root_node_struct(&resp).unwrap();
+            let params = root.find_tag(4).unwrap();
+            let count = params.find_tag(1).unwrap().u32().unwrap();
+            let salt = params.find_tag(2).unwrap().slice().unwrap();
+            let mut prover = Spake2Prover::new(pw, count, salt, &req, &resp).unwrap();
+
+            let pA = prover.get_pA().unwrap().to_vec();
+            let pake1 = encode(|tw| tw.str8(TagType::Context(1), &pA).unwrap());
+            let resp = handle(
+                pake,
+                &mut sess_mgr,
+                &mut exch,
+                PAKE::handle_pasepake1,
+                &pake1,
+            );
+            let root = get_root_node_struct(&resp).unwrap();
+            let pB = root.find_tag(1).unwrap().slice().unwrap();
+            let cB = root.find_tag(2).unwrap().slice().unwrap();
+            // With the wrong passcode, the verifier's confirmation doesn't match either
+            let cA = prover.handle_pB(pB, cB).unwrap_or([0; 32]);
+
+            let pake3 = encode(|tw| tw.str8(TagType::Context(1), &cA).unwrap());
+            let resp = handle(
+                pake,
+                &mut sess_mgr,
+                &mut exch,
+                PAKE::handle_pasepake3,
+                &pake3,
+            );
+            parse_status_report(&resp).unwrap()
+        }
+
+        #[test]
+        fn test_failed_attempt_keeps_window() {
+            // A commissioner with the wrong passcode doesn't close the window, for the
+            // one with the right passcode
+            let q = test_work_q();
+            let pase_mgr = pase_mgr();
+            pase_mgr.open_basic_window(None, None).unwrap();
+            let mut pake = PAKE::new(pase_mgr.clone());
+
+            let status = run_pase(&mut pake, 654321);
+            assert_eq!(status.proto_code, SCStatusCodes::InvalidParameter as u16);
+            assert!(test_new_session(&q).is_none());
+            assert!(pase_mgr.window_id().unwrap().is_some());
+
+            let status = run_pase(&mut pake, 123456);
+            assert!(status.is_success());
+            let session = Session::clone(&test_new_session(&q).unwrap());
+            assert_eq!(session.get_peer_sess_id(), 5);
+            assert_eq!(pase_mgr.window_id().unwrap(), None);
+        }
+    }
 }
@@ -1,5 +1,6 @@
 use crate::crypto::{self, HmacSha256};
 use byteorder::{ByteOrder, LittleEndian};
+use rand::prelude::*;
 use subtle::ConstantTimeEq;
 
 use crate::{
@@ -57,6 +58,92 @@ const SPAKE2P_KEY_CONFIRM_INFO: [u8; 16] = *b"ConfirmationKeys";
 const SPAKE2P_CONTEXT_PREFIX: [u8; 26] = *b"CHIP PAKE V1 Commissioning";
 const CRYPTO_GROUP_SIZE_BYTES: usize = 32;
 const CRYPTO_W_SIZE_BYTES: usize = CRYPTO_GROUP_SIZE_BYTES + 8;
+const CRYPTO_PUBLIC_KEY_SIZE_BYTES: usize = (CRYPTO_GROUP_SIZE_BYTES * 2) + 1;
+
+/// The size of a pre-computed verifier: w0 || L
+pub const VERIFIER_SIZE_BYTES: usize = CRYPTO_GROUP_SIZE_BYTES + CRYPTO_PUBLIC_KEY_SIZE_BYTES;
+
+// As per the spec the iteration count should be between 1000 and 100000
+const MIN_ITERATION_COUNT: u32 = 1000;
+const MAX_ITERATION_COUNT: u32 = 100000;
+const DEFAULT_ITERATION_COUNT: u32 = 2000;
+// As per the spec the salt should be between 16 to 32 bytes
+const MIN_SALT_LEN: usize = 16;
+const MAX_SALT_LEN: usize = 32;
+// As per the spec the passcode is 27-bit, and these trivial values aren't allowed
+const MAX_PASSCODE: u32 = 99999998;
+const INVALID_PASSCODES: [u32; 12] = [
+    0, 11111111, 22222222, 33333333, 44444444, 55555555, 66666666, 77777777, 88888888, 99999999,
+    12345678, 87654321,
+];
+
+pub type VerifierStr = [u8; VERIFIER_SIZE_BYTES];
+
+#[derive(Clone)]
+pub enum VerifierOption {
+    /// With the passcode, the verifier is computed on the device
+    Password(u32),
+    /// A verifier that was computed, from the passcode, outside the device
+    Verifier(VerifierStr),
+}
+
+/// The data that a commissioner's passcode is verified against during PASE
+#[derive(Clone)]
+pub struct VerifierData {
+    pub data: VerifierOption,
+    // The PBKDF2 parameters
+    pub count: u32,
+    salt: [u8; MAX_SALT_LEN],
+    salt_len: usize,
+}
+
+impl VerifierData {
+    /// Verify against a passcode, with a random salt and the default iteration count
+    pub fn new_with_pw(pw: u32) -> Result<Self, Error> {
+        let mut salt = [0u8; MIN_SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        VerifierData::new_with_pw_params(pw, DEFAULT_ITERATION_COUNT, &salt)
+    }
+
+    /// Verify against a passcode, with the PBKDF2 parameters 'count' and 'salt'
+    pub fn new_with_pw_params(pw: u32, count: u32, salt: &[u8]) -> Result<Self, Error> {
+        if pw > MAX_PASSCODE || INVALID_PASSCODES.contains(&pw) {
+            return Err(Error::Invalid);
+        }
+        VerifierData::new_with(VerifierOption::Password(pw), count, salt)
+    }
+
+    /// Verify against a pre-computed verifier (w0 || L). The 'count' and 'salt' must be the
+    /// PBKDF2 parameters that the verifier was computed with
+    pub fn new(verifier: &[u8], count: u32, salt: &[u8]) -> Result<Self, Error> {
+        let mut v = [0u8; VERIFIER_SIZE_BYTES];
+        if verifier.len() != v.len() {
+            return Err(Error::Invalid);
+        }
+        v.copy_from_slice(verifier);
+        VerifierData::new_with(VerifierOption::Verifier(v), count, salt)
+    }
+
+    fn new_with(data: VerifierOption, count: u32, salt: &[u8]) -> Result<Self, Error> {
+        if !(MIN_ITERATION_COUNT..=MAX_ITERATION_COUNT).contains(&count)
+            || !(MIN_SALT_LEN..=MAX_SALT_LEN).contains(&salt.len())
+        {
+            return Err(Error::Invalid);
+        }
+        let mut v = VerifierData {
+            data,
+            count,
+            salt: [0; MAX_SALT_LEN],
+            salt_len: salt.len(),
+        };
+        v.salt[..salt.len()].copy_from_slice(salt);
+        Ok(v)
+    }
+
+    pub fn salt(&self) -> &[u8] {
+        &self.salt[..self.salt_len]
+    }
+}
 
 #[cfg(feature = "crypto_openssl")]
 fn crypto_spake2_new() -> Result<Box<dyn CryptoSpake2>, Error> {
@@ -115,16 +202,23 @@ impl Spake2P {
         let _ = pbkdf2_hmac(&pw_str, iter as usize, salt, w0w1s);
     }
 
-    pub fn start_verifier(&mut self, pw: u32, iter: u32, salt: &[u8]) -> Result<(), Error> {
-        let mut w0w1s: [u8; (2 * CRYPTO_W_SIZE_BYTES)] = [0; (2 * CRYPTO_W_SIZE_BYTES)];
-        Spake2P::get_w0w1s(pw, iter, salt, &mut w0w1s);
-        self.crypto_spake2 = Some(crypto_spake2_new()?);
+    pub fn start_verifier(&mut self, verifier: &VerifierData) -> Result<(), Error> {
+        let mut crypto_spake2 = crypto_spake2_new()?;
+        match &verifier.data {
+            VerifierOption::Password(pw) => {
+                let mut w0w1s: [u8; (2 * CRYPTO_W_SIZE_BYTES)] = [0; (2 * CRYPTO_W_SIZE_BYTES)];
+                Spake2P::get_w0w1s(*pw, verifier.count, verifier.salt(), &mut w0w1s);
 
-        let w0s_len = w0w1s.len() / 2;
-        if let Some(crypto_spake2) = &mut self.crypto_spake2 {
-            crypto_spake2.set_w0_from_w0s(&w0w1s[0..w0s_len])?;
-            crypto_spake2.set_L(&w0w1s[w0s_len..])?;
+                let w0s_len = w0w1s.len() / 2;
+                crypto_spake2.set_w0_from_w0s(&w0w1s[0..w0s_len])?;
+                crypto_spake2.set_L(&w0w1s[w0s_len..])?;
+            }
+            VerifierOption::Verifier(v) => {
+                crypto_spake2.set_w0(&v[0..CRYPTO_GROUP_SIZE_BYTES])?;
+                crypto_spake2.set_L_direct(&v[CRYPTO_GROUP_SIZE_BYTES..])?;
+            }
         }
+        self.crypto_spake2 = Some(crypto_spake2);
 
         self.mode = Spake2Mode::Verifier(Spake2VerifierState::Init);
         Ok(())
@@ -208,10 +302,65 @@ impl Spake2P {
     }
 }
 
+/// The prover side of Spake2+, that the tests run as the commissioner
+#[cfg(all(test, feature = "crypto_openssl"))]
+#[allow(non_snake_case)]
+pub struct Spake2Prover {
+    crypto: CryptoOpenSSL,
+    context: Sha256,
+    pA: [u8; CRYPTO_PUBLIC_KEY_SIZE_BYTES],
+}
+
+#[cfg(all(test, feature = "crypto_openssl"))]
+#[allow(non_snake_case)]
+impl Spake2Prover {
+    /// The prover for the passcode, with the PBKDF params that the verifier returned in
+    /// 'resp', to the request 'req'
+    pub fn new(pw: u32, count: u32, salt: &[u8], req: &[u8], resp: &[u8]) -> Result<Self, Error> {
+        let mut w0w1s = [0u8; 2 * CRYPTO_W_SIZE_BYTES];
+        Spake2P::get_w0w1s(pw, count, salt, &mut w0w1s);
+        let mut crypto = CryptoOpenSSL::new()?;
+        crypto.set_w0_from_w0s(&w0w1s[..CRYPTO_W_SIZE_BYTES])?;
+        crypto.set_w1_from_w1s(&w0w1s[CRYPTO_W_SIZE_BYTES..])?;
+
+        let mut context = Sha256::new()?;
+        context.update(&SPAKE2P_CONTEXT_PREFIX)?;
+        context.update(req)?;
+        context.update(resp)?;
+        Ok(Self {
+            crypto,
+            context,
+            pA: [0; CRYPTO_PUBLIC_KEY_SIZE_BYTES],
+        })
+    }
+
+    pub fn get_pA(&mut self) -> Result<&[u8], Error> {
+        self.crypto.get_pA(&mut self.pA)?;
+        Ok(&self.pA)
+    }
+
+    /// Confirm the verifier's cB, and return our cA
+    pub fn handle_pB(mut self, pB: &[u8], cB: &[u8]) -> Result<[u8; 32], Error> {
+        let mut hash = [0u8; crypto::SHA256_HASH_LEN_BYTES];
+        self.context.finish(&mut hash)?;
+        let mut TT = [0u8; crypto::SHA256_HASH_LEN_BYTES];
+        self.crypto.get_TT_as_prover(&hash, &self.pA, pB, &mut TT)?;
+
+        let mut Ke = [0u8; 16];
+        let mut cA = [0u8; 32];
+        let mut our_cB = [0u8; 32];
+        Spake2P::get_Ke_and_cAcB(&TT, &self.pA, pB, &mut Ke, &mut cA, &mut our_cB)?;
+        if our_cB != cB {
+            return Err(Error::InvalidSignature);
+        }
+        Ok(cA)
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
-    use super::Spake2P;
+    use super::{Spake2P, VerifierData, VERIFIER_SIZE_BYTES};
     use crate::{
         crypto,
         secure_channel::{spake2p::CRYPTO_W_SIZE_BYTES, spake2p_test_vectors::test_vectors::*},
@@ -256,4 +405,25 @@ mod tests {
             assert_eq!(cB, t.cB);
         }
     }
+
+    #[test]
+    fn test_verifier_data_params() {
+        let salt = [0u8; 16];
+        assert!(VerifierData::new_with_pw_params(20202021, 1000, &salt).is_ok());
+        assert!(VerifierData::new(&[0u8; VERIFIER_SIZE_BYTES], 100000, &[0u8; 32]).is_ok());
+
+        // Trivial and out of range passcodes
+        assert!(VerifierData::new_with_pw_params(12345678, 1000, &salt).is_err());
+        assert!(VerifierData::new_with_pw_params(99999999, 1000, &salt).is_err());
+        // Iteration count out of range
+        assert!(VerifierData::new_with_pw_params(20202021, 999, &salt).is_err());
+        // Salt too short, and too long
+        assert!(VerifierData::new_with_pw_params(20202021, 1000, &[0u8; 15]).is_err());
+        assert!(VerifierData::new_with_pw_params(20202021, 1000, &[0u8; 33]).is_err());
+        // Verifier of an incorrect size
+        assert!(VerifierData::new(&[0u8; VERIFIER_SIZE_BYTES - 1], 1000, &salt).is_err());
+
+        let v = VerifierData::new_with_pw(123456).unwrap();
+        assert_eq!(v.salt().len(), 16);
+    }
 }
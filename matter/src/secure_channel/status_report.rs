@@ -1,5 +1,6 @@
 use super::common::*;
 use crate::{error::Error, transport::packet::Packet};
+use byteorder::{ByteOrder, LittleEndian};
 
 #[allow(dead_code)]
 #[derive(Debug, Copy, Clone)]
@@ -22,6 +23,20 @@ pub enum GeneralCode {
     PermissionDenied = 15,
     DataLoss = 16,
 }
+/// A Status Report received from the peer
+#[derive(Debug, PartialEq)]
+pub struct StatusReport {
+    pub general_code: u16,
+    pub proto_id: u32,
+    pub proto_code: u16,
+}
+
+impl StatusReport {
+    pub fn is_success(&self) -> bool {
+        self.general_code == GeneralCode::Success as u16
+    }
+}
+
 pub fn create_status_report(
     proto_tx: &mut Packet,
     general_code: GeneralCode,
@@ -41,3 +56,40 @@ pub fn create_status_report(
 
     Ok(())
 }
+
+pub fn parse_status_report(buf: &[u8]) -> Result<StatusReport, Error> {
+    if buf.len() < 8 {
+        return Err(Error::TruncatedPacket);
+    }
+    Ok(StatusReport {
+        general_code: LittleEndian::read_u16(&buf[0..2]),
+        proto_id: LittleEndian::read_u32(&buf[2..6]),
+        proto_code: LittleEndian::read_u16(&buf[6..8]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_report() {
+        let mut tx = Packet::new_tx().unwrap();
+        create_sc_status_report(&mut tx, SCStatusCodes::NoSharedTrustRoots, None).unwrap();
+        let report = parse_status_report(tx.as_borrow_slice()).unwrap();
+        assert_eq!(
+            report,
+            StatusReport {
+                general_code: GeneralCode::Failure as u16,
+                proto_id: PROTO_ID_SECURE_CHANNEL as u32,
+                proto_code: SCStatusCodes::NoSharedTrustRoots as u16,
+            }
+        );
+        assert!(!report.is_success());
+
+        assert_eq!(
+            parse_status_report(&[0, 0, 0, 0]),
+            Err(Error::TruncatedPacket)
+        );
+    }
+}
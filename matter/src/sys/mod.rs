@@ -12,3 +12,13 @@ pub use self::sys_linux::*;
 mod posix;
 #[cfg(any(target_os = "macos", target_os = "linux"))]
 pub use self::posix::*;
+
+/// The kind of the DNS-SD service that the device advertises
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ServiceMode {
+    /// The operational service (_matter._tcp) for a fabric that the device is commissioned in
+    Commissioned,
+    /// The commissionable service (_matterc._udp), while the commissioning window is open.
+    /// An Enhanced Commissioning Window is advertised with a commissioning mode of 2
+    Commissionable { discriminator: u16, enhanced: bool },
+}
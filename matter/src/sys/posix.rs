@@ -1,69 +1,99 @@
 use std::{
-    convert::TryInto,
-    fs::{DirBuilder, File},
-    io::{Read, Write},
-    sync::{Arc, Mutex, Once},
+    fs::{self, File},
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
 };
 
-use crate::error::Error;
+use crate::{error::Error, persist::KvStore};
 
-pub struct Psm {}
-
-static mut G_PSM: Option<Arc<Mutex<Psm>>> = None;
-static INIT: Once = Once::new();
-
-const PSM_DIR: &str = "/tmp/plonk_psm";
-
-macro_rules! psm_path {
-    ($key:ident) => {
-        format!("{}/{}", PSM_DIR, $key)
-    };
+/// A [KvStore] that keeps each key in a separate file of a directory
+///
+/// A value is written to a temporary file first, which is then renamed over the old one,
+/// so a value is never left half-written. Each device on a host needs its own directory.
+pub struct FileKvStore {
+    dir: PathBuf,
+    // Serialises the writes, which share the temporary file
+    write_lock: Mutex<()>,
 }
 
-impl Psm {
-    fn new() -> Result<Self, Error> {
-        let result = DirBuilder::new().create(PSM_DIR);
-        if let Err(e) = result {
-            if e.kind() != std::io::ErrorKind::AlreadyExists {
-                return Err(e.into());
-            }
-        }
+impl FileKvStore {
+    /// Create a store in 'dir', which is created if it doesn't exist yet
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            write_lock: Mutex::new(()),
+        })
+    }
 
-        Ok(Self {})
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(key)
     }
 
-    pub fn get() -> Result<Arc<Mutex<Self>>, Error> {
-        unsafe {
-            INIT.call_once(|| {
-                G_PSM = Some(Arc::new(Mutex::new(Psm::new().unwrap())));
-            });
-            Ok(G_PSM.as_ref().ok_or(Error::Invalid)?.clone())
-        }
+    // Make the rename, or the removal, of a file durable
+    fn sync_dir(&self) -> Result<(), Error> {
+        File::open(&self.dir)?.sync_all()?;
+        Ok(())
     }
+}
 
-    pub fn set_kv_slice(&self, key: &str, val: &[u8]) -> Result<(), Error> {
-        let mut f = File::create(psm_path!(key))?;
+impl KvStore for FileKvStore {
+    fn set_kv_slice(&self, key: &str, val: &[u8]) -> Result<(), Error> {
+        let _guard = self.write_lock.lock()?;
+        let tmp = self.dir.join(".tmp");
+        let mut f = File::create(&tmp)?;
         f.write_all(val)?;
-        Ok(())
+        f.sync_all()?;
+        fs::rename(&tmp, self.path(key))?;
+        self.sync_dir()
     }
 
-    pub fn get_kv_slice(&self, key: &str, val: &mut Vec<u8>) -> Result<usize, Error> {
-        let mut f = File::open(psm_path!(key))?;
+    fn get_kv_slice(&self, key: &str, val: &mut Vec<u8>) -> Result<usize, Error> {
+        let mut f = match File::open(self.path(key)) {
+            Ok(f) => f,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Err(Error::NotFound),
+            Err(e) => return Err(e.into()),
+        };
         let len = f.read_to_end(val)?;
         Ok(len)
     }
 
-    pub fn set_kv_u64(&self, key: &str, val: u64) -> Result<(), Error> {
-        let mut f = File::create(psm_path!(key))?;
-        f.write_all(&val.to_be_bytes())?;
-        Ok(())
+    fn remove_kv(&self, key: &str) -> Result<(), Error> {
+        let _guard = self.write_lock.lock()?;
+        match fs::remove_file(self.path(key)) {
+            Ok(()) => self.sync_dir(),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
     }
+}
 
-    pub fn get_kv_u64(&self, key: &str, val: &mut u64) -> Result<(), Error> {
-        let mut f = File::open(psm_path!(key))?;
-        let mut vec = Vec::new();
-        let _ = f.read_to_end(&mut vec)?;
-        *val = u64::from_be_bytes(vec.as_slice().try_into()?);
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_kv_store() {
+        let dir = std::env::temp_dir().join(format!("matter-kv-test-{}", std::process::id()));
+        let store = FileKvStore::new(&dir).unwrap();
+        let mut val = Vec::new();
+        assert_eq!(store.get_kv_slice("a", &mut val), Err(Error::NotFound));
+
+        store.set_kv_slice("a", &[1, 2, 3]).unwrap();
+        store.set_kv_slice("a", &[4, 5]).unwrap();
+        assert_eq!(store.get_kv_slice("a", &mut val), Ok(2));
+        assert_eq!(val, vec![4, 5]);
+
+        // The values are still there for a new store on the same directory
+        let store = FileKvStore::new(&dir).unwrap();
+        let mut val = Vec::new();
+        assert_eq!(store.get_kv_slice("a", &mut val), Ok(2));
+
+        store.remove_kv("a").unwrap();
+        store.remove_kv("a").unwrap();
+        assert_eq!(store.get_kv_slice("a", &mut val), Err(Error::NotFound));
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
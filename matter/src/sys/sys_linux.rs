@@ -1,20 +1,128 @@
-use std::sync::{Arc, Mutex, Once};
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    sync::{Arc, Mutex, Once},
+    thread,
+};
 
 use crate::error::Error;
 
-use log::error;
+use log::{error, info};
+use socket2::{Domain, Protocol, Socket, Type};
 
-pub struct Mdns {}
+use super::ServiceMode;
+
+/* A minimal mDNS/DNS-SD responder
+ *
+ * - A socket each for IPv4 and IPv6 listens on the mDNS port, and answers the queries
+ *   that match any of the published services
+ * - All the records of a service (PTR, SRV, TXT, A, AAAA) are sent together, whichever
+ *   of its records is queried
+ * - A service is announced when it is published, and a goodbye is sent when it is dropped
+ * - A query from a port other than the mDNS port is a legacy unicast query (RFC 6762, 6.7).
+ *   It is answered by unicast, with the query's ID and questions, like a DNS response
+ * - The records are spread over as many packets as it takes to keep each one within the MTU
+ */
+
+const MDNS_PORT: u16 = 5353;
+const MDNS_IPV4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_IPV6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+const MATTER_PORT: u16 = 5540;
+const MAX_PACKET_SIZE: usize = 1500;
+// The largest response that fits in the MTU, along with the IPv6 and UDP headers
+const MAX_RESPONSE_SIZE: usize = MAX_PACKET_SIZE - 48;
+const DNS_HEADER_SIZE: usize = 12;
+
+// TTLs as recommended by RFC 6762, for host related and for other records
+const HOST_TTL: u32 = 120;
+const OTHER_TTL: u32 = 4500;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+const CLASS_CACHE_FLUSH: u16 = 0x8000;
+
+const SERVICES_DNS_SD: &str = "_services._dns-sd._udp.local";
+
+struct Service {
+    id: u64,
+    // The instance name
+    name: String,
+    // The service type, along with the protocol, e.g. _matter._tcp
+    service: &'static str,
+    port: u16,
+    subtypes: Vec<String>,
+    txt: Vec<String>,
+}
+
+impl Service {
+    fn service_name(&self) -> String {
+        format!("{}.local", self.service)
+    }
+
+    fn instance_name(&self) -> String {
+        format!("{}.{}.local", self.name, self.service)
+    }
+
+    fn subtype_name(&self, subtype: &str) -> String {
+        format!("{}._sub.{}.local", subtype, self.service)
+    }
+
+    fn matches(&self, hostname: &str, name: &str) -> bool {
+        name.eq_ignore_ascii_case(SERVICES_DNS_SD)
+            || name.eq_ignore_ascii_case(&self.service_name())
+            || name.eq_ignore_ascii_case(&self.instance_name())
+            || name.eq_ignore_ascii_case(hostname)
+            || self
+                .subtypes
+                .iter()
+                .any(|s| name.eq_ignore_ascii_case(&self.subtype_name(s)))
+    }
+}
+
+pub struct Mdns {
+    // The host name that the SRV records point to
+    hostname: String,
+    vid: u16,
+    pid: u16,
+    services: Vec<Service>,
+    next_id: u64,
+    // The sockets, along with the multicast group that they send to
+    sockets: Option<Vec<(Arc<UdpSocket>, SocketAddr)>>,
+}
 
 static mut G_MDNS: Option<Arc<Mutex<Mdns>>> = None;
 static INIT: Once = Once::new();
 
-#[allow(dead_code)]
-pub struct MdnsService {}
+/// A published service, the service is withdrawn when this is dropped
+pub struct MdnsService {
+    id: u64,
+}
+
+impl Drop for MdnsService {
+    fn drop(&mut self) {
+        if let Ok(mdns) = Mdns::get() {
+            if let Ok(mut mdns) = mdns.lock() {
+                mdns.remove(self.id);
+            }
+        }
+    }
+}
 
 impl Mdns {
     fn new() -> Self {
-        Self {}
+        Self {
+            hostname: format!("{:016X}.local", rand::random::<u64>()),
+            vid: 0,
+            pid: 0,
+            services: Vec::new(),
+            next_id: 0,
+            sockets: None,
+        }
     }
 
     pub fn get() -> Result<Arc<Mutex<Self>>, Error> {
@@ -26,8 +134,554 @@ impl Mdns {
         }
     }
 
-    pub fn publish_service(name: &str) -> Result<MdnsService, Error> {
-        error!("Linux is not yet supported for MDNS Service");
-        Ok(MdnsService {})
+    /// Set the values that are advertised in the commissionable service
+    pub fn set_values(&mut self, vid: u16, pid: u16) {
+        self.vid = vid;
+        self.pid = pid;
+    }
+
+    pub fn publish_service(name: &str, mode: ServiceMode) -> Result<MdnsService, Error> {
+        let mdns = Mdns::get()?;
+        let mut mdns = mdns.lock()?;
+        let id = mdns.add(name, mode);
+        Ok(MdnsService { id })
+    }
+
+    fn add(&mut self, name: &str, mode: ServiceMode) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let service = match mode {
+            ServiceMode::Commissioned => Service {
+                id,
+                name: name.to_owned(),
+                service: "_matter._tcp",
+                port: MATTER_PORT,
+                subtypes: Vec::new(),
+                txt: Vec::new(),
+            },
+            ServiceMode::Commissionable {
+                discriminator,
+                enhanced,
+            } => Service {
+                id,
+                name: name.to_owned(),
+                service: "_matterc._udp",
+                port: MATTER_PORT,
+                subtypes: vec![
+                    format!("_L{}", discriminator),
+                    format!("_S{}", discriminator >> 8),
+                    format!("_V{}", self.vid),
+                    "_CM".to_owned(),
+                ],
+                txt: vec![
+                    format!("D={}", discriminator),
+                    format!("VP={}+{}", self.vid, self.pid),
+                    format!("CM={}", if enhanced { 2 } else { 1 }),
+                ],
+            },
+        };
+        info!(
+            "Publishing mDNS service {} on {}",
+            service.instance_name(),
+            self.hostname
+        );
+
+        if self.sockets.is_none() {
+            self.sockets = Some(start_responder());
+        }
+        let announcement = self.encode_records(&service, false);
+        self.services.push(service);
+        self.send(&announcement);
+        id
+    }
+
+    fn remove(&mut self, id: u64) {
+        if let Some(index) = self.services.iter().position(|s| s.id == id) {
+            let service = self.services.remove(index);
+            let goodbye = self.encode_records(&service, true);
+            self.send(&goodbye);
+        }
+    }
+
+    fn send(&self, packets: &[Vec<u8>]) {
+        if let Some(sockets) = &self.sockets {
+            for (socket, group) in sockets {
+                for packet in packets {
+                    if let Err(e) = socket.send_to(packet, group) {
+                        error!("Error sending mDNS packet to {}: {}", group, e);
+                    }
+                }
+            }
+        }
+    }
+
+    // Returns the packets of the response to a query, none if no question matches the
+    // services. A legacy unicast response repeats the query's ID and questions
+    fn respond(&self, query: &Query, legacy_unicast: bool) -> Vec<Vec<u8>> {
+        let mut writer = DnsWriter::new();
+        for service in &self.services {
+            if query
+                .questions
+                .iter()
+                .any(|(name, _)| service.matches(&self.hostname, name))
+            {
+                self.write_records(&mut writer, service, false);
+            }
+        }
+        writer.finish(if legacy_unicast { Some(query) } else { None })
+    }
+
+    fn encode_records(&self, service: &Service, goodbye: bool) -> Vec<Vec<u8>> {
+        let mut writer = DnsWriter::new();
+        self.write_records(&mut writer, service, goodbye);
+        writer.finish(None)
+    }
+
+    fn write_records(&self, w: &mut DnsWriter, service: &Service, goodbye: bool) {
+        // A goodbye is the same set of records, with a TTL of 0
+        let (host_ttl, other_ttl) = if goodbye {
+            (0, 0)
+        } else {
+            (HOST_TTL, OTHER_TTL)
+        };
+        let service_name = service.service_name();
+        let instance_name = service.instance_name();
+
+        w.record(SERVICES_DNS_SD, TYPE_PTR, false, other_ttl, |w| {
+            w.name(&service_name)
+        });
+        w.record(&service_name, TYPE_PTR, false, other_ttl, |w| {
+            w.name(&instance_name)
+        });
+        for subtype in &service.subtypes {
+            w.record(
+                &service.subtype_name(subtype),
+                TYPE_PTR,
+                false,
+                other_ttl,
+                |w| w.name(&instance_name),
+            );
+        }
+        w.record(&instance_name, TYPE_SRV, true, host_ttl, |w| {
+            // Priority and Weight
+            w.u16(0);
+            w.u16(0);
+            w.u16(service.port);
+            w.name(&self.hostname);
+        });
+        w.record(&instance_name, TYPE_TXT, true, other_ttl, |w| {
+            if service.txt.is_empty() {
+                // A TXT record must have at least one string, even if empty
+                w.buf.push(0);
+            }
+            for txt in &service.txt {
+                w.buf.push(txt.len() as u8);
+                w.buf.extend_from_slice(txt.as_bytes());
+            }
+        });
+        if let Some(addr) = get_ipv4_addr() {
+            w.record(&self.hostname, TYPE_A, true, host_ttl, |w| {
+                w.buf.extend_from_slice(&addr.octets())
+            });
+        }
+        for addr in get_ipv6_addrs() {
+            w.record(&self.hostname, TYPE_AAAA, true, host_ttl, |w| {
+                w.buf.extend_from_slice(&addr.octets())
+            });
+        }
+    }
+}
+
+fn start_responder() -> Vec<(Arc<UdpSocket>, SocketAddr)> {
+    let mut sockets = Vec::new();
+    let groups = [
+        SocketAddr::new(IpAddr::V4(MDNS_IPV4), MDNS_PORT),
+        SocketAddr::new(IpAddr::V6(MDNS_IPV6), MDNS_PORT),
+    ];
+    for group in groups {
+        match open_socket(&group) {
+            Ok(socket) => {
+                let socket = Arc::new(socket);
+                let rx_socket = socket.clone();
+                thread::spawn(move || run_responder(&rx_socket, group));
+                sockets.push((socket, group));
+            }
+            // The other address family may still work
+            Err(e) => error!("Error opening mDNS socket for {}: {}", group, e),
+        }
+    }
+    sockets
+}
+
+fn open_socket(group: &SocketAddr) -> Result<UdpSocket, std::io::Error> {
+    // Other mDNS responders on the host may be bound to the port too
+    let socket = match group.ip() {
+        IpAddr::V4(group) => {
+            let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+            socket.set_reuse_address(true)?;
+            socket.bind(&SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), MDNS_PORT).into())?;
+            socket.join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED)?;
+            socket.set_multicast_loop_v4(true)?;
+            socket
+        }
+        IpAddr::V6(group) => {
+            let socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
+            socket.set_only_v6(true)?;
+            socket.set_reuse_address(true)?;
+            socket.bind(&SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), MDNS_PORT).into())?;
+            socket.join_multicast_v6(&group, 0)?;
+            socket.set_multicast_loop_v6(true)?;
+            socket
+        }
+    };
+    Ok(socket.into())
+}
+
+fn run_responder(socket: &UdpSocket, group: SocketAddr) {
+    let mut buf = [0u8; MAX_PACKET_SIZE];
+    loop {
+        let (len, src) = match socket.recv_from(&mut buf) {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Error receiving on mDNS socket: {}", e);
+                continue;
+            }
+        };
+        let query = match parse_query(&buf[..len]) {
+            Some(q) => q,
+            None => continue,
+        };
+        // Queries that don't originate from the mDNS port expect a unicast response
+        let legacy_unicast = src.port() != MDNS_PORT;
+        let response = match Mdns::get() {
+            Ok(mdns) => match mdns.lock() {
+                Ok(mdns) => mdns.respond(&query, legacy_unicast),
+                Err(_) => Vec::new(),
+            },
+            Err(_) => Vec::new(),
+        };
+        let dst = if legacy_unicast { src } else { group };
+        for packet in response {
+            if let Err(e) = socket.send_to(&packet, dst) {
+                error!("Error sending mDNS response to {}: {}", dst, e);
+            }
+        }
+    }
+}
+
+// The address of the interface, that the default route goes through
+fn get_ipv4_addr() -> Option<Ipv4Addr> {
+    // Nothing is sent, connecting only selects the source address
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((MDNS_IPV4, MDNS_PORT)).ok()?;
+    match socket.local_addr().ok()?.ip() {
+        IpAddr::V4(addr) if !addr.is_unspecified() && !addr.is_loopback() => Some(addr),
+        _ => None,
+    }
+}
+
+// All the IPv6 addresses of the host, except the loopback
+fn get_ipv6_addrs() -> Vec<Ipv6Addr> {
+    // Each line is: <address> <ifindex> <prefix len> <scope> <flags> <interface name>
+    let if_inet6 = fs::read_to_string("/proc/net/if_inet6").unwrap_or_default();
+    if_inet6
+        .lines()
+        .filter_map(|line| {
+            let addr = line.split_whitespace().next()?;
+            let addr = u128::from_str_radix(addr, 16).ok()?;
+            let addr = Ipv6Addr::from(addr);
+            if addr.is_loopback() {
+                None
+            } else {
+                Some(addr)
+            }
+        })
+        .collect()
+}
+
+// The names aren't compressed, so that the records can go in any of the packets
+struct DnsWriter {
+    // The record being written
+    buf: Vec<u8>,
+    records: Vec<Vec<u8>>,
+}
+
+impl DnsWriter {
+    fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            records: Vec::new(),
+        }
+    }
+
+    fn u16(&mut self, val: u16) {
+        self.buf.extend_from_slice(&val.to_be_bytes());
+    }
+
+    fn name(&mut self, name: &str) {
+        for label in name.split('.').filter(|l| !l.is_empty()) {
+            self.buf.push(label.len() as u8);
+            self.buf.extend_from_slice(label.as_bytes());
+        }
+        self.buf.push(0);
+    }
+
+    fn record<F>(&mut self, name: &str, rtype: u16, cache_flush: bool, ttl: u32, rdata: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        self.name(name);
+        self.u16(rtype);
+        let class = if cache_flush {
+            CLASS_IN | CLASS_CACHE_FLUSH
+        } else {
+            CLASS_IN
+        };
+        self.u16(class);
+        self.buf.extend_from_slice(&ttl.to_be_bytes());
+        // The RDATA length is filled in once the RDATA is written
+        let len_offset = self.buf.len();
+        self.u16(0);
+        rdata(self);
+        let rdata_len = (self.buf.len() - len_offset - 2) as u16;
+        self.buf[len_offset..len_offset + 2].copy_from_slice(&rdata_len.to_be_bytes());
+        self.records.push(std::mem::take(&mut self.buf));
+    }
+
+    // Packs the records into as few packets as keep within the MTU. The packets of a legacy
+    // unicast response each carry the query's ID and questions
+    fn finish(self, query: Option<&Query>) -> Vec<Vec<u8>> {
+        let (id, qdcount, questions) = match query {
+            Some(q) => (q.id, q.qdcount, q.question_section.as_slice()),
+            None => (0, 0, &[][..]),
+        };
+        let mut packets = Vec::new();
+        let mut packet = Vec::new();
+        let mut answers = 0_u16;
+        for record in self.records {
+            // A record too large for a packet of its own still goes out, alone
+            if answers > 0 && packet.len() + record.len() > MAX_RESPONSE_SIZE {
+                packets.push(Self::finish_packet(packet, answers));
+                packet = Vec::new();
+                answers = 0;
+            }
+            if answers == 0 {
+                // The header: Flags: Response + Authoritative, the answer count is filled in
+                // once the packet is done
+                packet.extend_from_slice(&id.to_be_bytes());
+                packet.extend_from_slice(&[0x84, 0]);
+                packet.extend_from_slice(&qdcount.to_be_bytes());
+                packet.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+                packet.extend_from_slice(questions);
+            }
+            packet.extend_from_slice(&record);
+            answers += 1;
+        }
+        if answers > 0 {
+            packets.push(Self::finish_packet(packet, answers));
+        }
+        packets
+    }
+
+    fn finish_packet(mut packet: Vec<u8>, answers: u16) -> Vec<u8> {
+        packet[6..8].copy_from_slice(&answers.to_be_bytes());
+        packet
+    }
+}
+
+struct Query {
+    id: u16,
+    // The (name, type) of the questions, that are for the types that are answered
+    questions: Vec<(String, u16)>,
+    qdcount: u16,
+    // The question section as it was received. Its compressed names can only point back
+    // into the header or the questions, so they still hold once it is copied behind the
+    // header of the response
+    question_section: Vec<u8>,
+}
+
+// Returns the query, if the packet is a query
+fn parse_query(packet: &[u8]) -> Option<Query> {
+    if packet.len() < DNS_HEADER_SIZE || packet[2] & 0x80 != 0 {
+        // Too short, or a response
+        return None;
+    }
+    let qdcount = u16::from_be_bytes([packet[4], packet[5]]);
+    let mut offset = DNS_HEADER_SIZE;
+    let mut questions = Vec::with_capacity(qdcount as usize);
+    for _ in 0..qdcount {
+        let name = parse_name(packet, &mut offset)?;
+        let qtype = u16::from_be_bytes([*packet.get(offset)?, *packet.get(offset + 1)?]);
+        // Skip the type and the class
+        offset += 4;
+        if matches!(
+            qtype,
+            TYPE_A | TYPE_PTR | TYPE_TXT | TYPE_AAAA | TYPE_SRV | TYPE_ANY
+        ) {
+            questions.push((name, qtype));
+        }
+    }
+    Some(Query {
+        id: u16::from_be_bytes([packet[0], packet[1]]),
+        questions,
+        qdcount,
+        question_section: packet.get(DNS_HEADER_SIZE..offset)?.to_vec(),
+    })
+}
+
+fn parse_name(packet: &[u8], offset: &mut usize) -> Option<String> {
+    let mut labels: Vec<&str> = Vec::new();
+    let mut pos = *offset;
+    // Where parsing continues, once the name is done, if the name is compressed
+    let mut end = None;
+    // Guard against pointer loops
+    let mut jumps = 0;
+    loop {
+        let len = *packet.get(pos)? as usize;
+        if len == 0 {
+            pos += 1;
+            break;
+        } else if len & 0xC0 == 0xC0 {
+            let ptr = ((len & 0x3F) << 8) | *packet.get(pos + 1)? as usize;
+            if end.is_none() {
+                end = Some(pos + 2);
+            }
+            jumps += 1;
+            if jumps > 16 {
+                return None;
+            }
+            pos = ptr;
+        } else {
+            let label = packet.get(pos + 1..pos + 1 + len)?;
+            labels.push(std::str::from_utf8(label).ok()?);
+            pos += 1 + len;
+        }
+    }
+    *offset = end.unwrap_or(pos);
+    Some(labels.join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query_compressed() {
+        // A query for _matterc._udp.local PTR, and a compressed _L3840._sub._matterc._udp.local
+        let mut packet = vec![0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0];
+        packet.extend_from_slice(b"\x08_matterc\x04_udp\x05local\x00");
+        packet.extend_from_slice(&[0, 12, 0, 1]);
+        packet.extend_from_slice(b"\x06_L3840\x04_sub\xc0\x0c");
+        packet.extend_from_slice(&[0, 12, 0, 1]);
+
+        let query = parse_query(&packet).unwrap();
+        assert_eq!(
+            query.questions,
+            vec![
+                ("_matterc._udp.local".to_owned(), TYPE_PTR),
+                ("_L3840._sub._matterc._udp.local".to_owned(), TYPE_PTR)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_query_rejects() {
+        // A response, and a pointer loop
+        let response = [0, 0, 0x84, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1];
+        assert!(parse_query(&response).is_none());
+        let pointer_loop = [0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0xc0, 0x0c, 0, 1, 0, 1];
+        assert!(parse_query(&pointer_loop).is_none());
+    }
+
+    fn commissionable_mdns(subtypes: Vec<String>) -> Mdns {
+        let mut mdns = Mdns::new();
+        mdns.set_values(0xFFF1, 0x8000);
+        mdns.services.push(Service {
+            id: 0,
+            name: "ABCD".to_owned(),
+            service: "_matterc._udp",
+            port: MATTER_PORT,
+            subtypes,
+            txt: vec!["D=3840".to_owned()],
+        });
+        mdns
+    }
+
+    // A query with the ID 0x1234, for a PTR record of the name
+    fn ptr_query(name: &str) -> Vec<u8> {
+        let mut packet = vec![0x12, 0x34, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        let mut writer = DnsWriter::new();
+        writer.name(name);
+        packet.extend_from_slice(&writer.buf);
+        packet.extend_from_slice(&[0, 12, 0, 1]);
+        packet
+    }
+
+    fn answers(packet: &[u8]) -> u16 {
+        u16::from_be_bytes([packet[6], packet[7]])
+    }
+
+    #[test]
+    fn test_respond_commissionable() {
+        let mdns = commissionable_mdns(vec!["_L3840".to_owned()]);
+
+        let q = |name: &str| parse_query(&ptr_query(name)).unwrap();
+        assert!(mdns.respond(&q("_matter._tcp.local"), false).is_empty());
+        assert_eq!(
+            mdns.respond(&q("_L3840._sub._matterc._udp.local"), false)
+                .len(),
+            1
+        );
+        let response = mdns.respond(&q("ABCD._matterc._udp.local"), false);
+        // The response is an authoritative answer, with the records for the service, and
+        // without the query's ID and questions
+        assert_eq!(&response[0][..6], &[0, 0, 0x84, 0, 0, 0]);
+        assert!(answers(&response[0]) >= 5);
+    }
+
+    #[test]
+    fn test_respond_legacy_unicast() {
+        let mdns = commissionable_mdns(vec!["_L3840".to_owned()]);
+
+        let query = ptr_query("_matterc._udp.local");
+        let response = mdns.respond(&parse_query(&query).unwrap(), true);
+        assert_eq!(response.len(), 1);
+        // The ID and the question are repeated, ahead of the answers
+        let question_len = query.len() - DNS_HEADER_SIZE;
+        assert_eq!(&response[0][..6], &[0x12, 0x34, 0x84, 0, 0, 1]);
+        assert_eq!(
+            &response[0][DNS_HEADER_SIZE..DNS_HEADER_SIZE + question_len],
+            &query[DNS_HEADER_SIZE..]
+        );
+        assert!(answers(&response[0]) >= 5);
+    }
+
+    #[test]
+    fn test_respond_split() {
+        // Enough subtypes that their PTR records don't fit in a single packet
+        let subtypes: Vec<String> = (0..100).map(|i| format!("_L{}", i)).collect();
+        let mdns = commissionable_mdns(subtypes);
+        let single = commissionable_mdns(Vec::new());
+
+        for legacy_unicast in [false, true] {
+            let query = parse_query(&ptr_query("_matterc._udp.local")).unwrap();
+            let response = mdns.respond(&query, legacy_unicast);
+            assert!(response.len() > 1);
+            for packet in &response {
+                assert!(packet.len() <= MAX_RESPONSE_SIZE);
+                assert_eq!(
+                    &packet[..2],
+                    if legacy_unicast {
+                        &[0x12, 0x34]
+                    } else {
+                        &[0, 0]
+                    }
+                );
+            }
+            // All the records are there, across the packets
+            let total: u16 = response.iter().map(|p| answers(p)).sum();
+            let expected = answers(&single.respond(&query, legacy_unicast)[0]);
+            assert_eq!(total, expected + 100);
+        }
     }
 }
@@ -1,9 +1,13 @@
 use std::sync::{Arc, Mutex, Once};
 
+use super::ServiceMode;
 use crate::error::Error;
 use astro_dnssd::{DNSServiceBuilder, RegisteredDnsService};
 
-pub struct Mdns {}
+pub struct Mdns {
+    vid: u16,
+    pid: u16,
+}
 
 static mut G_MDNS: Option<Arc<Mutex<Mdns>>> = None;
 static INIT: Once = Once::new();
@@ -15,7 +19,7 @@ pub struct MdnsService {
 
 impl Mdns {
     fn new() -> Self {
-        Self {}
+        Self { vid: 0, pid: 0 }
     }
 
     pub fn get() -> Result<Arc<Mutex<Self>>, Error> {
@@ -27,8 +31,34 @@ impl Mdns {
         }
     }
 
-    pub fn publish_service(name: &str) -> Result<MdnsService, Error> {
-        let s = DNSServiceBuilder::new("_matter._tcp", 5540)
+    /// Set the values that are advertised in the commissionable service
+    pub fn set_values(&mut self, vid: u16, pid: u16) {
+        self.vid = vid;
+        self.pid = pid;
+    }
+
+    pub fn publish_service(name: &str, mode: ServiceMode) -> Result<MdnsService, Error> {
+        let builder = match mode {
+            ServiceMode::Commissioned => DNSServiceBuilder::new("_matter._tcp", 5540),
+            ServiceMode::Commissionable {
+                discriminator,
+                enhanced,
+            } => {
+                let mdns = Mdns::get()?;
+                let mdns = mdns.lock()?;
+                let regtype = format!(
+                    "_matterc._udp,_L{},_S{},_V{},_CM",
+                    discriminator,
+                    discriminator >> 8,
+                    mdns.vid
+                );
+                DNSServiceBuilder::new(&regtype, 5540)
+                    .with_key_value("D".to_owned(), discriminator.to_string())
+                    .with_key_value("VP".to_owned(), format!("{}+{}", mdns.vid, mdns.pid))
+                    .with_key_value("CM".to_owned(), if enhanced { "2" } else { "1" }.to_owned())
+            }
+        };
+        let s = builder
             .with_name(name)
             .register()
             .map_err(|_| Error::MdnsError)?;
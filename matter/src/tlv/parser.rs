@@ -145,9 +145,23 @@ static VALUE_EXTRACTOR: [ExtractValue; MAX_VALUE_INDEX] = [
     // True 9
     { |_t| (0, ElementType::True) },
     // F32  10
-    { |_t| (0, ElementType::Last) },
+    {
+        |t| {
+            (
+                0,
+                ElementType::F32(LittleEndian::read_f32(&t.buf[t.current..])),
+            )
+        }
+    },
     // F64  11
-    { |_t| (0, ElementType::Last) },
+    {
+        |t| {
+            (
+                0,
+                ElementType::F64(LittleEndian::read_f64(&t.buf[t.current..])),
+            )
+        }
+    },
     // Utf8l 12
     {
         |t| match read_length_value(1, t) {
@@ -381,6 +395,33 @@ impl<'a> TLVElement<'a> {
         }
     }
 
+    pub fn i16(&self) -> Result<i16, Error> {
+        match self.element_type {
+            ElementType::S8(a) => Ok(a.into()),
+            ElementType::S16(a) => Ok(a),
+            _ => Err(Error::TLVTypeMismatch),
+        }
+    }
+
+    pub fn i32(&self) -> Result<i32, Error> {
+        match self.element_type {
+            ElementType::S8(a) => Ok(a.into()),
+            ElementType::S16(a) => Ok(a.into()),
+            ElementType::S32(a) => Ok(a),
+            _ => Err(Error::TLVTypeMismatch),
+        }
+    }
+
+    pub fn i64(&self) -> Result<i64, Error> {
+        match self.element_type {
+            ElementType::S8(a) => Ok(a.into()),
+            ElementType::S16(a) => Ok(a.into()),
+            ElementType::S32(a) => Ok(a.into()),
+            ElementType::S64(a) => Ok(a),
+            _ => Err(Error::TLVTypeMismatch),
+        }
+    }
+
     pub fn u8(&self) -> Result<u8, Error> {
         match self.element_type {
             ElementType::U8(a) => Ok(a),
@@ -415,6 +456,25 @@ impl<'a> TLVElement<'a> {
         }
     }
 
+    pub fn f32(&self) -> Result<f32, Error> {
+        match self.element_type {
+            ElementType::F32(a) => Ok(a),
+            _ => Err(Error::TLVTypeMismatch),
+        }
+    }
+
+    pub fn f64(&self) -> Result<f64, Error> {
+        match self.element_type {
+            ElementType::F32(a) => Ok(a.into()),
+            ElementType::F64(a) => Ok(a),
+            _ => Err(Error::TLVTypeMismatch),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.element_type == ElementType::Null
+    }
+
     pub fn slice(&self) -> Result<&'a [u8], Error> {
         match self.element_type {
             ElementType::Str8l(s)
@@ -689,11 +749,21 @@ impl<'a> Iterator for TLVContainerIterator<'a> {
     }
 }
 
+/// Returns None, if the element with the context tag is either absent or null
+pub fn nullable<'a, T>(
+    t: &TLVElement<'a>,
+    tag: u32,
+    f: impl Fn(&TLVElement<'a>) -> Result<T, Error>,
+) -> Result<Option<T>, Error> {
+    match t.find_tag(tag) {
+        Ok(e) if e.get_element_type() == ElementType::Null => Ok(None),
+        Ok(e) => f(&e).map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
 pub fn get_root_node(b: &[u8]) -> Result<TLVElement, Error> {
-    TLVList::new(b)
-        .iter()
-        .next()
-        .ok_or(Error::InvalidData)
+    TLVList::new(b).iter().next().ok_or(Error::InvalidData)
 }
 
 pub fn get_root_node_struct(b: &[u8]) -> Result<TLVElement, Error> {
@@ -856,6 +926,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_valid_value_float() {
+        // A struct with a tagged f32 (1.5) and a tagged f64 (-2.5)
+        let b = [
+            0x15, 0x2a, 0x1, 0x0, 0x0, 0xc0, 0x3f, 0x2b, 0x2, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x4,
+            0xc0, 0x18,
+        ];
+        let root = get_root_node_struct(&b).unwrap();
+        assert_eq!(root.find_tag(1).unwrap().f32(), Ok(1.5));
+        assert_eq!(root.find_tag(1).unwrap().f64(), Ok(1.5));
+        assert_eq!(root.find_tag(2).unwrap().f64(), Ok(-2.5));
+        assert_eq!(root.find_tag(2).unwrap().f32(), Err(Error::TLVTypeMismatch));
+    }
+
     #[test]
     fn test_valid_value_string() {
         // This is a tagged string, with tag 0 and length 4, and we have 4 bytes in the string
@@ -1,4 +1,4 @@
-use super::{TLVContainerIterator, TLVElement, TLVWriter, TagType};
+use super::{ElementType, TLVContainerIterator, TLVElement, TLVWriter, TagType};
 use crate::error::Error;
 use core::slice::Iter;
 
@@ -108,6 +108,79 @@ impl ToTLV for Vec<u8> {
     }
 }
 
+/// The raw element, for the fields that aren't parsed yet
+impl<'a> FromTLV<'a> for TLVElement<'a> {
+    fn from_tlv(t: &TLVElement<'a>) -> Result<TLVElement<'a>, Error> {
+        Ok(*t)
+    }
+}
+
+/// Re-encode the element, along with all that it contains, with a new tag
+impl<'a> ToTLV for TLVElement<'a> {
+    fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        match self.get_element_type() {
+            ElementType::S8(v) => tw.i8(tag, v),
+            ElementType::S16(v) => tw.i16(tag, v),
+            ElementType::S32(v) => tw.i32(tag, v),
+            ElementType::S64(v) => tw.i64(tag, v),
+            ElementType::U8(v) => tw.u8(tag, v),
+            ElementType::U16(v) => tw.u16(tag, v),
+            ElementType::U32(v) => tw.u32(tag, v),
+            ElementType::U64(v) => tw.u64(tag, v),
+            ElementType::False => tw.bool(tag, false),
+            ElementType::True => tw.bool(tag, true),
+            ElementType::F32(v) => tw.f32(tag, v),
+            ElementType::F64(v) => tw.f64(tag, v),
+            ElementType::Utf8l(v) => tw.utf8(tag, v),
+            ElementType::Utf16l(v) => tw.utf16(tag, v),
+            ElementType::Str8l(v) => tw.str8(tag, v),
+            ElementType::Str16l(v) => tw.str16(tag, v),
+            ElementType::Null => tw.null(tag),
+            ElementType::Struct(_) | ElementType::Array(_) | ElementType::List(_) => {
+                match self.get_element_type() {
+                    ElementType::Struct(_) => tw.start_struct(tag)?,
+                    ElementType::Array(_) => tw.start_array(tag)?,
+                    _ => tw.start_list(tag)?,
+                }
+                if let Some(iter) = self.iter() {
+                    for e in iter {
+                        e.to_tlv(tw, e.get_tag())?;
+                    }
+                }
+                tw.end_container()
+            }
+            _ => Err(Error::Invalid),
+        }
+    }
+}
+
+/// A value that may be null. Unlike an Option<>, it is always encoded, as a Null if there
+/// is no value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Nullable<T> {
+    Null,
+    NotNull(T),
+}
+
+impl<'a, T: FromTLV<'a>> FromTLV<'a> for Nullable<T> {
+    fn from_tlv(t: &TLVElement<'a>) -> Result<Nullable<T>, Error> {
+        if t.is_null() {
+            Ok(Nullable::Null)
+        } else {
+            Ok(Nullable::NotNull(T::from_tlv(t)?))
+        }
+    }
+}
+
+impl<T: ToTLV> ToTLV for Nullable<T> {
+    fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        match self {
+            Nullable::Null => tw.null(tag),
+            Nullable::NotNull(v) => v.to_tlv(tw, tag),
+        }
+    }
+}
+
 /// Applies to all the Option<> Processing
 impl<'a, T: FromTLV<'a>> FromTLV<'a> for Option<T> {
     fn from_tlv(t: &TLVElement<'a>) -> Result<Option<T>, Error> {
@@ -233,8 +306,12 @@ impl<'a, T> FromTLV<'a> for TLVArray<'a, T> {
 
 #[cfg(test)]
 mod tests {
-    use super::{FromTLV, OctetStr, TLVElement, TLVWriter, TagType, ToTLV};
-    use crate::{error::Error, tlv::TLVList, utils::writebuf::WriteBuf};
+    use super::{FromTLV, Nullable, OctetStr, TLVElement, TLVWriter, TagType, ToTLV};
+    use crate::{
+        error::Error,
+        tlv::{get_root_node_struct, TLVList},
+        utils::writebuf::WriteBuf,
+    };
     use matter_macro_derive::{FromTLV, ToTLV};
 
     #[derive(ToTLV)]
@@ -309,4 +386,62 @@ mod tests {
         assert_eq!(test.b, None);
         assert_eq!(test.c, Some(11));
     }
+
+    #[derive(FromTLV, ToTLV, Debug, PartialEq)]
+    struct TestDeriveNullable {
+        a: Option<Nullable<u16>>,
+        b: Option<Nullable<u16>>,
+        c: Option<Nullable<u16>>,
+    }
+
+    #[test]
+    fn test_derive_nullable() {
+        let mut buf: [u8; 20] = [0; 20];
+        let buf_len = buf.len();
+        let mut writebuf = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut writebuf);
+
+        let test = TestDeriveNullable {
+            a: Some(Nullable::Null),
+            b: Some(Nullable::NotNull(11)),
+            c: None,
+        };
+        test.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+        assert_eq!(writebuf.as_borrow_slice(), [21, 52, 0, 36, 1, 11, 24]);
+
+        let root = TLVList::new(writebuf.as_borrow_slice())
+            .iter()
+            .next()
+            .unwrap();
+        assert_eq!(TestDeriveNullable::from_tlv(&root).unwrap(), test);
+    }
+
+    #[test]
+    fn test_element_totlv() {
+        // {0: 10, 1: [1, "ab"], 2: null}
+        let b = [21, 36, 0, 10, 54, 1, 4, 1, 12, 2, 97, 98, 24, 52, 2, 24];
+        let root = get_root_node_struct(&b).unwrap();
+
+        let mut buf: [u8; 20] = [0; 20];
+        let buf_len = buf.len();
+        let mut writebuf = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut writebuf);
+        tw.start_struct(TagType::Anonymous).unwrap();
+        root.find_tag(1)
+            .unwrap()
+            .to_tlv(&mut tw, TagType::Context(5))
+            .unwrap();
+        tw.end_container().unwrap();
+        assert_eq!(
+            writebuf.as_borrow_slice(),
+            [21, 54, 5, 4, 1, 12, 2, 97, 98, 24, 24]
+        );
+
+        // The whole structure, as is
+        let mut buf: [u8; 20] = [0; 20];
+        let mut writebuf = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut writebuf);
+        root.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+        assert_eq!(writebuf.as_borrow_slice(), b);
+    }
 }
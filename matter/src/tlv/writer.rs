@@ -73,6 +73,33 @@ impl<'a, 'b> TLVWriter<'a, 'b> {
         self.buf.le_i8(data)
     }
 
+    pub fn i16(&mut self, tag_type: TagType, data: i16) -> Result<(), Error> {
+        if data >= i8::MIN as i16 && data <= i8::MAX as i16 {
+            self.i8(tag_type, data as i8)
+        } else {
+            self.put_control_tag(tag_type, WriteElementType::S16)?;
+            self.buf.le_u16(data as u16)
+        }
+    }
+
+    pub fn i32(&mut self, tag_type: TagType, data: i32) -> Result<(), Error> {
+        if data >= i16::MIN as i32 && data <= i16::MAX as i32 {
+            self.i16(tag_type, data as i16)
+        } else {
+            self.put_control_tag(tag_type, WriteElementType::S32)?;
+            self.buf.le_u32(data as u32)
+        }
+    }
+
+    pub fn i64(&mut self, tag_type: TagType, data: i64) -> Result<(), Error> {
+        if data >= i32::MIN as i64 && data <= i32::MAX as i64 {
+            self.i32(tag_type, data as i32)
+        } else {
+            self.put_control_tag(tag_type, WriteElementType::S64)?;
+            self.buf.le_u64(data as u64)
+        }
+    }
+
     pub fn u8(&mut self, tag_type: TagType, data: u8) -> Result<(), Error> {
         self.put_control_tag(tag_type, WriteElementType::U8)?;
         self.buf.le_u8(data)
@@ -111,6 +138,16 @@ impl<'a, 'b> TLVWriter<'a, 'b> {
         }
     }
 
+    pub fn f32(&mut self, tag_type: TagType, data: f32) -> Result<(), Error> {
+        self.put_control_tag(tag_type, WriteElementType::F32)?;
+        self.buf.le_u32(data.to_bits())
+    }
+
+    pub fn f64(&mut self, tag_type: TagType, data: f64) -> Result<(), Error> {
+        self.put_control_tag(tag_type, WriteElementType::F64)?;
+        self.buf.le_u64(data.to_bits())
+    }
+
     pub fn str8(&mut self, tag_type: TagType, data: &[u8]) -> Result<(), Error> {
         if data.len() > 256 {
             error!("use put_str16() instead");
@@ -191,6 +228,10 @@ impl<'a, 'b> TLVWriter<'a, 'b> {
         self.no_val(tag_type, WriteElementType::List)
     }
 
+    pub fn null(&mut self, tag_type: TagType) -> Result<(), Error> {
+        self.no_val(tag_type, WriteElementType::Null)
+    }
+
     pub fn end_container(&mut self) -> Result<(), Error> {
         self.no_val(TagType::Anonymous, WriteElementType::EndCnt)
     }
@@ -203,6 +244,11 @@ impl<'a, 'b> TLVWriter<'a, 'b> {
         }
     }
 
+    /// Copy TLV elements that were already encoded elsewhere, as is
+    pub fn raw(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.buf.copy_from_slice(data)
+    }
+
     pub fn get_tail(&self) -> usize {
         self.buf.get_tail()
     }
@@ -210,6 +256,25 @@ impl<'a, 'b> TLVWriter<'a, 'b> {
     pub fn rewind_to(&mut self, anchor: usize) {
         self.buf.rewind_tail_to(anchor);
     }
+
+    /// Hold back 'len' bytes at the end of the buffer, for what must be written last
+    pub fn shrink(&mut self, len: usize) -> Result<(), Error> {
+        self.buf.shrink(len)
+    }
+
+    pub fn expand(&mut self, len: usize) -> Result<(), Error> {
+        self.buf.expand(len)
+    }
+
+    /// Returns true if a write failed for lack of space, since clear_overflow(). The
+    /// encoders often ignore such errors, so this is how we know the buffer filled up
+    pub fn is_overflowed(&self) -> bool {
+        self.buf.is_overflowed()
+    }
+
+    pub fn clear_overflow(&mut self) {
+        self.buf.clear_overflow();
+    }
 }
 
 #[cfg(test)]
@@ -298,4 +363,26 @@ mod tests {
             [36, 1, 13, 48, 2, 5, 10, 11, 12, 13, 14, 48, 3, 2, 10, 11, 36, 4, 13, 0]
         );
     }
+
+    #[test]
+    fn test_put_signed_and_float() {
+        let mut buf: [u8; 24] = [0; 24];
+        let buf_len = buf.len();
+        let mut writebuf = WriteBuf::new(&mut buf, buf_len);
+        let mut tw = TLVWriter::new(&mut writebuf);
+
+        // The signed integers are encoded in the smallest width that holds them
+        tw.i16(TagType::Context(1), -2).unwrap();
+        tw.i32(TagType::Context(2), -300).unwrap();
+        tw.i64(TagType::Context(3), 0x1_0000_0000).unwrap();
+        tw.f32(TagType::Anonymous, 1.5).unwrap();
+
+        assert_eq!(
+            buf,
+            [
+                32, 1, 0xfe, 33, 2, 0xd4, 0xfe, 35, 3, 0, 0, 0, 0, 1, 0, 0, 0, 10, 0, 0, 0xc0,
+                0x3f, 0, 0
+            ]
+        );
+    }
 }
@@ -1,6 +1,7 @@
 pub mod exchange;
 pub mod mgr;
 pub mod mrp;
+pub mod msg_ctr;
 pub mod network;
 pub mod packet;
 pub mod plain_hdr;
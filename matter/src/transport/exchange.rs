@@ -1,6 +1,7 @@
 use boxslab::{BoxSlab, Slab};
 use colored::*;
 use log::{error, info, trace};
+use rand::Rng;
 use std::any::Any;
 use std::fmt;
 
@@ -11,7 +12,14 @@ use heapless::LinearMap;
 
 use super::packet::PacketPool;
 use super::session::CloneData;
-use super::{mrp::ReliableMessage, packet::Packet, session::SessionHandle, session::SessionMgr};
+use super::{
+    mrp::{ReliableMessage, RetransAction},
+    network::Address,
+    packet::Packet,
+    session::SessionHandle,
+    session::SessionMgr,
+    session::{Session, SessionMode, MAX_SESSIONS},
+};
 
 pub struct ExchangeCtx<'a> {
     pub exch: &'a mut Exchange,
@@ -125,7 +133,10 @@ impl Exchange {
 
         session.pre_send(&mut proto_tx)?;
         self.mrp.pre_send(&mut proto_tx)?;
-        session.send(proto_tx)
+        session.send(&mut proto_tx)?;
+        let peer_params = *session.get_peer_mrp_params();
+        self.mrp.post_send(&mut proto_tx, &peer_params);
+        Ok(())
     }
 }
 
@@ -162,6 +173,9 @@ pub struct ExchangeMgr {
     // keys: exch-id
     exchanges: LinearMap<u16, Exchange, MAX_EXCHANGES>,
     sess_mgr: SessionMgr,
+    next_exch_id: u16,
+    // The local session ids of the evicted sessions, until the protocols are told
+    evicted: Vec<u16>,
 }
 
 pub const MAX_MRP_ENTRIES: usize = 4;
@@ -171,9 +185,63 @@ impl ExchangeMgr {
         Self {
             sess_mgr,
             exchanges: Default::default(),
+            next_exch_id: rand::thread_rng().gen_range(0..0xFFFF),
+            evicted: Vec::new(),
+        }
+    }
+
+    fn get_next_exch_id(&mut self) -> u16 {
+        loop {
+            let exch_id = self.next_exch_id;
+            self.next_exch_id = self.next_exch_id.wrapping_add(1);
+            // Ensure the id doesn't match any existing exchange, including the ones initiated by peers
+            if !self.exchanges.contains_key(&exch_id) {
+                return exch_id;
+            }
         }
     }
 
+    /// Initiate a new exchange on the session with the local session id
+    pub fn initiate(&mut self, sess_id: u16) -> Result<ExchangeCtx<'_>, Error> {
+        let sess_idx = self
+            .sess_mgr
+            .get_index_with_id(sess_id)
+            .ok_or(Error::NoSession)?;
+        self.initiate_on(sess_idx)
+    }
+
+    /// Initiate a new exchange on the unsecured session with the peer, creating the
+    /// session if it doesn't exist yet. This is used for establishing secure sessions.
+    pub fn initiate_unsecured(&mut self, peer: Address) -> Result<ExchangeCtx<'_>, Error> {
+        let sess_idx = match self.sess_mgr.get_or_add(0, peer, None, false) {
+            Ok(idx) => idx,
+            Err(Error::NoSpace) => {
+                let evict_index = self.sess_mgr.get_lru();
+                self.vacate_session(evict_index)?;
+                self.sess_mgr.get_or_add(0, peer, None, false)?
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        };
+        self.initiate_on(sess_idx)
+    }
+
+    fn initiate_on(&mut self, sess_idx: usize) -> Result<ExchangeCtx<'_>, Error> {
+        let exch_id = self.get_next_exch_id();
+        let exch = ExchangeMgr::_get(
+            &mut self.exchanges,
+            sess_idx,
+            exch_id,
+            Role::Initiator,
+            true,
+        )?;
+        Ok(ExchangeCtx {
+            exch,
+            sess: self.sess_mgr.get_session_handle(sess_idx),
+        })
+    }
+
     pub fn get_sess_mgr(&mut self) -> &mut SessionMgr {
         &mut self.sess_mgr
     }
@@ -232,9 +300,17 @@ impl ExchangeMgr {
         let index = match index {
             Some(s) => s,
             None => {
-                // The sessions were full, evict one session, and re-perform post-recv
-                let evict_index = self.sess_mgr.get_lru();
-                self.evict_session(evict_index)?;
+                // The sessions were full, evict one session, and re-perform post-recv. A
+                // group sender only takes the place of another group sender
+                let evict_index = if proto_rx.plain.is_group() {
+                    self.sess_mgr.get_lru_group().ok_or_else(|| {
+                        error!("Sessions full, dropping the group message");
+                        Error::NoSpace
+                    })?
+                } else {
+                    self.sess_mgr.get_lru()
+                };
+                self.vacate_session(evict_index)?;
                 info!("Reattempting session creation");
                 self.sess_mgr.post_recv(&proto_rx)?.ok_or(Error::Invalid)?
             }
@@ -244,6 +320,24 @@ impl ExchangeMgr {
         // Decrypt the message
         session.recv(&mut proto_rx)?;
 
+        if let SessionMode::Group(fab_idx, _) = session.get_session_mode() {
+            // Group messages are neither acknowledged, nor responded to
+            if proto_rx.proto.is_reliable() {
+                error!("Dropping a reliable group message");
+                return Err(Error::Invalid);
+            }
+            // The message is authenticated by now, so its counter can be trusted
+            let src = session.get_peer_node_id().unwrap_or_default();
+            if let Err(e) = self
+                .sess_mgr
+                .accept_group_ctr(fab_idx, src, proto_rx.plain.ctr)
+            {
+                error!("Dropping a duplicate group message from {:x}", src);
+                return Err(e);
+            }
+        }
+        let session = self.sess_mgr.get_session_handle(index);
+
         // Get the exchange
         let exch = ExchangeMgr::_get(
             &mut self.exchanges,
@@ -300,9 +394,75 @@ impl ExchangeMgr {
         }
     }
 
-    pub fn evict_session(&mut self, index: usize) -> Result<(), Error> {
+    /// Resend the messages that weren't acknowledged in time. If a message isn't
+    /// acknowledged even after the retransmissions, its exchange and session are closed
+    pub fn retrans(&mut self) {
+        let mut failed_sessions: LinearMap<usize, (), MAX_EXCHANGES> = LinearMap::new();
+        for (exch_id, exchange) in self.exchanges.iter_mut() {
+            let peer_params = self
+                .sess_mgr
+                .mut_by_index(exchange.sess_idx)
+                .map(|s| *s.get_peer_mrp_params())
+                .unwrap_or_default();
+            match exchange.mrp.retrans_action(&peer_params) {
+                RetransAction::Wait => (),
+                RetransAction::Resend(data, peer) => {
+                    if let Err(e) = self.sess_mgr.send_raw(data, peer) {
+                        error!("Error in retransmitting on exch {}: {:?}", exch_id, e);
+                    }
+                }
+                RetransAction::GiveUp => {
+                    error!("Peer unreachable, closing exch {}", exch_id);
+                    exchange.close();
+                    let _ = failed_sessions.insert(exchange.sess_idx, ());
+                }
+            }
+        }
+        for (sess_idx, _) in failed_sessions.iter() {
+            info!("Peer unreachable, closing session with index: {}", sess_idx);
+            if let Err(e) = self.evict_session(*sess_idx) {
+                error!("Error in closing session {}: {:?}", sess_idx, e);
+            }
+        }
+    }
+
+    /// Evict all the sessions of a fabric, as the fabric is removed
+    pub fn evict_fabric_sessions(&mut self, fab_idx: u8) -> Result<(), Error> {
+        self.sess_mgr.remove_group_peers(fab_idx);
+        self.evict_sessions_if("the fabric is removed", |s| {
+            s.get_local_fabric_idx() == Some(fab_idx)
+        })
+    }
+
+    /// Evict all the PASE sessions, as the Fail-Safe expires
+    pub fn evict_pase_sessions(&mut self) -> Result<(), Error> {
+        self.evict_sessions_if("the PASE commissioning is over", |s| {
+            s.get_session_mode() == SessionMode::Pase
+        })
+    }
+
+    fn evict_sessions_if(
+        &mut self,
+        reason: &str,
+        f: impl Fn(&Session) -> bool,
+    ) -> Result<(), Error> {
+        for index in 0..MAX_SESSIONS {
+            if self.sess_mgr.mut_by_index(index).is_some_and(|s| f(s)) {
+                info!("Evicting session with index: {}, {}", index, reason);
+                self.evict_session(index)?;
+            }
+        }
+        Ok(())
+    }
+
+    // The sessions are full, reclaim the LRU session at 'index' for a new one
+    fn vacate_session(&mut self, index: usize) -> Result<(), Error> {
         info!("Sessions full, vacating session with index: {}", index);
-        // If we enter here, we have an LRU session that needs to be reclaimed
+        self.evict_session(index)
+    }
+
+    /// Close the session, along with its exchanges. The caller logs why
+    pub fn evict_session(&mut self, index: usize) -> Result<(), Error> {
         // As per the spec, we need to send a CLOSE here
 
         let mut session = self.sess_mgr.get_session_handle(index);
@@ -313,10 +473,11 @@ impl ExchangeMgr {
             None,
         )?;
 
-        if let Some((_, exchange)) =
-            self.exchanges
-                .iter_mut()
-                .find(|(_, e)| if e.sess_idx == index { true } else { false })
+        // Nothing is ever sent on a Group session
+        if let Some((_, exchange)) = self
+            .exchanges
+            .iter_mut()
+            .find(|(_, e)| e.sess_idx == index && !session.is_group())
         {
             // Send Close_session on this exchange, and then close the session
             // Should this be done for all exchanges?
@@ -344,16 +505,24 @@ impl ExchangeMgr {
             // Remove from exchange list
             self.exchanges.remove(&exch_id);
         }
+        if !session.is_group() {
+            self.evicted.push(session.get_local_sess_id());
+        }
         self.sess_mgr.remove(index);
         Ok(())
     }
 
+    /// The local session ids of the sessions that were evicted since the last call
+    pub fn take_evicted(&mut self) -> Vec<u16> {
+        std::mem::take(&mut self.evicted)
+    }
+
     pub fn add_session(&mut self, clone_data: CloneData) -> Result<SessionHandle, Error> {
         let sess_idx = match self.sess_mgr.clone_session(&clone_data) {
             Ok(idx) => idx,
             Err(Error::NoSpace) => {
                 let evict_index = self.sess_mgr.get_lru();
-                self.evict_session(evict_index)?;
+                self.vacate_session(evict_index)?;
                 self.sess_mgr.clone_session(&clone_data)?
             }
             Err(e) => {
@@ -530,4 +699,40 @@ mod tests {
         }
         //        println!("Session mgr {}", mgr.sess_mgr);
     }
+
+    #[test]
+    fn test_evict_fabric_sessions() {
+        let mut sess_mgr = SessionMgr::new();
+        let transport = Box::new(DummyNetwork::new());
+        sess_mgr.add_network_interface(transport).unwrap();
+        let mut mgr = ExchangeMgr::new(sess_mgr);
+
+        for (local_sess_id, mode) in [
+            (1, SessionMode::Case(1)),
+            (2, SessionMode::Case(2)),
+            (3, SessionMode::Case(1)),
+            (4, SessionMode::Pase),
+        ] {
+            let clone_data = CloneData::new(
+                12341234,
+                43211234,
+                local_sess_id + 100,
+                local_sess_id,
+                Address::default(),
+                mode,
+            );
+            mgr.add_session(clone_data).unwrap();
+        }
+        let _ = ExchangeMgr::_get(&mut mgr.exchanges, 0, 10, Role::Responder, true).unwrap();
+
+        mgr.evict_fabric_sessions(1).unwrap();
+        assert_eq!(mgr.take_evicted(), vec![1, 3]);
+        assert!(mgr.take_evicted().is_empty());
+        assert!(mgr.sess_mgr.get_with_id(1).is_none());
+        assert!(mgr.sess_mgr.get_with_id(3).is_none());
+        assert!(mgr.get_with_id(10).is_none());
+        // The sessions of the other fabrics, and the PASE session remain
+        assert!(mgr.sess_mgr.get_with_id(2).is_some());
+        assert!(mgr.sess_mgr.get_with_id(4).is_some());
+    }
 }
@@ -3,30 +3,47 @@ use boxslab::{BoxSlab, Slab};
 use heapless::LinearMap;
 use log::{debug, error, info};
 
+use std::sync::Arc;
+
+use crate::data_model::sdm::failsafe::FailSafe;
 use crate::error::*;
+use crate::fabric::FabricMgr;
+use crate::group_keys::GroupKeys;
 
 use crate::transport::mrp::ReliableMessage;
 use crate::transport::packet::PacketPool;
 use crate::transport::{exchange, packet::Packet, proto_demux, queue, session, udp};
 
-use super::proto_demux::ProtoCtx;
+use super::proto_demux::{ProtoCtx, SessionEvent, UnsolicitedDest};
 use super::queue::Msg;
 
 pub struct Mgr {
     exch_mgr: exchange::ExchangeMgr,
     proto_demux: proto_demux::ProtoDemux,
     rx_q: Receiver<Msg>,
+    fabric_mgr: Arc<FabricMgr>,
+    failsafe: Arc<FailSafe>,
+    // How far we have caught up with the fabrics that were removed
+    removed_cursor: usize,
 }
 
 impl Mgr {
-    pub fn new() -> Result<Mgr, Error> {
+    pub fn new(
+        fabric_mgr: Arc<FabricMgr>,
+        group_keys: Arc<GroupKeys>,
+        failsafe: Arc<FailSafe>,
+    ) -> Result<Mgr, Error> {
         let mut sess_mgr = session::SessionMgr::new();
         let udp_transport = Box::new(udp::UdpListener::new()?);
         sess_mgr.add_network_interface(udp_transport)?;
+        sess_mgr.set_group_keys(group_keys);
         Ok(Mgr {
             proto_demux: proto_demux::ProtoDemux::new(),
             exch_mgr: exchange::ExchangeMgr::new(sess_mgr),
             rx_q: queue::WorkQ::init()?,
+            fabric_mgr,
+            failsafe,
+            removed_cursor: 0,
         })
     }
 
@@ -47,10 +64,15 @@ impl Mgr {
     }
 
     fn handle_rxtx(&mut self) -> Result<(), Error> {
-        let result = self.exch_mgr.recv().map_err(|e| {
-            error!("Error in recv: {:?}", e);
-            e
-        })?;
+        let result = match self.exch_mgr.recv() {
+            Ok(r) => r,
+            // Nothing was received, this is fine
+            Err(Error::Timeout) => return Ok(()),
+            Err(e) => {
+                error!("Error in recv: {:?}", e);
+                return Err(e);
+            }
+        };
 
         if result.is_none() {
             // Nothing to process, return quietly
@@ -67,6 +89,12 @@ impl Mgr {
         // Proto Dispatch
         match self.proto_demux.handle(&mut proto_ctx) {
             Ok(r) => {
+                if proto_ctx.exch_ctx.sess.is_group() {
+                    // Nothing is ever sent on a Group session, so the exchange is done
+                    // once the message is handled
+                    proto_ctx.exch_ctx.exch.close();
+                    return Ok(());
+                }
                 if let proto_demux::ResponseRequired::No = r {
                     // We need to send the Ack if reliability is enabled, in this case
                     return Ok(());
@@ -112,6 +140,25 @@ impl Mgr {
         Ok(())
     }
 
+    fn handle_unsolicited(&mut self) -> Result<(), Error> {
+        while let Some((proto_id, dest)) = self.proto_demux.get_unsolicited() {
+            let mut tx = Self::new_tx()?;
+            let mut exch_ctx = match dest {
+                UnsolicitedDest::Session(sess_id) => self.exch_mgr.initiate(sess_id)?,
+                UnsolicitedDest::Peer(peer) => self.exch_mgr.initiate_unsecured(peer)?,
+            };
+            self.proto_demux
+                .handle_unsolicited(proto_id, &mut exch_ctx, &mut tx)?;
+
+            let exch_id = exch_ctx.exch.get_id();
+            self.send_to_exchange(exch_id, tx).map_err(|e| {
+                error!("Error in sending msg {:?}", e);
+                e
+            })?;
+        }
+        Ok(())
+    }
+
     pub fn start(&mut self) -> Result<(), Error> {
         loop {
             // Handle network operations
@@ -125,6 +172,11 @@ impl Mgr {
                 continue;
             }
 
+            // Handle any messages that protocols want to send on their own
+            if self.handle_unsolicited().is_err() {
+                error!("Error in handle_unsolicited");
+            }
+
             // Handle any pending acknowledgement send
             let mut acks_to_send: LinearMap<u16, (), { exchange::MAX_MRP_ENTRIES }> =
                 LinearMap::new();
@@ -144,6 +196,42 @@ impl Mgr {
                 }
             }
 
+            // Handle any pending retransmissions
+            self.exch_mgr.retrans();
+
+            // Handle an expiry of the Fail-Safe, the fabric it rolls back is handled below
+            if self.failsafe.check_expiry() {
+                if let Err(e) = self.exch_mgr.evict_pase_sessions() {
+                    error!("Error in closing the PASE sessions {:?}", e);
+                }
+            }
+
+            // Handle the sessions of any fabrics that were removed
+            for fab_idx in self.fabric_mgr.get_removed_since(&mut self.removed_cursor) {
+                if let Err(e) = self.exch_mgr.evict_fabric_sessions(fab_idx) {
+                    error!(
+                        "Error in evicting the sessions of fabric {} {:?}",
+                        fab_idx, e
+                    );
+                }
+            }
+
+            // Handle the state that the protocols keep for the evicted sessions
+            for sess_id in self.exch_mgr.take_evicted() {
+                let event = SessionEvent::Evicted(sess_id);
+                if let Err(e) = self.proto_demux.handle_session_event(&event) {
+                    error!(
+                        "Error in handling the eviction of session {} {:?}",
+                        sess_id, e
+                    );
+                }
+            }
+
+            // Handle any changes to the groups that we are a member of
+            if let Err(e) = self.exch_mgr.get_sess_mgr().update_multicast() {
+                error!("Error in updating multicast groups {:?}", e);
+            }
+
             // Handle exchange purging
             //    This need not be done in each turn of the loop, maybe once in 5 times or so?
             self.exch_mgr.purge();
@@ -2,26 +2,127 @@ use std::time::Duration;
 use std::time::SystemTime;
 
 use crate::{error::*, secure_channel, transport::packet::Packet};
-use log::error;
+use log::{error, info};
+use rand::Rng;
+
+use super::network::Address;
 
 // 200 ms
 const MRP_STANDALONE_ACK_TIMEOUT: u64 = 200;
 
+// The retransmission parameters, as defined in the spec. The peer's intervals are the
+// spec defaults, until the session parameters are exchanged
+const MRP_IDLE_INTERVAL: Duration = Duration::from_millis(500);
+const MRP_ACTIVE_INTERVAL: Duration = Duration::from_millis(300);
+// The peer is considered active, if we heard from it within this time
+const MRP_ACTIVE_THRESHOLD: Duration = Duration::from_millis(4000);
+// The max intervals that a peer may ask for
+const MRP_MAX_INTERVAL: Duration = Duration::from_secs(3600);
+// The initial transmission, and up to 4 retransmissions
+const MRP_MAX_TRANSMISSIONS: u8 = 5;
+const MRP_BACKOFF_BASE: f64 = 1.6;
+const MRP_BACKOFF_JITTER: f64 = 0.25;
+const MRP_BACKOFF_MARGIN: f64 = 1.1;
+const MRP_BACKOFF_THRESHOLD: u8 = 1;
+
+// The time to wait for an acknowledgement, after the message was sent 'send_count' times
+//   t = i * MARGIN * BASE^max(0, n - THRESHOLD) * (1 + random(0, 1) * JITTER)
+// where 'n' is the number of retransmissions so far
+fn backoff(interval: Duration, send_count: u8, random: f64) -> Duration {
+    let retrans_count = send_count.saturating_sub(1);
+    let exponent = retrans_count.saturating_sub(MRP_BACKOFF_THRESHOLD) as i32;
+    interval.mul_f64(
+        MRP_BACKOFF_MARGIN * MRP_BACKOFF_BASE.powi(exponent) * (1.0 + random * MRP_BACKOFF_JITTER),
+    )
+}
+
+/// The MRP parameters of a node, as exchanged during session establishment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MrpParams {
+    /// The retransmission interval, while the node is idle
+    pub idle_interval: Duration,
+    /// The retransmission interval, while the node is active
+    pub active_interval: Duration,
+    /// How long the node stays active, after its last activity
+    pub active_threshold: Duration,
+}
+
+impl Default for MrpParams {
+    fn default() -> Self {
+        Self {
+            idle_interval: MRP_IDLE_INTERVAL,
+            active_interval: MRP_ACTIVE_INTERVAL,
+            active_threshold: MRP_ACTIVE_THRESHOLD,
+        }
+    }
+}
+
+impl MrpParams {
+    /// The parameters that a peer asked for, with the spec defaults for those that
+    /// were not supplied
+    pub fn new(
+        idle_interval: Option<Duration>,
+        active_interval: Option<Duration>,
+        active_threshold: Option<Duration>,
+    ) -> Self {
+        let default = Self::default();
+        Self {
+            idle_interval: idle_interval
+                .unwrap_or(default.idle_interval)
+                .min(MRP_MAX_INTERVAL),
+            active_interval: active_interval
+                .unwrap_or(default.active_interval)
+                .min(MRP_MAX_INTERVAL),
+            active_threshold: active_threshold.unwrap_or(default.active_threshold),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct RetransEntry {
     // The msg counter that we are waiting to be acknowledged
     msg_ctr: u32,
-    // This will additionally have retransmission count and periods once we implement it
+    // The encoded message, that is retransmitted as-is
+    data: Vec<u8>,
+    peer: Address,
+    // The number of times the message was sent
+    send_count: u8,
+    retrans_time: SystemTime,
 }
 
 impl RetransEntry {
-    pub fn new(msg_ctr: u32) -> Self {
-        Self { msg_ctr }
+    pub fn new(msg_ctr: u32, data: &[u8], peer: Address, interval: Duration) -> Self {
+        let mut entry = Self {
+            msg_ctr,
+            data: data.to_vec(),
+            peer,
+            send_count: 0,
+            retrans_time: SystemTime::now(),
+        };
+        entry.sent(interval);
+        entry
     }
 
     pub fn get_msg_ctr(&self) -> u32 {
         self.msg_ctr
     }
+
+    fn sent(&mut self, interval: Duration) {
+        self.send_count += 1;
+        let random = rand::thread_rng().gen_range(0.0..1.0);
+        self.retrans_time = SystemTime::now() + backoff(interval, self.send_count, random);
+    }
+}
+
+/// What needs to be done for a pending retransmission
+#[derive(Debug, PartialEq)]
+pub enum RetransAction<'a> {
+    /// Nothing, the acknowledgement may still arrive
+    Wait,
+    /// Resend the encoded message to the peer
+    Resend(&'a [u8], Address),
+    /// The message was sent the maximum number of times, without an acknowledgement
+    GiveUp,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -59,6 +160,8 @@ impl AckEntry {
 pub struct ReliableMessage {
     retrans: Option<RetransEntry>,
     ack: Option<AckEntry>,
+    // When we last received a message on this exchange
+    last_rx: Option<SystemTime>,
 }
 
 impl ReliableMessage {
@@ -96,26 +199,76 @@ impl ReliableMessage {
             self.ack = None;
         }
 
-        if !proto_tx.is_reliable() {
-            return Ok(());
-        }
-
-        if self.retrans.is_some() {
+        if proto_tx.is_reliable() && self.retrans.is_some() {
             // This indicates there was some existing entry for same sess-id/exch-id, which shouldnt happen
             error!("Previous retrans entry for this exchange already exists");
             return Err(Error::Invalid);
         }
-
-        self.retrans = Some(RetransEntry::new(proto_tx.plain.ctr));
         Ok(())
     }
 
+    // Keep the encoded message, until it is acknowledged
+    pub fn post_send(&mut self, proto_tx: &mut Packet, peer_params: &MrpParams) {
+        if proto_tx.is_reliable() {
+            let interval = self.peer_interval(peer_params);
+            let msg_ctr = proto_tx.plain.ctr;
+            let peer = proto_tx.peer;
+            self.retrans = Some(RetransEntry::new(
+                msg_ctr,
+                proto_tx.as_borrow_slice(),
+                peer,
+                interval,
+            ));
+        }
+    }
+
+    // The peer's retransmission interval, depending on whether it is active or idle
+    fn peer_interval(&self, peer_params: &MrpParams) -> Duration {
+        let since_rx = self.last_rx.map(|t| t.elapsed());
+        if let Some(Ok(since_rx)) = since_rx {
+            if since_rx < peer_params.active_threshold {
+                return peer_params.active_interval;
+            }
+        }
+        peer_params.idle_interval
+    }
+
+    /// Check the pending retransmission, if any, and what needs to be done for it,
+    /// as per the retransmission parameters of the peer
+    pub fn retrans_action(&mut self, peer_params: &MrpParams) -> RetransAction<'_> {
+        let interval = self.peer_interval(peer_params);
+        match &self.retrans {
+            Some(e) if e.retrans_time > SystemTime::now() => return RetransAction::Wait,
+            Some(e) if e.send_count >= MRP_MAX_TRANSMISSIONS => {
+                error!(
+                    "No acknowledgement for msg counter {}, giving up",
+                    e.msg_ctr
+                );
+                self.retrans = None;
+                return RetransAction::GiveUp;
+            }
+            _ => (),
+        }
+        match &mut self.retrans {
+            Some(entry) => {
+                info!(
+                    "Retransmitting msg counter {}, attempt {}",
+                    entry.msg_ctr, entry.send_count
+                );
+                entry.sent(interval);
+                RetransAction::Resend(&entry.data, entry.peer)
+            }
+            None => RetransAction::Wait,
+        }
+    }
+
     /* A note about Message ACKs, it is a bit asymmetric in the sense that:
      * -  there can be only one pending ACK per exchange (so this is per-exchange)
      * -  there can be only one pending retransmission per exchange (so this is per-exchange)
      * -  duplicate detection should happen per session (obviously), so that part is per-session
      */
     pub fn recv(&mut self, proto_rx: &Packet) -> Result<(), Error> {
+        self.last_rx = Some(SystemTime::now());
         if proto_rx.proto.is_ack() {
             // Handle received Acks
             let ack_msg_ctr = proto_rx.proto.get_ack_msg_ctr().ok_or(Error::Invalid)?;
@@ -141,3 +294,70 @@ impl ReliableMessage {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let interval = Duration::from_millis(500);
+        // The first transmission and the first retransmission aren't backed off
+        assert_eq!(backoff(interval, 1, 0.0), Duration::from_millis(550));
+        assert_eq!(backoff(interval, 2, 0.0), Duration::from_millis(550));
+        assert_eq!(backoff(interval, 3, 0.0), Duration::from_millis(880));
+        assert_eq!(backoff(interval, 4, 0.0), Duration::from_millis(1408));
+        // The jitter adds up to 25%
+        assert_eq!(backoff(interval, 1, 1.0), Duration::from_micros(687_500));
+        assert_eq!(backoff(interval, 3, 1.0), Duration::from_millis(1100));
+    }
+
+    #[test]
+    fn test_retrans_and_give_up() {
+        let mut mrp = ReliableMessage::new();
+        let params = MrpParams::default();
+        assert_eq!(mrp.retrans_action(&params), RetransAction::Wait);
+
+        let data = [1, 2, 3];
+        let peer = Address::default();
+        mrp.retrans = Some(RetransEntry::new(10, &data, peer, MRP_IDLE_INTERVAL));
+        assert_eq!(mrp.retrans_action(&params), RetransAction::Wait);
+
+        for _ in 1..MRP_MAX_TRANSMISSIONS {
+            mrp.retrans.as_mut().unwrap().retrans_time = SystemTime::now();
+            assert_eq!(
+                mrp.retrans_action(&params),
+                RetransAction::Resend(&data, peer)
+            );
+            assert_eq!(mrp.retrans_action(&params), RetransAction::Wait);
+        }
+        mrp.retrans.as_mut().unwrap().retrans_time = SystemTime::now();
+        assert_eq!(mrp.retrans_action(&params), RetransAction::GiveUp);
+        assert!(mrp.is_empty());
+        assert_eq!(mrp.retrans_action(&params), RetransAction::Wait);
+    }
+
+    #[test]
+    fn test_peer_interval() {
+        // A sleepy peer, with a long idle interval
+        let params = MrpParams::new(
+            Some(Duration::from_secs(30)),
+            Some(Duration::from_millis(200)),
+            None,
+        );
+        assert_eq!(params.active_threshold, MRP_ACTIVE_THRESHOLD);
+
+        let mut mrp = ReliableMessage::new();
+        assert_eq!(mrp.peer_interval(&params), Duration::from_secs(30));
+        // The peer is active, until the threshold since we last heard from it
+        mrp.last_rx = Some(SystemTime::now());
+        assert_eq!(mrp.peer_interval(&params), Duration::from_millis(200));
+        mrp.last_rx = Some(SystemTime::now() - MRP_ACTIVE_THRESHOLD);
+        assert_eq!(mrp.peer_interval(&params), Duration::from_secs(30));
+
+        // The intervals are capped
+        let params = MrpParams::new(Some(Duration::from_secs(100_000)), None, None);
+        assert_eq!(params.idle_interval, MRP_MAX_INTERVAL);
+        assert_eq!(params.active_interval, MRP_ACTIVE_INTERVAL);
+    }
+}
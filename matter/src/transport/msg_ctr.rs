@@ -0,0 +1,121 @@
+/* Message Counters of the received group messages
+ *
+ * - Group keys live as long as their key set, so a group message could be replayed as long
+ *   as the key is in use. The receiver tracks the counters of each (fabric, source node),
+ *   and drops the messages that it has already seen, before they are dispatched
+ * - The counters of a peer are trusted from its first message (trust-first), anything older
+ *   than that message is treated as a duplicate
+ * - The counters may roll over, so they are compared modulo 2^32. A message that is ahead
+ *   of the largest counter by less than 2^31 is new, one that is more than the window size
+ *   behind it is a duplicate
+ */
+
+// The number of counters behind the largest one, that are tracked
+const MSG_CTR_WINDOW_SIZE: u32 = 32;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RxCtrWindow {
+    max_ctr: u32,
+    // Bit 'n' is set if the counter 'max_ctr - n - 1' was received
+    bitmap: u32,
+}
+
+impl RxCtrWindow {
+    /// The window, as it is synchronised with the first message of the peer
+    pub fn new(ctr: u32) -> Self {
+        Self {
+            max_ctr: ctr,
+            bitmap: u32::MAX,
+        }
+    }
+
+    /// Record the counter of a message. Returns false if it is a duplicate
+    pub fn accept(&mut self, ctr: u32) -> bool {
+        let ahead = ctr.wrapping_sub(self.max_ctr);
+        if ahead == 0 {
+            false
+        } else if ahead < (1 << 31) {
+            // The previous max counter is tracked too, as long as it is within the window
+            self.bitmap = if ahead > MSG_CTR_WINDOW_SIZE {
+                0
+            } else {
+                self.bitmap.checked_shl(ahead).unwrap_or(0) | (1 << (ahead - 1))
+            };
+            self.max_ctr = ctr;
+            true
+        } else {
+            let behind = self.max_ctr.wrapping_sub(ctr);
+            if behind > MSG_CTR_WINDOW_SIZE {
+                return false;
+            }
+            let bit = 1 << (behind - 1);
+            if self.bitmap & bit != 0 {
+                false
+            } else {
+                self.bitmap |= bit;
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RxCtrWindow;
+
+    #[test]
+    fn test_duplicates() {
+        let mut w = RxCtrWindow::new(100);
+        assert!(!w.accept(100));
+        // Older than the first message
+        assert!(!w.accept(99));
+        assert!(w.accept(101));
+        assert!(!w.accept(101));
+    }
+
+    #[test]
+    fn test_out_of_order() {
+        let mut w = RxCtrWindow::new(100);
+        assert!(w.accept(105));
+        assert!(w.accept(103));
+        assert!(w.accept(102));
+        assert!(!w.accept(103));
+        assert!(w.accept(104));
+        assert!(!w.accept(104));
+        assert!(w.accept(101));
+        assert!(!w.accept(100));
+    }
+
+    #[test]
+    fn test_window_limits() {
+        let mut w = RxCtrWindow::new(100);
+        assert!(w.accept(200));
+        // Within the window
+        assert!(w.accept(168));
+        // Behind the window
+        assert!(!w.accept(167));
+        // Far ahead
+        assert!(w.accept(200 + (1 << 30)));
+        assert!(!w.accept(200));
+    }
+
+    #[test]
+    fn test_window_size_jump() {
+        let mut w = RxCtrWindow::new(100);
+        assert!(w.accept(132));
+        // The previous max is at the edge of the window
+        assert!(!w.accept(100));
+        assert!(w.accept(101));
+        assert!(!w.accept(99));
+    }
+
+    #[test]
+    fn test_rollover() {
+        let mut w = RxCtrWindow::new(u32::MAX - 1);
+        assert!(w.accept(u32::MAX));
+        assert!(w.accept(0));
+        assert!(w.accept(1));
+        assert!(!w.accept(u32::MAX));
+        assert!(!w.accept(0));
+    }
+}
@@ -1,6 +1,6 @@
 use std::{
     fmt::{Debug, Display},
-    net::{IpAddr, Ipv4Addr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
 };
 
 use crate::error::Error;
@@ -35,4 +35,13 @@ impl Debug for Address {
 pub trait NetworkInterface {
     fn recv(&self, in_buf: &mut [u8]) -> Result<(usize, Address), Error>;
     fn send(&self, out_buf: &[u8], addr: Address) -> Result<usize, Error>;
+
+    /// Join the IPv6 multicast group, that group messages are received on
+    fn join_multicast(&self, _addr: Ipv6Addr) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn leave_multicast(&self, _addr: Ipv6Addr) -> Result<(), Error> {
+        Ok(())
+    }
 }
@@ -191,6 +191,16 @@ impl<'a> Packet<'a> {
         }
     }
 
+    /// Check if the payload can be authenticated with the key, without decrypting it
+    pub fn can_decrypt(&self, peer_nodeid: u64, dec_key: &[u8]) -> bool {
+        match &self.data {
+            Direction::Rx(pb, RxState::PlainDecode) => {
+                proto_hdr::can_decrypt(&self.plain, pb, peer_nodeid, dec_key)
+            }
+            _ => false,
+        }
+    }
+
     pub fn is_plain_hdr_decoded(&self) -> Result<bool, Error> {
         match &self.data {
             Direction::Rx(_, state) => match state {
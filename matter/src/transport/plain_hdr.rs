@@ -8,6 +8,7 @@ use log::info;
 pub enum SessionType {
     None,
     Encrypted,
+    Group,
 }
 
 impl Default for SessionType {
@@ -25,14 +26,20 @@ bitflags! {
     }
 }
 
+// The session type in the security flags
+const SEC_FLAGS_SESSION_TYPE_MASK: u8 = 0x03;
+const SEC_FLAGS_SESSION_TYPE_GROUP: u8 = 0x01;
+
 // This is the unencrypted message
 #[derive(Debug, Default)]
 pub struct PlainHdr {
     pub flags: MsgFlags,
     pub sess_type: SessionType,
     pub sess_id: u16,
+    pub sec_flags: u8,
     pub ctr: u32,
     peer_nodeid: Option<u64>,
+    group_id: Option<u16>,
 }
 
 impl PlainHdr {
@@ -48,6 +55,10 @@ impl PlainHdr {
             None
         }
     }
+
+    pub fn get_group_id(&self) -> Option<u16> {
+        self.group_id
+    }
 }
 
 impl PlainHdr {
@@ -55,17 +66,26 @@ impl PlainHdr {
     pub fn decode(&mut self, msg: &mut ParseBuf) -> Result<(), Error> {
         self.flags = MsgFlags::from_bits(msg.le_u8()?).ok_or(Error::Invalid)?;
         self.sess_id = msg.le_u16()?;
-        let _sec_flags = msg.le_u8()?;
-        self.sess_type = if self.sess_id != 0 {
-            SessionType::Encrypted
-        } else {
-            SessionType::None
-        };
+        self.sec_flags = msg.le_u8()?;
+        self.sess_type =
+            if self.sec_flags & SEC_FLAGS_SESSION_TYPE_MASK == SEC_FLAGS_SESSION_TYPE_GROUP {
+                SessionType::Group
+            } else if self.sess_id != 0 {
+                SessionType::Encrypted
+            } else {
+                SessionType::None
+            };
         self.ctr = msg.le_u32()?;
 
         if self.flags.contains(MsgFlags::SRC_ADDR_PRESENT) {
             self.peer_nodeid = Some(msg.le_u64()?);
         }
+        if self.flags.contains(MsgFlags::DSIZ_UNICAST_NODEID) {
+            // This would be our own node id
+            let _dest_nodeid = msg.le_u64()?;
+        } else if self.flags.contains(MsgFlags::DSIZ_GROUPCAST_NODEID) {
+            self.group_id = Some(msg.le_u16()?);
+        }
 
         info!(
             "[decode] flags: {:?}, session type: {:#?}, sess_id: {}, ctr: {}",
@@ -77,7 +97,7 @@ impl PlainHdr {
     pub fn encode(&mut self, resp_buf: &mut WriteBuf) -> Result<(), Error> {
         resp_buf.le_u8(self.flags.bits())?;
         resp_buf.le_u16(self.sess_id)?;
-        resp_buf.le_u8(0)?;
+        resp_buf.le_u8(self.sec_flags)?;
         resp_buf.le_u32(self.ctr)?;
         if let Some(d) = self.peer_nodeid {
             resp_buf.le_u64(d)?;
@@ -86,7 +106,11 @@ impl PlainHdr {
     }
 
     pub fn is_encrypted(&self) -> bool {
-        self.sess_type == SessionType::Encrypted
+        self.sess_type == SessionType::Encrypted || self.sess_type == SessionType::Group
+    }
+
+    pub fn is_group(&self) -> bool {
+        self.sess_type == SessionType::Group
     }
 }
 
@@ -3,7 +3,8 @@ use boxslab::BoxSlab;
 use crate::error::*;
 
 use super::exchange::ExchangeCtx;
-use super::packet::PacketPool;
+use super::network::Address;
+use super::packet::{Packet, PacketPool};
 
 const MAX_PROTOCOLS: usize = 4;
 
@@ -12,6 +13,21 @@ pub enum ResponseRequired {
     Yes,
     No,
 }
+/// Where an unsolicited message is to be sent
+pub enum UnsolicitedDest {
+    /// On an existing session, with this local session id
+    Session(u16),
+    /// On an unsecured session with the peer at this address
+    Peer(Address),
+}
+
+/// The changes to the sessions, that the protocols are told about
+pub enum SessionEvent {
+    /// The session with this local session id was evicted. Anything that a protocol keeps
+    /// for the session must go along with it
+    Evicted(u16),
+}
+
 pub struct ProtoDemux {
     proto_id_handlers: [Option<Box<dyn HandleProto>>; MAX_PROTOCOLS],
 }
@@ -41,9 +57,31 @@ pub trait HandleProto {
 
     fn get_proto_id(&self) -> usize;
 
-    fn handle_session_event(&self) -> Result<(), Error> {
+    fn handle_session_event(&mut self, _event: &SessionEvent) -> Result<(), Error> {
         Ok(())
     }
+
+    /// Protocols that have to send a message on their own (not in response to a
+    /// received message), return the local session id to send it on
+    fn get_unsolicited_sess(&mut self) -> Option<u16> {
+        None
+    }
+
+    /// Protocols that establish sessions send their first message on an unsecured
+    /// session, to the peer address returned here
+    fn get_unsolicited_peer(&mut self) -> Option<Address> {
+        None
+    }
+
+    /// Encode the unsolicited message, on a new exchange initiated on the session
+    /// returned by get_unsolicited_sess() or get_unsolicited_peer()
+    fn handle_unsolicited(
+        &mut self,
+        _exch_ctx: &mut ExchangeCtx,
+        _tx: &mut Packet,
+    ) -> Result<(), Error> {
+        Err(Error::NoHandler)
+    }
 }
 
 impl Default for ProtoDemux {
@@ -75,4 +113,40 @@ impl ProtoDemux {
             .ok_or(Error::NoHandler)?
             .handle_proto_id(proto_ctx);
     }
+
+    /// Tell all the protocols about a change to the sessions
+    pub fn handle_session_event(&mut self, event: &SessionEvent) -> Result<(), Error> {
+        for handler in self.proto_id_handlers.iter_mut().flatten() {
+            handler.handle_session_event(event)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the protocol id, and the destination, for a protocol that has an
+    /// unsolicited message to send
+    pub fn get_unsolicited(&mut self) -> Option<(usize, UnsolicitedDest)> {
+        self.proto_id_handlers
+            .iter_mut()
+            .enumerate()
+            .find_map(|(proto_id, h)| {
+                let h = h.as_mut()?;
+                if let Some(sess_id) = h.get_unsolicited_sess() {
+                    return Some((proto_id, UnsolicitedDest::Session(sess_id)));
+                }
+                h.get_unsolicited_peer()
+                    .map(|peer| (proto_id, UnsolicitedDest::Peer(peer)))
+            })
+    }
+
+    pub fn handle_unsolicited(
+        &mut self,
+        proto_id: usize,
+        exch_ctx: &mut ExchangeCtx,
+        tx: &mut Packet,
+    ) -> Result<(), Error> {
+        self.proto_id_handlers[proto_id]
+            .as_mut()
+            .ok_or(Error::NoHandler)?
+            .handle_unsolicited(exch_ctx, tx)
+    }
 }
@@ -85,7 +85,7 @@ impl ProtoHdr {
     ) -> Result<(), Error> {
         if let Some(d) = dec_key {
             // We decrypt only if the decryption key is valid
-            decrypt_in_place(plain_hdr.ctr, peer_nodeid, parsebuf, d)?;
+            decrypt_in_place(plain_hdr.sec_flags, plain_hdr.ctr, peer_nodeid, parsebuf, d)?;
         }
 
         self.exch_flags = ExchFlags::from_bits(parsebuf.le_u8()?).ok_or(Error::Invalid)?;
@@ -146,11 +146,11 @@ impl fmt::Display for ProtoHdr {
     }
 }
 
-fn get_iv(recvd_ctr: u32, peer_nodeid: u64, iv: &mut [u8]) -> Result<(), Error> {
-    // The IV is the source address (64-bit) followed by the message counter (32-bit)
+fn get_iv(sec_flags: u8, recvd_ctr: u32, peer_nodeid: u64, iv: &mut [u8]) -> Result<(), Error> {
+    // The IV is the security flags, followed by the message counter (32-bit) and the
+    // source address (64-bit)
     let mut write_buf = WriteBuf::new(iv, iv.len());
-    // For some reason, this is 0 in the 'bypass' mode
-    write_buf.le_u8(0)?;
+    write_buf.le_u8(sec_flags)?;
     write_buf.le_u32(recvd_ctr)?;
     write_buf.le_u64(peer_nodeid)?;
     Ok(())
@@ -164,8 +164,9 @@ pub fn encrypt_in_place(
     key: &[u8],
 ) -> Result<(), Error> {
     // IV
+    // We only send unicast messages, which have no security flags set
     let mut iv = [0_u8; crypto::AEAD_NONCE_LEN_BYTES];
-    get_iv(send_ctr, peer_nodeid, &mut iv)?;
+    get_iv(0, send_ctr, peer_nodeid, &mut iv)?;
 
     // Cipher Text
     let tag_space = [0u8; crypto::AEAD_MIC_LEN_BYTES];
@@ -185,37 +186,64 @@ pub fn encrypt_in_place(
 }
 
 fn decrypt_in_place(
+    sec_flags: u8,
     recvd_ctr: u32,
     peer_nodeid: u64,
     parsebuf: &mut ParseBuf,
     key: &[u8],
 ) -> Result<(), Error> {
     // AAD:
-    //    the unencrypted header of this packet
-    let mut aad = [0_u8; crypto::AEAD_AAD_LEN_BYTES];
+    //    the unencrypted header of this packet, which is variable sized in length
+    let mut aad = [0_u8; plain_hdr::max_plain_hdr_len()];
     let parsed_slice = parsebuf.parsed_as_slice();
-    if parsed_slice.len() == aad.len() {
-        // The plain_header is variable sized in length, I wonder if the AAD is fixed at 8, or the variable size.
-        // If so, we need to handle it cleanly here.
-        aad.copy_from_slice(parsed_slice);
-    } else {
-        return Err(Error::InvalidAAD);
-    }
+    let aad = aad.get_mut(..parsed_slice.len()).ok_or(Error::InvalidAAD)?;
+    aad.copy_from_slice(parsed_slice);
+
+    let cipher_text = parsebuf.as_borrow_slice();
+    decrypt(sec_flags, recvd_ctr, peer_nodeid, aad, cipher_text, key)?;
+    // println!("Plain Text: {:x?}", cipher_text);
+    parsebuf.tail(crypto::AEAD_MIC_LEN_BYTES)?;
+    Ok(())
+}
 
+/// Check if the message can be authenticated with the key. A copy of the payload is
+/// decrypted, so that the message can still be decrypted with another key otherwise
+pub fn can_decrypt(
+    plain_hdr: &plain_hdr::PlainHdr,
+    parsebuf: &ParseBuf,
+    peer_nodeid: u64,
+    key: &[u8],
+) -> bool {
+    let mut cipher_text = parsebuf.unparsed_as_slice().to_vec();
+    decrypt(
+        plain_hdr.sec_flags,
+        plain_hdr.ctr,
+        peer_nodeid,
+        parsebuf.parsed_as_slice(),
+        &mut cipher_text,
+        key,
+    )
+    .is_ok()
+}
+
+fn decrypt(
+    sec_flags: u8,
+    recvd_ctr: u32,
+    peer_nodeid: u64,
+    aad: &[u8],
+    cipher_text: &mut [u8],
+    key: &[u8],
+) -> Result<(), Error> {
     // IV:
     //   the specific way for creating IV is in get_iv
     let mut iv = [0_u8; crypto::AEAD_NONCE_LEN_BYTES];
-    get_iv(recvd_ctr, peer_nodeid, &mut iv)?;
+    get_iv(sec_flags, recvd_ctr, peer_nodeid, &mut iv)?;
 
-    let cipher_text = parsebuf.as_borrow_slice();
     //println!("AAD: {:x?}", aad);
     //println!("Cipher Text: {:x?}", cipher_text);
     //println!("IV: {:x?}", iv);
     //println!("Key: {:x?}", key);
-
-    crypto::decrypt_in_place(key, &iv, &aad, cipher_text)?;
-    // println!("Plain Text: {:x?}", cipher_text);
-    parsebuf.tail(crypto::AEAD_MIC_LEN_BYTES)?;
+    crypto::decrypt_in_place(key, &iv, aad, cipher_text)?;
     Ok(())
 }
 
@@ -260,7 +288,7 @@ mod tests {
         parsebuf.le_u32().unwrap();
         parsebuf.le_u32().unwrap();
 
-        decrypt_in_place(recvd_ctr, 0, &mut parsebuf, &key).unwrap();
+        decrypt_in_place(0, recvd_ctr, 0, &mut parsebuf, &key).unwrap();
         assert_eq!(
             parsebuf.as_slice(),
             [
@@ -272,6 +300,53 @@ mod tests {
         );
     }
 
+    #[test]
+    pub fn test_group_decrypt() {
+        let key = [0x5a; 16];
+        let src_nodeid = 0x1122_3344_5566_7788;
+        let ctr = 1234;
+
+        // Source node id and group id present, the session type is group
+        let mut hdr_buf = [0u8; plain_hdr::max_plain_hdr_len()];
+        let mut hdr = WriteBuf::new(&mut hdr_buf, plain_hdr::max_plain_hdr_len());
+        hdr.le_u8(0x06).unwrap();
+        hdr.le_u16(0xabcd).unwrap();
+        hdr.le_u8(0x01).unwrap();
+        hdr.le_u32(ctr).unwrap();
+        hdr.le_u64(src_nodeid).unwrap();
+        hdr.le_u16(0x0010).unwrap();
+        let hdr = hdr.as_slice();
+
+        let plain_text = [0x05, 0x08, 0x34, 0x12, 0x01, 0x00, 0x15, 0x18];
+        let mut payload = plain_text.to_vec();
+        payload.extend_from_slice(&[0; crypto::AEAD_MIC_LEN_BYTES]);
+        let mut iv = [0_u8; crypto::AEAD_NONCE_LEN_BYTES];
+        get_iv(0x01, ctr, src_nodeid, &mut iv).unwrap();
+        crypto::encrypt_in_place(&key, &iv, hdr, &mut payload, plain_text.len()).unwrap();
+
+        let mut input_buf = hdr.to_vec();
+        input_buf.extend_from_slice(&payload);
+        let input_buf_len = input_buf.len();
+        let mut parsebuf = ParseBuf::new(&mut input_buf, input_buf_len);
+        let mut plain_hdr = plain_hdr::PlainHdr::default();
+        plain_hdr.decode(&mut parsebuf).unwrap();
+        assert!(plain_hdr.is_group());
+        assert_eq!(plain_hdr.get_src_u64(), Some(src_nodeid));
+        assert_eq!(plain_hdr.get_group_id(), Some(0x0010));
+
+        // A wrong key doesn't disturb the decryption with the right one
+        assert!(!can_decrypt(&plain_hdr, &parsebuf, src_nodeid, &[0xa5; 16]));
+        assert!(can_decrypt(&plain_hdr, &parsebuf, src_nodeid, &key));
+
+        let mut proto_hdr = ProtoHdr::default();
+        proto_hdr
+            .decrypt_and_decode(&plain_hdr, &mut parsebuf, src_nodeid, Some(&key))
+            .unwrap();
+        assert_eq!(proto_hdr.exch_id, 0x1234);
+        assert_eq!(proto_hdr.proto_id, 0x0001);
+        assert_eq!(parsebuf.as_slice(), [0x15, 0x18]);
+    }
+
     #[test]
     pub fn test_encrypt_success() {
         // These values are captured from an execution run of the chip-tool binary
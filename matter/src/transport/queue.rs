@@ -48,3 +48,24 @@ impl WorkQ {
         self.tx.send(msg).await.map_err(|e| e.into())
     }
 }
+
+/// The work queue is global, the tests that add sessions through it take turns
+#[cfg(test)]
+pub fn test_work_q() -> std::sync::MutexGuard<'static, Option<Receiver<Msg>>> {
+    use std::sync::Mutex;
+    static WORK_Q: Mutex<Option<Receiver<Msg>>> = Mutex::new(None);
+    let mut q = WORK_Q.lock().unwrap_or_else(|e| e.into_inner());
+    if q.is_none() {
+        *q = Some(WorkQ::init().unwrap());
+    }
+    q
+}
+
+/// The next session that was queued to be added, if any
+#[cfg(test)]
+pub fn test_new_session(q: &Option<Receiver<Msg>>) -> Option<CloneData> {
+    match q.as_ref().unwrap().try_recv() {
+        Ok(Msg::NewSession(clone_data)) => Some(clone_data),
+        _ => None,
+    }
+}
@@ -1,21 +1,27 @@
 use core::fmt;
 use std::{
     any::Any,
+    net::Ipv6Addr,
     ops::{Deref, DerefMut},
+    sync::Arc,
     time::SystemTime,
 };
 
 use crate::{
+    acl::NocCatIds,
     error::*,
+    group_keys::GroupKeys,
     transport::{plain_hdr, proto_hdr},
     utils::writebuf::WriteBuf,
 };
 use boxslab::{BoxSlab, Slab};
 use colored::*;
-use log::{info, trace};
+use log::{error, info, trace};
 use rand::Rng;
 
 use super::{
+    mrp::MrpParams,
+    msg_ctr::RxCtrWindow,
     network::{Address, NetworkInterface},
     packet::{Packet, PacketPool},
 };
@@ -28,6 +34,8 @@ pub enum SessionMode {
     Case(u8),
     Pase,
     PlainText,
+    // The Group session will capture the local fabric index and the group id
+    Group(u8, u16),
 }
 
 impl Default for SessionMode {
@@ -41,6 +49,8 @@ pub struct Session {
     peer_addr: Address,
     local_nodeid: u64,
     peer_nodeid: Option<u64>,
+    // The CASE Authenticated Tags from the peer's NOC
+    peer_cat_ids: NocCatIds,
     // I find the session initiator/responder role getting confused with exchange initiator/responder
     // So, we might keep this as enc_key and dec_key for now
     dec_key: [u8; MATTER_AES128_KEY_SIZE],
@@ -50,6 +60,8 @@ pub struct Session {
     peer_sess_id: u16,
     msg_ctr: u32,
     mode: SessionMode,
+    // The retransmission parameters that the peer asked for
+    peer_mrp: MrpParams,
     data: Option<Box<dyn Any>>,
     last_use: SystemTime,
 }
@@ -59,6 +71,8 @@ pub struct CloneData {
     pub dec_key: [u8; MATTER_AES128_KEY_SIZE],
     pub enc_key: [u8; MATTER_AES128_KEY_SIZE],
     pub att_challenge: [u8; MATTER_AES128_KEY_SIZE],
+    pub peer_cat_ids: NocCatIds,
+    pub peer_mrp: MrpParams,
     local_sess_id: u16,
     peer_sess_id: u16,
     local_nodeid: u64,
@@ -79,6 +93,8 @@ impl CloneData {
             dec_key: [0; MATTER_AES128_KEY_SIZE],
             enc_key: [0; MATTER_AES128_KEY_SIZE],
             att_challenge: [0; MATTER_AES128_KEY_SIZE],
+            peer_cat_ids: Default::default(),
+            peer_mrp: Default::default(),
             local_nodeid,
             peer_nodeid,
             peer_addr,
@@ -97,6 +113,7 @@ impl Session {
             peer_addr,
             local_nodeid: 0,
             peer_nodeid,
+            peer_cat_ids: Default::default(),
             dec_key: [0; MATTER_AES128_KEY_SIZE],
             enc_key: [0; MATTER_AES128_KEY_SIZE],
             att_challenge: [0; MATTER_AES128_KEY_SIZE],
@@ -104,6 +121,7 @@ impl Session {
             local_sess_id: 0,
             msg_ctr: rand::thread_rng().gen_range(0..MATTER_MSG_CTR_RANGE),
             mode: SessionMode::PlainText,
+            peer_mrp: Default::default(),
             data: None,
             last_use: SystemTime::now(),
         }
@@ -115,6 +133,7 @@ impl Session {
             peer_addr: clone_from.peer_addr,
             local_nodeid: clone_from.local_nodeid,
             peer_nodeid: Some(clone_from.peer_nodeid),
+            peer_cat_ids: clone_from.peer_cat_ids,
             dec_key: clone_from.dec_key,
             enc_key: clone_from.enc_key,
             att_challenge: clone_from.att_challenge,
@@ -122,6 +141,7 @@ impl Session {
             peer_sess_id: clone_from.peer_sess_id,
             msg_ctr: rand::thread_rng().gen_range(0..MATTER_MSG_CTR_RANGE),
             mode: clone_from.mode,
+            peer_mrp: clone_from.peer_mrp,
             data: None,
             last_use: SystemTime::now(),
         }
@@ -156,20 +176,42 @@ impl Session {
         self.peer_sess_id
     }
 
+    pub fn get_peer_node_id(&self) -> Option<u64> {
+        self.peer_nodeid
+    }
+
+    pub fn get_peer_cat_ids(&self) -> &NocCatIds {
+        &self.peer_cat_ids
+    }
+
     pub fn get_peer_addr(&self) -> Address {
         self.peer_addr
     }
 
+    pub fn get_peer_mrp_params(&self) -> &MrpParams {
+        &self.peer_mrp
+    }
+
+    /// The handshakes set the peer's parameters on the unsecured session too, for the
+    /// retransmissions of the handshake itself
+    pub fn set_peer_mrp_params(&mut self, params: MrpParams) {
+        self.peer_mrp = params;
+    }
+
     pub fn is_encrypted(&self) -> bool {
         match self.mode {
-            SessionMode::Case(_) | SessionMode::Pase => true,
+            SessionMode::Case(_) | SessionMode::Pase | SessionMode::Group(_, _) => true,
             SessionMode::PlainText => false,
         }
     }
 
+    pub fn is_group(&self) -> bool {
+        matches!(self.mode, SessionMode::Group(_, _))
+    }
+
     pub fn get_local_fabric_idx(&self) -> Option<u8> {
         match self.mode {
-            SessionMode::Case(a) => Some(a),
+            SessionMode::Case(a) | SessionMode::Group(a, _) => Some(a),
             _ => None,
         }
     }
@@ -186,15 +228,18 @@ impl Session {
 
     pub fn get_dec_key(&self) -> Option<&[u8]> {
         match self.mode {
-            SessionMode::Case(_) | SessionMode::Pase => Some(&self.dec_key),
+            SessionMode::Case(_) | SessionMode::Pase | SessionMode::Group(_, _) => {
+                Some(&self.dec_key)
+            }
             SessionMode::PlainText => None,
         }
     }
 
+    // Nothing is ever sent on a Group session
     pub fn get_enc_key(&self) -> Option<&[u8]> {
         match self.mode {
             SessionMode::Case(_) | SessionMode::Pase => Some(&self.enc_key),
-            SessionMode::PlainText => None,
+            SessionMode::PlainText | SessionMode::Group(_, _) => None,
         }
     }
 
@@ -274,10 +319,25 @@ impl fmt::Display for Session {
 }
 
 pub const MAX_SESSIONS: usize = 16;
+// The group senders, whose message counters are tracked
+const MAX_GROUP_PEERS: usize = 32;
+
+// The message counters of a group sender. These are kept apart from the Group sessions, so
+// that they outlive the session of the sender
+struct GroupPeer {
+    fab_idx: u8,
+    node_id: u64,
+    ctr: RxCtrWindow,
+}
+
 pub struct SessionMgr {
     next_sess_id: u16,
     sessions: [Option<Session>; MAX_SESSIONS],
     network: Option<Box<dyn NetworkInterface>>,
+    // The keys that the group messages are decrypted with
+    group_keys: Option<Arc<GroupKeys>>,
+    group_peers: Vec<GroupPeer>,
+    multicast_addrs: Vec<Ipv6Addr>,
 }
 
 impl Default for SessionMgr {
@@ -292,9 +352,47 @@ impl SessionMgr {
             sessions: Default::default(),
             next_sess_id: 1,
             network: None,
+            group_keys: None,
+            group_peers: Vec::new(),
+            multicast_addrs: Vec::new(),
         }
     }
 
+    pub fn set_group_keys(&mut self, group_keys: Arc<GroupKeys>) {
+        self.group_keys = Some(group_keys);
+    }
+
+    /// Join the multicast groups of the groups that were added, and leave the ones of the
+    /// groups that were removed
+    pub fn update_multicast(&mut self) -> Result<(), Error> {
+        let addrs = match &self.group_keys {
+            Some(g) => g.get_changed_multicast_addrs()?,
+            None => None,
+        };
+        let mut addrs = match addrs {
+            Some(a) => a,
+            None => return Ok(()),
+        };
+        addrs.sort_unstable();
+        addrs.dedup();
+
+        let network = self.network.as_ref().ok_or(Error::NoNetworkInterface)?;
+        for addr in self.multicast_addrs.iter().filter(|a| !addrs.contains(a)) {
+            info!("Leaving multicast group {}", addr);
+            if let Err(e) = network.leave_multicast(*addr) {
+                error!("Error leaving multicast group {}: {:?}", addr, e);
+            }
+        }
+        for addr in addrs.iter().filter(|a| !self.multicast_addrs.contains(a)) {
+            info!("Joining multicast group {}", addr);
+            if let Err(e) = network.join_multicast(*addr) {
+                error!("Error joining multicast group {}: {:?}", addr, e);
+            }
+        }
+        self.multicast_addrs = addrs;
+        Ok(())
+    }
+
     pub fn add_network_interface(
         &mut self,
         interface: Box<dyn NetworkInterface>,
@@ -348,6 +446,50 @@ impl SessionMgr {
         lru_index
     }
 
+    /// The least recently used Group session, if there is any. A group sender may only take
+    /// the place of another group sender, so that it can't knock the unicast peers off
+    pub fn get_lru_group(&self) -> Option<usize> {
+        self.sessions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| s.as_ref().filter(|s| s.is_group()).map(|s| (i, s.last_use)))
+            .min_by_key(|(_, last_use)| *last_use)
+            .map(|(i, _)| i)
+    }
+
+    /// Record the counter of a group message, that was authenticated. A message that was
+    /// already received is a duplicate, and must be dropped
+    pub fn accept_group_ctr(&mut self, fab_idx: u8, node_id: u64, ctr: u32) -> Result<(), Error> {
+        if let Some(peer) = self
+            .group_peers
+            .iter_mut()
+            .find(|p| p.fab_idx == fab_idx && p.node_id == node_id)
+        {
+            return if peer.ctr.accept(ctr) {
+                Ok(())
+            } else {
+                Err(Error::Duplicate)
+            };
+        }
+        // Forgetting a peer would let its messages be replayed, so new peers are turned
+        // away instead
+        if self.group_peers.len() >= MAX_GROUP_PEERS {
+            error!("Group peers full, dropping the message from {:x}", node_id);
+            return Err(Error::NoSpace);
+        }
+        self.group_peers.push(GroupPeer {
+            fab_idx,
+            node_id,
+            ctr: RxCtrWindow::new(ctr),
+        });
+        Ok(())
+    }
+
+    /// Forget the group senders of a fabric, as the fabric is removed
+    pub fn remove_group_peers(&mut self, fab_idx: u8) {
+        self.group_peers.retain(|p| p.fab_idx != fab_idx);
+    }
+
     pub fn add(&mut self, peer_addr: Address, peer_nodeid: Option<u64>) -> Result<usize, Error> {
         let session = Session::new(peer_addr, peer_nodeid);
         self.add_session(session)
@@ -393,6 +535,7 @@ impl SessionMgr {
                 x.local_sess_id == sess_id
                     && x.peer_addr == peer_addr
                     && x.is_encrypted() == is_encrypted
+                    && !x.is_group()
                     && nodeid_matches
             } else {
                 false
@@ -400,11 +543,14 @@ impl SessionMgr {
         })
     }
 
+    pub fn get_index_with_id(&self, sess_id: u16) -> Option<usize> {
+        self.sessions
+            .iter()
+            .position(|x| x.as_ref().map(|s| s.local_sess_id) == Some(sess_id))
+    }
+
     pub fn get_with_id(&mut self, sess_id: u16) -> Option<SessionHandle> {
-        let index = self
-            .sessions
-            .iter_mut()
-            .position(|x| x.as_ref().map(|s| s.local_sess_id) == Some(sess_id))?;
+        let index = self.get_index_with_id(sess_id)?;
         Some(self.get_session_handle(index))
     }
 
@@ -429,6 +575,9 @@ impl SessionMgr {
     // We will try to get a session for this Packet. If no session exists, we will try to add one
     // If the session list is full we will return a None
     pub fn post_recv(&mut self, rx: &Packet) -> Result<Option<usize>, Error> {
+        if rx.plain.is_group() {
+            return self.post_recv_group(rx);
+        }
         let sess_index = match self.get_or_add(
             rx.plain.sess_id,
             rx.peer,
@@ -444,6 +593,38 @@ impl SessionMgr {
         Ok(sess_index)
     }
 
+    // A group message is accepted only if it can be authenticated with an operational key
+    // of the group that it is addressed to. Each sender gets its own Group session
+    fn post_recv_group(&mut self, rx: &Packet) -> Result<Option<usize>, Error> {
+        let group_id = rx.plain.get_group_id().ok_or(Error::Invalid)?;
+        let src = rx.plain.get_src_u64().ok_or(Error::Invalid)?;
+        let group_keys = self.group_keys.as_ref().ok_or(Error::NoSession)?;
+        let (fab_idx, key) = group_keys
+            .get_op_keys(rx.plain.sess_id, group_id)?
+            .into_iter()
+            .find(|(_, key)| rx.can_decrypt(src, key))
+            .ok_or(Error::NoSession)?;
+
+        let mode = SessionMode::Group(fab_idx, group_id);
+        let existing = self.sessions.iter().position(|x| match x {
+            Some(s) => s.mode == mode && s.peer_nodeid == Some(src) && s.dec_key == key,
+            None => false,
+        });
+        if existing.is_some() {
+            return Ok(existing);
+        }
+
+        let mut session = Session::new(rx.peer, Some(src));
+        session.mode = mode;
+        session.local_sess_id = rx.plain.sess_id;
+        session.dec_key = key;
+        match self.add_session(session) {
+            Ok(index) => Ok(Some(index)),
+            Err(Error::NoSpace) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     pub fn recv(&mut self) -> Result<(BoxSlab<PacketPool>, Option<usize>), Error> {
         let mut rx = Slab::<PacketPool>::new(Packet::new_rx()?).ok_or(Error::PacketPoolExhaust)?;
 
@@ -464,19 +645,20 @@ impl SessionMgr {
         Ok((rx, sess_handle))
     }
 
-    pub fn send(
-        &mut self,
-        sess_idx: usize,
-        mut proto_tx: BoxSlab<PacketPool>,
-    ) -> Result<(), Error> {
+    pub fn send(&mut self, sess_idx: usize, proto_tx: &mut Packet) -> Result<(), Error> {
         self.sessions[sess_idx]
             .as_mut()
             .ok_or(Error::NoSession)?
-            .do_send(&mut proto_tx)?;
+            .do_send(proto_tx)?;
 
-        let network = self.network.as_ref().ok_or(Error::NoNetworkInterface)?;
         let peer = proto_tx.peer;
-        network.send(proto_tx.as_borrow_slice(), peer)?;
+        self.send_raw(proto_tx.as_borrow_slice(), peer)
+    }
+
+    /// Send a message that was already encoded, e.g. an MRP retransmission
+    pub fn send_raw(&self, buf: &[u8], peer: Address) -> Result<(), Error> {
+        let network = self.network.as_ref().ok_or(Error::NoNetworkInterface)?;
+        network.send(buf, peer)?;
         println!("Message Sent to {}", peer);
         Ok(())
     }
@@ -510,7 +692,7 @@ impl<'a> SessionHandle<'a> {
         self.sess_mgr.get_next_sess_id()
     }
 
-    pub fn send(&mut self, proto_tx: BoxSlab<PacketPool>) -> Result<(), Error> {
+    pub fn send(&mut self, proto_tx: &mut Packet) -> Result<(), Error> {
         self.sess_mgr.send(self.sess_idx, proto_tx)
     }
 }
@@ -533,9 +715,9 @@ impl<'a> DerefMut for SessionHandle<'a> {
 #[cfg(test)]
 mod tests {
 
-    use crate::transport::network::Address;
+    use crate::{error::Error, transport::network::Address};
 
-    use super::SessionMgr;
+    use super::{Session, SessionMgr, SessionMode};
 
     #[test]
     fn test_next_sess_id_doesnt_reuse() {
@@ -563,4 +745,32 @@ mod tests {
         assert_eq!(sm.get_next_sess_id(), 65535);
         assert_eq!(sm.get_next_sess_id(), 2);
     }
+
+    #[test]
+    fn test_group_ctr_duplicates() {
+        let mut sm = SessionMgr::new();
+        assert_eq!(sm.accept_group_ctr(1, 5, 10), Ok(()));
+        assert_eq!(sm.accept_group_ctr(1, 5, 10), Err(Error::Duplicate));
+        // The counters are tracked per fabric and source node
+        assert_eq!(sm.accept_group_ctr(2, 5, 10), Ok(()));
+        assert_eq!(sm.accept_group_ctr(1, 6, 10), Ok(()));
+        assert_eq!(sm.accept_group_ctr(1, 5, 11), Ok(()));
+
+        sm.remove_group_peers(1);
+        assert_eq!(sm.accept_group_ctr(1, 5, 10), Ok(()));
+        assert_eq!(sm.accept_group_ctr(2, 5, 10), Err(Error::Duplicate));
+    }
+
+    #[test]
+    fn test_lru_group_skips_unicast() {
+        let mut sm = SessionMgr::new();
+        sm.add(Address::default(), None).unwrap();
+        assert_eq!(sm.get_lru_group(), None);
+
+        let mut group = Session::new(Address::default(), Some(5));
+        group.mode = SessionMode::Group(1, 0x10);
+        let group_idx = sm.add_session(group).unwrap();
+        sm.add(Address::default(), None).unwrap();
+        assert_eq!(sm.get_lru_group(), Some(group_idx));
+    }
 }
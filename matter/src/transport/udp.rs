@@ -1,5 +1,7 @@
 use crate::error::*;
 use smol::net::{Ipv6Addr, UdpSocket};
+use smol::Timer;
+use std::time::Duration;
 
 use super::network::{Address, NetworkInterface};
 
@@ -15,6 +17,10 @@ pub const MAX_RX_BUF_SIZE: usize = 1583;
 /* The Matter Port */
 const MATTER_PORT: u16 = 5540;
 
+// The receive doesn't block forever, so that the periodic work (subscription reports,
+// MRP retransmissions) gets a chance to run. This is well below the MRP intervals
+const RECV_TIMEOUT: Duration = Duration::from_millis(100);
+
 impl UdpListener {
     pub fn new() -> Result<UdpListener, Error> {
         Ok(UdpListener {
@@ -25,10 +31,17 @@ impl UdpListener {
 
 impl NetworkInterface for UdpListener {
     fn recv(&self, in_buf: &mut [u8]) -> Result<(usize, Address), Error> {
-        let (size, addr) = smol::block_on(self.socket.recv_from(in_buf)).map_err(|e| {
-            println!("Error on the network: {:?}", e);
-            Error::Network
-        })?;
+        let recv = async {
+            self.socket.recv_from(in_buf).await.map_err(|e| {
+                println!("Error on the network: {:?}", e);
+                Error::Network
+            })
+        };
+        let timeout = async {
+            Timer::after(RECV_TIMEOUT).await;
+            Err(Error::Timeout)
+        };
+        let (size, addr) = smol::block_on(smol::future::or(recv, timeout))?;
         Ok((size, Address::Udp(addr)))
     }
 
@@ -37,4 +50,13 @@ impl NetworkInterface for UdpListener {
             Address::Udp(addr) => Ok(smol::block_on(self.socket.send_to(out_buf, addr))?),
         }
     }
+
+    fn join_multicast(&self, addr: Ipv6Addr) -> Result<(), Error> {
+        // On the default interface
+        Ok(self.socket.join_multicast_v6(&addr, 0)?)
+    }
+
+    fn leave_multicast(&self, addr: Ipv6Addr) -> Result<(), Error> {
+        Ok(self.socket.leave_multicast_v6(&addr, 0)?)
+    }
 }
@@ -30,6 +30,11 @@ impl<'a> ParseBuf<'a> {
         &mut self.buf[self.read_off..(self.read_off + self.left)]
     }
 
+    // Return the data that is yet to be parsed
+    pub fn unparsed_as_slice(&self) -> &[u8] {
+        &self.buf[self.read_off..(self.read_off + self.left)]
+    }
+
     pub fn parsed_as_slice(&self) -> &[u8] {
         &self.buf[0..self.read_off]
     }
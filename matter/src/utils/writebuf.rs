@@ -6,6 +6,10 @@ pub struct WriteBuf<'a> {
     buf: &'a mut [u8],
     start: usize,
     end: usize,
+    // The space at the end, that is held back from the appends
+    end_reserve: usize,
+    // Set when an append didn't fit
+    overflowed: bool,
 }
 
 impl<'a> WriteBuf<'a> {
@@ -14,6 +18,8 @@ impl<'a> WriteBuf<'a> {
             buf: &mut buf[..len],
             start: 0,
             end: 0,
+            end_reserve: 0,
+            overflowed: false,
         }
     }
 
@@ -29,6 +35,33 @@ impl<'a> WriteBuf<'a> {
         self.end += new_offset
     }
 
+    /// Hold back 'with' bytes at the end of the buffer, for what must be written last
+    pub fn shrink(&mut self, with: usize) -> Result<(), Error> {
+        if self.end + self.end_reserve + with > self.buf.len() {
+            return Err(Error::NoSpace);
+        }
+        self.end_reserve += with;
+        Ok(())
+    }
+
+    /// Release 'with' bytes that were held back by shrink()
+    pub fn expand(&mut self, with: usize) -> Result<(), Error> {
+        if with > self.end_reserve {
+            return Err(Error::Invalid);
+        }
+        self.end_reserve -= with;
+        Ok(())
+    }
+
+    /// Returns true if any append failed for lack of space, since clear_overflow()
+    pub fn is_overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    pub fn clear_overflow(&mut self) {
+        self.overflowed = false;
+    }
+
     pub fn as_borrow_slice(&self) -> &[u8] {
         &self.buf[self.start..self.end]
     }
@@ -42,7 +75,8 @@ impl<'a> WriteBuf<'a> {
     }
 
     pub fn empty_as_mut_slice(&mut self) -> &mut [u8] {
-        &mut self.buf[self.end..]
+        let len = self.buf.len() - self.end_reserve;
+        &mut self.buf[self.end..len]
     }
 
     pub fn reset(&mut self, reserve: usize) {
@@ -81,11 +115,12 @@ impl<'a> WriteBuf<'a> {
     where
         F: FnOnce(&mut Self),
     {
-        if self.end + size <= self.buf.len() {
+        if self.end + size <= self.buf.len() - self.end_reserve {
             f(self);
             self.end += size;
             return Ok(());
         }
+        self.overflowed = true;
         Err(Error::NoSpace)
     }
 
@@ -306,4 +341,23 @@ mod tests {
         buf.le_u16(66).unwrap();
         assert_eq!(buf.as_borrow_slice(), [65, 0, 66, 0,]);
     }
+
+    #[test]
+    fn test_shrink_expand() {
+        let mut test_slice: [u8; 10] = [0; 10];
+        let mut buf = WriteBuf::new(&mut test_slice, 10);
+
+        buf.shrink(4).unwrap();
+        buf.le_u32(0xcafebabe).unwrap();
+        assert!(buf.le_u32(0xcafebabe).is_err());
+        assert!(buf.is_overflowed());
+        buf.le_u16(65).unwrap();
+
+        buf.clear_overflow();
+        buf.expand(4).unwrap();
+        buf.le_u32(0xcafebabe).unwrap();
+        assert!(!buf.is_overflowed());
+        assert_eq!(buf.get_tail(), 10);
+        assert!(buf.expand(1).is_err());
+    }
 }
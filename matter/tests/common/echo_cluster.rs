@@ -1,5 +1,7 @@
 use matter::{
-    data_model::objects::{Access, AttrValue, Attribute, Cluster, ClusterType, Quality},
+    data_model::objects::{
+        Access, AttrDetails, AttrValue, Attribute, Cluster, ClusterType, Command, Quality,
+    },
     error::Error,
     interaction_model::{command::CommandReq, core::IMStatusCode, messages::ib},
     tlv::{TLVWriter, TagType, ToTLV},
@@ -12,6 +14,7 @@ pub const ID: u32 = 0xABCD;
 pub enum Commands {
     EchoReq = 0x00,
     EchoResp = 0x01,
+    TimedEchoReq = 0x02,
 }
 
 pub struct EchoCluster {
@@ -25,6 +28,7 @@ pub enum Attributes {
     Att2 = 1,
     AttWrite = 2,
     AttCustom = 3,
+    AttWriteTimed = 4,
 }
 
 pub const ATTR_CUSTOM_VALUE: u32 = 0xcafebeef;
@@ -43,9 +47,11 @@ impl ClusterType for EchoCluster {
         &self,
         tag: TagType,
         tw: &mut TLVWriter,
-        attr_id: u16,
+        attr: &AttrDetails,
     ) -> Result<(), IMStatusCode> {
-        match num::FromPrimitive::from_u16(attr_id).ok_or(IMStatusCode::UnsupportedAttribute)? {
+        match num::FromPrimitive::from_u16(attr.attr_id)
+            .ok_or(IMStatusCode::UnsupportedAttribute)?
+        {
             Attributes::AttCustom => {
                 let _ = tw.u32(tag, ATTR_CUSTOM_VALUE);
                 Ok(())
@@ -65,7 +71,7 @@ impl ClusterType for EchoCluster {
         match cmd {
             // This will generate an echo response on the same endpoint
             // with data multiplied by the multiplier
-            Commands::EchoReq => {
+            Commands::EchoReq | Commands::TimedEchoReq => {
                 let a = cmd_req.data.u8().unwrap();
                 let mut echo_response = cmd_req.cmd;
                 echo_response.path.leaf = Some(Commands::EchoResp as u32);
@@ -117,6 +123,16 @@ impl EchoCluster {
             Access::READ | Access::NEED_VIEW,
             Quality::NONE,
         )?)?;
+        c.base.add_attribute(Attribute::new(
+            Attributes::AttWriteTimed as u16,
+            AttrValue::Uint16(ATTR_WRITE_DEFAULT_VALUE),
+            Access::WRITE | Access::NEED_OPERATE | Access::TIMED_ONLY,
+            Quality::NONE,
+        )?)?;
+        c.base.add_command(Command::new(
+            Commands::TimedEchoReq as u16,
+            Access::NEED_OPERATE | Access::TIMED_ONLY,
+        ))?;
         Ok(c)
     }
 }
@@ -1,41 +1,80 @@
-use crate::common::echo_cluster;
+use crate::common::{echo_cluster, test_vectors};
 use boxslab::Slab;
 use matter::{
+    acl::{AclEntry, AclMgr, AuthMode, Privilege},
     data_model::{
         cluster_basic_information::BasicInfoConfig,
         core::DataModel,
         device_types::device_type_add_on_off_light,
+        events::EventLog,
         sdm::dev_att::{DataType, DevAttDataFetcher},
     },
     error::Error,
     fabric::FabricMgr,
-    interaction_model::{core::OpCode, messages::ib::CmdPath, messages::msg, InteractionModel},
-    tlv::{TLVWriter, TagType, ToTLV},
+    group_keys::GroupKeys,
+    interaction_model::{
+        core::OpCode,
+        messages::ib::{AttrDataType, AttrPath, AttrResp, CmdPath, InvResp},
+        messages::msg::{self, ReadReq, TimedReq},
+        InteractionModel,
+    },
+    persist::MemKvStore,
+    secure_channel::{pake::PaseMgr, spake2p::VerifierData},
+    tlv::{self, TLVElement, TLVWriter, TagType, ToTLV},
     transport::packet::Packet,
-    transport::proto_demux::HandleProto,
+    transport::proto_demux::{HandleProto, ResponseRequired},
     transport::{
         exchange::{self, Exchange, ExchangeCtx},
         network::Address,
         packet::PacketPool,
         proto_demux::ProtoCtx,
-        session::SessionMgr,
+        session::{CloneData, SessionMgr, SessionMode},
     },
     utils::writebuf::WriteBuf,
 };
 use std::{
     net::{Ipv4Addr, SocketAddr},
     sync::Arc,
+    thread,
+    time::Duration,
 };
 
 pub struct DummyDevAtt {}
 impl DevAttDataFetcher for DummyDevAtt {
-    fn get_devatt_data(&self, _data_type: DataType, _data: &mut [u8]) -> Result<usize, Error> {
-        Ok(2)
+    fn get_devatt_data(&self, data_type: DataType, data: &mut [u8]) -> Result<usize, Error> {
+        // The DAC key pair signs the CSRs, any key pair will do for that
+        let key: &[u8] = match data_type {
+            DataType::DACPubKey => &test_vectors::NOC_PUBKEY,
+            DataType::DACPrivKey => &test_vectors::NOC_PRIVKEY,
+            _ => return Ok(2),
+        };
+        data[..key.len()].copy_from_slice(key);
+        Ok(key.len())
     }
 }
 
+// The peer node id and fabric index of the CASE session that the transactions are run on
+pub const IM_ENGINE_PEER_ID: u64 = 445566;
+pub const IM_ENGINE_FAB_IDX: u8 = 1;
+pub const IM_ENGINE_FABRIC_ID: u64 = 0x1122;
+pub const IM_ENGINE_COMPRESSED_ID: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
 // Create an Interaction Model, Data Model and run a rx/tx transaction through it
 pub fn im_engine(action: OpCode, data_in: &[u8], data_out: &mut [u8]) -> (DataModel, usize) {
+    let data_model = init_data_model();
+    let out_data_len = im_engine_with_dm(&data_model, action, data_in, data_out);
+    (data_model, out_data_len)
+}
+
+// Create the Data Model with the echo clusters that the tests run against
+pub fn init_data_model() -> DataModel {
+    let store = Arc::new(MemKvStore::new());
+    let fabric_mgr = Arc::new(FabricMgr::new(store.clone()).unwrap());
+    init_data_model_with(store, fabric_mgr)
+}
+
+// Create the Data Model on a storage and fabrics, that the test looks into
+pub fn init_data_model_with(store: Arc<MemKvStore>, fabric_mgr: Arc<FabricMgr>) -> DataModel {
     let dev_det = BasicInfoConfig {
         vid: 10,
         pid: 11,
@@ -43,38 +82,212 @@ pub fn im_engine(action: OpCode, data_in: &[u8], data_out: &mut [u8]) -> (DataMo
         sw_ver: 13,
     };
     let dev_att = Box::new(DummyDevAtt {});
-    let fabric_mgr = Arc::new(FabricMgr::new().unwrap());
-    let data_model = DataModel::new(dev_det, dev_att, fabric_mgr.clone()).unwrap();
+    let acl_mgr = Arc::new(AclMgr::new(store.clone()).unwrap());
+    let group_keys = Arc::new(GroupKeys::new(store.clone()).unwrap());
+    let events = Arc::new(EventLog::new(store).unwrap());
+    // The commissionable service isn't published, the tests don't bind the mDNS socket
+    let pase_mgr =
+        PaseMgr::new_with_publisher(VerifierData::new_with_pw(123456).unwrap(), 250, |_, _| {
+            Ok(Box::new(()))
+        });
+    let data_model = DataModel::new(
+        dev_det,
+        dev_att,
+        fabric_mgr,
+        acl_mgr.clone(),
+        group_keys.clone(),
+        events,
+        pase_mgr,
+    )
+    .unwrap();
+
+    // The peer's fabric, that the groups are configured on
+    group_keys
+        .add_fabric(
+            IM_ENGINE_FAB_IDX,
+            IM_ENGINE_FABRIC_ID,
+            &IM_ENGINE_COMPRESSED_ID,
+        )
+        .unwrap();
+
+    // The peer is the administrator of its fabric
+    let mut admin = AclEntry::new(IM_ENGINE_FAB_IDX, Privilege::Administer, AuthMode::Case);
+    admin.add_subject(IM_ENGINE_PEER_ID).unwrap();
+    acl_mgr.add(admin).unwrap();
 
     {
         let mut d = data_model.node.write().unwrap();
-        let light_endpoint = device_type_add_on_off_light(&mut d).unwrap();
+        let light_endpoint =
+            device_type_add_on_off_light(&mut d, data_model.group_keys.clone()).unwrap();
         d.add_cluster(0, echo_cluster::EchoCluster::new(2).unwrap())
             .unwrap();
         d.add_cluster(light_endpoint, echo_cluster::EchoCluster::new(3).unwrap())
             .unwrap();
     }
+    data_model
+}
 
-    let mut interaction_model = Box::new(InteractionModel::new(Box::new(data_model.clone())));
+// Create an Interaction Model on an existing Data Model and run a rx/tx transaction through it
+pub fn im_engine_with_dm(
+    data_model: &DataModel,
+    action: OpCode,
+    data_in: &[u8],
+    data_out: &mut [u8],
+) -> usize {
     let mut exch = Exchange::new(1, 0, exchange::Role::Responder);
+    im_engine_with_exch(data_model, &mut exch, action, data_in, data_out)
+}
 
-    let mut sess_mgr: SessionMgr = Default::default();
-    let sess_idx = sess_mgr
-        .get_or_add(
+// Run a rx/tx transaction on an existing exchange, e.g. one that a Timed Request opened
+// a window on
+pub fn im_engine_with_exch(
+    data_model: &DataModel,
+    exch: &mut Exchange,
+    action: OpCode,
+    data_in: &[u8],
+    data_out: &mut [u8],
+) -> usize {
+    let mut sess = ImSession::new(SessionMode::Case(IM_ENGINE_FAB_IDX));
+    im_engine_with_session(data_model, &mut sess, exch, action, data_in, data_out)
+}
+
+// Run a rx/tx transaction on a session other than the CASE session, e.g. a Group session
+pub fn im_engine_with_mode(
+    data_model: &DataModel,
+    mode: SessionMode,
+    action: OpCode,
+    data_in: &[u8],
+    data_out: &mut [u8],
+) -> usize {
+    let mut exch = Exchange::new(1, 0, exchange::Role::Responder);
+    let mut sess = ImSession::new(mode);
+    im_engine_with_session(data_model, &mut sess, &mut exch, action, data_in, data_out)
+}
+
+// A session of the peer, that lasts across the transactions that are run on it, e.g. the
+// CSRRequest and the UpdateNOC that uses the key pair that it generated
+pub struct ImSession {
+    sess_mgr: SessionMgr,
+    sess_idx: usize,
+}
+
+impl ImSession {
+    pub fn new(mode: SessionMode) -> Self {
+        let mut sess_mgr: SessionMgr = Default::default();
+        let clone_data = CloneData::new(
             0,
+            IM_ENGINE_PEER_ID,
+            1,
+            1,
             Address::Udp(SocketAddr::new(
                 std::net::IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
                 5542,
             )),
-            None,
-            false,
-        )
-        .unwrap();
-    let sess = sess_mgr.get_session_handle(sess_idx);
-    let exch_ctx = ExchangeCtx {
-        exch: &mut exch,
-        sess,
-    };
+            mode,
+        );
+        let sess_idx = sess_mgr.clone_session(&clone_data).unwrap();
+        Self { sess_mgr, sess_idx }
+    }
+}
+
+// A message, with its opcode
+pub type ImMsg = (OpCode, Vec<u8>);
+
+// A node with an Interaction Model that lasts across the interactions, e.g. with the
+// subscriptions that were created on it, and a CASE session with the other node
+pub struct ImNode {
+    pub im: InteractionModel,
+    sess: ImSession,
+}
+
+impl ImNode {
+    pub fn new(data_model: &DataModel) -> Self {
+        Self {
+            im: InteractionModel::new(Box::new(data_model.clone())),
+            sess: ImSession::new(SessionMode::Case(IM_ENGINE_FAB_IDX)),
+        }
+    }
+
+    // The local session id of the session with the other node
+    pub fn sess_id(&mut self) -> u16 {
+        self.sess
+            .sess_mgr
+            .get_session_handle(self.sess.sess_idx)
+            .get_local_sess_id()
+    }
+
+    // Wait for an unsolicited message, and encode it on the exchange
+    pub fn unsolicited(&mut self, exch: &mut Exchange) -> ImMsg {
+        let sess_id = self.sess_id();
+        loop {
+            if let Some(id) = self.im.get_unsolicited_sess() {
+                assert_eq!(id, sess_id);
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let sess = self.sess.sess_mgr.get_session_handle(self.sess.sess_idx);
+        let mut exch_ctx = ExchangeCtx { exch, sess };
+        let mut tx = Packet::new_tx().unwrap();
+        self.im.handle_unsolicited(&mut exch_ctx, &mut tx).unwrap();
+        let opcode = num::FromPrimitive::from_u8(tx.get_proto_opcode()).unwrap();
+        (opcode, tx.as_borrow_slice().to_vec())
+    }
+
+    // Handle the message on the exchange, and return the response, if any
+    pub fn handle(&mut self, exch: &mut Exchange, msg: ImMsg) -> Option<ImMsg> {
+        let sess = self.sess.sess_mgr.get_session_handle(self.sess.sess_idx);
+        let exch_ctx = ExchangeCtx { exch, sess };
+        let mut rx = Slab::<PacketPool>::new(Packet::new_rx().unwrap()).unwrap();
+        let tx = Slab::<PacketPool>::new(Packet::new_tx().unwrap()).unwrap();
+        rx.set_proto_id(0x01);
+        rx.set_proto_opcode(msg.0 as u8);
+        let in_data_len = msg.1.len();
+        rx.as_borrow_slice()[..in_data_len].copy_from_slice(&msg.1);
+        rx.get_parsebuf().unwrap().set_len(in_data_len);
+
+        let mut ctx = ProtoCtx::new(exch_ctx, rx, tx);
+        match self.im.handle_proto_id(&mut ctx).unwrap() {
+            ResponseRequired::Yes => {
+                let opcode = num::FromPrimitive::from_u8(ctx.tx.get_proto_opcode()).unwrap();
+                Some((opcode, ctx.tx.as_borrow_slice().to_vec()))
+            }
+            ResponseRequired::No => None,
+        }
+    }
+}
+
+// Run the unsolicited message from the initiator through the responder, until the
+// interaction is complete
+pub fn run_interaction(initiator: &mut ImNode, responder: &mut ImNode) {
+    let mut i_exch = Exchange::new(10, 0, exchange::Role::Initiator);
+    let mut r_exch = Exchange::new(10, 0, exchange::Role::Responder);
+    let mut msg = initiator.unsolicited(&mut i_exch);
+    loop {
+        msg = match responder.handle(&mut r_exch, msg) {
+            Some(m) => m,
+            None => break,
+        };
+        msg = match initiator.handle(&mut i_exch, msg) {
+            Some(m) => m,
+            None => break,
+        };
+    }
+}
+
+// Run a rx/tx transaction on an existing session and exchange
+pub fn im_engine_with_session(
+    data_model: &DataModel,
+    sess: &mut ImSession,
+    exch: &mut Exchange,
+    action: OpCode,
+    data_in: &[u8],
+    data_out: &mut [u8],
+) -> usize {
+    let mut interaction_model = Box::new(InteractionModel::new(Box::new(data_model.clone())));
+
+    let sess = sess.sess_mgr.get_session_handle(sess.sess_idx);
+    let exch_ctx = ExchangeCtx { exch, sess };
     let mut rx = Slab::<PacketPool>::new(Packet::new_rx().unwrap()).unwrap();
     let tx = Slab::<PacketPool>::new(Packet::new_tx().unwrap()).unwrap();
     // Create fake rx packet
@@ -90,7 +303,173 @@ pub fn im_engine(action: OpCode, data_in: &[u8], data_out: &mut [u8]) -> (DataMo
     interaction_model.handle_proto_id(&mut ctx).unwrap();
     let out_data_len = ctx.tx.as_borrow_slice().len();
     data_out[..out_data_len].copy_from_slice(ctx.tx.as_borrow_slice());
-    (data_model, out_data_len)
+    out_data_len
+}
+
+// Open a Timed Interaction window on the exchange, for the action that follows on it
+pub fn timed_req(data_model: &DataModel, exch: &mut Exchange, timeout: u16) {
+    let mut buf = [0u8; 100];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+    TimedReq::new(timeout)
+        .to_tlv(&mut tw, TagType::Anonymous)
+        .unwrap();
+
+    let mut out_buf = [0u8; 100];
+    im_engine_with_exch(
+        data_model,
+        exch,
+        OpCode::TimedRequest,
+        wb.as_borrow_slice(),
+        &mut out_buf,
+    );
+}
+
+// Run an Invoke Request for a single command on the exchange, with the fields that 'data'
+// writes, and pass its response to 'f'
+pub fn invoke_cmd_on_exch(
+    data_model: &DataModel,
+    exch: &mut Exchange,
+    path: &CmdPath,
+    data: &dyn Fn(&mut TLVWriter),
+    timed: bool,
+    f: impl FnOnce(InvResp),
+) {
+    let mut sess = ImSession::new(SessionMode::Case(IM_ENGINE_FAB_IDX));
+    invoke_cmd_with(data_model, &mut sess, exch, path, data, timed, f);
+}
+
+// Run an Invoke Request for a single command on the session, and pass its response to 'f'
+pub fn invoke_cmd_on_session(
+    data_model: &DataModel,
+    sess: &mut ImSession,
+    path: &CmdPath,
+    data: &dyn Fn(&mut TLVWriter),
+    f: impl FnOnce(InvResp),
+) {
+    let mut exch = Exchange::new(1, 0, exchange::Role::Responder);
+    invoke_cmd_with(data_model, sess, &mut exch, path, data, false, f);
+}
+
+fn invoke_cmd_with(
+    data_model: &DataModel,
+    sess: &mut ImSession,
+    exch: &mut Exchange,
+    path: &CmdPath,
+    data: &dyn Fn(&mut TLVWriter),
+    timed: bool,
+    f: impl FnOnce(InvResp),
+) {
+    let mut buf = [0u8; 800];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+    tw.start_struct(TagType::Anonymous).unwrap();
+    tw.bool(
+        TagType::Context(msg::InvReqTag::SupressResponse as u8),
+        false,
+    )
+    .unwrap();
+    tw.bool(TagType::Context(msg::InvReqTag::TimedReq as u8), timed)
+        .unwrap();
+    tw.start_array(TagType::Context(msg::InvReqTag::InvokeRequests as u8))
+        .unwrap();
+    tw.start_struct(TagType::Anonymous).unwrap();
+    path.to_tlv(&mut tw, TagType::Context(0)).unwrap();
+    tw.start_struct(TagType::Context(1)).unwrap();
+    data(&mut tw);
+    tw.end_container().unwrap();
+    tw.end_container().unwrap();
+    tw.end_container().unwrap();
+    tw.end_container().unwrap();
+
+    let mut out_buf = [0u8; 800];
+    let out_buf_len = im_engine_with_session(
+        data_model,
+        sess,
+        exch,
+        OpCode::InvokeRequest,
+        wb.as_borrow_slice(),
+        &mut out_buf,
+    );
+    let out_buf = &out_buf[..out_buf_len];
+    tlv::print_tlv_list(out_buf);
+    let root = tlv::get_root_node_struct(out_buf).unwrap();
+    let response = root
+        .find_tag(msg::InvRespTag::InvokeResponses as u32)
+        .unwrap()
+        .confirm_array()
+        .unwrap()
+        .iter()
+        .unwrap()
+        .next()
+        .unwrap();
+    f(InvResp::from_tlv(&response).unwrap());
+}
+
+// Run an Invoke Request for a single command, and pass its response to 'f'
+pub fn invoke_cmd(
+    data_model: &DataModel,
+    path: &CmdPath,
+    data: &dyn Fn(&mut TLVWriter),
+    f: impl FnOnce(InvResp),
+) {
+    let mut exch = Exchange::new(1, 0, exchange::Role::Responder);
+    invoke_cmd_on_exch(data_model, &mut exch, path, data, false, f);
+}
+
+// Read a single attribute, and pass its value to 'f'
+pub fn read_attr(data_model: &DataModel, path: &AttrPath, f: impl FnOnce(&TLVElement)) {
+    let mut sess = ImSession::new(SessionMode::Case(IM_ENGINE_FAB_IDX));
+    read_attr_on_session(data_model, &mut sess, path, f);
+}
+
+// Read a single attribute on the session, and pass its value to 'f'
+pub fn read_attr_on_session(
+    data_model: &DataModel,
+    sess: &mut ImSession,
+    path: &AttrPath,
+    f: impl FnOnce(&TLVElement),
+) {
+    let mut buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+    ReadReq::new(true)
+        .set_attr_requests(&[*path])
+        .to_tlv(&mut tw, TagType::Anonymous)
+        .unwrap();
+
+    let mut out_buf = [0u8; 800];
+    let mut exch = Exchange::new(1, 0, exchange::Role::Responder);
+    let out_buf_len = im_engine_with_session(
+        data_model,
+        sess,
+        &mut exch,
+        OpCode::ReadRequest,
+        wb.as_borrow_slice(),
+        &mut out_buf,
+    );
+    let out_buf = &out_buf[..out_buf_len];
+    tlv::print_tlv_list(out_buf);
+    let root = tlv::get_root_node_struct(out_buf).unwrap();
+    let report = root
+        .find_tag(msg::ReportDataTag::AttributeReports as u32)
+        .unwrap()
+        .confirm_array()
+        .unwrap()
+        .iter()
+        .unwrap()
+        .next()
+        .unwrap();
+    match AttrResp::from_tlv(&report).unwrap() {
+        AttrResp::Data(d) => match d.data {
+            AttrDataType::Tlv(t) => f(&t),
+            _ => panic!("Incorrect AttrDataType"),
+        },
+        _ => panic!("Expected Attribute Data"),
+    }
 }
 
 pub struct TestData<'a, 'b> {
@@ -105,13 +484,21 @@ impl<'a, 'b> TestData<'a, 'b> {
     }
 
     pub fn commands(&mut self, cmds: &[(CmdPath, Option<u8>)]) -> Result<(), Error> {
+        self.commands_timed(cmds, false)
+    }
+
+    pub fn commands_timed(
+        &mut self,
+        cmds: &[(CmdPath, Option<u8>)],
+        timed: bool,
+    ) -> Result<(), Error> {
         self.tw.start_struct(TagType::Anonymous)?;
         self.tw.bool(
             TagType::Context(msg::InvReqTag::SupressResponse as u8),
             false,
         )?;
         self.tw
-            .bool(TagType::Context(msg::InvReqTag::TimedReq as u8), false)?;
+            .bool(TagType::Context(msg::InvReqTag::TimedReq as u8), timed)?;
         self.tw
             .start_array(TagType::Context(msg::InvReqTag::InvokeRequests as u8))?;
 
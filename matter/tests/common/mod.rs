@@ -1,2 +1,3 @@
 pub mod echo_cluster;
 pub mod im_engine;
+pub mod test_vectors;
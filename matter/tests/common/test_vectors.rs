@@ -0,0 +1,110 @@
+// The certificates of the peer's fabric, fabric 0x1122, that the Operational Credentials
+// tests install. Its root issues an intermediate CA, and the NOCs of node 0x7788
+pub const RCAC: [u8; 231] = [
+    0x15, 0x30, 0x01, 0x01, 0x01, 0x24, 0x02, 0x01, 0x37, 0x03, 0x24, 0x14, 0x01, 0x18, 0x26, 0x04,
+    0x80, 0x22, 0x81, 0x27, 0x26, 0x05, 0x80, 0x25, 0x4d, 0x3a, 0x37, 0x06, 0x24, 0x14, 0x01, 0x18,
+    0x24, 0x07, 0x01, 0x24, 0x08, 0x01, 0x30, 0x09, 0x41, 0x04, 0x3d, 0xcc, 0x48, 0x33, 0x26, 0xd9,
+    0xbd, 0xaa, 0xfa, 0xf1, 0xd1, 0xfa, 0xbd, 0x83, 0x4d, 0xff, 0x9a, 0x6e, 0x23, 0x09, 0xf5, 0xef,
+    0x17, 0xab, 0xad, 0x9f, 0x72, 0x93, 0x31, 0xfd, 0x8f, 0xbb, 0x34, 0x74, 0x91, 0x88, 0x71, 0x08,
+    0xcf, 0x12, 0xef, 0x74, 0x81, 0x8c, 0xcd, 0x62, 0x87, 0x77, 0xe5, 0x6b, 0x29, 0xd0, 0x3f, 0xa8,
+    0xb9, 0xfe, 0x4c, 0xd9, 0x4b, 0x63, 0xd0, 0xe8, 0x6b, 0xf0, 0x37, 0x0a, 0x35, 0x01, 0x29, 0x01,
+    0x18, 0x24, 0x02, 0x60, 0x30, 0x04, 0x14, 0x22, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1,
+    0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0x30, 0x05, 0x14, 0x22, 0xa1,
+    0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1,
+    0xa1, 0xa1, 0x18, 0x30, 0x0b, 0x40, 0x52, 0x6b, 0x88, 0x61, 0xf3, 0x51, 0x6e, 0x98, 0x11, 0xa8,
+    0x60, 0x34, 0xe4, 0x3a, 0x0a, 0x78, 0xe6, 0xc3, 0x63, 0x94, 0x4c, 0x65, 0xa2, 0x9e, 0xca, 0x4e,
+    0xe4, 0x41, 0xd6, 0xb8, 0x83, 0x27, 0xdd, 0x20, 0x73, 0xc9, 0x05, 0x2a, 0x79, 0x1d, 0xfd, 0x69,
+    0xa8, 0x1b, 0x87, 0xd4, 0x1e, 0xaf, 0x93, 0xaf, 0x47, 0xab, 0x3e, 0x29, 0x02, 0xff, 0x68, 0x81,
+    0x3e, 0x28, 0xac, 0xb6, 0x0e, 0x6e, 0x18,
+];
+
+pub const ICAC: [u8; 239] = [
+    0x15, 0x30, 0x01, 0x01, 0x02, 0x24, 0x02, 0x01, 0x37, 0x03, 0x24, 0x14, 0x01, 0x25, 0x15, 0x22,
+    0x11, 0x18, 0x26, 0x04, 0x80, 0x22, 0x81, 0x27, 0x26, 0x05, 0x80, 0x25, 0x4d, 0x3a, 0x37, 0x06,
+    0x24, 0x13, 0x02, 0x25, 0x15, 0x22, 0x11, 0x18, 0x24, 0x07, 0x01, 0x24, 0x08, 0x01, 0x30, 0x09,
+    0x41, 0x04, 0x44, 0x2c, 0xae, 0x9c, 0x01, 0xd8, 0x89, 0x59, 0x82, 0xd6, 0x0d, 0xf6, 0x8b, 0xee,
+    0x20, 0x83, 0x36, 0x47, 0xe0, 0xb7, 0xe8, 0x27, 0xa1, 0xbd, 0xf1, 0x64, 0x5d, 0xe8, 0x32, 0xeb,
+    0xa9, 0xee, 0x2e, 0xb7, 0xf6, 0xe8, 0x02, 0x91, 0x0f, 0x0b, 0xcd, 0xc7, 0xff, 0x69, 0x23, 0xcd,
+    0x15, 0x00, 0xbb, 0xed, 0x5f, 0x96, 0xde, 0xb5, 0xf1, 0xe0, 0x61, 0x33, 0x8d, 0x2c, 0x04, 0x73,
+    0x93, 0xd7, 0x37, 0x0a, 0x35, 0x01, 0x29, 0x01, 0x18, 0x24, 0x02, 0x60, 0x30, 0x04, 0x14, 0x22,
+    0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33,
+    0x33, 0x33, 0x33, 0x30, 0x05, 0x14, 0x22, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1,
+    0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0x18, 0x30, 0x0b, 0x40, 0xb9, 0xc7,
+    0xf7, 0x0a, 0x52, 0x90, 0x20, 0xe0, 0xd0, 0xce, 0xa1, 0x73, 0xd4, 0x44, 0xae, 0x2a, 0xdf, 0x97,
+    0x3b, 0xc3, 0x41, 0xbb, 0xad, 0x0d, 0xe0, 0xf1, 0xf9, 0xd8, 0xe7, 0xb7, 0x85, 0x52, 0x0a, 0xc6,
+    0x8c, 0xde, 0xd0, 0x50, 0x5f, 0x98, 0x83, 0x56, 0xb5, 0xa2, 0xf8, 0xfd, 0xf0, 0x13, 0xc9, 0x57,
+    0x61, 0x9d, 0x42, 0x3d, 0xee, 0xe8, 0x93, 0x54, 0x31, 0x03, 0x6d, 0x42, 0x35, 0x1f, 0x18,
+];
+
+// Issued by the ICAC
+pub const NOC: [u8; 247] = [
+    0x15, 0x30, 0x01, 0x01, 0x03, 0x24, 0x02, 0x01, 0x37, 0x03, 0x24, 0x13, 0x02, 0x25, 0x15, 0x22,
+    0x11, 0x18, 0x26, 0x04, 0x80, 0x22, 0x81, 0x27, 0x26, 0x05, 0x80, 0x25, 0x4d, 0x3a, 0x37, 0x06,
+    0x25, 0x11, 0x88, 0x77, 0x25, 0x15, 0x22, 0x11, 0x18, 0x24, 0x07, 0x01, 0x24, 0x08, 0x01, 0x30,
+    0x09, 0x41, 0x04, 0x30, 0x01, 0x65, 0x4c, 0x10, 0x61, 0x7a, 0xb1, 0x00, 0xc9, 0x2c, 0xe2, 0xdb,
+    0xed, 0xf7, 0x27, 0x95, 0x8c, 0x2e, 0x50, 0x9c, 0xde, 0x82, 0x58, 0x35, 0xcf, 0x60, 0x73, 0xde,
+    0xd7, 0x54, 0xc6, 0x02, 0x1d, 0x5f, 0x5b, 0xc2, 0x8e, 0x28, 0x85, 0xd0, 0x69, 0xea, 0x7f, 0xf7,
+    0x80, 0x6a, 0xa1, 0xb6, 0x6f, 0x9a, 0xc2, 0x09, 0xb8, 0x38, 0x61, 0x1e, 0xd2, 0x60, 0x3c, 0xea,
+    0xda, 0xd1, 0x46, 0x37, 0x0a, 0x35, 0x01, 0x28, 0x01, 0x18, 0x24, 0x02, 0x01, 0x36, 0x03, 0x04,
+    0x02, 0x04, 0x01, 0x18, 0x30, 0x04, 0x14, 0x22, 0x00, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44,
+    0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x30, 0x05, 0x14, 0x22, 0x33,
+    0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33,
+    0x33, 0x33, 0x18, 0x30, 0x0b, 0x40, 0xdf, 0x9a, 0x06, 0xf4, 0x99, 0x6a, 0xb9, 0x96, 0x89, 0xdd,
+    0x9e, 0x7b, 0x7c, 0x0c, 0x31, 0x29, 0x95, 0xe9, 0xf3, 0x12, 0xe0, 0x8d, 0x62, 0x91, 0x18, 0x97,
+    0xe4, 0x02, 0xd7, 0x60, 0x7e, 0x9e, 0x22, 0x2e, 0x6f, 0x2d, 0x74, 0x3a, 0xc9, 0x07, 0x31, 0x7e,
+    0x13, 0xcc, 0x52, 0x61, 0x82, 0xe9, 0x3b, 0xe7, 0x6c, 0xf5, 0x04, 0x5e, 0xf9, 0x9c, 0x1d, 0x0e,
+    0xf8, 0xdd, 0x86, 0x00, 0xa2, 0xde, 0x18,
+];
+
+pub const NOC_PUBKEY: [u8; 65] = [
+    0x04, 0x30, 0x01, 0x65, 0x4c, 0x10, 0x61, 0x7a, 0xb1, 0x00, 0xc9, 0x2c, 0xe2, 0xdb, 0xed, 0xf7,
+    0x27, 0x95, 0x8c, 0x2e, 0x50, 0x9c, 0xde, 0x82, 0x58, 0x35, 0xcf, 0x60, 0x73, 0xde, 0xd7, 0x54,
+    0xc6, 0x02, 0x1d, 0x5f, 0x5b, 0xc2, 0x8e, 0x28, 0x85, 0xd0, 0x69, 0xea, 0x7f, 0xf7, 0x80, 0x6a,
+    0xa1, 0xb6, 0x6f, 0x9a, 0xc2, 0x09, 0xb8, 0x38, 0x61, 0x1e, 0xd2, 0x60, 0x3c, 0xea, 0xda, 0xd1,
+    0x46,
+];
+
+pub const NOC_PRIVKEY: [u8; 32] = [
+    0x16, 0x33, 0xe7, 0x5e, 0x9b, 0x9c, 0x7b, 0x02, 0x40, 0x6d, 0xc2, 0xe8, 0x09, 0x99, 0xc5, 0x46,
+    0xf8, 0x63, 0x9b, 0xfb, 0xb2, 0x13, 0x02, 0x52, 0x39, 0x26, 0x36, 0x28, 0x9d, 0x16, 0xc2, 0x40,
+];
+
+// Issued by the root itself
+pub const NOC_NO_ICAC: [u8; 247] = [
+    0x15, 0x30, 0x01, 0x01, 0x04, 0x24, 0x02, 0x01, 0x37, 0x03, 0x24, 0x14, 0x01, 0x25, 0x15, 0x22,
+    0x11, 0x18, 0x26, 0x04, 0x80, 0x22, 0x81, 0x27, 0x26, 0x05, 0x80, 0x25, 0x4d, 0x3a, 0x37, 0x06,
+    0x25, 0x11, 0x88, 0x77, 0x25, 0x15, 0x22, 0x11, 0x18, 0x24, 0x07, 0x01, 0x24, 0x08, 0x01, 0x30,
+    0x09, 0x41, 0x04, 0xca, 0x19, 0xce, 0x2f, 0x64, 0xf6, 0x4d, 0xa4, 0xff, 0x39, 0xec, 0xb8, 0x62,
+    0xd2, 0xc7, 0x75, 0xc0, 0xf7, 0x07, 0xb8, 0x36, 0x9d, 0xca, 0x76, 0xc7, 0xa2, 0xda, 0x35, 0x7c,
+    0x8d, 0x46, 0x6a, 0xff, 0x66, 0xc0, 0xd2, 0x18, 0x3c, 0x65, 0x11, 0x1c, 0x24, 0xd3, 0xe1, 0x74,
+    0x0e, 0x42, 0x3a, 0x5e, 0x64, 0xa0, 0x34, 0x45, 0x29, 0x9f, 0x16, 0x48, 0xe2, 0x48, 0x8f, 0xa8,
+    0x8a, 0x3d, 0xb3, 0x37, 0x0a, 0x35, 0x01, 0x28, 0x01, 0x18, 0x24, 0x02, 0x01, 0x36, 0x03, 0x04,
+    0x02, 0x04, 0x01, 0x18, 0x30, 0x04, 0x14, 0x22, 0x01, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44,
+    0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x30, 0x05, 0x14, 0x22, 0xa1,
+    0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1, 0xa1,
+    0xa1, 0xa1, 0x18, 0x30, 0x0b, 0x40, 0x65, 0x1a, 0x71, 0x1c, 0x74, 0xde, 0x3e, 0x0e, 0x14, 0x33,
+    0x28, 0x12, 0xb6, 0x25, 0x3d, 0x45, 0xbe, 0x42, 0x43, 0x89, 0xf6, 0x6e, 0xa7, 0x00, 0x53, 0xa1,
+    0xab, 0x87, 0x6c, 0xa1, 0xb8, 0x29, 0x16, 0x8d, 0xc5, 0x22, 0x7a, 0xf7, 0xc3, 0x45, 0x47, 0x9d,
+    0xf8, 0xec, 0xd4, 0xe1, 0x13, 0xab, 0x50, 0x7c, 0x75, 0x1a, 0xb4, 0x1c, 0x6b, 0x7a, 0x69, 0x78,
+    0x61, 0x56, 0x9e, 0x2d, 0x86, 0x53, 0x18,
+];
+
+// Issued by a root, that has the same name as the fabric's root, but a different key
+pub const NOC_OTHER_ROOT: [u8; 247] = [
+    0x15, 0x30, 0x01, 0x01, 0x09, 0x24, 0x02, 0x01, 0x37, 0x03, 0x24, 0x14, 0x01, 0x25, 0x15, 0x22,
+    0x11, 0x18, 0x26, 0x04, 0x80, 0x22, 0x81, 0x27, 0x26, 0x05, 0x80, 0x25, 0x4d, 0x3a, 0x37, 0x06,
+    0x25, 0x11, 0x88, 0x77, 0x25, 0x15, 0x22, 0x11, 0x18, 0x24, 0x07, 0x01, 0x24, 0x08, 0x01, 0x30,
+    0x09, 0x41, 0x04, 0xe8, 0xaa, 0x2b, 0x40, 0xc8, 0xfd, 0x76, 0x9a, 0xe7, 0x08, 0xd0, 0xe2, 0xb6,
+    0x51, 0x0d, 0x57, 0x3d, 0x14, 0x6d, 0x78, 0x85, 0xe1, 0x87, 0x2f, 0xc5, 0xa2, 0x50, 0x1c, 0xfd,
+    0x82, 0xaa, 0x82, 0x8c, 0x3d, 0xa7, 0xb8, 0x3d, 0x09, 0x1e, 0xae, 0x88, 0x11, 0x5d, 0x2c, 0x5d,
+    0x1d, 0x72, 0xb5, 0xc3, 0xf2, 0x7c, 0x60, 0x03, 0xb7, 0x31, 0xd3, 0x6c, 0x9a, 0xfd, 0xae, 0xcd,
+    0x23, 0x7f, 0x4d, 0x37, 0x0a, 0x35, 0x01, 0x28, 0x01, 0x18, 0x24, 0x02, 0x01, 0x36, 0x03, 0x04,
+    0x02, 0x04, 0x01, 0x18, 0x30, 0x04, 0x14, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66,
+    0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x30, 0x05, 0x14, 0x55, 0x55,
+    0x55, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55,
+    0x55, 0x55, 0x18, 0x30, 0x0b, 0x40, 0xdc, 0x1b, 0x6c, 0x9a, 0xc9, 0xa8, 0x52, 0x28, 0x9a, 0x47,
+    0x1d, 0xc6, 0x9f, 0x26, 0xef, 0xb6, 0x64, 0x90, 0x39, 0x71, 0xb9, 0xea, 0xc9, 0x0f, 0xac, 0x35,
+    0x9d, 0xf4, 0x4f, 0x35, 0xa0, 0x92, 0xf6, 0x3a, 0x21, 0x66, 0x50, 0x83, 0xca, 0xe3, 0xf2, 0x0d,
+    0x07, 0x28, 0xf8, 0xd2, 0xdf, 0x6e, 0xb7, 0xa6, 0x1a, 0x8f, 0xda, 0xc0, 0x5e, 0xd3, 0xd4, 0xd8,
+    0x18, 0x35, 0x76, 0x92, 0xed, 0x80, 0x18,
+];
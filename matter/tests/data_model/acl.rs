@@ -0,0 +1,365 @@
+use matter::{
+    acl::{AclEntry, AuthMode, Privilege, Target},
+    data_model::{
+        core::DataModel,
+        objects::GlobalElements,
+        sdm::{general_commissioning, noc},
+        system_model::access_control,
+    },
+    interaction_model::{
+        core::{IMStatusCode, OpCode},
+        messages::{
+            ib::{AttrData, AttrDataType, AttrPath, AttrResp, AttrStatus, CmdPath, InvResp},
+            msg::{self, ReadReq, WriteReq},
+        },
+    },
+    tlv::{self, FromTLV, TLVElement, TLVWriter, TagType, ToTLV},
+    utils::writebuf::WriteBuf,
+};
+
+use crate::common::{
+    echo_cluster,
+    im_engine::{
+        im_engine_with_dm, init_data_model, invoke_cmd, IM_ENGINE_FAB_IDX, IM_ENGINE_PEER_ID,
+    },
+};
+
+// Replace the ACL with a single entry for the peer of the IM Engine
+fn set_peer_entry(dm: &DataModel, privilege: Privilege, target: Option<Target>) {
+    let mut entry = AclEntry::new(IM_ENGINE_FAB_IDX, privilege, AuthMode::Case);
+    entry.add_subject(IM_ENGINE_PEER_ID).unwrap();
+    if let Some(t) = target {
+        entry.add_target(t).unwrap();
+    }
+    dm.acl_mgr.erase_all().unwrap();
+    dm.acl_mgr.add(entry).unwrap();
+}
+
+// Run a Read Request and pass the attribute reports to 'f'
+fn handle_read(dm: &DataModel, input: &[AttrPath], f: impl Fn(&[AttrResp])) {
+    handle_read_filtered(dm, input, true, f)
+}
+
+fn handle_read_filtered(
+    dm: &DataModel,
+    input: &[AttrPath],
+    fabric_filtered: bool,
+    f: impl Fn(&[AttrResp]),
+) {
+    let mut buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+
+    let read_req = ReadReq::new(fabric_filtered).set_attr_requests(input);
+    read_req.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+
+    let mut out_buf = [0u8; 800];
+    let out_buf_len =
+        im_engine_with_dm(dm, OpCode::ReadRequest, wb.as_borrow_slice(), &mut out_buf);
+    let out_buf = &out_buf[..out_buf_len];
+    tlv::print_tlv_list(out_buf);
+    let root = tlv::get_root_node_struct(out_buf).unwrap();
+
+    let mut responses = Vec::new();
+    if let Ok(reports) = root.find_tag(msg::ReportDataTag::AttributeReports as u32) {
+        for r in reports.confirm_array().unwrap().iter().unwrap() {
+            responses.push(AttrResp::from_tlv(&r).unwrap());
+        }
+    }
+    f(&responses);
+}
+
+// Run a Write Request and return the attribute statuses
+fn handle_write(dm: &DataModel, input: &[AttrData]) -> Vec<AttrStatus> {
+    let mut buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+
+    let write_req = WriteReq::new(false, input);
+    write_req.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+
+    let mut out_buf = [0u8; 400];
+    let out_buf_len =
+        im_engine_with_dm(dm, OpCode::WriteRequest, wb.as_borrow_slice(), &mut out_buf);
+    let out_buf = &out_buf[..out_buf_len];
+    tlv::print_tlv_list(out_buf);
+    let root = tlv::get_root_node_struct(out_buf).unwrap();
+
+    root.find_tag(msg::WriteRespTag::WriteResponses as u32)
+        .unwrap()
+        .confirm_array()
+        .unwrap()
+        .iter()
+        .unwrap()
+        .map(|r| AttrStatus::from_tlv(&r).unwrap())
+        .collect()
+}
+
+fn echo_attr_path(endpoint: Option<u16>, attr: echo_cluster::Attributes) -> AttrPath {
+    AttrPath {
+        endpoint,
+        cluster: Some(echo_cluster::ID),
+        attr: Some(attr as u16),
+        ..Default::default()
+    }
+}
+
+fn attr_status(path: &AttrPath, status: IMStatusCode) -> AttrStatus {
+    AttrStatus::new(&path.to_gp(), status, 0)
+}
+
+fn write_echo_attr(dm: &DataModel, value: u16) -> Vec<AttrStatus> {
+    let data =
+        |tag: TagType, t: &mut TLVWriter| t.u16(tag, value).map_err(|_| IMStatusCode::Failure);
+    let input = &[AttrData {
+        data_ver: None,
+        path: echo_attr_path(Some(0), echo_cluster::Attributes::AttWrite),
+        data: AttrDataType::Closure(&data),
+    }];
+    handle_write(dm, input)
+}
+
+#[test]
+fn test_read_no_acl_entry() {
+    // Without any ACL entries, a concrete read is rejected with UnsupportedAccess
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    dm.acl_mgr.erase_all().unwrap();
+
+    let path = echo_attr_path(Some(0), echo_cluster::Attributes::Att1);
+    handle_read(&dm, &[path], |responses| {
+        assert_eq!(responses.len(), 1);
+        match responses[0] {
+            AttrResp::Status(s) => {
+                assert_eq!(s, attr_status(&path, IMStatusCode::UnsupportedAccess))
+            }
+            _ => panic!("Expected an Attribute Status"),
+        }
+    });
+}
+
+#[test]
+fn test_read_wildcard_target() {
+    // A View entry that targets endpoint 0 only
+    // A wildcard read on all endpoints, silently skips endpoint 1
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    set_peer_entry(&dm, Privilege::View, Some(Target::new(Some(0), None, None)));
+
+    let path = echo_attr_path(None, echo_cluster::Attributes::Att1);
+    handle_read(&dm, &[path], |responses| {
+        assert_eq!(responses.len(), 1);
+        match responses[0] {
+            AttrResp::Data(d) => assert_eq!(d.path.endpoint, Some(0)),
+            _ => panic!("Expected Attribute Data"),
+        }
+    });
+}
+
+#[test]
+fn test_write_needs_operate() {
+    // A View entry can't write an attribute that needs Operate, an Operate entry can
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    let path = echo_attr_path(Some(0), echo_cluster::Attributes::AttWrite);
+    set_peer_entry(&dm, Privilege::View, None);
+    let status = write_echo_attr(&dm, 5);
+    assert_eq!(
+        status,
+        vec![attr_status(&path, IMStatusCode::UnsupportedAccess)]
+    );
+
+    set_peer_entry(&dm, Privilege::Operate, None);
+    let status = write_echo_attr(&dm, 5);
+    assert_eq!(status, vec![attr_status(&path, IMStatusCode::Sucess)]);
+}
+
+#[test]
+fn test_invoke_needs_operate() {
+    // A View entry can't invoke a command
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    set_peer_entry(&dm, Privilege::View, None);
+
+    let target = CmdPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Commands::EchoReq as u16),
+    );
+    let data = |tw: &mut TLVWriter| tw.u8(TagType::Context(0), 5).unwrap();
+    invoke_cmd(&dm, &target, &data, |resp| match resp {
+        InvResp::Status(path, status) => {
+            assert_eq!(path, target);
+            assert_eq!(status.status, IMStatusCode::UnsupportedAccess);
+        }
+        _ => panic!("Expected an Invoke Status"),
+    });
+}
+
+#[test]
+fn test_commissioning_cmds_need_admin() {
+    // An Operate entry can't arm the Fail-Safe or install a trusted root
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    set_peer_entry(&dm, Privilege::Operate, None);
+
+    let targets = [
+        CmdPath::new(
+            Some(0),
+            Some(general_commissioning::ID),
+            Some(general_commissioning::Commands::ArmFailsafe as u16),
+        ),
+        CmdPath::new(
+            Some(0),
+            Some(noc::ID),
+            Some(noc::Commands::AddTrustedRootCert as u16),
+        ),
+    ];
+    for target in targets {
+        let data = |tw: &mut TLVWriter| tw.u8(TagType::Context(0), 60).unwrap();
+        invoke_cmd(&dm, &target, &data, |resp| match resp {
+            InvResp::Status(path, status) => {
+                assert_eq!(path, target);
+                assert_eq!(status.status, IMStatusCode::UnsupportedAccess);
+            }
+            _ => panic!("Expected an Invoke Status"),
+        });
+    }
+    assert!(!dm.failsafe.is_armed());
+}
+
+#[test]
+fn test_acl_attribute_write_read() {
+    // The administrator replaces the ACL of its fabric, with itself and an Operate entry
+    // for another node. The ACL attribute reads back both entries
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    let mut admin = AclEntry::new(IM_ENGINE_FAB_IDX, Privilege::Administer, AuthMode::Case);
+    admin.add_subject(IM_ENGINE_PEER_ID).unwrap();
+    let mut operator = AclEntry::new(IM_ENGINE_FAB_IDX, Privilege::Operate, AuthMode::Case);
+    operator.add_subject(0x1234).unwrap();
+    operator
+        .add_target(Target::new(Some(1), Some(echo_cluster::ID), None))
+        .unwrap();
+
+    let data = |tag: TagType, t: &mut TLVWriter| {
+        let _ = t.start_array(tag);
+        for e in [&admin, &operator] {
+            e.to_tlv(t, TagType::Anonymous)
+                .map_err(|_| IMStatusCode::Failure)?;
+        }
+        let _ = t.end_container();
+        Ok(())
+    };
+    let acl_path = AttrPath {
+        endpoint: Some(0),
+        cluster: Some(access_control::ID),
+        attr: Some(access_control::Attributes::Acl as u16),
+        ..Default::default()
+    };
+    let input = &[AttrData {
+        data_ver: None,
+        path: acl_path,
+        data: AttrDataType::Closure(&data),
+    }];
+    let status = handle_write(&dm, input);
+    assert_eq!(status, vec![attr_status(&acl_path, IMStatusCode::Sucess)]);
+
+    let mut entries = Vec::new();
+    dm.acl_mgr
+        .for_each_acl(|e| entries.push(e.clone()))
+        .unwrap();
+    assert_eq!(entries, vec![admin.clone(), operator.clone()]);
+
+    handle_read(&dm, &[acl_path], |responses| {
+        assert_eq!(responses.len(), 1);
+        let data = match responses[0] {
+            AttrResp::Data(d) => d.data,
+            _ => panic!("Expected Attribute Data"),
+        };
+        let read: Vec<AclEntry> = match data {
+            AttrDataType::Tlv(t) => t
+                .confirm_array()
+                .unwrap()
+                .iter()
+                .unwrap()
+                .map(|e| AclEntry::from_tlv(&e).unwrap())
+                .collect(),
+            _ => panic!("Expected TLV data"),
+        };
+        assert_eq!(read, vec![admin.clone(), operator.clone()]);
+    });
+
+    // With an empty ACL, the attribute can't be read anymore
+    dm.acl_mgr.erase_all().unwrap();
+    handle_read(&dm, &[acl_path], |responses| match responses[0] {
+        AttrResp::Status(s) => {
+            assert_eq!(s, attr_status(&acl_path, IMStatusCode::UnsupportedAccess))
+        }
+        _ => panic!("Expected an Attribute Status"),
+    });
+}
+
+// The entries of the ACL attribute report
+fn acl_entries<'a>(responses: &[AttrResp<'a>]) -> Vec<TLVElement<'a>> {
+    assert_eq!(responses.len(), 1);
+    let data = match responses[0] {
+        AttrResp::Data(d) => d.data,
+        _ => panic!("Expected Attribute Data"),
+    };
+    match data {
+        AttrDataType::Tlv(t) => t.confirm_array().unwrap().iter().unwrap().collect(),
+        _ => panic!("Expected TLV data"),
+    }
+}
+
+#[test]
+fn test_acl_attribute_fabric_scoped_read() {
+    // The ACL has an entry on the peer's fabric and one on another fabric. A fabric-filtered
+    // read only reports the peer's entry, otherwise the other entry only has its fabric index
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    let mut admin = AclEntry::new(IM_ENGINE_FAB_IDX, Privilege::Administer, AuthMode::Case);
+    admin.add_subject(IM_ENGINE_PEER_ID).unwrap();
+    let other_fab_idx = IM_ENGINE_FAB_IDX + 1;
+    let mut other = AclEntry::new(other_fab_idx, Privilege::Administer, AuthMode::Case);
+    other.add_subject(0x1234).unwrap();
+    dm.acl_mgr.add(other).unwrap();
+
+    let acl_path = AttrPath {
+        endpoint: Some(0),
+        cluster: Some(access_control::ID),
+        attr: Some(access_control::Attributes::Acl as u16),
+        ..Default::default()
+    };
+
+    handle_read_filtered(&dm, &[acl_path], true, |responses| {
+        let entries = acl_entries(responses);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(AclEntry::from_tlv(&entries[0]).unwrap(), admin);
+    });
+
+    handle_read_filtered(&dm, &[acl_path], false, |responses| {
+        let entries = acl_entries(responses);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(AclEntry::from_tlv(&entries[0]).unwrap(), admin);
+        // The fabric-sensitive fields of the other fabric's entry are omitted
+        let fields = entries[1].confirm_struct().unwrap().iter().unwrap().count();
+        assert_eq!(fields, 1);
+        let fab_idx = entries[1]
+            .find_tag(GlobalElements::FabricIndex as u32)
+            .unwrap()
+            .u8()
+            .unwrap();
+        assert_eq!(fab_idx, other_fab_idx);
+    });
+}
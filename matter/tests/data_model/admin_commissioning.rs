@@ -0,0 +1,264 @@
+use matter::{
+    data_model::{
+        core::DataModel,
+        sdm::{admin_commissioning, general_commissioning as gen_comm},
+    },
+    interaction_model::{
+        core::IMStatusCode,
+        messages::ib::{AttrPath, CmdPath, InvResp, Status},
+    },
+    secure_channel::spake2p::VERIFIER_SIZE_BYTES,
+    tlv::{ElementType, TLVWriter, TagType},
+    transport::{
+        exchange::{self, Exchange},
+        session::SessionMode,
+    },
+};
+
+use crate::common::im_engine::{
+    init_data_model, invoke_cmd_on_exch, invoke_cmd_on_session, read_attr, timed_req, ImSession,
+    IM_ENGINE_FAB_IDX,
+};
+
+// The Enhanced Commissioning Window parameters
+struct OpenCommWindow {
+    timeout: u16,
+    verifier_len: usize,
+    discriminator: u16,
+    iterations: u32,
+}
+
+impl Default for OpenCommWindow {
+    fn default() -> Self {
+        Self {
+            timeout: 300,
+            verifier_len: VERIFIER_SIZE_BYTES,
+            discriminator: 1234,
+            iterations: 1000,
+        }
+    }
+}
+
+// Run an Administrator Commissioning command, that is preceded by a Timed Request if
+// 'timed' is set, and return the command status
+fn invoke(
+    dm: &DataModel,
+    cmd: admin_commissioning::Commands,
+    data: &dyn Fn(&mut TLVWriter),
+    timed: bool,
+) -> Status {
+    let mut exch = Exchange::new(1, 0, exchange::Role::Responder);
+    if timed {
+        timed_req(dm, &mut exch, 500);
+    }
+
+    let path = CmdPath::new(Some(0), Some(admin_commissioning::ID), Some(cmd as u16));
+    let mut status = None;
+    invoke_cmd_on_exch(dm, &mut exch, &path, data, timed, |resp| match resp {
+        InvResp::Status(p, s) => {
+            assert_eq!(p, path);
+            status = Some(s);
+        }
+        _ => panic!("Expected an Invoke Status"),
+    });
+    status.unwrap()
+}
+
+fn open_comm_window(dm: &DataModel, params: &OpenCommWindow) -> Status {
+    let data = |tw: &mut TLVWriter| {
+        tw.u16(TagType::Context(0), params.timeout).unwrap();
+        tw.str8(TagType::Context(1), &[0u8; 100][..params.verifier_len])
+            .unwrap();
+        tw.u16(TagType::Context(2), params.discriminator).unwrap();
+        tw.u32(TagType::Context(3), params.iterations).unwrap();
+        tw.str8(TagType::Context(4), &[0u8; 16]).unwrap();
+    };
+    invoke(
+        dm,
+        admin_commissioning::Commands::OpenCommWindow,
+        &data,
+        true,
+    )
+}
+
+fn revoke_comm(dm: &DataModel) -> Status {
+    invoke(dm, admin_commissioning::Commands::RevokeComm, &|_| (), true)
+}
+
+// Read an attribute of the cluster, None if it is Null
+fn read_admin_attr(dm: &DataModel, attr: admin_commissioning::Attributes) -> Option<u8> {
+    let path = AttrPath {
+        endpoint: Some(0),
+        cluster: Some(admin_commissioning::ID),
+        attr: Some(attr as u16),
+        ..Default::default()
+    };
+    let mut value = None;
+    read_attr(dm, &path, |t| {
+        value = match t.get_element_type() {
+            ElementType::Null => None,
+            _ => Some(t.u8().unwrap()),
+        }
+    });
+    value
+}
+
+// Arm the Fail-Safe on the session, as the commissioner does once PASE is established
+fn arm_failsafe(dm: &DataModel, sess: &mut ImSession) {
+    let path = CmdPath::new(
+        Some(0),
+        Some(gen_comm::ID),
+        Some(gen_comm::Commands::ArmFailsafe as u16),
+    );
+    let data = |tw: &mut TLVWriter| {
+        tw.u8(TagType::Context(0), 60).unwrap();
+        tw.u8(TagType::Context(1), 1).unwrap();
+    };
+    invoke_cmd_on_session(dm, sess, &path, &data, |resp| match resp {
+        InvResp::Cmd(_) => (),
+        _ => panic!("Expected a response command"),
+    });
+}
+
+fn success() -> Status {
+    Status::new(IMStatusCode::Sucess, 0)
+}
+
+fn cluster_failure(cluster_status: u16) -> Status {
+    Status::new(IMStatusCode::Failure, cluster_status)
+}
+
+#[test]
+fn test_commands_need_timed() {
+    // The commands can only be invoked in a Timed Interaction
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    let data = |tw: &mut TLVWriter| tw.u16(TagType::Context(0), 300).unwrap();
+    let status = invoke(
+        &dm,
+        admin_commissioning::Commands::OpenBasicCommWindow,
+        &data,
+        false,
+    );
+    assert_eq!(status.status, IMStatusCode::NeedsTimedInteraction);
+}
+
+#[test]
+fn test_open_and_revoke_window() {
+    // The window is opened by the administrator of fabric 1, it can't be opened twice,
+    // and it can only be revoked once
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    assert_eq!(
+        read_admin_attr(&dm, admin_commissioning::Attributes::WindowStatus),
+        Some(0)
+    );
+
+    assert_eq!(open_comm_window(&dm, &OpenCommWindow::default()), success());
+    assert_eq!(
+        read_admin_attr(&dm, admin_commissioning::Attributes::WindowStatus),
+        Some(1)
+    );
+    assert_eq!(
+        read_admin_attr(&dm, admin_commissioning::Attributes::AdminFabricIndex),
+        Some(1)
+    );
+
+    // Busy
+    assert_eq!(
+        open_comm_window(&dm, &OpenCommWindow::default()),
+        cluster_failure(2)
+    );
+
+    assert_eq!(revoke_comm(&dm), success());
+    assert_eq!(
+        read_admin_attr(&dm, admin_commissioning::Attributes::WindowStatus),
+        Some(0)
+    );
+    assert_eq!(
+        read_admin_attr(&dm, admin_commissioning::Attributes::AdminFabricIndex),
+        None
+    );
+
+    // Window Not Open
+    assert_eq!(revoke_comm(&dm), cluster_failure(4));
+}
+
+#[test]
+fn test_open_window_invalid_params() {
+    // Invalid PAKE parameters fail with PAKEParameterError, an invalid timeout or
+    // discriminator with InvalidCommand
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    let params = OpenCommWindow {
+        verifier_len: VERIFIER_SIZE_BYTES - 1,
+        ..Default::default()
+    };
+    assert_eq!(open_comm_window(&dm, &params), cluster_failure(3));
+    let params = OpenCommWindow {
+        iterations: 999,
+        ..Default::default()
+    };
+    assert_eq!(open_comm_window(&dm, &params), cluster_failure(3));
+
+    let params = OpenCommWindow {
+        timeout: 901,
+        ..Default::default()
+    };
+    assert_eq!(
+        open_comm_window(&dm, &params).status,
+        IMStatusCode::InvalidCommand
+    );
+    let params = OpenCommWindow {
+        discriminator: 0x1000,
+        ..Default::default()
+    };
+    assert_eq!(
+        open_comm_window(&dm, &params).status,
+        IMStatusCode::InvalidCommand
+    );
+
+    assert_eq!(
+        read_admin_attr(&dm, admin_commissioning::Attributes::WindowStatus),
+        Some(0)
+    );
+}
+
+#[test]
+fn test_revoke_aborts_pase_commissioning() {
+    // Revoking the window expires the Fail-Safe that was armed over PASE, and has the
+    // PASE sessions closed
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    assert_eq!(open_comm_window(&dm, &OpenCommWindow::default()), success());
+    let mut sess = ImSession::new(SessionMode::Pase);
+    arm_failsafe(&dm, &mut sess);
+    assert!(!dm.failsafe.check_expiry());
+    assert!(dm.failsafe.is_armed());
+
+    assert_eq!(revoke_comm(&dm), success());
+    assert!(dm.failsafe.check_expiry());
+    assert!(!dm.failsafe.is_armed());
+    // Only once
+    assert!(!dm.failsafe.check_expiry());
+}
+
+#[test]
+fn test_revoke_keeps_case_failsafe() {
+    // A Fail-Safe that was armed over CASE isn't part of the PASE commissioning
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    assert_eq!(open_comm_window(&dm, &OpenCommWindow::default()), success());
+    let mut sess = ImSession::new(SessionMode::Case(IM_ENGINE_FAB_IDX));
+    arm_failsafe(&dm, &mut sess);
+
+    assert_eq!(revoke_comm(&dm), success());
+    // The PASE sessions are still closed
+    assert!(dm.failsafe.check_expiry());
+    assert!(dm.failsafe.is_armed());
+}
@@ -2,21 +2,34 @@ use matter::{
     data_model::{
         cluster_on_off,
         core::DataModel,
-        objects::{AttrValue, GlobalElements},
+        objects::{
+            Access, AttrChange, AttrValue, Attribute, Cluster, ClusterType, Constraint,
+            GlobalElements, Quality,
+        },
     },
     interaction_model::{
         core::{IMStatusCode, OpCode},
         messages::{
-            ib::{AttrData, AttrDataTag, AttrDataType, AttrPath, AttrResp, AttrStatus},
-            msg::{ReadReq, WriteReq},
+            ib::{
+                AttrData, AttrDataTag, AttrDataType, AttrPath, AttrResp, AttrStatus,
+                DataVersionFilter,
+            },
+            msg::{ReadReq, StatusResp, WriteReq},
         },
         messages::{msg, GenericPath},
     },
-    tlv::{self, ElementType, FromTLV, TLVElement, TLVList, TLVWriter, TagType, ToTLV},
+    tlv::{self, ElementType, FromTLV, Nullable, TLVElement, TLVList, TLVWriter, TagType, ToTLV},
+    transport::{
+        exchange::{Exchange, Role},
+        packet::MAX_RX_BUF_SIZE,
+    },
     utils::writebuf::WriteBuf,
 };
 
-use crate::common::{echo_cluster, im_engine::im_engine};
+use crate::common::{
+    echo_cluster,
+    im_engine::{im_engine_with_dm, im_engine_with_exch, init_data_model},
+};
 
 enum ExpectedReportData<'a> {
     Data(AttrData<'a>),
@@ -25,6 +38,16 @@ enum ExpectedReportData<'a> {
 
 // Helper for handling Invoke Command sequences
 fn handle_read_reqs(input: &[AttrPath], expected: &[ExpectedReportData]) {
+    handle_filtered_read_reqs(&init_data_model(), input, &[], expected);
+}
+
+// Helper for handling Read Requests with Data Version Filters
+fn handle_filtered_read_reqs(
+    dm: &DataModel,
+    input: &[AttrPath],
+    dataver_filters: &[DataVersionFilter],
+    expected: &[ExpectedReportData],
+) {
     let mut buf = [0u8; 400];
 
     let buf_len = buf.len();
@@ -32,10 +55,13 @@ fn handle_read_reqs(input: &[AttrPath], expected: &[ExpectedReportData]) {
     let mut tw = TLVWriter::new(&mut wb);
     let mut out_buf = [0u8; 400];
 
-    let read_req = ReadReq::new(true).set_attr_requests(input);
+    let read_req = ReadReq::new(true)
+        .set_attr_requests(input)
+        .set_dataver_filters(dataver_filters);
     read_req.to_tlv(&mut tw, TagType::Anonymous).unwrap();
 
-    let (_, out_buf_len) = im_engine(OpCode::ReadRequest, wb.as_borrow_slice(), &mut out_buf);
+    let out_buf_len =
+        im_engine_with_dm(dm, OpCode::ReadRequest, wb.as_borrow_slice(), &mut out_buf);
     let out_buf = &out_buf[..out_buf_len];
     tlv::print_tlv_list(out_buf);
     let root = tlv::get_root_node_struct(out_buf).unwrap();
@@ -78,6 +104,12 @@ fn handle_read_reqs(input: &[AttrPath], expected: &[ExpectedReportData]) {
 
 // Helper for handling Invoke Command sequences
 fn handle_write_reqs(input: &[AttrData], expected: &[AttrStatus]) -> DataModel {
+    let dm = init_data_model();
+    handle_write_reqs_on(&dm, input, expected);
+    dm
+}
+
+fn handle_write_reqs_on(dm: &DataModel, input: &[AttrData], expected: &[AttrStatus]) {
     let mut buf = [0u8; 400];
     let mut out_buf = [0u8; 400];
 
@@ -88,7 +120,8 @@ fn handle_write_reqs(input: &[AttrData], expected: &[AttrStatus]) -> DataModel {
     let write_req = WriteReq::new(false, input);
     write_req.to_tlv(&mut tw, TagType::Anonymous).unwrap();
 
-    let (dm, out_buf_len) = im_engine(OpCode::WriteRequest, wb.as_borrow_slice(), &mut out_buf);
+    let out_buf_len =
+        im_engine_with_dm(dm, OpCode::WriteRequest, wb.as_borrow_slice(), &mut out_buf);
     let out_buf = &out_buf[..out_buf_len];
     tlv::print_tlv_list(out_buf);
     let root = tlv::get_root_node_struct(out_buf).unwrap();
@@ -109,7 +142,6 @@ fn handle_write_reqs(input: &[AttrData], expected: &[AttrStatus]) -> DataModel {
         index += 1;
     }
     assert_eq!(index, expected.len());
-    dm
 }
 
 macro_rules! attr_data {
@@ -282,6 +314,58 @@ fn test_read_wc_endpoint_only_1_has_cluster() {
     handle_read_reqs(input, expected);
 }
 
+#[test]
+fn test_read_dataver_filter() {
+    // 2 Attr Read Requests, with a Data Version Filter that matches the cluster on endpoint 0
+    // - endpoint 0, att1: skipped
+    // - wildcard endpoint, att1: only endpoint 1 is reported
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    let data_ver = dm
+        .node
+        .read()
+        .unwrap()
+        .get_cluster(0, echo_cluster::ID)
+        .unwrap()
+        .base()
+        .get_dataver();
+
+    let ep0_att1 = GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Attributes::Att1 as u32),
+    );
+    let ep1_att1 = GenericPath::new(
+        Some(1),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Attributes::Att1 as u32),
+    );
+    let wc_ep_att1 = GenericPath::new(
+        None,
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Attributes::Att1 as u32),
+    );
+    let input = &[AttrPath::new(&ep0_att1), AttrPath::new(&wc_ep_att1)];
+    let filters = &[DataVersionFilter::new(0, echo_cluster::ID, data_ver)];
+
+    let expected = &[attr_data!(ep1_att1, ElementType::U16(0x1234))];
+    handle_filtered_read_reqs(&dm, input, filters, expected);
+
+    // A stale data version doesn't filter anything
+    let filters = &[DataVersionFilter::new(
+        0,
+        echo_cluster::ID,
+        data_ver.wrapping_sub(1),
+    )];
+    let expected = &[
+        attr_data!(ep0_att1, ElementType::U16(0x1234)),
+        attr_data!(ep0_att1, ElementType::U16(0x1234)),
+        attr_data!(ep1_att1, ElementType::U16(0x1234)),
+    ];
+    handle_filtered_read_reqs(&dm, input, filters, expected);
+}
+
 fn get_tlvs<'a>(buf: &'a mut [u8], data: &[u16]) -> TLVElement<'a> {
     let buf_len = buf.len();
     let mut wb = WriteBuf::new(buf, buf_len);
@@ -450,6 +534,55 @@ fn test_write_success() {
     );
 }
 
+#[test]
+fn test_write_same_value() {
+    // Writing the value that the attribute already has, is not a change: the data
+    // version stays the same, and the application isn't notified
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+    let rx = dm.attr_changes();
+    let dataver = |dm: &DataModel| {
+        let node = dm.node.read().unwrap();
+        let c = node.get_cluster(0, echo_cluster::ID).unwrap();
+        c.base().get_dataver()
+    };
+
+    let val0 = 10;
+    let attr_data0 = |tag, t: &mut TLVWriter| {
+        let _ = t.u16(tag, val0);
+        Ok(())
+    };
+    let ep0_att = GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Attributes::AttWrite as u32),
+    );
+    let input = &[AttrData::new(
+        None,
+        AttrPath::new(&ep0_att),
+        AttrDataType::Closure(&attr_data0),
+    )];
+    let expected = &[AttrStatus::new(&ep0_att, IMStatusCode::Sucess, 0)];
+
+    let before = dataver(&dm);
+    handle_write_reqs_on(&dm, input, expected);
+    let after = dataver(&dm);
+    assert_ne!(after, before);
+    assert_eq!(
+        rx.try_recv(),
+        Ok(AttrChange {
+            endpoint: 0,
+            cluster: echo_cluster::ID,
+            attr: echo_cluster::Attributes::AttWrite as u16,
+            value: AttrValue::Uint16(val0),
+        })
+    );
+
+    handle_write_reqs_on(&dm, input, expected);
+    assert_eq!(dataver(&dm), after);
+    assert!(rx.try_recv().is_err());
+}
+
 #[test]
 fn test_write_wc_endpoint() {
     // 1 Attr Write Request
@@ -607,3 +740,431 @@ fn test_write_unsupported_fields() {
         .unwrap()
     );
 }
+
+// A cluster with attributes of the various value types
+const VALUES_CLUSTER_ID: u32 = 0xABCE;
+
+struct ValuesCluster {
+    base: Cluster,
+}
+
+impl ClusterType for ValuesCluster {
+    fn base(&self) -> &Cluster {
+        &self.base
+    }
+    fn base_mut(&mut self) -> &mut Cluster {
+        &mut self.base
+    }
+}
+
+fn add_values_cluster(dm: &DataModel, values: Vec<AttrValue>) {
+    let attrs = values
+        .into_iter()
+        .enumerate()
+        .map(|(id, value)| Attribute::new(id as u16, value, Access::RWVM, Quality::NONE).unwrap())
+        .collect();
+    add_values_cluster_attrs(dm, attrs);
+}
+
+fn add_values_cluster_attrs(dm: &DataModel, attrs: Vec<Attribute>) {
+    let mut base = Cluster::new(VALUES_CLUSTER_ID).unwrap();
+    for attr in attrs {
+        base.add_attribute(attr).unwrap();
+    }
+    dm.node
+        .write()
+        .unwrap()
+        .add_cluster(1, Box::new(ValuesCluster { base }))
+        .unwrap();
+}
+
+#[test]
+fn test_read_write_value_types() {
+    // The values of the various types are encoded in TLV, and writes are decoded into the
+    // type of the attribute
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    add_values_cluster(
+        &dm,
+        vec![
+            AttrValue::Int16(-300),
+            AttrValue::Utf8("Kitchen".to_owned()),
+            AttrValue::null(AttrValue::Uint8(0)),
+            AttrValue::Double(0.5),
+            AttrValue::list(AttrValue::Uint16(0), vec![]),
+        ],
+    );
+    let path = |attr: u32| GenericPath::new(Some(1), Some(VALUES_CLUSTER_ID), Some(attr));
+    let input = &[
+        AttrPath::new(&path(0)),
+        AttrPath::new(&path(1)),
+        AttrPath::new(&path(2)),
+        AttrPath::new(&path(3)),
+    ];
+    let expected = &[
+        attr_data!(path(0), ElementType::S16(-300)),
+        attr_data!(path(1), ElementType::Utf8l(b"Kitchen")),
+        attr_data!(path(2), ElementType::Null),
+        attr_data!(path(3), ElementType::F64(0.5)),
+    ];
+    handle_filtered_read_reqs(&dm, input, &[], expected);
+
+    let int16 = |tag, t: &mut TLVWriter| {
+        let _ = t.i16(tag, -2);
+        Ok(())
+    };
+    let utf8 = |tag, t: &mut TLVWriter| {
+        let _ = t.utf8(tag, b"Hall");
+        Ok(())
+    };
+    let nullable = |tag, t: &mut TLVWriter| {
+        let _ = t.u8(tag, 5);
+        Ok(())
+    };
+    let list = |tag, t: &mut TLVWriter| {
+        let _ = t.start_array(tag);
+        let _ = t.u16(TagType::Anonymous, 7);
+        let _ = t.u16(TagType::Anonymous, 0x1234);
+        let _ = t.end_container();
+        Ok(())
+    };
+    let input = &[
+        AttrData::new(None, AttrPath::new(&path(0)), AttrDataType::Closure(&int16)),
+        AttrData::new(None, AttrPath::new(&path(1)), AttrDataType::Closure(&utf8)),
+        AttrData::new(
+            None,
+            AttrPath::new(&path(2)),
+            AttrDataType::Closure(&nullable),
+        ),
+        AttrData::new(None, AttrPath::new(&path(4)), AttrDataType::Closure(&list)),
+    ];
+    let expected = &[
+        AttrStatus::new(&path(0), IMStatusCode::Sucess, 0),
+        AttrStatus::new(&path(1), IMStatusCode::Sucess, 0),
+        AttrStatus::new(&path(2), IMStatusCode::Sucess, 0),
+        AttrStatus::new(&path(4), IMStatusCode::Sucess, 0),
+    ];
+    handle_write_reqs_on(&dm, input, expected);
+
+    let read = |attr: u16| dm.read_attribute_raw(1, VALUES_CLUSTER_ID, attr).unwrap();
+    assert_eq!(read(0), AttrValue::Int16(-2));
+    assert_eq!(read(1), AttrValue::Utf8("Hall".to_owned()));
+    assert_eq!(read(2), AttrValue::nullable(AttrValue::Uint8(5)));
+    assert_eq!(
+        read(4),
+        AttrValue::list(
+            AttrValue::Uint16(0),
+            vec![AttrValue::Uint16(7), AttrValue::Uint16(0x1234)]
+        )
+    );
+
+    // A value of another type is rejected
+    let input = &[AttrData::new(
+        None,
+        AttrPath::new(&path(1)),
+        AttrDataType::Closure(&nullable),
+    )];
+    let expected = &[AttrStatus::new(&path(1), IMStatusCode::InvalidDataType, 0)];
+    handle_write_reqs_on(&dm, input, expected);
+    assert_eq!(read(1), AttrValue::Utf8("Hall".to_owned()));
+}
+
+#[test]
+fn test_write_constraints() {
+    // The writes that don't meet the constraint of the attribute are rejected, and leave the
+    // value as it was
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    let attr = |id: u16, value: AttrValue, constraint: Option<Constraint>| {
+        let attr = Attribute::new(id, value, Access::RWVM, Quality::NONE).unwrap();
+        match constraint {
+            Some(c) => attr.set_constraint(c),
+            None => attr,
+        }
+    };
+    add_values_cluster_attrs(
+        &dm,
+        vec![
+            attr(
+                0,
+                AttrValue::Uint8(10),
+                Some(Constraint::Range { min: 1, max: 100 }),
+            ),
+            attr(1, AttrValue::Enum8(0), Some(Constraint::OneOf(&[0, 1, 2]))),
+            attr(
+                2,
+                AttrValue::Utf8("".to_owned()),
+                Some(Constraint::MaxLength(4)),
+            ),
+            attr(
+                3,
+                AttrValue::nullable(AttrValue::Uint16(5)),
+                Some(Constraint::Range { min: 0, max: 10 }),
+            ),
+            attr(4, AttrValue::Uint16(0), None),
+        ],
+    );
+    let path = |attr: u32| GenericPath::new(Some(1), Some(VALUES_CLUSTER_ID), Some(attr));
+
+    let u8_0 = |tag, t: &mut TLVWriter| {
+        let _ = t.u8(tag, 0);
+        Ok(())
+    };
+    let u8_3 = |tag, t: &mut TLVWriter| {
+        let _ = t.u8(tag, 3);
+        Ok(())
+    };
+    let u16_11 = |tag, t: &mut TLVWriter| {
+        let _ = t.u16(tag, 11);
+        Ok(())
+    };
+    let long_str = |tag, t: &mut TLVWriter| {
+        let _ = t.utf8(tag, b"Kitchen");
+        Ok(())
+    };
+    let null = |tag, t: &mut TLVWriter| {
+        let _ = t.null(tag);
+        Ok(())
+    };
+    let input = &[
+        AttrData::new(None, AttrPath::new(&path(0)), AttrDataType::Closure(&u8_0)),
+        AttrData::new(None, AttrPath::new(&path(1)), AttrDataType::Closure(&u8_3)),
+        AttrData::new(
+            None,
+            AttrPath::new(&path(2)),
+            AttrDataType::Closure(&long_str),
+        ),
+        AttrData::new(
+            None,
+            AttrPath::new(&path(3)),
+            AttrDataType::Closure(&u16_11),
+        ),
+        AttrData::new(None, AttrPath::new(&path(4)), AttrDataType::Closure(&null)),
+        AttrData::new(
+            None,
+            AttrPath::new(&path(4)),
+            AttrDataType::Closure(&long_str),
+        ),
+    ];
+    let expected = &[
+        AttrStatus::new(&path(0), IMStatusCode::ConstraintError, 0),
+        AttrStatus::new(&path(1), IMStatusCode::ConstraintError, 0),
+        AttrStatus::new(&path(2), IMStatusCode::ConstraintError, 0),
+        AttrStatus::new(&path(3), IMStatusCode::ConstraintError, 0),
+        AttrStatus::new(&path(4), IMStatusCode::ConstraintError, 0),
+        AttrStatus::new(&path(4), IMStatusCode::InvalidDataType, 0),
+    ];
+    handle_write_reqs_on(&dm, input, expected);
+
+    let read = |attr: u16| dm.read_attribute_raw(1, VALUES_CLUSTER_ID, attr).unwrap();
+    assert_eq!(read(0), AttrValue::Uint8(10));
+    assert_eq!(read(1), AttrValue::Enum8(0));
+    assert_eq!(read(2), AttrValue::Utf8("".to_owned()));
+    assert_eq!(read(3), AttrValue::nullable(AttrValue::Uint16(5)));
+    assert_eq!(read(4), AttrValue::Uint16(0));
+
+    // The values that meet the constraints are written, null is always allowed for a
+    // nullable attribute
+    let input = &[
+        AttrData::new(None, AttrPath::new(&path(1)), AttrDataType::Closure(&u8_0)),
+        AttrData::new(None, AttrPath::new(&path(3)), AttrDataType::Closure(&null)),
+    ];
+    let expected = &[
+        AttrStatus::new(&path(1), IMStatusCode::Sucess, 0),
+        AttrStatus::new(&path(3), IMStatusCode::Sucess, 0),
+    ];
+    handle_write_reqs_on(&dm, input, expected);
+    assert_eq!(read(3), AttrValue::null(AttrValue::Uint16(5)));
+}
+
+// Run a read, acknowledging each of its ReportData messages with a StatusResponse, until
+// the last one. Returns all the ReportData messages
+fn handle_chunked_read_reqs(dm: &DataModel, input: &[AttrPath]) -> Vec<Vec<u8>> {
+    let mut buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+    ReadReq::new(true)
+        .set_attr_requests(input)
+        .to_tlv(&mut tw, TagType::Anonymous)
+        .unwrap();
+
+    let mut exch = Exchange::new(1, 0, Role::Responder);
+    let mut action = OpCode::ReadRequest;
+    let mut reports = Vec::new();
+    loop {
+        let mut out_buf = [0u8; MAX_RX_BUF_SIZE];
+        let out_buf_len =
+            im_engine_with_exch(dm, &mut exch, action, wb.as_borrow_slice(), &mut out_buf);
+        let out_buf = &out_buf[..out_buf_len];
+        tlv::print_tlv_list(out_buf);
+        reports.push(out_buf.to_vec());
+
+        let root = tlv::get_root_node_struct(out_buf).unwrap();
+        let more_chunks = root
+            .find_tag(msg::ReportDataTag::MoreChunkedMsgs as u32)
+            .is_ok_and(|t| t.bool().unwrap());
+        if !more_chunks {
+            assert!(root
+                .find_tag(msg::ReportDataTag::SupressResponse as u32)
+                .unwrap()
+                .bool()
+                .unwrap());
+            return reports;
+        }
+
+        wb.reset(0);
+        let mut tw = TLVWriter::new(&mut wb);
+        StatusResp::new(IMStatusCode::Sucess)
+            .to_tlv(&mut tw, TagType::Anonymous)
+            .unwrap();
+        action = OpCode::StatusResponse;
+    }
+}
+
+fn attr_reports<'a>(report: &'a [u8]) -> Vec<AttrResp<'a>> {
+    tlv::get_root_node_struct(report)
+        .unwrap()
+        .find_tag(msg::ReportDataTag::AttributeReports as u32)
+        .unwrap()
+        .confirm_array()
+        .unwrap()
+        .iter()
+        .unwrap()
+        .map(|r| AttrResp::from_tlv(&r).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_read_chunked_wildcard() {
+    // The reports that don't fit in a single ReportData are split across several of them,
+    // and each attribute is reported exactly once
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    add_values_cluster(&dm, vec![AttrValue::Utf8("x".repeat(250)); 5]);
+
+    let reports = handle_chunked_read_reqs(&dm, &[AttrPath::default()]);
+    assert!(reports.len() > 1);
+    let mut paths = Vec::new();
+    for report in &reports {
+        for resp in attr_reports(report) {
+            match resp {
+                AttrResp::Data(d) => paths.push(d.path.to_gp()),
+                AttrResp::Status(_) => panic!("Wildcard reads don't report errors"),
+            }
+        }
+    }
+
+    let mut expected = Vec::new();
+    dm.node
+        .read()
+        .unwrap()
+        .for_each_attribute(&GenericPath::new(None, None, None), |path, c| {
+            let access = c
+                .base()
+                .get_attribute_access(path.leaf.unwrap() as u16)
+                .unwrap();
+            if access.contains(Access::READ) {
+                expected.push(*path);
+            }
+        });
+    assert_eq!(paths, expected);
+}
+
+#[test]
+fn test_read_chunked_list() {
+    // A list attribute that doesn't fit in a ReportData of its own, is reported as an empty
+    // list, followed by its entries that are appended one at a time
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    let entries: Vec<AttrValue> = (0..40)
+        .map(|i| AttrValue::Utf8(format!("{:02}", i).repeat(30)))
+        .collect();
+    add_values_cluster(
+        &dm,
+        vec![AttrValue::list(
+            AttrValue::Utf8("".to_owned()),
+            entries.clone(),
+        )],
+    );
+
+    let path = GenericPath::new(Some(1), Some(VALUES_CLUSTER_ID), Some(0));
+    let reports = handle_chunked_read_reqs(&dm, &[AttrPath::new(&path)]);
+    assert!(reports.len() > 1);
+    let mut responses = Vec::new();
+    for report in &reports {
+        responses.extend(attr_reports(report));
+    }
+
+    let mut responses = responses.into_iter().map(|r| match r {
+        AttrResp::Data(d) => d,
+        AttrResp::Status(_) => panic!("Invalid response, expected AttrRespIn::Data"),
+    });
+    let first = responses.next().unwrap();
+    assert_eq!(first.path, AttrPath::new(&path));
+    match first.data {
+        AttrDataType::Tlv(t) => assert_eq!(t.confirm_array().unwrap().iter().unwrap().count(), 0),
+        _ => panic!("Invalid data"),
+    }
+
+    let mut append_path = AttrPath::new(&path);
+    append_path.list_index = Some(Nullable::Null);
+    let mut values = Vec::new();
+    for d in responses {
+        assert_eq!(d.path, append_path);
+        match d.data {
+            AttrDataType::Tlv(t) => values.push(AttrValue::Utf8(
+                String::from_utf8(t.slice().unwrap().to_vec()).unwrap(),
+            )),
+            _ => panic!("Invalid data"),
+        }
+    }
+    assert_eq!(values, entries);
+}
+
+#[test]
+fn test_read_chunked_list_large_entry() {
+    // An entry of a list attribute that doesn't fit even in a ReportData of its own, is
+    // reported as a ResourceExhausted status, the other entries are still reported
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    let entries = vec![
+        AttrValue::Utf8("first".to_owned()),
+        AttrValue::Utf8("x".repeat(MAX_RX_BUF_SIZE)),
+        AttrValue::Utf8("last".to_owned()),
+    ];
+    add_values_cluster(
+        &dm,
+        vec![AttrValue::list(AttrValue::Utf8("".to_owned()), entries)],
+    );
+
+    let path = GenericPath::new(Some(1), Some(VALUES_CLUSTER_ID), Some(0));
+    let reports = handle_chunked_read_reqs(&dm, &[AttrPath::new(&path)]);
+    let mut responses = Vec::new();
+    for report in &reports {
+        responses.extend(attr_reports(report));
+    }
+    assert_eq!(responses.len(), 4);
+
+    let entry = |r: &AttrResp| match r {
+        AttrResp::Data(d) => match d.data {
+            AttrDataType::Tlv(t) => String::from_utf8(t.slice().unwrap().to_vec()).unwrap(),
+            _ => panic!("Invalid data"),
+        },
+        AttrResp::Status(_) => panic!("Invalid response, expected AttrRespIn::Data"),
+    };
+    assert_eq!(entry(&responses[1]), "first");
+    match &responses[2] {
+        AttrResp::Status(s) => assert_eq!(
+            *s,
+            AttrStatus::new(&path, IMStatusCode::ResourceExhausted, 0)
+        ),
+        AttrResp::Data(_) => panic!("Invalid response, expected AttrRespIn::Status"),
+    }
+    assert_eq!(entry(&responses[3]), "last");
+}
@@ -1,5 +1,8 @@
 use matter::{
-    data_model::cluster_on_off,
+    data_model::{
+        cluster_on_off,
+        objects::{AttrChange, AttrValue},
+    },
     interaction_model::{
         core::{IMStatusCode, OpCode},
         messages::ib::{CmdDataType, CmdPath, InvResp},
@@ -11,7 +14,7 @@ use matter::{
 
 use crate::common::{
     echo_cluster,
-    im_engine::{im_engine, TestData},
+    im_engine::{im_engine, im_engine_with_dm, init_data_model, TestData},
 };
 
 enum ExpectedInvResp {
@@ -196,3 +199,100 @@ fn test_invoke_cmd_wc_endpoint_only_1_has_cluster() {
     let expected = &[ExpectedInvResp::Status(expected_path, 0)];
     handle_commands(input, expected);
 }
+
+#[test]
+fn test_invoke_timed_only_cmd_without_timed() {
+    // 1 Timed-only command on the echo cluster, in a non-timed invoke
+    // should generate a NeedsTimedInteraction status
+    let _ = env_logger::try_init();
+
+    let target = CmdPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Commands::TimedEchoReq as u16),
+    );
+    let input = &[(target, Some(5))];
+    let expected = &[ExpectedInvResp::Status(
+        target,
+        IMStatusCode::NeedsTimedInteraction as u16,
+    )];
+    handle_commands(input, expected);
+}
+
+#[test]
+fn test_invoke_cmd_notifies_attr_changes() {
+    // The On command on the light turns the OnOff attribute on, the application
+    // gets notified of that, once. Turning it on again changes nothing
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+    let rx = dm.attr_changes();
+
+    let target = CmdPath::new(
+        Some(1),
+        Some(cluster_on_off::ID),
+        Some(cluster_on_off::Commands::On as u16),
+    );
+    for _ in 0..2 {
+        let mut buf = [0u8; 400];
+        let mut out_buf = [0u8; 400];
+        let buf_len = buf.len();
+        let mut wb = WriteBuf::new(&mut buf, buf_len);
+        TestData::new(&mut wb)
+            .commands(&[(target, Some(1))])
+            .unwrap();
+        im_engine_with_dm(
+            &dm,
+            OpCode::InvokeRequest,
+            wb.as_borrow_slice(),
+            &mut out_buf,
+        );
+    }
+
+    let expected = AttrChange {
+        endpoint: 1,
+        cluster: cluster_on_off::ID,
+        attr: cluster_on_off::Attributes::OnOff as u16,
+        value: AttrValue::Bool(true),
+    };
+    assert_eq!(rx.try_recv(), Ok(expected));
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn test_app_attr_update() {
+    // An update from the application, e.g. a button press, bumps the data version
+    // and is notified like any other change
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+    let rx = dm.attr_changes();
+    let attr = cluster_on_off::Attributes::OnOff as u16;
+    let dataver = |dm: &matter::data_model::core::DataModel| {
+        let node = dm.node.read().unwrap();
+        let c = node.get_cluster(1, cluster_on_off::ID).unwrap();
+        c.base().get_dataver()
+    };
+
+    let before = dataver(&dm);
+    dm.write_attribute_raw(1, cluster_on_off::ID, attr, AttrValue::Bool(true))
+        .unwrap();
+    assert_ne!(dataver(&dm), before);
+    assert_eq!(
+        dm.read_attribute_raw(1, cluster_on_off::ID, attr),
+        Ok(AttrValue::Bool(true))
+    );
+    assert_eq!(
+        rx.try_recv(),
+        Ok(AttrChange {
+            endpoint: 1,
+            cluster: cluster_on_off::ID,
+            attr,
+            value: AttrValue::Bool(true),
+        })
+    );
+
+    // Unknown attributes are rejected
+    assert_eq!(
+        dm.write_attribute_raw(1, cluster_on_off::ID, 0x99, AttrValue::Bool(true)),
+        Err(IMStatusCode::UnsupportedAttribute)
+    );
+}
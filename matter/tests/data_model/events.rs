@@ -0,0 +1,294 @@
+use matter::{
+    data_model::{
+        core::DataModel,
+        events::{EventLog, EventPriority},
+    },
+    interaction_model::{
+        core::{IMStatusCode, OpCode},
+        messages::{
+            ib::{EventDataTag, EventFilter, EventPath, EventRespTag, EventStatus},
+            msg::{self, ReadReq, SubscribeReq},
+            GenericPath,
+        },
+    },
+    persist::MemKvStore,
+    tlv::{self, FromTLV, TLVElement, TLVWriter, TagType, ToTLV},
+    utils::writebuf::WriteBuf,
+};
+use std::sync::Arc;
+
+use crate::common::{
+    echo_cluster,
+    im_engine::{im_engine_with_dm, init_data_model},
+};
+
+enum ExpectedEvent {
+    // The endpoint and event number of the event
+    Data(u16, u64),
+    Status(EventPath, IMStatusCode),
+}
+
+// Emit an event on the echo cluster, with the data in tag 0
+fn emit_event(dm: &DataModel, endpoint: u16, priority: EventPriority, data: u8) -> u64 {
+    dm.emit_event(endpoint, echo_cluster::ID, 1, priority, &|tag, tw| {
+        tw.start_struct(tag)?;
+        tw.u8(TagType::Context(0), data)?;
+        tw.end_container()
+    })
+    .unwrap()
+}
+
+fn validate_event_reports(root: &TLVElement, expected: &[ExpectedEvent]) {
+    let mut index = 0;
+    let report_iter = root
+        .find_tag(msg::ReportDataTag::EventReports as u32)
+        .unwrap()
+        .confirm_array()
+        .unwrap()
+        .iter()
+        .unwrap();
+    for report in report_iter {
+        match &expected[index] {
+            ExpectedEvent::Data(endpoint, event_no) => {
+                let data = report.find_tag(EventRespTag::Data as u32).unwrap();
+                let path = data.find_tag(EventDataTag::Path as u32).unwrap();
+                let path = EventPath::from_tlv(&path).unwrap();
+                assert_eq!(path.endpoint, Some(*endpoint));
+                assert_eq!(path.cluster, Some(echo_cluster::ID));
+                assert_eq!(
+                    data.find_tag(EventDataTag::EventNumber as u32)
+                        .unwrap()
+                        .u64()
+                        .unwrap(),
+                    *event_no
+                );
+            }
+            ExpectedEvent::Status(e_path, e_status) => {
+                let status = report.find_tag(EventRespTag::Status as u32).unwrap();
+                let status = EventStatus::from_tlv(&status).unwrap();
+                assert_eq!(status.path, *e_path);
+                assert_eq!(status.status.status, *e_status);
+            }
+        }
+        index += 1;
+    }
+    assert_eq!(index, expected.len());
+}
+
+// Helper for handling Read Requests with event paths
+fn handle_read_events(
+    dm: &DataModel,
+    input: &[EventPath],
+    event_min: Option<u64>,
+    expected: &[ExpectedEvent],
+) {
+    let mut buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+
+    let filters = event_min.map(EventFilter::new);
+    let mut read_req = ReadReq::new(false).set_event_requests(input);
+    if let Some(f) = &filters {
+        read_req = read_req.set_event_filters(std::slice::from_ref(f));
+    }
+    read_req.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+
+    let mut out_buf = [0u8; 800];
+    let out_buf_len =
+        im_engine_with_dm(dm, OpCode::ReadRequest, wb.as_borrow_slice(), &mut out_buf);
+    let out_buf = &out_buf[..out_buf_len];
+    tlv::print_tlv_list(out_buf);
+    let root = tlv::get_root_node_struct(out_buf).unwrap();
+    validate_event_reports(&root, expected);
+}
+
+#[test]
+fn test_read_events_success() {
+    // 3 events
+    // - one Info event on endpoint 0
+    // - one Critical event on endpoint 1
+    // - one Debug event on endpoint 0
+    // A wildcard read returns all of them in order, a read on endpoint 0 returns 2
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    let e0 = emit_event(&dm, 0, EventPriority::Info, 5);
+    let e1 = emit_event(&dm, 1, EventPriority::Critical, 10);
+    let e2 = emit_event(&dm, 0, EventPriority::Debug, 15);
+
+    let wc_path = EventPath::new(&GenericPath::new(None, Some(echo_cluster::ID), None));
+    let expected = &[
+        ExpectedEvent::Data(0, e0),
+        ExpectedEvent::Data(1, e1),
+        ExpectedEvent::Data(0, e2),
+    ];
+    handle_read_events(&dm, &[wc_path], None, expected);
+
+    let ep0_path = EventPath::new(&GenericPath::new(Some(0), Some(echo_cluster::ID), None));
+    let expected = &[ExpectedEvent::Data(0, e0), ExpectedEvent::Data(0, e2)];
+    handle_read_events(&dm, &[ep0_path], None, expected);
+}
+
+#[test]
+fn test_read_events_event_min() {
+    // Only the events with an event number at least that of the filter are returned
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    emit_event(&dm, 0, EventPriority::Info, 5);
+    let e1 = emit_event(&dm, 1, EventPriority::Info, 10);
+
+    let wc_path = EventPath::default();
+    handle_read_events(&dm, &[wc_path], Some(e1), &[ExpectedEvent::Data(1, e1)]);
+    handle_read_events(&dm, &[wc_path], Some(e1 + 1), &[]);
+}
+
+#[test]
+fn test_read_events_unsupported_fields() {
+    // 2 event paths
+    // - endpoint doesn't exist - UnsupportedEndpoint
+    // - cluster doesn't exist - UnsupportedCluster
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    emit_event(&dm, 0, EventPriority::Info, 5);
+
+    let invalid_endpoint = EventPath::new(&GenericPath::new(Some(2), Some(echo_cluster::ID), None));
+    let invalid_cluster = EventPath::new(&GenericPath::new(Some(0), Some(0x1234), None));
+    let expected = &[
+        ExpectedEvent::Status(invalid_endpoint, IMStatusCode::UnsupportedEndpoint),
+        ExpectedEvent::Status(invalid_cluster, IMStatusCode::UnsupportedCluster),
+    ];
+    handle_read_events(&dm, &[invalid_endpoint, invalid_cluster], None, expected);
+}
+
+#[test]
+fn test_subscribe_events_priming_report() {
+    // The priming report of an event subscription carries the events already in the log
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    let e0 = emit_event(&dm, 0, EventPriority::Critical, 5);
+
+    let mut buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+    let input = &[EventPath::default()];
+    let subs_req = SubscribeReq::new(true, 1, 10).set_event_requests(input);
+    subs_req.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+
+    let mut out_buf = [0u8; 800];
+    let out_buf_len = im_engine_with_dm(
+        &dm,
+        OpCode::SubscribeRequest,
+        wb.as_borrow_slice(),
+        &mut out_buf,
+    );
+    let out_buf = &out_buf[..out_buf_len];
+    tlv::print_tlv_list(out_buf);
+    let root = tlv::get_root_node_struct(out_buf).unwrap();
+    root.find_tag(msg::ReportDataTag::SubscriptionId as u32)
+        .unwrap()
+        .u32()
+        .unwrap();
+    validate_event_reports(&root, &[ExpectedEvent::Data(0, e0)]);
+}
+
+#[test]
+fn test_read_events_oversized() {
+    // An event that doesn't fit even in an empty report is dropped, instead of being retried
+    // forever, and the events after it are still reported
+    let _ = env_logger::try_init();
+
+    let log = EventLog::new(Arc::new(MemKvStore::new())).unwrap();
+    log.emit(0, echo_cluster::ID, 1, EventPriority::Info, &|tag, tw| {
+        tw.str16(tag, &[0xab; 150])
+    })
+    .unwrap();
+    let e1 = log
+        .emit(0, echo_cluster::ID, 1, EventPriority::Info, &|tag, tw| {
+            tw.u8(tag, 5)
+        })
+        .unwrap();
+
+    let mut buf = [0u8; 100];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+    let start = tw.get_tail();
+    let done = log
+        .read(&EventPath::default(), 0, 0, start, &mut tw, |_, _| true)
+        .unwrap();
+    assert_eq!(done, 2);
+    assert!(!tw.is_overflowed());
+
+    // Behind another report, the event that doesn't fit is left for the next report
+    let done = log
+        .read(&EventPath::default(), 0, 0, start + 1, &mut tw, |_, _| true)
+        .unwrap();
+    assert_eq!(done, 0);
+    assert!(tw.is_overflowed());
+
+    let report = tlv::get_root_node_struct(wb.as_borrow_slice()).unwrap();
+    let data = report.find_tag(EventRespTag::Data as u32).unwrap();
+    assert_eq!(
+        data.find_tag(EventDataTag::EventNumber as u32)
+            .unwrap()
+            .u64()
+            .unwrap(),
+        e1
+    );
+}
+
+// Emit an event with no fields on the log, and return the EventDataIB that it is reported with
+fn emit_and_read(log: &EventLog, buf: &mut [u8]) -> (u64, usize) {
+    let event_no = log
+        .emit(0, echo_cluster::ID, 1, EventPriority::Info, &|tag, tw| {
+            tw.start_struct(tag)?;
+            tw.end_container()
+        })
+        .unwrap();
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+    let start = tw.get_tail();
+    log.read(
+        &EventPath::default(),
+        event_no,
+        0,
+        start,
+        &mut tw,
+        |_, _| true,
+    )
+    .unwrap();
+    (event_no, wb.as_borrow_slice().len())
+}
+
+#[test]
+fn test_event_numbers_across_reboot() {
+    // The event numbers keep increasing after a reboot, even beyond the numbers that are
+    // reserved at a time. The events carry an Epoch Timestamp, as the clock is set
+    let _ = env_logger::try_init();
+
+    let store = Arc::new(MemKvStore::new());
+    let log = EventLog::new(store.clone()).unwrap();
+    let mut buf = [0u8; 100];
+    let (first, _) = emit_and_read(&log, &mut buf);
+    let mut last = first;
+    for _ in 0..0x1000 {
+        let (event_no, _) = emit_and_read(&log, &mut buf);
+        assert!(event_no > last);
+        last = event_no;
+    }
+
+    let log = EventLog::new(store).unwrap();
+    let (event_no, len) = emit_and_read(&log, &mut buf);
+    assert!(event_no > last);
+
+    let report = tlv::get_root_node_struct(&buf[..len]).unwrap();
+    let data = report.find_tag(EventRespTag::Data as u32).unwrap();
+    assert!(data.find_tag(EventDataTag::EpochTimestamp as u32).is_ok());
+    assert!(data.find_tag(EventDataTag::SystemTimestamp as u32).is_err());
+}
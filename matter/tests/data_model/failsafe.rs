@@ -0,0 +1,97 @@
+use matter::{
+    data_model::{core::DataModel, sdm::general_commissioning as gen_comm},
+    interaction_model::messages::{
+        ib::{AttrPath, CmdDataType, CmdPath, InvResp},
+        GenericPath,
+    },
+    tlv::{TLVWriter, TagType},
+};
+
+use crate::common::im_engine::{init_data_model, invoke_cmd, read_attr};
+
+const INDOOR: u8 = 0;
+const INDOOR_OUTDOOR: u8 = 2;
+
+// Run a General Commissioning command, and return the ErrorCode of its response
+fn invoke(dm: &DataModel, cmd: gen_comm::Commands, data: &dyn Fn(&mut TLVWriter)) -> u8 {
+    let path = CmdPath::new(Some(0), Some(gen_comm::ID), Some(cmd as u16));
+    let mut status = None;
+    invoke_cmd(dm, &path, data, |resp| match resp {
+        InvResp::Cmd(c) => match c.data {
+            CmdDataType::Tlv(t) => status = Some(t.find_tag(0).unwrap().u8().unwrap()),
+            _ => panic!("Incorrect CmdDataType"),
+        },
+        _ => panic!("Expected a response command"),
+    });
+    status.unwrap()
+}
+
+fn arm(dm: &DataModel, expiry_len: u8, bread_crumb: u8) {
+    let data = |tw: &mut TLVWriter| {
+        tw.u8(TagType::Context(0), expiry_len).unwrap();
+        tw.u8(TagType::Context(1), bread_crumb).unwrap();
+    };
+    assert_eq!(invoke(dm, gen_comm::Commands::ArmFailsafe, &data), 0);
+}
+
+fn set_reg_config(dm: &DataModel, reg_config: u8) -> u8 {
+    let data = |tw: &mut TLVWriter| {
+        tw.u8(TagType::Context(0), reg_config).unwrap();
+        tw.utf8(TagType::Context(1), b"XX").unwrap();
+        tw.u64(TagType::Context(2), 0).unwrap();
+    };
+    invoke(dm, gen_comm::Commands::SetRegulatoryConfig, &data)
+}
+
+fn read_gen_comm_attr(dm: &DataModel, attr: gen_comm::Attributes) -> u64 {
+    let path = AttrPath::new(&GenericPath::new(
+        Some(0),
+        Some(gen_comm::ID),
+        Some(attr as u32),
+    ));
+    let mut value = None;
+    read_attr(dm, &path, |t| value = Some(t.u64().unwrap()));
+    value.unwrap()
+}
+
+fn read_reg_config(dm: &DataModel) -> u8 {
+    read_gen_comm_attr(dm, gen_comm::Attributes::RegConfig) as u8
+}
+
+fn read_bread_crumb(dm: &DataModel) -> u64 {
+    read_gen_comm_attr(dm, gen_comm::Attributes::BreadCrumb)
+}
+
+#[test]
+fn test_failsafe_expiry_rolls_back() {
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+
+    arm(&dm, 60, 5);
+    assert_eq!(read_bread_crumb(&dm), 5);
+    assert_eq!(set_reg_config(&dm, INDOOR), 0);
+    assert_eq!(read_reg_config(&dm), INDOOR);
+    assert!(!dm.failsafe.check_expiry());
+    assert!(dm.failsafe.is_armed());
+
+    // Re-arming with 0 expires the Fail-Safe, the change is rolled back and the
+    // Breadcrumb is reset
+    arm(&dm, 0, 6);
+    assert!(dm.failsafe.check_expiry());
+    assert!(!dm.failsafe.is_armed());
+    assert_eq!(read_reg_config(&dm), INDOOR_OUTDOOR);
+    assert_eq!(read_bread_crumb(&dm), 0);
+}
+
+#[test]
+fn test_reg_config_without_failsafe() {
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+
+    // A location outside the range is rejected
+    assert_eq!(set_reg_config(&dm, 3), 1);
+    // Without a Fail-Safe, the change takes effect right away
+    assert_eq!(set_reg_config(&dm, INDOOR), 0);
+    assert!(!dm.failsafe.check_expiry());
+    assert_eq!(read_reg_config(&dm), INDOOR);
+}
@@ -0,0 +1,318 @@
+use matter::{
+    acl::{AclEntry, AuthMode, Privilege},
+    data_model::{cluster_on_off, core::DataModel, objects::AttrValue, sdm::group_key_management},
+    group_keys::{EpochKey, GroupKeyMapEntry, GroupKeySet, KeySetPolicy},
+    interaction_model::{
+        core::{IMStatusCode, OpCode},
+        messages::{
+            ib::{AttrData, AttrDataType, AttrPath, AttrStatus, CmdDataType, CmdPath, InvResp},
+            msg::{self, WriteReq},
+        },
+    },
+    tlv::{self, ElementType, FromTLV, TLVWriter, TagType, ToTLV},
+    transport::session::SessionMode,
+    utils::writebuf::WriteBuf,
+};
+
+use crate::common::im_engine::{
+    im_engine_with_dm, im_engine_with_mode, init_data_model, invoke_cmd, read_attr, TestData,
+    IM_ENGINE_FAB_IDX,
+};
+
+const GROUP_ID: u16 = 0x10;
+const KEY_SET_ID: u16 = 0x1a1;
+
+fn key_set(id: u16) -> GroupKeySet {
+    GroupKeySet {
+        id,
+        policy: KeySetPolicy::TrustFirst,
+        epoch_keys: vec![
+            EpochKey {
+                key: [1; 16],
+                start_time: 1000,
+            },
+            EpochKey {
+                key: [2; 16],
+                start_time: 2000,
+            },
+        ],
+    }
+}
+
+// Run a Group Key Management command, and pass the response to 'f'
+fn invoke(
+    dm: &DataModel,
+    cmd: group_key_management::Commands,
+    data: &dyn Fn(&mut TLVWriter),
+    f: impl FnMut(InvResp),
+) {
+    let path = CmdPath::new(Some(0), Some(group_key_management::ID), Some(cmd as u16));
+    invoke_cmd(dm, &path, data, f);
+}
+
+fn invoke_status(
+    dm: &DataModel,
+    cmd: group_key_management::Commands,
+    data: &dyn Fn(&mut TLVWriter),
+) -> IMStatusCode {
+    let mut status = IMStatusCode::Failure;
+    invoke(dm, cmd, data, |resp| match resp {
+        InvResp::Status(_, s) => status = s.status,
+        _ => panic!("Expected an Invoke Status"),
+    });
+    status
+}
+
+fn key_set_write(dm: &DataModel, key_set: &GroupKeySet) -> IMStatusCode {
+    let data = |tw: &mut TLVWriter| key_set.to_tlv(tw, TagType::Context(0), true).unwrap();
+    invoke_status(dm, group_key_management::Commands::KeySetWrite, &data)
+}
+
+fn key_set_remove(dm: &DataModel, id: u16) -> IMStatusCode {
+    let data = |tw: &mut TLVWriter| tw.u16(TagType::Context(0), id).unwrap();
+    invoke_status(dm, group_key_management::Commands::KeySetRemove, &data)
+}
+
+fn key_map_path() -> AttrPath {
+    AttrPath {
+        endpoint: Some(0),
+        cluster: Some(group_key_management::ID),
+        attr: Some(group_key_management::Attributes::GroupKeyMap as u16),
+        ..Default::default()
+    }
+}
+
+fn write_key_map(dm: &DataModel, entries: &[(u16, u16)]) -> IMStatusCode {
+    let data = |tag: TagType, t: &mut TLVWriter| {
+        let _ = t.start_array(tag);
+        for (group_id, key_set_id) in entries {
+            let _ = t.start_struct(TagType::Anonymous);
+            let _ = t.u16(TagType::Context(1), *group_id);
+            let _ = t.u16(TagType::Context(2), *key_set_id);
+            let _ = t.end_container();
+        }
+        let _ = t.end_container();
+        Ok(())
+    };
+    let input = &[AttrData {
+        data_ver: None,
+        path: key_map_path(),
+        data: AttrDataType::Closure(&data),
+    }];
+
+    let mut buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+    WriteReq::new(false, input)
+        .to_tlv(&mut tw, TagType::Anonymous)
+        .unwrap();
+
+    let mut out_buf = [0u8; 400];
+    let out_buf_len =
+        im_engine_with_dm(dm, OpCode::WriteRequest, wb.as_borrow_slice(), &mut out_buf);
+    let out_buf = &out_buf[..out_buf_len];
+    tlv::print_tlv_list(out_buf);
+    let root = tlv::get_root_node_struct(out_buf).unwrap();
+    let status = root
+        .find_tag(msg::WriteRespTag::WriteResponses as u32)
+        .unwrap()
+        .confirm_array()
+        .unwrap()
+        .iter()
+        .unwrap()
+        .next()
+        .unwrap();
+    AttrStatus::from_tlv(&status).unwrap().status.status
+}
+
+fn read_key_map(dm: &DataModel) -> Vec<GroupKeyMapEntry> {
+    let mut entries = Vec::new();
+    read_attr(dm, &key_map_path(), |t| {
+        if let Some(iter) = t.confirm_array().unwrap().iter() {
+            entries = iter
+                .map(|e| GroupKeyMapEntry::from_tlv(&e).unwrap())
+                .collect();
+        }
+    });
+    entries
+}
+
+// Invoke a command over a Group session, as the member endpoints would receive it
+fn group_invoke(dm: &DataModel, path: CmdPath) {
+    let mut buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut td = TestData::new(&mut wb);
+    // The command fields are ignored by the On/Off cluster
+    td.commands(&[(path, Some(0))]).unwrap();
+
+    let mut out_buf = [0u8; 400];
+    im_engine_with_mode(
+        dm,
+        SessionMode::Group(IM_ENGINE_FAB_IDX, GROUP_ID),
+        OpCode::InvokeRequest,
+        wb.as_borrow_slice(),
+        &mut out_buf,
+    );
+}
+
+fn is_light_on(dm: &DataModel) -> bool {
+    let value = dm
+        .read_attribute_raw(
+            1,
+            cluster_on_off::ID,
+            cluster_on_off::Attributes::OnOff as u16,
+        )
+        .unwrap();
+    value == AttrValue::Bool(true)
+}
+
+fn toggle_path(endpoint: Option<u16>) -> CmdPath {
+    CmdPath::new(
+        endpoint,
+        Some(cluster_on_off::ID),
+        Some(cluster_on_off::Commands::Toggle as u16),
+    )
+}
+
+#[test]
+fn test_key_set_write_read_remove() {
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+
+    assert_eq!(
+        key_set_write(&dm, &key_set(KEY_SET_ID)),
+        IMStatusCode::Sucess
+    );
+    // The IPK can't be written, nor removed
+    assert_eq!(
+        key_set_write(&dm, &key_set(0)),
+        IMStatusCode::InvalidCommand
+    );
+    assert_eq!(key_set_remove(&dm, 0), IMStatusCode::InvalidCommand);
+
+    // The key set is read back without its epoch keys
+    let data = |tw: &mut TLVWriter| tw.u16(TagType::Context(0), KEY_SET_ID).unwrap();
+    invoke(
+        &dm,
+        group_key_management::Commands::KeySetRead,
+        &data,
+        |resp| match resp {
+            InvResp::Cmd(c) => match c.data {
+                CmdDataType::Tlv(t) => {
+                    let ks = t.find_tag(0).unwrap();
+                    assert_eq!(ks.find_tag(0).unwrap().u16().unwrap(), KEY_SET_ID);
+                    for key_tag in [2, 4, 6] {
+                        let key = ks.find_tag(key_tag).unwrap();
+                        assert_eq!(key.get_element_type(), ElementType::Null);
+                    }
+                    assert_eq!(ks.find_tag(3).unwrap().u64().unwrap(), 1000);
+                    assert_eq!(ks.find_tag(5).unwrap().u64().unwrap(), 2000);
+                }
+                _ => panic!("Incorrect CmdDataType"),
+            },
+            _ => panic!("Expected a KeySetReadResponse"),
+        },
+    );
+
+    // The IPK is always listed
+    invoke(
+        &dm,
+        group_key_management::Commands::KeySetReadAllIndices,
+        &|_| (),
+        |resp| match resp {
+            InvResp::Cmd(c) => match c.data {
+                CmdDataType::Tlv(t) => {
+                    let ids: Vec<u16> = t
+                        .find_tag(0)
+                        .unwrap()
+                        .confirm_array()
+                        .unwrap()
+                        .iter()
+                        .unwrap()
+                        .map(|i| i.u16().unwrap())
+                        .collect();
+                    assert_eq!(ids, vec![0, KEY_SET_ID]);
+                }
+                _ => panic!("Incorrect CmdDataType"),
+            },
+            _ => panic!("Expected a KeySetReadAllIndicesResponse"),
+        },
+    );
+
+    assert_eq!(key_set_remove(&dm, KEY_SET_ID), IMStatusCode::Sucess);
+    assert_eq!(key_set_remove(&dm, KEY_SET_ID), IMStatusCode::NotFound);
+    let status = invoke_status(&dm, group_key_management::Commands::KeySetRead, &data);
+    assert_eq!(status, IMStatusCode::NotFound);
+}
+
+#[test]
+fn test_group_key_map_write() {
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+
+    // The key set must exist, and can't be the IPK
+    assert_eq!(
+        write_key_map(&dm, &[(GROUP_ID, KEY_SET_ID)]),
+        IMStatusCode::ConstraintError
+    );
+    assert_eq!(
+        key_set_write(&dm, &key_set(KEY_SET_ID)),
+        IMStatusCode::Sucess
+    );
+    assert_eq!(
+        write_key_map(&dm, &[(GROUP_ID, 0)]),
+        IMStatusCode::ConstraintError
+    );
+    // A group is mapped to only one key set
+    assert_eq!(
+        write_key_map(&dm, &[(GROUP_ID, KEY_SET_ID), (GROUP_ID, KEY_SET_ID)]),
+        IMStatusCode::ConstraintError
+    );
+
+    assert_eq!(
+        write_key_map(&dm, &[(GROUP_ID, KEY_SET_ID)]),
+        IMStatusCode::Sucess
+    );
+    let expected = GroupKeyMapEntry {
+        group_id: GROUP_ID,
+        key_set_id: KEY_SET_ID,
+        fab_idx: IM_ENGINE_FAB_IDX,
+    };
+    assert_eq!(read_key_map(&dm), vec![expected]);
+
+    // Removing the key set removes its mappings
+    assert_eq!(key_set_remove(&dm, KEY_SET_ID), IMStatusCode::Sucess);
+    assert_eq!(read_key_map(&dm), vec![]);
+}
+
+#[test]
+fn test_group_invoke() {
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+    dm.group_keys
+        .add_group_endpoint(IM_ENGINE_FAB_IDX, GROUP_ID, 1, "Living Room")
+        .unwrap();
+
+    // Without an ACL entry for the group, nothing happens
+    group_invoke(&dm, toggle_path(None));
+    assert!(!is_light_on(&dm));
+
+    let mut entry = AclEntry::new(IM_ENGINE_FAB_IDX, Privilege::Operate, AuthMode::Group);
+    entry.add_subject(GROUP_ID as u64).unwrap();
+    dm.acl_mgr.add(entry).unwrap();
+    group_invoke(&dm, toggle_path(None));
+    assert!(is_light_on(&dm));
+
+    // A group command with an endpoint is dropped
+    group_invoke(&dm, toggle_path(Some(1)));
+    assert!(is_light_on(&dm));
+
+    // Once the endpoint leaves the group, it doesn't receive the group's commands
+    dm.group_keys
+        .remove_group_endpoint(IM_ENGINE_FAB_IDX, GROUP_ID, 1)
+        .unwrap();
+    group_invoke(&dm, toggle_path(None));
+    assert!(is_light_on(&dm));
+}
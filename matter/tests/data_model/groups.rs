@@ -0,0 +1,184 @@
+use matter::{
+    data_model::{cluster_groups, core::DataModel},
+    group_keys::{EpochKey, GroupKeyMapEntry, GroupKeySet, KeySetPolicy},
+    interaction_model::{
+        core::IMStatusCode,
+        messages::ib::{CmdDataType, CmdPath, InvResp},
+    },
+    tlv::{TLVElement, TLVWriter, TagType},
+};
+
+use crate::common::im_engine::{init_data_model, invoke_cmd, IM_ENGINE_FAB_IDX};
+
+const LIGHT_ENDPOINT: u16 = 1;
+const GROUP_ID: u16 = 0x10;
+const KEY_SET_ID: u16 = 0x1a1;
+
+// Map the group to a key set, so that endpoints can be added to it
+fn map_group(dm: &DataModel, group_id: u16) {
+    let key_set = GroupKeySet {
+        id: KEY_SET_ID,
+        policy: KeySetPolicy::TrustFirst,
+        epoch_keys: vec![EpochKey {
+            key: [1; 16],
+            start_time: 1000,
+        }],
+    };
+    dm.group_keys
+        .set_key_set(IM_ENGINE_FAB_IDX, key_set)
+        .unwrap();
+    let entry = GroupKeyMapEntry {
+        group_id,
+        key_set_id: KEY_SET_ID,
+        fab_idx: IM_ENGINE_FAB_IDX,
+    };
+    dm.group_keys
+        .set_key_map(IM_ENGINE_FAB_IDX, vec![entry])
+        .unwrap();
+}
+
+// Run a Groups command on the light, and pass the response to 'f'
+fn invoke(
+    dm: &DataModel,
+    cmd: cluster_groups::Commands,
+    data: &dyn Fn(&mut TLVWriter),
+    f: &mut dyn FnMut(InvResp),
+) {
+    let path = CmdPath::new(
+        Some(LIGHT_ENDPOINT),
+        Some(cluster_groups::ID),
+        Some(cmd as u16),
+    );
+    invoke_cmd(dm, &path, data, f);
+}
+
+// Run a command that has a response command, and pass the response's fields to 'f'
+fn invoke_resp(
+    dm: &DataModel,
+    cmd: cluster_groups::Commands,
+    data: &dyn Fn(&mut TLVWriter),
+    mut f: impl FnMut(&TLVElement),
+) {
+    invoke(dm, cmd, data, &mut |resp| match resp {
+        InvResp::Cmd(c) => match c.data {
+            CmdDataType::Tlv(t) => f(&t),
+            _ => panic!("Incorrect CmdDataType"),
+        },
+        _ => panic!("Expected a response command"),
+    });
+}
+
+// The status and the group id of an AddGroup, a ViewGroup or a RemoveGroup response
+fn group_cmd(dm: &DataModel, cmd: cluster_groups::Commands, group_id: u16) -> IMStatusCode {
+    let data = |tw: &mut TLVWriter| {
+        tw.u16(TagType::Context(0), group_id).unwrap();
+        tw.utf8(TagType::Context(1), b"Kitchen").unwrap();
+    };
+    let mut status = 0;
+    invoke_resp(dm, cmd, &data, |t| {
+        status = t.find_tag(0).unwrap().u8().unwrap();
+        assert_eq!(t.find_tag(1).unwrap().u16().unwrap(), group_id);
+    });
+    num::FromPrimitive::from_u8(status).unwrap()
+}
+
+fn get_membership(dm: &DataModel, groups: &[u16]) -> (u8, Vec<u16>) {
+    let data = |tw: &mut TLVWriter| {
+        tw.start_array(TagType::Context(0)).unwrap();
+        for g in groups {
+            tw.u16(TagType::Anonymous, *g).unwrap();
+        }
+        tw.end_container().unwrap();
+    };
+    let mut result = (0, Vec::new());
+    invoke_resp(
+        dm,
+        cluster_groups::Commands::GetGroupMembership,
+        &data,
+        |t| {
+            result.0 = t.find_tag(0).unwrap().u8().unwrap();
+            result.1 = t
+                .find_tag(1)
+                .unwrap()
+                .confirm_array()
+                .unwrap()
+                .iter()
+                .map(|i| i.map(|e| e.u16().unwrap()).collect())
+                .unwrap_or_default();
+        },
+    );
+    result
+}
+
+#[test]
+fn test_add_view_remove_group() {
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+
+    // The group needs a key set first
+    assert_eq!(
+        group_cmd(&dm, cluster_groups::Commands::AddGroup, GROUP_ID),
+        IMStatusCode::UnsupportedAccess
+    );
+    map_group(&dm, GROUP_ID);
+    assert_eq!(
+        group_cmd(&dm, cluster_groups::Commands::AddGroup, 0),
+        IMStatusCode::ConstraintError
+    );
+    assert_eq!(
+        group_cmd(&dm, cluster_groups::Commands::AddGroup, GROUP_ID),
+        IMStatusCode::Sucess
+    );
+    assert_eq!(
+        dm.group_keys
+            .get_group_endpoints(IM_ENGINE_FAB_IDX, GROUP_ID)
+            .unwrap(),
+        vec![LIGHT_ENDPOINT]
+    );
+
+    let data = |tw: &mut TLVWriter| tw.u16(TagType::Context(0), GROUP_ID).unwrap();
+    invoke_resp(&dm, cluster_groups::Commands::ViewGroup, &data, |t| {
+        assert_eq!(t.find_tag(0).unwrap().u8().unwrap(), 0);
+        assert_eq!(t.find_tag(2).unwrap().slice().unwrap(), b"Kitchen");
+    });
+
+    assert_eq!(
+        group_cmd(&dm, cluster_groups::Commands::RemoveGroup, GROUP_ID),
+        IMStatusCode::Sucess
+    );
+    assert_eq!(
+        group_cmd(&dm, cluster_groups::Commands::RemoveGroup, GROUP_ID),
+        IMStatusCode::NotFound
+    );
+    assert_eq!(
+        group_cmd(&dm, cluster_groups::Commands::ViewGroup, GROUP_ID),
+        IMStatusCode::NotFound
+    );
+}
+
+#[test]
+fn test_group_membership() {
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+    map_group(&dm, GROUP_ID);
+    assert_eq!(get_membership(&dm, &[]), (4, vec![]));
+
+    assert_eq!(
+        group_cmd(&dm, cluster_groups::Commands::AddGroup, GROUP_ID),
+        IMStatusCode::Sucess
+    );
+    assert_eq!(get_membership(&dm, &[]), (3, vec![GROUP_ID]));
+    assert_eq!(get_membership(&dm, &[GROUP_ID, 0x20]), (3, vec![GROUP_ID]));
+    assert_eq!(get_membership(&dm, &[0x20]), (3, vec![]));
+
+    invoke(
+        &dm,
+        cluster_groups::Commands::RemoveAllGroups,
+        &|_| (),
+        &mut |resp| match resp {
+            InvResp::Status(_, s) => assert_eq!(s.status, IMStatusCode::Sucess),
+            _ => panic!("Expected an Invoke Status"),
+        },
+    );
+    assert_eq!(get_membership(&dm, &[]), (4, vec![]));
+}
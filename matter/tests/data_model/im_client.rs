@@ -0,0 +1,232 @@
+use matter::{
+    data_model::{core::DataModel, objects::AttrValue, sdm::admin_commissioning},
+    error::Error,
+    interaction_model::{
+        client::ImClient,
+        core::{IMStatusCode, OpCode},
+        messages::{
+            ib::{AttrData, AttrDataType, AttrPath, AttrResp, CmdPath, InvResp},
+            GenericPath,
+        },
+    },
+    tlv::{ElementType, TLVWriter, TagType},
+    transport::{
+        exchange::{self, Exchange},
+        proto_demux::{HandleProto, SessionEvent},
+    },
+};
+use std::{thread, time::Duration};
+
+use crate::common::{
+    echo_cluster,
+    im_engine::{init_data_model, run_interaction, ImNode},
+};
+
+// The local session id of the sessions on both the nodes
+const SESS_ID: u16 = 1;
+
+fn echo_attr(endpoint: u16, attr: echo_cluster::Attributes) -> AttrPath {
+    AttrPath::new(&GenericPath::new(
+        Some(endpoint),
+        Some(echo_cluster::ID),
+        Some(attr as u32),
+    ))
+}
+
+fn init_nodes() -> (ImNode, ImNode, DataModel, ImClient) {
+    let server_dm = init_data_model();
+    let client = ImNode::new(&init_data_model());
+    let server = ImNode::new(&server_dm);
+    let im_client = client.im.get_client();
+    (client, server, server_dm, im_client)
+}
+
+fn assert_attr_u16(report: &AttrResp, path: &AttrPath, value: u16) {
+    match report {
+        AttrResp::Data(d) => {
+            assert_eq!(d.path, *path);
+            match d.data {
+                AttrDataType::Tlv(t) => assert_eq!(t.get_element_type(), ElementType::U16(value)),
+                _ => panic!("Expected TLV data"),
+            }
+        }
+        _ => panic!("Expected Attribute Data"),
+    }
+}
+
+#[test]
+fn test_client_read() {
+    // Read 2 attributes from the peer, one of which doesn't exist
+    let _ = env_logger::try_init();
+    let (mut client, mut server, _, im_client) = init_nodes();
+
+    let paths = [
+        echo_attr(0, echo_cluster::Attributes::Att1),
+        AttrPath::new(&GenericPath::new(
+            Some(0),
+            Some(echo_cluster::ID),
+            Some(0x99),
+        )),
+    ];
+    let handle = thread::spawn(move || im_client.read(SESS_ID, &paths, false));
+    run_interaction(&mut client, &mut server);
+
+    let resp = handle.join().unwrap().unwrap();
+    let reports = resp.attr_reports().unwrap();
+    assert_eq!(reports.len(), 2);
+    assert_attr_u16(&reports[0], &paths[0], 0x1234);
+    match reports[1] {
+        AttrResp::Status(s) => assert_eq!(s.status.status, IMStatusCode::UnsupportedAttribute),
+        _ => panic!("Expected Attribute Status"),
+    }
+}
+
+#[test]
+fn test_client_write() {
+    // Write an attribute on the peer
+    let _ = env_logger::try_init();
+    let (mut client, mut server, server_dm, im_client) = init_nodes();
+
+    let path = echo_attr(0, echo_cluster::Attributes::AttWrite);
+    let handle = thread::spawn(move || {
+        let data = |tag, t: &mut TLVWriter| {
+            let _ = t.u16(tag, 25);
+            Ok(())
+        };
+        let attrs = [AttrData::new(None, path, AttrDataType::Closure(&data))];
+        im_client.write(SESS_ID, &attrs, None)
+    });
+    run_interaction(&mut client, &mut server);
+
+    let resp = handle.join().unwrap().unwrap();
+    let statuses = resp.write_statuses().unwrap();
+    assert_eq!(statuses.len(), 1);
+    assert_eq!(statuses[0].path, path);
+    assert_eq!(statuses[0].status.status, IMStatusCode::Sucess);
+
+    let node = server_dm.node.read().unwrap();
+    let echo = node.get_cluster(0, echo_cluster::ID).unwrap();
+    assert_eq!(
+        AttrValue::Uint16(25),
+        *echo
+            .base()
+            .read_attribute_raw(echo_cluster::Attributes::AttWrite as u16)
+            .unwrap()
+    );
+}
+
+fn open_basic_comm_window(
+    client: &mut ImNode,
+    server: &mut ImNode,
+    im_client: ImClient,
+    timed: Option<u16>,
+) -> IMStatusCode {
+    let cmd = CmdPath::new(
+        Some(0),
+        Some(admin_commissioning::ID),
+        Some(admin_commissioning::Commands::OpenBasicCommWindow as u16),
+    );
+    let handle = thread::spawn(move || {
+        let data = |tw: &mut TLVWriter| tw.u16(TagType::Context(0), 300);
+        im_client.invoke(SESS_ID, cmd, &data, timed)
+    });
+    run_interaction(client, server);
+
+    let resp = handle.join().unwrap().unwrap();
+    let responses = resp.inv_responses().unwrap();
+    assert_eq!(responses.len(), 1);
+    match responses[0] {
+        InvResp::Status(p, s) => {
+            assert_eq!(p, cmd);
+            s.status
+        }
+        _ => panic!("Expected an Invoke Status"),
+    }
+}
+
+#[test]
+fn test_client_invoke_timed() {
+    // A timed-only command fails without a Timed Request, and succeeds with one
+    let _ = env_logger::try_init();
+    let (mut client, mut server, _, im_client) = init_nodes();
+
+    assert_eq!(
+        open_basic_comm_window(&mut client, &mut server, im_client.clone(), None),
+        IMStatusCode::NeedsTimedInteraction
+    );
+    assert_eq!(
+        open_basic_comm_window(&mut client, &mut server, im_client, Some(500)),
+        IMStatusCode::Sucess
+    );
+}
+
+#[test]
+fn test_client_subscribe() {
+    // Subscribe to an attribute on the peer, and receive a report once it changes. The
+    // subscription is cancelled once the application drops it.
+    let _ = env_logger::try_init();
+    let (mut client, mut server, server_dm, im_client) = init_nodes();
+
+    let path = echo_attr(0, echo_cluster::Attributes::Att1);
+    let handle = thread::spawn(move || im_client.subscribe(SESS_ID, &[path], 0, 60, false));
+    run_interaction(&mut client, &mut server);
+    let subs = handle.join().unwrap().unwrap();
+    assert_eq!(subs.max_int, 60);
+    let reports = subs.priming.attr_reports().unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_attr_u16(&reports[0], &path, 0x1234);
+
+    // No report, until the attribute changes
+    assert!(server.im.get_unsolicited_sess().is_none());
+    let update_attr = |value| {
+        let mut node = server_dm.node.write().unwrap();
+        let echo = node.get_cluster_mut(0, echo_cluster::ID).unwrap();
+        echo.base_mut()
+            .write_attribute_raw(
+                echo_cluster::Attributes::Att1 as u16,
+                AttrValue::Uint16(value),
+            )
+            .unwrap();
+    };
+    update_attr(0x3030);
+    run_interaction(&mut server, &mut client);
+    let report = subs.recv_report(Duration::from_secs(1)).unwrap();
+    let reports = report.attr_reports().unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_attr_u16(&reports[0], &path, 0x3030);
+
+    // The peer is told that the subscription is gone, with the next report
+    drop(subs);
+    update_attr(0x3535);
+    let mut s_exch = Exchange::new(20, 0, exchange::Role::Initiator);
+    let mut c_exch = Exchange::new(20, 0, exchange::Role::Responder);
+    let report = server.unsolicited(&mut s_exch);
+    let (opcode, status) = client.handle(&mut c_exch, report).unwrap();
+    assert_eq!(opcode, OpCode::StatusResponse);
+    assert!(server.handle(&mut s_exch, (opcode, status)).is_none());
+    assert!(!c_exch.is_state_open());
+    // And the peer stops reporting
+    update_attr(0x4040);
+    assert!(server.im.get_unsolicited_sess().is_none());
+}
+
+#[test]
+fn test_client_subscription_session_evicted() {
+    // The subscriptions that we created on a session are gone, once it is evicted
+    let _ = env_logger::try_init();
+    let (mut client, mut server, _, im_client) = init_nodes();
+
+    let path = echo_attr(0, echo_cluster::Attributes::Att1);
+    let handle = thread::spawn(move || im_client.subscribe(SESS_ID, &[path], 0, 60, false));
+    run_interaction(&mut client, &mut server);
+    let subs = handle.join().unwrap().unwrap();
+
+    client
+        .im
+        .handle_session_event(&SessionEvent::Evicted(SESS_ID))
+        .unwrap();
+    assert!(matches!(
+        subs.recv_report(Duration::from_secs(1)),
+        Err(Error::NoExchange)
+    ));
+}
@@ -0,0 +1,356 @@
+use std::sync::Arc;
+
+use matter::{
+    acl::{AclEntry, AclMgr, AuthMode, Privilege},
+    cert::Cert,
+    core::{CommissioningData, Matter},
+    crypto::KeyPair,
+    data_model::{
+        cluster_basic_information::BasicInfoConfig,
+        core::DataModel,
+        sdm::{admin_commissioning, noc},
+    },
+    fabric::{Fabric, FabricMgr},
+    group_keys::{EpochKey, GroupKeySet, GroupKeys, KeySetPolicy},
+    interaction_model::{
+        core::IMStatusCode,
+        messages::{
+            ib::{AttrPath, CmdDataType, CmdPath, InvResp},
+            GenericPath,
+        },
+    },
+    persist::MemKvStore,
+    secure_channel::{pake::CommWindowStatus, spake2p::VerifierData},
+    tlv::{ElementType, TLVElement, TLVWriter, TagType},
+    transport::session::SessionMode,
+};
+
+use crate::common::{
+    im_engine::{
+        init_data_model, init_data_model_with, invoke_cmd_on_session, read_attr,
+        read_attr_on_session, DummyDevAtt, ImSession, IM_ENGINE_FAB_IDX,
+    },
+    test_vectors,
+};
+
+// The Operational Credentials status codes, that are checked here
+const STATUS_OK: u8 = 0;
+const STATUS_INVALID_NOC: u8 = 3;
+const STATUS_INVALID_FABRIC_INDEX: u8 = 11;
+
+// The CASE session of the peer, on its fabric
+fn peer_session() -> ImSession {
+    ImSession::new(SessionMode::Case(IM_ENGINE_FAB_IDX))
+}
+
+// Run an Operational Credentials command on the session, and pass the response to 'f'
+fn invoke(
+    dm: &DataModel,
+    sess: &mut ImSession,
+    cmd: noc::Commands,
+    data: &dyn Fn(&mut TLVWriter),
+    f: &mut dyn FnMut(InvResp),
+) {
+    let path = CmdPath::new(Some(0), Some(noc::ID), Some(cmd as u16));
+    invoke_cmd_on_session(dm, sess, &path, data, f);
+}
+
+// Read an Operational Credentials attribute, and pass its value to 'f'
+fn read_noc_attr(dm: &DataModel, attr: noc::Attributes, f: impl FnOnce(&TLVElement)) {
+    let path = AttrPath::new(&GenericPath::new(Some(0), Some(noc::ID), Some(attr as u32)));
+    read_attr(dm, &path, f);
+}
+
+// The status of the NOCResponse to a command
+fn noc_resp_status(
+    dm: &DataModel,
+    sess: &mut ImSession,
+    cmd: noc::Commands,
+    data: &dyn Fn(&mut TLVWriter),
+) -> u8 {
+    let mut status = None;
+    invoke(dm, sess, cmd, data, &mut |resp| match resp {
+        InvResp::Cmd(c) => {
+            assert_eq!(c.path.path.leaf, Some(noc::Commands::NOCResp as u32));
+            match c.data {
+                CmdDataType::Tlv(t) => status = Some(t.find_tag(0).unwrap().u8().unwrap()),
+                _ => panic!("Incorrect CmdDataType"),
+            }
+        }
+        _ => panic!("Expected a NOCResponse"),
+    });
+    status.unwrap()
+}
+
+// Generate the key pair, that the NOC of the next AddNOC or UpdateNOC on the session is for
+fn csr_request(dm: &DataModel, sess: &mut ImSession) {
+    let data = |tw: &mut TLVWriter| tw.str8(TagType::Context(0), &[0x11; 32]).unwrap();
+    invoke(
+        dm,
+        sess,
+        noc::Commands::CSRReq,
+        &data,
+        &mut |resp| match resp {
+            InvResp::Cmd(c) => assert_eq!(c.path.path.leaf, Some(noc::Commands::CSRResp as u32)),
+            _ => panic!("Expected a CSRResponse"),
+        },
+    );
+}
+
+fn update_noc(dm: &DataModel, sess: &mut ImSession, noc: &[u8]) -> u8 {
+    let data = |tw: &mut TLVWriter| tw.str16(TagType::Context(0), noc).unwrap();
+    noc_resp_status(dm, sess, noc::Commands::UpdateNOC, &data)
+}
+
+// Install the peer's fabric, with the NOC that the ICAC issues, on a new storage
+fn add_peer_fabric() -> (Arc<MemKvStore>, Arc<FabricMgr>) {
+    let store = Arc::new(MemKvStore::new());
+    let fabric_mgr = Arc::new(FabricMgr::new(store.clone()).unwrap());
+    let fabric = Fabric::new(
+        KeyPair::new_from_components(&test_vectors::NOC_PUBKEY, &test_vectors::NOC_PRIVKEY)
+            .unwrap(),
+        Cert::new(&test_vectors::RCAC).unwrap(),
+        Some(Cert::new(&test_vectors::ICAC).unwrap()),
+        Cert::new(&test_vectors::NOC).unwrap(),
+        &[0x55; 16],
+        0xfff1,
+    )
+    .unwrap();
+    assert_eq!(fabric_mgr.add(fabric).unwrap(), IM_ENGINE_FAB_IDX);
+    (store, fabric_mgr)
+}
+
+fn key_set() -> GroupKeySet {
+    GroupKeySet {
+        id: 0x1a1,
+        policy: KeySetPolicy::TrustFirst,
+        epoch_keys: vec![EpochKey {
+            key: [1; 16],
+            start_time: 1000,
+        }],
+    }
+}
+
+// The fabrics that have ACL entries
+fn acl_fabrics(acl_mgr: &AclMgr) -> Vec<u8> {
+    let mut fabrics = Vec::new();
+    acl_mgr.for_each_acl(|e| fabrics.push(e.fab_idx)).unwrap();
+    fabrics
+}
+
+// Whether the fabric has no groups and no key sets
+fn no_groups(group_keys: &GroupKeys, fab_idx: u8) -> bool {
+    group_keys.get_groups(fab_idx).unwrap().is_empty()
+        && group_keys.get_key_set_ids(fab_idx).unwrap().is_empty()
+}
+
+#[test]
+fn test_remove_unknown_fabric() {
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+
+    for fab_idx in [0, 1, 5] {
+        let data = |tw: &mut TLVWriter| tw.u8(TagType::Context(0), fab_idx).unwrap();
+        assert_eq!(
+            noc_resp_status(&dm, &mut peer_session(), noc::Commands::RemoveFabric, &data),
+            STATUS_INVALID_FABRIC_INDEX
+        );
+    }
+}
+
+#[test]
+fn test_update_fabric_label() {
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+
+    // A label that is too long is rejected before the fabric is looked at
+    let data = |tw: &mut TLVWriter| tw.utf8(TagType::Context(0), &[b'a'; 33]).unwrap();
+    invoke(
+        &dm,
+        &mut peer_session(),
+        noc::Commands::UpdateFabricLabel,
+        &data,
+        &mut |resp| match resp {
+            InvResp::Status(_, s) => assert_eq!(s.status, IMStatusCode::ConstraintError),
+            _ => panic!("Expected an Invoke Status"),
+        },
+    );
+
+    // The peer's session is on a fabric, that isn't installed
+    let data = |tw: &mut TLVWriter| tw.utf8(TagType::Context(0), b"Home").unwrap();
+    assert_eq!(
+        noc_resp_status(
+            &dm,
+            &mut peer_session(),
+            noc::Commands::UpdateFabricLabel,
+            &data
+        ),
+        STATUS_INVALID_FABRIC_INDEX
+    );
+}
+
+#[test]
+fn test_read_fabric_attributes() {
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+
+    read_noc_attr(&dm, noc::Attributes::SupportedFabrics, |t| {
+        assert_eq!(t.u8().unwrap(), 2)
+    });
+    read_noc_attr(&dm, noc::Attributes::CommissionedFabrics, |t| {
+        assert_eq!(t.u8().unwrap(), 0)
+    });
+    read_noc_attr(&dm, noc::Attributes::CurrentFabricIndex, |t| {
+        assert_eq!(t.u8().unwrap(), IM_ENGINE_FAB_IDX)
+    });
+    for attr in [
+        noc::Attributes::NOCs,
+        noc::Attributes::Fabrics,
+        noc::Attributes::TrustedRootCertificates,
+    ] {
+        // No fabric has been added yet
+        read_noc_attr(&dm, attr, |t| {
+            let entries = t.confirm_array().unwrap().iter().map_or(0, |i| i.count());
+            assert_eq!(entries, 0);
+        });
+    }
+}
+
+#[test]
+fn test_remove_fabric() {
+    let _ = env_logger::try_init();
+    let (store, fabric_mgr) = add_peer_fabric();
+    let dm = init_data_model_with(store.clone(), fabric_mgr.clone());
+    dm.group_keys
+        .set_key_set(IM_ENGINE_FAB_IDX, key_set())
+        .unwrap();
+    dm.group_keys
+        .add_group_endpoint(IM_ENGINE_FAB_IDX, 0x10, 1, "Lights")
+        .unwrap();
+    assert!(!no_groups(&dm.group_keys, IM_ENGINE_FAB_IDX));
+    // The entries of the other fabrics remain
+    let mut other = AclEntry::new(2, Privilege::View, AuthMode::Case);
+    other.add_subject(0x1234).unwrap();
+    dm.acl_mgr.add(other).unwrap();
+    let mut cursor = 0;
+    assert!(fabric_mgr.get_removed_since(&mut cursor).is_empty());
+
+    let data = |tw: &mut TLVWriter| tw.u8(TagType::Context(0), IM_ENGINE_FAB_IDX).unwrap();
+    assert_eq!(
+        noc_resp_status(&dm, &mut peer_session(), noc::Commands::RemoveFabric, &data),
+        STATUS_OK
+    );
+
+    // The transport evicts the sessions of the fabrics that are reported as removed
+    assert_eq!(
+        fabric_mgr.get_removed_since(&mut cursor),
+        vec![IM_ENGINE_FAB_IDX]
+    );
+    assert!(fabric_mgr.is_empty());
+    assert_eq!(acl_fabrics(&dm.acl_mgr), vec![2]);
+    assert!(no_groups(&dm.group_keys, IM_ENGINE_FAB_IDX));
+
+    // Nothing of the fabric is left in the storage either
+    assert!(FabricMgr::new(store.clone()).unwrap().is_empty());
+    assert_eq!(acl_fabrics(&AclMgr::new(store.clone()).unwrap()), vec![2]);
+    assert!(no_groups(
+        &GroupKeys::new(store).unwrap(),
+        IM_ENGINE_FAB_IDX
+    ));
+}
+
+#[test]
+fn test_update_noc() {
+    let _ = env_logger::try_init();
+    let (store, fabric_mgr) = add_peer_fabric();
+    let dm = init_data_model_with(store.clone(), fabric_mgr);
+    let mut sess = peer_session();
+    dm.failsafe
+        .arm(60, SessionMode::Case(IM_ENGINE_FAB_IDX))
+        .unwrap();
+
+    // A NOC that doesn't chain up to the fabric's root
+    csr_request(&dm, &mut sess);
+    assert_eq!(
+        update_noc(&dm, &mut sess, &test_vectors::NOC_OTHER_ROOT),
+        STATUS_INVALID_NOC
+    );
+
+    // A NOC that the root issues, without an ICAC
+    csr_request(&dm, &mut sess);
+    assert_eq!(
+        update_noc(&dm, &mut sess, &test_vectors::NOC_NO_ICAC),
+        STATUS_OK
+    );
+    dm.failsafe
+        .disarm(SessionMode::Case(IM_ENGINE_FAB_IDX))
+        .unwrap();
+
+    read_noc_attr(&dm, noc::Attributes::NOCs, |t| {
+        let mut nocs = t.confirm_array().unwrap().iter().unwrap();
+        let noc = nocs.next().unwrap();
+        assert!(nocs.next().is_none());
+        assert_eq!(
+            noc.find_tag(1).unwrap().slice().unwrap(),
+            test_vectors::NOC_NO_ICAC
+        );
+        assert_eq!(
+            noc.find_tag(2).unwrap().get_element_type(),
+            ElementType::Null
+        );
+    });
+
+    // The updated fabric is persisted
+    let fabric_mgr = FabricMgr::new(store).unwrap();
+    let fabric = fabric_mgr.get_fabric(IM_ENGINE_FAB_IDX as usize).unwrap();
+    let fabric = fabric.as_ref().as_ref().unwrap();
+    assert!(fabric.icac.is_none());
+    assert_eq!(fabric.get_node_id(), 0x7788);
+}
+
+#[test]
+fn test_factory_reset() {
+    let _ = env_logger::try_init();
+    let (store, _) = add_peer_fabric();
+    let mut admin = AclEntry::new(IM_ENGINE_FAB_IDX, Privilege::Administer, AuthMode::Case);
+    admin.add_subject(0x1234).unwrap();
+    AclMgr::new(store.clone()).unwrap().add(admin).unwrap();
+    let group_keys = GroupKeys::new(store.clone()).unwrap();
+    group_keys
+        .add_fabric(IM_ENGINE_FAB_IDX, 0x1122, &[1; 8])
+        .unwrap();
+    group_keys
+        .set_key_set(IM_ENGINE_FAB_IDX, key_set())
+        .unwrap();
+
+    let dev_det = BasicInfoConfig {
+        vid: 10,
+        pid: 11,
+        hw_ver: 12,
+        sw_ver: 13,
+    };
+    let comm_data = CommissioningData {
+        verifier: VerifierData::new_with_pw(123456).unwrap(),
+        discriminator: 250,
+    };
+    let matter = Matter::new(dev_det, Box::new(DummyDevAtt {}), comm_data, store.clone()).unwrap();
+    matter.factory_reset().unwrap();
+
+    assert!(FabricMgr::new(store.clone()).unwrap().is_empty());
+    assert!(acl_fabrics(&AclMgr::new(store.clone()).unwrap()).is_empty());
+    assert!(no_groups(
+        &GroupKeys::new(store).unwrap(),
+        IM_ENGINE_FAB_IDX
+    ));
+
+    // The device can be commissioned afresh
+    let path = AttrPath::new(&GenericPath::new(
+        Some(0),
+        Some(admin_commissioning::ID),
+        Some(admin_commissioning::Attributes::WindowStatus as u32),
+    ));
+    let mut pase = ImSession::new(SessionMode::Pase);
+    read_attr_on_session(&matter.get_data_model(), &mut pase, &path, |t| {
+        assert_eq!(t.u8().unwrap(), CommWindowStatus::BasicOpen as u8)
+    });
+}
@@ -0,0 +1,299 @@
+use matter::{
+    data_model::{core::DataModel, objects::AttrValue},
+    interaction_model::{
+        core::{IMStatusCode, OpCode},
+        messages::{
+            ib::{AttrDataType, AttrPath, AttrResp},
+            msg::{self, StatusResp, SubscribeReq, SubscribeResp},
+            GenericPath,
+        },
+    },
+    tlv::{self, ElementType, FromTLV, TLVWriter, TagType, ToTLV},
+    transport::{
+        exchange::{self, Exchange},
+        proto_demux::{HandleProto, SessionEvent},
+    },
+    utils::writebuf::WriteBuf,
+};
+use std::{thread, time::Duration};
+
+use crate::common::{
+    echo_cluster,
+    im_engine::{im_engine, init_data_model, ImMsg, ImNode},
+};
+
+// Helper for handling Subscribe Request sequences
+fn handle_subscribe_req(
+    input: &[AttrPath],
+    min_int: u16,
+    max_int: u16,
+    out_buf: &mut [u8],
+) -> usize {
+    let mut buf = [0u8; 400];
+
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+
+    let subs_req = SubscribeReq::new(true, min_int, max_int).set_attr_requests(input);
+    subs_req.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+
+    let (_, out_buf_len) = im_engine(OpCode::SubscribeRequest, wb.as_borrow_slice(), out_buf);
+    tlv::print_tlv_list(&out_buf[..out_buf_len]);
+    out_buf_len
+}
+
+#[test]
+fn test_subscribe_priming_report() {
+    // 2 Attr Subscribe Requests
+    // - first on endpoint 0, att1
+    // - second on endpoint 1, att2
+    // The priming report should carry the subscription id and both the attributes
+    let _ = env_logger::try_init();
+
+    let ep0_att1 = GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Attributes::Att1 as u32),
+    );
+    let ep1_att2 = GenericPath::new(
+        Some(1),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Attributes::Att2 as u32),
+    );
+    let input = &[AttrPath::new(&ep0_att1), AttrPath::new(&ep1_att2)];
+    let expected = &[(ep0_att1, 0x1234), (ep1_att2, 0x5678)];
+
+    let mut out_buf = [0u8; 400];
+    let out_buf_len = handle_subscribe_req(input, 1, 10, &mut out_buf);
+    let root = tlv::get_root_node_struct(&out_buf[..out_buf_len]).unwrap();
+
+    // The subscription id must be present, and supress response must not be set
+    root.find_tag(msg::ReportDataTag::SubscriptionId as u32)
+        .unwrap()
+        .u32()
+        .unwrap();
+    assert!(root
+        .find_tag(msg::ReportDataTag::SupressResponse as u32)
+        .is_err());
+
+    let mut index = 0;
+    let response_iter = root
+        .find_tag(msg::ReportDataTag::AttributeReports as u32)
+        .unwrap()
+        .confirm_array()
+        .unwrap()
+        .iter()
+        .unwrap();
+    for response in response_iter {
+        match AttrResp::from_tlv(&response).unwrap() {
+            AttrResp::Data(d) => {
+                assert_eq!(AttrPath::new(&expected[index].0), d.path);
+                match d.data {
+                    AttrDataType::Tlv(t) => {
+                        assert_eq!(t.get_element_type(), ElementType::U16(expected[index].1))
+                    }
+                    _ => panic!("Invalid data type"),
+                }
+            }
+            _ => panic!("Invalid response, expected AttrRespIn::Data"),
+        }
+        index += 1;
+    }
+    assert_eq!(index, expected.len());
+}
+
+#[test]
+fn test_subscribe_invalid_intervals() {
+    // Min interval greater than Max interval, should be rejected
+    let _ = env_logger::try_init();
+
+    let ep0_att1 = GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Attributes::Att1 as u32),
+    );
+    let input = &[AttrPath::new(&ep0_att1)];
+
+    let mut out_buf = [0u8; 400];
+    let out_buf_len = handle_subscribe_req(input, 10, 1, &mut out_buf);
+    let root = tlv::get_root_node_struct(&out_buf[..out_buf_len]).unwrap();
+    let status = StatusResp::from_tlv(&root).unwrap();
+    assert_eq!(status.status, IMStatusCode::InvalidAction);
+}
+
+fn echo_attr(endpoint: u16, attr: echo_cluster::Attributes) -> AttrPath {
+    AttrPath::new(&GenericPath::new(
+        Some(endpoint),
+        Some(echo_cluster::ID),
+        Some(attr as u32),
+    ))
+}
+
+fn encode(to_tlv: &dyn Fn(&mut TLVWriter)) -> Vec<u8> {
+    let mut buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+    to_tlv(&mut tw);
+    wb.as_borrow_slice().to_vec()
+}
+
+fn status_resp(status: IMStatusCode) -> ImMsg {
+    let data = encode(&|tw| {
+        StatusResp { status }
+            .to_tlv(tw, TagType::Anonymous)
+            .unwrap()
+    });
+    (OpCode::StatusResponse, data)
+}
+
+// The subscription id, and the value of the single attribute report, of a ReportData
+fn report_value(msg: &ImMsg) -> (u32, u16) {
+    assert_eq!(msg.0, OpCode::ReportData);
+    let root = tlv::get_root_node_struct(&msg.1).unwrap();
+    let id = root
+        .find_tag(msg::ReportDataTag::SubscriptionId as u32)
+        .unwrap()
+        .u32()
+        .unwrap();
+    let mut reports = root
+        .find_tag(msg::ReportDataTag::AttributeReports as u32)
+        .unwrap()
+        .confirm_array()
+        .unwrap()
+        .iter()
+        .unwrap();
+    let value = match AttrResp::from_tlv(&reports.next().unwrap()).unwrap() {
+        AttrResp::Data(d) => match d.data {
+            AttrDataType::Tlv(t) => t.u16().unwrap(),
+            _ => panic!("Invalid data type"),
+        },
+        _ => panic!("Invalid response, expected AttrRespIn::Data"),
+    };
+    assert!(reports.next().is_none());
+    (id, value)
+}
+
+// Subscribe to the attribute, acknowledge the priming report, and return the
+// SubscribeResponse
+fn subscribe(server: &mut ImNode, path: &AttrPath, max_int: u16, keep_subs: bool) -> SubscribeResp {
+    let mut exch = Exchange::new(1, 0, exchange::Role::Responder);
+    let paths = [*path];
+    let req = encode(&|tw| {
+        let mut req = SubscribeReq::new(true, 0, max_int).set_attr_requests(&paths);
+        req.keep_subs = keep_subs;
+        req.to_tlv(tw, TagType::Anonymous).unwrap();
+    });
+    let priming = server
+        .handle(&mut exch, (OpCode::SubscribeRequest, req))
+        .unwrap();
+    let (priming_id, _) = report_value(&priming);
+
+    // The SubscribeResponse follows, once the priming report is acknowledged
+    let (opcode, resp) = server
+        .handle(&mut exch, status_resp(IMStatusCode::Sucess))
+        .unwrap();
+    assert_eq!(opcode, OpCode::SubscriptResponse);
+    let resp = SubscribeResp::from_tlv(&tlv::get_root_node_struct(&resp).unwrap()).unwrap();
+    assert_eq!(resp.subs_id, priming_id);
+    resp
+}
+
+fn update_attr(dm: &DataModel, endpoint: u16, attr: echo_cluster::Attributes, value: u16) {
+    let mut node = dm.node.write().unwrap();
+    let echo = node.get_cluster_mut(endpoint, echo_cluster::ID).unwrap();
+    echo.base_mut()
+        .write_attribute_raw(attr as u16, AttrValue::Uint16(value))
+        .unwrap();
+}
+
+// Receive the report that is pending on the server, and acknowledge it
+fn recv_report(server: &mut ImNode) -> (u32, u16) {
+    let mut exch = Exchange::new(2, 0, exchange::Role::Initiator);
+    let report = server.unsolicited(&mut exch);
+    let value = report_value(&report);
+    assert!(server
+        .handle(&mut exch, status_resp(IMStatusCode::Sucess))
+        .is_none());
+    value
+}
+
+#[test]
+fn test_subscribe_report_on_change() {
+    // The subscriber is sent a report, once the attribute changes
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+    let mut server = ImNode::new(&dm);
+
+    let path = echo_attr(0, echo_cluster::Attributes::Att1);
+    let resp = subscribe(&mut server, &path, 60, true);
+    assert_eq!(resp.max_int, 60);
+    assert!(server.im.get_unsolicited_sess().is_none());
+
+    update_attr(&dm, 0, echo_cluster::Attributes::Att1, 0x3030);
+    assert_eq!(recv_report(&mut server), (resp.subs_id, 0x3030));
+    assert!(server.im.get_unsolicited_sess().is_none());
+
+    // A change to the cluster on another endpoint isn't reported
+    update_attr(&dm, 1, echo_cluster::Attributes::Att1, 0x4040);
+    assert!(server.im.get_unsolicited_sess().is_none());
+}
+
+#[test]
+fn test_subscribe_report_on_max_int() {
+    // Without any change, the subscriber is sent a report once the max interval expires
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+    let mut server = ImNode::new(&dm);
+
+    let path = echo_attr(0, echo_cluster::Attributes::Att1);
+    let resp = subscribe(&mut server, &path, 1, true);
+    assert!(server.im.get_unsolicited_sess().is_none());
+
+    thread::sleep(Duration::from_millis(1100));
+    assert_eq!(recv_report(&mut server), (resp.subs_id, 0x1234));
+    assert!(server.im.get_unsolicited_sess().is_none());
+}
+
+#[test]
+fn test_subscribe_keep_subs() {
+    // The earlier subscriptions on the session are kept only if asked for
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+    let mut server = ImNode::new(&dm);
+
+    let att1 = echo_attr(0, echo_cluster::Attributes::Att1);
+    let att2 = echo_attr(1, echo_cluster::Attributes::Att2);
+    let first = subscribe(&mut server, &att1, 60, true);
+    let second = subscribe(&mut server, &att2, 60, true);
+    update_attr(&dm, 0, echo_cluster::Attributes::Att1, 0x3030);
+    assert_eq!(recv_report(&mut server), (first.subs_id, 0x3030));
+
+    let third = subscribe(&mut server, &att2, 60, false);
+    update_attr(&dm, 0, echo_cluster::Attributes::Att1, 0x3131);
+    assert!(server.im.get_unsolicited_sess().is_none());
+    update_attr(&dm, 1, echo_cluster::Attributes::Att2, 0x4040);
+    assert_eq!(recv_report(&mut server), (third.subs_id, 0x4040));
+    assert_ne!(third.subs_id, second.subs_id);
+    assert!(server.im.get_unsolicited_sess().is_none());
+}
+
+#[test]
+fn test_subscribe_session_evicted() {
+    // The subscriptions that were created on a session are gone, once it is evicted
+    let _ = env_logger::try_init();
+    let dm = init_data_model();
+    let mut server = ImNode::new(&dm);
+
+    let path = echo_attr(0, echo_cluster::Attributes::Att1);
+    subscribe(&mut server, &path, 60, true);
+    let sess_id = server.sess_id();
+    server
+        .im
+        .handle_session_event(&SessionEvent::Evicted(sess_id))
+        .unwrap();
+
+    update_attr(&dm, 0, echo_cluster::Attributes::Att1, 0x3030);
+    assert!(server.im.get_unsolicited_sess().is_none());
+}
@@ -0,0 +1,335 @@
+use std::{thread, time::Duration};
+
+use matter::{
+    data_model::{core::DataModel, objects::AttrValue},
+    interaction_model::{
+        core::{IMStatusCode, OpCode},
+        messages::{
+            ib::{AttrData, AttrDataType, AttrPath, AttrStatus, CmdDataType, CmdPath, InvResp},
+            msg::{self, ReadReq, StatusResp, TimedReq, WriteReq},
+            GenericPath,
+        },
+    },
+    tlv::{self, FromTLV, TLVWriter, TagType, ToTLV},
+    transport::exchange::{self, Exchange},
+    utils::writebuf::WriteBuf,
+};
+
+use crate::common::{
+    echo_cluster,
+    im_engine::{im_engine, im_engine_with_exch, init_data_model, timed_req, TestData},
+};
+
+fn get_status_resp(out_buf: &[u8]) -> IMStatusCode {
+    tlv::print_tlv_list(out_buf);
+    let root = tlv::get_root_node_struct(out_buf).unwrap();
+    StatusResp::from_tlv(&root).unwrap().status
+}
+
+#[test]
+fn test_timed_req_success() {
+    // A Timed Request should be acknowledged with a Success status
+    let _ = env_logger::try_init();
+
+    let mut buf = [0u8; 400];
+    let mut out_buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+
+    TimedReq::new(500)
+        .to_tlv(&mut tw, TagType::Anonymous)
+        .unwrap();
+
+    let (_, out_buf_len) = im_engine(OpCode::TimedRequest, wb.as_borrow_slice(), &mut out_buf);
+    assert_eq!(
+        get_status_resp(&out_buf[..out_buf_len]),
+        IMStatusCode::Sucess
+    );
+}
+
+#[test]
+fn test_timed_invoke_without_timed_req() {
+    // An Invoke Request that claims to be timed, without a preceding Timed Request
+    // should be rejected with TimedRequestMismatch
+    let _ = env_logger::try_init();
+
+    let mut buf = [0u8; 400];
+    let mut out_buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut td = TestData::new(&mut wb);
+
+    let target = CmdPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Commands::TimedEchoReq as u16),
+    );
+    td.commands_timed(&[(target, Some(5))], true).unwrap();
+
+    let (_, out_buf_len) = im_engine(OpCode::InvokeRequest, wb.as_borrow_slice(), &mut out_buf);
+    assert_eq!(
+        get_status_resp(&out_buf[..out_buf_len]),
+        IMStatusCode::TimedRequestMismatch
+    );
+}
+
+#[test]
+fn test_timed_invoke_success() {
+    // A Timed Request, followed by a timed Invoke Request of a Timed-only command on the
+    // same exchange, should invoke the command
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    let mut exch = Exchange::new(1, 0, exchange::Role::Responder);
+    timed_req(&dm, &mut exch, 500);
+
+    let mut buf = [0u8; 400];
+    let mut out_buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut td = TestData::new(&mut wb);
+
+    let target = CmdPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Commands::TimedEchoReq as u16),
+    );
+    td.commands_timed(&[(target, Some(5))], true).unwrap();
+
+    let out_buf_len = im_engine_with_exch(
+        &dm,
+        &mut exch,
+        OpCode::InvokeRequest,
+        wb.as_borrow_slice(),
+        &mut out_buf,
+    );
+    let out_buf = &out_buf[..out_buf_len];
+    tlv::print_tlv_list(out_buf);
+    let root = tlv::get_root_node_struct(out_buf).unwrap();
+    let response = root
+        .find_tag(msg::InvRespTag::InvokeResponses as u32)
+        .unwrap()
+        .confirm_array()
+        .unwrap()
+        .iter()
+        .unwrap()
+        .next()
+        .unwrap();
+    match InvResp::from_tlv(&response).unwrap() {
+        InvResp::Cmd(c) => {
+            assert_eq!(
+                c.path,
+                CmdPath::new(
+                    Some(0),
+                    Some(echo_cluster::ID),
+                    Some(echo_cluster::Commands::EchoResp as u16),
+                )
+            );
+            match c.data {
+                // The echo cluster on endpoint 0 multiplies by 2
+                CmdDataType::Tlv(t) => assert_eq!(t.find_tag(0).unwrap().u8().unwrap(), 10),
+                _ => panic!("Incorrect CmdDataType"),
+            }
+        }
+        _ => panic!("Expected an Invoke Response"),
+    }
+}
+
+// The path of the Timed-only attribute of the echo cluster on endpoint 0
+fn timed_attr_path() -> GenericPath {
+    GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Attributes::AttWriteTimed as u32),
+    )
+}
+
+// Write the Timed-only attribute on the exchange, and return the response
+fn write_timed_attr(
+    dm: &DataModel,
+    exch: &mut Exchange,
+    timed: bool,
+    value: u16,
+    out_buf: &mut [u8],
+) -> usize {
+    let mut buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+
+    let attr_data = |tag, t: &mut TLVWriter| {
+        let _ = t.u16(tag, value);
+        Ok(())
+    };
+    let input = &[AttrData::new(
+        None,
+        AttrPath::new(&timed_attr_path()),
+        AttrDataType::Closure(&attr_data),
+    )];
+    let mut write_req = WriteReq::new(false, input);
+    if timed {
+        write_req.timed_request = Some(true);
+    }
+    write_req.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+
+    im_engine_with_exch(
+        dm,
+        exch,
+        OpCode::WriteRequest,
+        wb.as_borrow_slice(),
+        out_buf,
+    )
+}
+
+fn get_write_status(out_buf: &[u8]) -> AttrStatus {
+    tlv::print_tlv_list(out_buf);
+    let root = tlv::get_root_node_struct(out_buf).unwrap();
+    let mut responses = root
+        .find_tag(msg::WriteRespTag::WriteResponses as u32)
+        .unwrap()
+        .confirm_array()
+        .unwrap()
+        .iter()
+        .unwrap();
+    let status = AttrStatus::from_tlv(&responses.next().unwrap()).unwrap();
+    assert!(responses.next().is_none());
+    status
+}
+
+fn read_timed_attr(dm: &DataModel) -> AttrValue {
+    let node = dm.node.read().unwrap();
+    let echo = node.get_cluster(0, echo_cluster::ID).unwrap();
+    echo.base()
+        .read_attribute_raw(echo_cluster::Attributes::AttWriteTimed as u16)
+        .unwrap()
+        .clone()
+}
+
+#[test]
+fn test_timed_write_success() {
+    // A Timed Request, followed by a timed Write Request of a Timed-only attribute on the
+    // same exchange, should write the attribute
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    let mut exch = Exchange::new(1, 0, exchange::Role::Responder);
+    timed_req(&dm, &mut exch, 500);
+
+    let mut out_buf = [0u8; 400];
+    let out_buf_len = write_timed_attr(&dm, &mut exch, true, 10, &mut out_buf);
+    assert_eq!(
+        get_write_status(&out_buf[..out_buf_len]),
+        AttrStatus::new(&timed_attr_path(), IMStatusCode::Sucess, 0)
+    );
+    assert_eq!(read_timed_attr(&dm), AttrValue::Uint16(10));
+}
+
+#[test]
+fn test_timed_write_without_timed_req() {
+    // A Write Request of a Timed-only attribute, outside of a Timed Interaction, should be
+    // rejected with NeedsTimedInteraction
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    let mut exch = Exchange::new(1, 0, exchange::Role::Responder);
+
+    let mut out_buf = [0u8; 400];
+    let out_buf_len = write_timed_attr(&dm, &mut exch, false, 10, &mut out_buf);
+    assert_eq!(
+        get_write_status(&out_buf[..out_buf_len]),
+        AttrStatus::new(&timed_attr_path(), IMStatusCode::NeedsTimedInteraction, 0)
+    );
+    assert_eq!(
+        read_timed_attr(&dm),
+        AttrValue::Uint16(echo_cluster::ATTR_WRITE_DEFAULT_VALUE)
+    );
+}
+
+#[test]
+fn test_timed_write_expired() {
+    // A timed Write Request that arrives after the timed window expired should be rejected
+    // with Timeout
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    let mut exch = Exchange::new(1, 0, exchange::Role::Responder);
+    timed_req(&dm, &mut exch, 1);
+    thread::sleep(Duration::from_millis(10));
+
+    let mut out_buf = [0u8; 400];
+    let out_buf_len = write_timed_attr(&dm, &mut exch, true, 10, &mut out_buf);
+    assert_eq!(
+        get_status_resp(&out_buf[..out_buf_len]),
+        IMStatusCode::Timeout
+    );
+    assert_eq!(
+        read_timed_attr(&dm),
+        AttrValue::Uint16(echo_cluster::ATTR_WRITE_DEFAULT_VALUE)
+    );
+}
+
+#[test]
+fn test_timed_write_mismatch() {
+    // A Write Request that doesn't claim to be timed, after a Timed Request, should be
+    // rejected with TimedRequestMismatch
+    let _ = env_logger::try_init();
+
+    let dm = init_data_model();
+    let mut exch = Exchange::new(1, 0, exchange::Role::Responder);
+    timed_req(&dm, &mut exch, 500);
+
+    let mut out_buf = [0u8; 400];
+    let out_buf_len = write_timed_attr(&dm, &mut exch, false, 10, &mut out_buf);
+    assert_eq!(
+        get_status_resp(&out_buf[..out_buf_len]),
+        IMStatusCode::TimedRequestMismatch
+    );
+    assert_eq!(
+        read_timed_attr(&dm),
+        AttrValue::Uint16(echo_cluster::ATTR_WRITE_DEFAULT_VALUE)
+    );
+}
+
+#[test]
+fn test_timed_req_followed_by_other_opcode() {
+    // Any action other than a Write or an Invoke Request, after a Timed Request, should be
+    // rejected with TimedRequestMismatch
+    let _ = env_logger::try_init();
+
+    let mut buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+    let path = AttrPath::new(&timed_attr_path());
+    ReadReq::new(true)
+        .set_attr_requests(&[path])
+        .to_tlv(&mut tw, TagType::Anonymous)
+        .unwrap();
+    let read_req = wb.as_borrow_slice().to_vec();
+
+    let mut buf = [0u8; 400];
+    let buf_len = buf.len();
+    let mut wb = WriteBuf::new(&mut buf, buf_len);
+    let mut tw = TLVWriter::new(&mut wb);
+    TimedReq::new(500)
+        .to_tlv(&mut tw, TagType::Anonymous)
+        .unwrap();
+    let timed = wb.as_borrow_slice().to_vec();
+
+    for (opcode, data) in [
+        (OpCode::ReadRequest, read_req),
+        (OpCode::TimedRequest, timed),
+    ] {
+        let dm = init_data_model();
+        let mut exch = Exchange::new(1, 0, exchange::Role::Responder);
+        timed_req(&dm, &mut exch, 500);
+
+        let mut out_buf = [0u8; 400];
+        let out_buf_len = im_engine_with_exch(&dm, &mut exch, opcode, &data, &mut out_buf);
+        assert_eq!(
+            get_status_resp(&out_buf[..out_buf_len]),
+            IMStatusCode::TimedRequestMismatch
+        );
+    }
+}
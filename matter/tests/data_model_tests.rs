@@ -1,6 +1,16 @@
 mod common;
 
 mod data_model {
+    mod admin_commissioning;
+    mod acl;
     mod attributes;
     mod commands;
+    mod events;
+    mod failsafe;
+    mod group_keys;
+    mod groups;
+    mod im_client;
+    mod noc;
+    mod subscribe;
+    mod timed;
 }
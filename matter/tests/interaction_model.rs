@@ -1,4 +1,5 @@
 use boxslab::Slab;
+use matter::acl::Accessor;
 use matter::error::Error;
 use matter::interaction_model::core::OpCode;
 use matter::interaction_model::messages::ib;
@@ -6,6 +7,7 @@ use matter::interaction_model::messages::msg::ReadReq;
 use matter::interaction_model::messages::msg::WriteReq;
 use matter::interaction_model::InteractionConsumer;
 use matter::interaction_model::InteractionModel;
+use matter::interaction_model::ReadCursor;
 use matter::interaction_model::Transaction;
 use matter::tlv::{TLVElement, TLVWriter};
 use matter::transport::exchange::Exchange;
@@ -64,11 +66,22 @@ impl InteractionConsumer for DataModel {
         Ok(())
     }
 
-    fn consume_read_attr(&self, _req: &ReadReq, _tlvwriter: &mut TLVWriter) -> Result<(), Error> {
-        Ok(())
+    fn consume_read_attr(
+        &self,
+        _accessor: &Accessor,
+        _req: &ReadReq,
+        _cursor: ReadCursor,
+        _tlvwriter: &mut TLVWriter,
+    ) -> Result<Option<ReadCursor>, Error> {
+        Ok(None)
     }
 
-    fn consume_write_attr(&self, _req: &WriteReq, _tlvwriter: &mut TLVWriter) -> Result<(), Error> {
+    fn consume_write_attr(
+        &self,
+        _accessor: &Accessor,
+        _req: &WriteReq,
+        _tlvwriter: &mut TLVWriter,
+    ) -> Result<(), Error> {
         Ok(())
     }
 }